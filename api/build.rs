@@ -2,7 +2,23 @@ use std::env;
 use std::path::PathBuf;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let proto_files = ["./proto/chessgame.proto", "./proto/auth.proto"];
+    let proto_files = [
+        "./proto/chessgame.proto",
+        "./proto/auth.proto",
+        "./proto/account.proto",
+        "./proto/session.proto",
+        "./proto/rating.proto",
+        "./proto/matchmaking.proto",
+        "./proto/challenge.proto",
+        "./proto/spectate.proto",
+        "./proto/tournament.proto",
+        "./proto/analysis.proto",
+        "./proto/explorer.proto",
+        "./proto/puzzle.proto",
+        "./proto/search.proto",
+        "./proto/archive.proto",
+        "./proto/stats.proto",
+    ];
     let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
 
     tonic_build::configure()
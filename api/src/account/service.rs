@@ -0,0 +1,295 @@
+use sea_orm::DatabaseConnection;
+use tonic::{Request, Response, Status};
+
+use entity::entities::users;
+use service::game::query as game_query;
+use service::sessions::mutation as session_mutation;
+use service::users::{mutation, query};
+
+use super::{
+    account_server::Account, ChangePasswordRequest, ChangePasswordResponse, DeleteAccountRequest,
+    DeleteAccountResponse, GetProfileRequest, Profile, UpdateProfileRequest,
+};
+use crate::auth::{interceptor::authenticated_user, password, session::RevokedSessions};
+use crate::chess::{events::GameEvents, lifecycle, pieces::Color};
+use crate::matchmaking::service::Matchmaker;
+use crate::rate_limit::login::LoginThrottle;
+
+const MAX_DISPLAY_NAME_LEN: usize = 50;
+const MAX_BIO_LEN: usize = 500;
+
+pub struct AccountService {
+    pub db_connection: DatabaseConnection,
+    pub revoked_sessions: RevokedSessions,
+    pub login_throttle: LoginThrottle,
+    pub events: GameEvents,
+    pub matchmaker: Matchmaker,
+}
+
+impl From<users::Model> for Profile {
+    fn from(user: users::Model) -> Self {
+        Profile {
+            id: user.id,
+            username: user.username,
+            display_name: user.display_name.unwrap_or_default(),
+            country: user.country.unwrap_or_default(),
+            bio: user.bio.unwrap_or_default(),
+            avatar_url: user.avatar_url.unwrap_or_default(),
+        }
+    }
+}
+
+// Empty strings clear a field
+fn non_empty(value: String) -> Option<String> {
+    let value = value.trim().to_string();
+    if value.is_empty() {
+        None
+    } else {
+        Some(value)
+    }
+}
+
+// Changing the password and deleting the account both need the current password
+async fn check_password(user: &users::Model, password: &str) -> Result<(), Status> {
+    if password::verify(password, &user.password).await {
+        Ok(())
+    } else {
+        Err(Status::permission_denied("Invalid password"))
+    }
+}
+
+// What is stored for the new password, never the password itself
+async fn new_password_hash(
+    user: &users::Model,
+    r: &ChangePasswordRequest,
+) -> Result<String, Status> {
+    check_password(user, &r.current_password).await?;
+    if r.new_password.is_empty() {
+        return Err(Status::invalid_argument("Invalid password"));
+    }
+    password::hash(&r.new_password).await
+}
+
+impl AccountService {
+    async fn find_active_user(&self, id: i32) -> Result<users::Model, Status> {
+        let user = query::Query::find_user_by_id(&self.db_connection, id)
+            .await
+            .map_err(|_| Status::internal("Could not load user"))?;
+        match user {
+            Some(user) if user.deleted_at.is_none() => Ok(user),
+            _ => Err(Status::not_found("User not found")),
+        }
+    }
+
+    async fn revoke_sessions(&self, user_id: i32, except: Option<i32>) -> Result<(), Status> {
+        let ids =
+            session_mutation::Mutation::revoke_all_sessions(&self.db_connection, user_id, except)
+                .await
+                .map_err(|_| Status::internal("Could not revoke sessions"))?;
+        self.revoked_sessions.revoke(&ids);
+        Ok(())
+    }
+
+    // The current password is checked like a login, so guesses are limited per account
+    async fn check_current_password(
+        &self,
+        user: &users::Model,
+        password: &str,
+    ) -> Result<(), Status> {
+        self.login_throttle.reserve_account(user.id).await?;
+        check_password(user, password).await?;
+        self.login_throttle.record_account_success(user.id).await;
+        Ok(())
+    }
+
+    // A deleted account stops seeking and loses the games it is still playing
+    async fn resign_everything(&self, user_id: i32) -> Result<(), Status> {
+        self.matchmaker.cancel(user_id);
+        let games = game_query::Query::find_active_games_by_user(&self.db_connection, user_id)
+            .await
+            .map_err(|_| Status::internal("Could not load games"))?;
+        for game_row in games {
+            let color = if game_row.player_white == user_id {
+                Color::White
+            } else {
+                Color::Black
+            };
+            let result = lifecycle::win_for(&color.opponent());
+            let finished = lifecycle::finish_game(
+                &self.db_connection,
+                &self.events,
+                &game_row,
+                result,
+                lifecycle::RESIGNATION,
+            )
+            .await;
+            match finished {
+                // Ended in the meantime
+                Err(status) if status.code() == tonic::Code::FailedPrecondition => {}
+                Err(status) => return Err(status),
+                Ok(_) => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+#[tonic::async_trait]
+impl Account for AccountService {
+    async fn get_profile(
+        &self,
+        request: Request<GetProfileRequest>,
+    ) -> Result<Response<Profile>, Status> {
+        let user = authenticated_user(&request)?;
+        let r = request.into_inner();
+        if r.username.is_empty() {
+            let user = self.find_active_user(user.id).await?;
+            return Ok(Response::new(user.into()));
+        }
+        let db_result = query::Query::find_user_by_username(&self.db_connection, &r.username)
+            .await
+            .map_err(|_| Status::internal("Could not load user"))?;
+        match db_result {
+            Some(user) if user.deleted_at.is_none() => Ok(Response::new(user.into())),
+            _ => Err(Status::not_found("User not found")),
+        }
+    }
+
+    async fn update_profile(
+        &self,
+        request: Request<UpdateProfileRequest>,
+    ) -> Result<Response<Profile>, Status> {
+        let user = authenticated_user(&request)?;
+        let r = request.into_inner();
+        let mut form_data = self.find_active_user(user.id).await?;
+
+        if let Some(display_name) = r.display_name {
+            if display_name.chars().count() > MAX_DISPLAY_NAME_LEN {
+                return Err(Status::invalid_argument("Display name is too long"));
+            }
+            form_data.display_name = non_empty(display_name);
+        }
+        if let Some(country) = r.country {
+            let country = non_empty(country).map(|c| c.to_uppercase());
+            // ISO 3166-1 alpha-2
+            if let Some(c) = &country {
+                if c.len() != 2 || !c.chars().all(|ch| ch.is_ascii_alphabetic()) {
+                    return Err(Status::invalid_argument("Invalid country code"));
+                }
+            }
+            form_data.country = country;
+        }
+        if let Some(bio) = r.bio {
+            if bio.chars().count() > MAX_BIO_LEN {
+                return Err(Status::invalid_argument("Bio is too long"));
+            }
+            form_data.bio = non_empty(bio);
+        }
+        if let Some(avatar_url) = r.avatar_url {
+            let avatar_url = non_empty(avatar_url);
+            if let Some(url) = &avatar_url {
+                if !url.starts_with("https://") && !url.starts_with("http://") {
+                    return Err(Status::invalid_argument("Invalid avatar url"));
+                }
+            }
+            form_data.avatar_url = avatar_url;
+        }
+
+        let db_result =
+            mutation::Mutation::update_profile_by_id(&self.db_connection, user.id, form_data)
+                .await
+                .map_err(|_| Status::internal("Could not update profile"))?;
+        Ok(Response::new(db_result.into()))
+    }
+
+    async fn change_password(
+        &self,
+        request: Request<ChangePasswordRequest>,
+    ) -> Result<Response<ChangePasswordResponse>, Status> {
+        let user = authenticated_user(&request)?;
+        let r = request.into_inner();
+        let current = self.find_active_user(user.id).await?;
+        self.login_throttle.reserve_account(user.id).await?;
+        let hashed = new_password_hash(&current, &r).await?;
+        self.login_throttle.record_account_success(user.id).await;
+        mutation::Mutation::update_password_by_id(&self.db_connection, user.id, &hashed)
+            .await
+            .map_err(|_| Status::internal("Could not update password"))?;
+        // Log out every other device
+        self.revoke_sessions(user.id, Some(user.session_id)).await?;
+        Ok(Response::new(ChangePasswordResponse { success: true }))
+    }
+
+    async fn delete_account(
+        &self,
+        request: Request<DeleteAccountRequest>,
+    ) -> Result<Response<DeleteAccountResponse>, Status> {
+        let user = authenticated_user(&request)?;
+        let r = request.into_inner();
+        let current = self.find_active_user(user.id).await?;
+        self.check_current_password(&current, &r.password).await?;
+        self.resign_everything(user.id).await?;
+        mutation::Mutation::delete_user(&self.db_connection, user.id)
+            .await
+            .map_err(|_| Status::internal("Could not delete account"))?;
+        self.revoke_sessions(user.id, None).await?;
+        Ok(Response::new(DeleteAccountResponse { success: true }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(password: &str) -> users::Model {
+        users::Model {
+            id: 7,
+            username: "alice".to_string(),
+            password: password.to_string(),
+            created_at: Default::default(),
+            updated_at: None,
+            display_name: None,
+            country: None,
+            bio: None,
+            avatar_url: None,
+            deleted_at: None,
+            bot_level: None,
+        }
+    }
+
+    fn change(current_password: &str, new_password: &str) -> ChangePasswordRequest {
+        ChangePasswordRequest {
+            current_password: current_password.to_string(),
+            new_password: new_password.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_new_password_hash() {
+        let current = user(&password::hash("old secret").await.unwrap());
+        let hashed = new_password_hash(&current, &change("old secret", "new secret"))
+            .await
+            .unwrap();
+        assert_ne!(hashed, "new secret");
+        assert!(password::verify("new secret", &hashed).await);
+
+        let status = new_password_hash(&current, &change("guess", "new secret"))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+        let status = new_password_hash(&current, &change("old secret", ""))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn test_check_password_for_deletion() {
+        let hashed = password::hash("secret").await.unwrap();
+        assert!(check_password(&user(&hashed), "secret").await.is_ok());
+        let status = check_password(&user(&hashed), "guess").await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+        // Bots have no password to delete them with
+        assert!(check_password(&user(""), "").await.is_err());
+    }
+}
@@ -0,0 +1,190 @@
+// Turns what either engine reports into the lines of an `AnalysisUpdate`: moves in SAN from the
+// analysed position and scores as centipawns or mate in N for the side to move.
+use super::{line, Line};
+use crate::chess::engine::{
+    position::{Move, Position},
+    search::{mate_in, SearchInfo},
+};
+use crate::chess::uci::{Info, Score};
+
+fn to_line(rank: usize, score: Score, moves: Vec<String>) -> Line {
+    Line {
+        rank: rank as u32,
+        score: Some(match score {
+            Score::Centipawns(centipawns) => line::Score::Centipawns(centipawns),
+            Score::Mate(mate) => line::Score::Mate(mate),
+        }),
+        moves,
+    }
+}
+
+pub fn san_line(position: &Position, moves: &[Move]) -> Vec<String> {
+    let mut position = position.clone();
+    let mut san = Vec::new();
+    for &mv in moves {
+        san.push(position.san(mv));
+        position = position.play(mv);
+    }
+    san
+}
+
+// Stops at the first move that is not legal, an engine's line should not have any
+pub fn uci_line(position: &Position, moves: &[String]) -> Vec<String> {
+    let mut position = position.clone();
+    let mut san = Vec::new();
+    for uci in moves {
+        match position.parse_uci(uci) {
+            Some(mv) => {
+                san.push(position.san(mv));
+                position = position.play(mv);
+            }
+            None => break,
+        }
+    }
+    san
+}
+
+pub fn search_score(score: i32) -> Score {
+    match mate_in(score) {
+        Some(mate) => Score::Mate(mate),
+        None => Score::Centipawns(score),
+    }
+}
+
+pub fn search_lines(position: &Position, infos: &[SearchInfo]) -> Vec<Line> {
+    infos
+        .iter()
+        .enumerate()
+        .map(|(i, info)| {
+            to_line(
+                i + 1,
+                search_score(info.score),
+                san_line(position, &info.pv),
+            )
+        })
+        .collect()
+}
+
+pub fn uci_lines(position: &Position, infos: &[Info]) -> Vec<Line> {
+    infos
+        .iter()
+        .filter_map(|info| {
+            let score = info.score?;
+            Some(to_line(info.multipv, score, uci_line(position, &info.pv)))
+        })
+        .collect()
+}
+
+// A UCI engine sends one `info` per line for every depth, in order of rank. This puts a depth
+// back together once its last line has arrived.
+pub struct LineCollector {
+    lines: Vec<Option<Info>>,
+}
+
+impl LineCollector {
+    pub fn new(lines: usize) -> LineCollector {
+        LineCollector {
+            lines: vec![None; lines.max(1)],
+        }
+    }
+
+    pub fn push(&mut self, info: Info) -> Option<Vec<Info>> {
+        // Progress reports such as `currmove` have no line
+        if info.score.is_none() || info.pv.is_empty() {
+            return None;
+        }
+        let index = info.multipv.checked_sub(1)?;
+        if index >= self.lines.len() {
+            return None;
+        }
+        let depth = info.depth;
+        self.lines[index] = Some(info);
+        if index + 1 < self.lines.len() {
+            return None;
+        }
+        let complete = self
+            .lines
+            .iter()
+            .all(|line| line.as_ref().is_some_and(|line| line.depth == depth));
+        if complete {
+            Some(self.lines.iter().flatten().cloned().collect())
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::engine::search::MATE;
+
+    fn info(depth: i32, multipv: usize, score: Score, pv: &str) -> Info {
+        Info {
+            depth: Some(depth),
+            multipv,
+            score: Some(score),
+            nodes: Some(100),
+            pv: pv.split_whitespace().map(String::from).collect(),
+        }
+    }
+
+    #[test]
+    fn test_lines_in_san() {
+        let position = Position::new();
+        let pv = vec!["e2e4".to_string(), "e7e5".to_string(), "g1f3".to_string()];
+        assert_eq!(uci_line(&position, &pv), vec!["e4", "e5", "Nf3"]);
+        // Cut at the first illegal move
+        let pv = vec!["e2e4".to_string(), "e2e4".to_string()];
+        assert_eq!(uci_line(&position, &pv), vec!["e4"]);
+
+        let fen = "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4";
+        let position = Position::from_fen(fen).unwrap();
+        let mate = position.parse_move("Qxf7").unwrap();
+        assert_eq!(san_line(&position, &[mate]), vec!["Qxf7#"]);
+        let lines = search_lines(
+            &position,
+            &[SearchInfo {
+                depth: 3,
+                score: MATE - 1,
+                nodes: 10,
+                pv: vec![mate],
+            }],
+        );
+        assert_eq!(lines[0].rank, 1);
+        assert_eq!(lines[0].score, Some(line::Score::Mate(1)));
+        assert_eq!(search_score(-35), Score::Centipawns(-35));
+    }
+
+    #[test]
+    fn test_collector_waits_for_every_line() {
+        let mut collector = LineCollector::new(2);
+        assert_eq!(
+            collector.push(info(1, 1, Score::Centipawns(30), "e2e4")),
+            None
+        );
+        let depth_one = collector
+            .push(info(1, 2, Score::Centipawns(20), "d2d4"))
+            .unwrap();
+        assert_eq!(depth_one.len(), 2);
+
+        // A new best line alone does not make depth 2
+        let mut progress = info(2, 1, Score::Centipawns(25), "");
+        progress.pv.clear();
+        assert_eq!(collector.push(progress), None);
+        assert_eq!(
+            collector.push(info(2, 1, Score::Centipawns(25), "d2d4")),
+            None
+        );
+        assert_eq!(collector.push(info(2, 3, Score::Mate(2), "g1f3")), None);
+        let depth_two = collector
+            .push(info(2, 2, Score::Centipawns(24), "e2e4"))
+            .unwrap();
+        assert_eq!(depth_two[0].pv, vec!["d2d4"]);
+
+        let lines = uci_lines(&Position::new(), &depth_two);
+        assert_eq!(lines[1].rank, 2);
+        assert_eq!(lines[1].moves, vec!["e4"]);
+        assert_eq!(lines[1].score, Some(line::Score::Centipawns(24)));
+    }
+}
@@ -0,0 +1,5 @@
+pub mod lines;
+pub mod report;
+pub mod service;
+pub mod worker;
+tonic::include_proto!("analysis"); // The string specified here must match the proto package name
@@ -0,0 +1,380 @@
+// Post-game report: every move is judged by how much worse the engine thinks the position got
+// for the player who made it, compared to the engine's own choice. Scores are capped so a
+// missed mate in a won position does not outweigh the rest of the game.
+use crate::chess::engine::position::{Move, Position};
+use crate::chess::pgn::PgnMove;
+use crate::chess::pieces::Color;
+use crate::chess::uci::Score;
+
+// Centipawns, mate counts as this much
+const EVAL_CAP: i32 = 1000;
+// Centipawn losses from which a move is an inaccuracy, a mistake and a blunder
+const INACCURACY: i32 = 50;
+const MISTAKE: i32 = 100;
+const BLUNDER: i32 = 300;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Classification {
+    Best,
+    Good,
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl Classification {
+    fn from_loss(loss: i32) -> Classification {
+        if loss >= BLUNDER {
+            Classification::Blunder
+        } else if loss >= MISTAKE {
+            Classification::Mistake
+        } else if loss >= INACCURACY {
+            Classification::Inaccuracy
+        } else {
+            Classification::Good
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Classification::Best => "best",
+            Classification::Good => "good",
+            Classification::Inaccuracy => "inaccuracy",
+            Classification::Mistake => "mistake",
+            Classification::Blunder => "blunder",
+        }
+    }
+
+    // "?!", "?" and "??" in PGN
+    fn nag(&self) -> Option<u8> {
+        match self {
+            Classification::Inaccuracy => Some(6),
+            Classification::Mistake => Some(2),
+            Classification::Blunder => Some(4),
+            _ => None,
+        }
+    }
+}
+
+// The engine's view of one position, `score` is for the side to move
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Evaluation {
+    pub score: Score,
+    pub best: Option<Move>,
+}
+
+impl Evaluation {
+    // Checkmate and stalemate need no engine
+    pub fn terminal(position: &Position) -> Option<Evaluation> {
+        if !position.legal_moves().is_empty() {
+            return None;
+        }
+        let score = if position.in_check() {
+            Score::Mate(0)
+        } else {
+            Score::Centipawns(0)
+        };
+        Some(Evaluation { score, best: None })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MoveReport {
+    pub san: String,
+    // The engine's choice in SAN
+    pub best: Option<String>,
+    // After the move, for white
+    pub score: Score,
+    pub loss: i32,
+    pub classification: Classification,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PlayerReport {
+    pub accuracy: f64,
+    // Average centipawn loss
+    pub acpl: f64,
+    pub inaccuracies: u32,
+    pub mistakes: u32,
+    pub blunders: u32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Report {
+    pub moves: Vec<MoveReport>,
+    pub white: PlayerReport,
+    pub black: PlayerReport,
+}
+
+fn centipawns(score: Score) -> i32 {
+    match score {
+        Score::Centipawns(centipawns) => centipawns.clamp(-EVAL_CAP, EVAL_CAP),
+        Score::Mate(moves) if moves > 0 => EVAL_CAP,
+        // Mate 0 is the side to move being checkmated
+        Score::Mate(_) => -EVAL_CAP,
+    }
+}
+
+// Turns a score for the side to move into one for white and back. Mate 0 stays as it is, only
+// the side to move can be checkmated.
+pub fn for_white(score: Score, turn: Color) -> Score {
+    match (turn, score) {
+        (Color::White, score) => score,
+        (Color::Black, Score::Centipawns(centipawns)) => Score::Centipawns(-centipawns),
+        (Color::Black, Score::Mate(moves)) => Score::Mate(-moves),
+    }
+}
+
+// Chance to win in percent, from the curve lichess fitted to its games
+fn win_percent(centipawns: i32) -> f64 {
+    50.0 + 50.0 * (2.0 / (1.0 + (-0.00368208 * centipawns as f64).exp()) - 1.0)
+}
+
+fn move_accuracy(win_before: f64, win_after: f64) -> f64 {
+    let drop = (win_before - win_after).max(0.0);
+    (103.1668 * (-0.04354 * drop).exp() - 3.1669).clamp(0.0, 100.0)
+}
+
+// The position before every move and the one after the last
+pub fn positions(moves: &[Move]) -> Vec<Position> {
+    let mut positions = vec![Position::new()];
+    for &mv in moves {
+        let next = positions[positions.len() - 1].play(mv);
+        positions.push(next);
+    }
+    positions
+}
+
+// `evaluations` has one entry per position, see `positions`
+pub fn build(moves: &[Move], evaluations: &[Evaluation]) -> Report {
+    let positions = positions(moves);
+    let mut reports = Vec::new();
+    let mut totals = [(0.0, 0.0, 0u32), (0.0, 0.0, 0u32)];
+    let mut players = [PlayerReport::default(), PlayerReport::default()];
+    for (ply, &mv) in moves.iter().enumerate() {
+        let position = &positions[ply];
+        let before = evaluations[ply];
+        let after = evaluations[ply + 1];
+        let before_cp = centipawns(before.score);
+        let after_cp = -centipawns(after.score);
+        let (loss, classification) = if before.best == Some(mv) {
+            (0, Classification::Best)
+        } else {
+            let loss = (before_cp - after_cp).max(0);
+            (loss, Classification::from_loss(loss))
+        };
+
+        let side = ply % 2;
+        let player = &mut players[side];
+        match classification {
+            Classification::Inaccuracy => player.inaccuracies += 1,
+            Classification::Mistake => player.mistakes += 1,
+            Classification::Blunder => player.blunders += 1,
+            _ => {}
+        }
+        let (loss_total, accuracy_total, count) = &mut totals[side];
+        *loss_total += loss as f64;
+        *accuracy_total += move_accuracy(win_percent(before_cp), win_percent(after_cp));
+        *count += 1;
+
+        reports.push(MoveReport {
+            san: position.san(mv),
+            best: before.best.map(|best| position.san(best)),
+            score: for_white(after.score, positions[ply + 1].turn()),
+            loss,
+            classification,
+        });
+    }
+    for (player, (loss_total, accuracy_total, count)) in players.iter_mut().zip(totals) {
+        if count > 0 {
+            player.acpl = loss_total / count as f64;
+            player.accuracy = accuracy_total / count as f64;
+        } else {
+            player.accuracy = 100.0;
+        }
+    }
+    let [white, black] = players;
+    Report {
+        moves: reports,
+        white,
+        black,
+    }
+}
+
+fn format_score(score: Score) -> String {
+    match score {
+        Score::Centipawns(centipawns) => centipawns.to_string(),
+        Score::Mate(moves) => format!("#{moves}"),
+    }
+}
+
+fn parse_score(text: &str) -> Option<Score> {
+    match text.strip_prefix('#') {
+        Some(moves) => moves.parse().ok().map(Score::Mate),
+        None => text.parse().ok().map(Score::Centipawns),
+    }
+}
+
+// The `evaluations` and `best_moves` columns of `game_analysis`
+pub fn encode(moves: &[Move], evaluations: &[Evaluation]) -> (String, String) {
+    let positions = positions(moves);
+    let scores: Vec<String> = evaluations
+        .iter()
+        .zip(&positions)
+        .map(|(evaluation, position)| format_score(for_white(evaluation.score, position.turn())))
+        .collect();
+    let best: Vec<String> = evaluations
+        .iter()
+        .map(|evaluation| evaluation.best.map_or("-".to_string(), |best| best.uci()))
+        .collect();
+    (scores.join(" "), best.join(" "))
+}
+
+pub fn decode(moves: &[Move], scores: &str, best_moves: &str) -> Option<Vec<Evaluation>> {
+    let positions = positions(moves);
+    let scores: Vec<&str> = scores.split_whitespace().collect();
+    let best_moves: Vec<&str> = best_moves.split_whitespace().collect();
+    if scores.len() != positions.len() || best_moves.len() != positions.len() {
+        return None;
+    }
+    positions
+        .iter()
+        .zip(scores.iter().zip(best_moves))
+        .map(|(position, (score, best))| {
+            let score = for_white(parse_score(score)?, position.turn());
+            let best = match best {
+                "-" => None,
+                uci => Some(position.parse_uci(uci)?),
+            };
+            Some(Evaluation { score, best })
+        })
+        .collect()
+}
+
+fn format_eval(score: Score) -> String {
+    match score {
+        Score::Centipawns(centipawns) => format!("{:.2}", centipawns as f64 / 100.0),
+        Score::Mate(moves) => format!("#{moves}"),
+    }
+}
+
+// Moves for the annotated PGN: evaluations as `[%eval]` comments, as most viewers draw them,
+// and the engine's choice named after anything worse than an inaccuracy
+pub fn annotate(report: &Report) -> Vec<PgnMove> {
+    report
+        .moves
+        .iter()
+        .map(|mv| {
+            let mut comment = String::new();
+            if let (Some(_), Some(best)) = (mv.classification.nag(), &mv.best) {
+                let mut name = mv.classification.as_str().to_string();
+                name[..1].make_ascii_uppercase();
+                comment.push_str(&format!("{name}. {best} was best. "));
+            }
+            comment.push_str(&format!("[%eval {}]", format_eval(mv.score)));
+            PgnMove {
+                san: mv.san.clone(),
+                nag: mv.classification.nag(),
+                comment: Some(comment),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluation(position: &Position, score: Score, best: Option<&str>) -> Evaluation {
+        Evaluation {
+            score,
+            best: best.and_then(|san| position.parse_move(san)),
+        }
+    }
+
+    // 1. e4 e5 2. Qh5 Nc6 3. Bc4 Nf6?? 4. Qxf7#
+    fn scholars_mate() -> (Vec<Move>, Vec<Evaluation>) {
+        let moves = Position::parse_game("e4 e5 Qh5 Nc6 Bc4 Nf6 Qxf7#").unwrap();
+        let positions = positions(&moves);
+        let evaluations = vec![
+            evaluation(&positions[0], Score::Centipawns(30), Some("e4")),
+            evaluation(&positions[1], Score::Centipawns(-30), Some("e5")),
+            evaluation(&positions[2], Score::Centipawns(25), Some("Nf3")),
+            // The early queen sortie cost white a little
+            evaluation(&positions[3], Score::Centipawns(-10), Some("Nc6")),
+            evaluation(&positions[4], Score::Centipawns(30), Some("Nf3")),
+            evaluation(&positions[5], Score::Centipawns(20), Some("g6")),
+            evaluation(&positions[6], Score::Mate(1), Some("Qxf7#")),
+            Evaluation::terminal(&positions[7]).unwrap(),
+        ];
+        (moves, evaluations)
+    }
+
+    #[test]
+    fn test_classification_by_loss() {
+        let (moves, evaluations) = scholars_mate();
+        let report = build(&moves, &evaluations);
+        let classes: Vec<&str> = report
+            .moves
+            .iter()
+            .map(|mv| mv.classification.as_str())
+            .collect();
+        assert_eq!(
+            classes,
+            vec![
+                "best",
+                "best",
+                "good",
+                "best",
+                "inaccuracy",
+                "blunder",
+                "best"
+            ]
+        );
+        assert_eq!(report.moves[2].loss, 15);
+        assert_eq!(report.moves[4].loss, 50);
+        assert_eq!(report.moves[5].loss, 20 + EVAL_CAP);
+        assert_eq!(report.moves[5].best.as_deref(), Some("g6"));
+        assert_eq!(report.moves[5].score, Score::Mate(1));
+        assert_eq!(report.moves[6].score, Score::Mate(0));
+
+        assert_eq!(report.black.blunders, 1);
+        assert_eq!(report.white.inaccuracies, 1);
+        assert_eq!(report.white.acpl, (15 + 50) as f64 / 4.0);
+        assert!(report.white.accuracy > report.black.accuracy);
+        assert!(report.black.accuracy < 80.0);
+    }
+
+    #[test]
+    fn test_accuracy_curve() {
+        assert_eq!(win_percent(0), 50.0);
+        assert!(win_percent(EVAL_CAP) > 97.0);
+        assert!((move_accuracy(60.0, 60.0) - 100.0).abs() < 0.01);
+        // A better position than expected is not penalised
+        assert!((move_accuracy(40.0, 60.0) - 100.0).abs() < 0.01);
+        assert_eq!(move_accuracy(100.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn test_encode_round_trip() {
+        let (moves, evaluations) = scholars_mate();
+        let (scores, best) = encode(&moves, &evaluations);
+        assert_eq!(scores, "30 30 25 10 30 -20 #1 #0");
+        assert_eq!(best, "e2e4 e7e5 g1f3 b8c6 g1f3 g7g6 h5f7 -");
+        assert_eq!(decode(&moves, &scores, &best), Some(evaluations));
+        assert_eq!(decode(&moves, "30 30", &best), None);
+    }
+
+    #[test]
+    fn test_annotations() {
+        let (moves, evaluations) = scholars_mate();
+        let annotated = annotate(&build(&moves, &evaluations));
+        assert_eq!(annotated[0].comment.as_deref(), Some("[%eval 0.30]"));
+        assert_eq!(annotated[5].nag, Some(4));
+        assert_eq!(
+            annotated[5].comment.as_deref(),
+            Some("Blunder. g6 was best. [%eval #1]")
+        );
+        assert_eq!(annotated[6].nag, None);
+    }
+}
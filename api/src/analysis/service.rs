@@ -0,0 +1,334 @@
+use std::pin::Pin;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+use sea_orm::DatabaseConnection;
+use tokio::sync::mpsc;
+use tokio_stream::{wrappers::ReceiverStream, Stream};
+use tonic::{Request, Response, Status};
+
+use entity::entities::game;
+use service::{game::query as game_query, game_analysis::query, users::query as user_query};
+
+use super::{
+    analysis_server::Analysis,
+    lines::{search_lines, uci_lines, LineCollector},
+    move_analysis,
+    report::{self, PlayerReport},
+    AnalysisUpdate, AnalyzeRequest, GameAnalysis, GameAnalysisRequest, MoveAnalysis,
+    PlayerAnalysis, TablebaseResult,
+};
+use crate::auth::interceptor::{authenticated_user, log_request};
+use crate::chess::{
+    engine::{
+        position::Position,
+        search::{Limits, Search, MAX_PLY},
+    },
+    history, pgn,
+    polyglot::OpeningBook,
+    syzygy::Tablebase,
+    uci::{EnginePool, Score},
+};
+
+const DEFAULT_TIME: Duration = Duration::from_secs(3);
+const MAX_TIME: Duration = Duration::from_secs(60);
+const MAX_LINES: usize = 5;
+const TT_ENTRIES: usize = 1 << 20;
+
+type UpdateSender = mpsc::Sender<Result<AnalysisUpdate, Status>>;
+
+pub struct AnalysisService {
+    pub db_connection: DatabaseConnection,
+    // Used instead of the built-in engine when the server has one
+    pub engine: Option<EnginePool>,
+    pub book: Option<OpeningBook>,
+    pub tablebase: Option<Tablebase>,
+}
+
+impl AnalysisService {
+    // In SAN, heaviest first
+    fn book_moves(&self, position: &Position) -> Vec<String> {
+        self.book.as_ref().map_or(Vec::new(), |book| {
+            book.moves(position)
+                .into_iter()
+                .map(|(mv, _)| position.san(mv))
+                .collect()
+        })
+    }
+
+    fn tablebase_result(&self, position: &Position) -> Option<TablebaseResult> {
+        let tablebase = self.tablebase.as_ref()?;
+        let wdl = tablebase.probe_wdl(position)?;
+        let best_moves = tablebase.best_moves(position).unwrap_or_default();
+        Some(TablebaseResult {
+            wdl: wdl.as_str().to_string(),
+            dtz: tablebase.probe_dtz(position),
+            best_moves: best_moves.into_iter().map(|mv| position.san(mv)).collect(),
+        })
+    }
+
+    async fn username(&self, user_id: i32) -> Result<String, Status> {
+        let user = user_query::Query::find_user_by_id(&self.db_connection, user_id)
+            .await
+            .map_err(|_| Status::internal("Could not load user"))?;
+        Ok(user.map(|user| user.username).unwrap_or_default())
+    }
+
+    // The seven tag roster
+    async fn pgn_tags(&self, game_row: &game::Model) -> Result<Vec<(&str, String)>, Status> {
+        let event = if game_row.rated {
+            "Rated game"
+        } else {
+            "Casual game"
+        };
+        Ok(vec![
+            ("Event", event.to_string()),
+            ("Site", "chessbicos".to_string()),
+            ("Date", game_row.created_at.format("%Y.%m.%d").to_string()),
+            ("Round", "-".to_string()),
+            ("White", self.username(game_row.player_white).await?),
+            ("Black", self.username(game_row.player_black).await?),
+            ("Result", result(game_row).to_string()),
+        ])
+    }
+}
+
+fn result(game_row: &game::Model) -> &str {
+    game_row.result.as_deref().unwrap_or("*")
+}
+
+fn to_player(report: &PlayerReport) -> PlayerAnalysis {
+    PlayerAnalysis {
+        accuracy: report.accuracy,
+        acpl: report.acpl,
+        inaccuracies: report.inaccuracies,
+        mistakes: report.mistakes,
+        blunders: report.blunders,
+    }
+}
+
+// A time is always set so no analysis runs for longer than MAX_TIME
+fn limits(request: &AnalyzeRequest) -> Limits {
+    let depth = (request.depth > 0).then_some(request.depth.min(MAX_PLY as i32 - 1));
+    let time = match (request.time_ms, depth) {
+        (0, Some(_)) => MAX_TIME,
+        (0, None) => DEFAULT_TIME,
+        (time_ms, _) => Duration::from_millis(time_ms as u64).min(MAX_TIME),
+    };
+    Limits {
+        depth,
+        time: Some(time),
+        ..Default::default()
+    }
+}
+
+// `base` has what is known before the search: the engine, book moves and tablebase result
+fn update(base: &AnalysisUpdate, depth: i32, nodes: u64, elapsed: Duration) -> AnalysisUpdate {
+    let time_ms = elapsed.as_millis() as u64;
+    AnalysisUpdate {
+        depth,
+        nodes,
+        nodes_per_second: nodes * 1000 / time_ms.max(1),
+        time_ms,
+        ..base.clone()
+    }
+}
+
+fn analyse_built_in(
+    position: Position,
+    base: AnalysisUpdate,
+    limits: Limits,
+    lines: usize,
+    sender: UpdateSender,
+) {
+    let started = Instant::now();
+    let mut search = Search::new(TT_ENTRIES);
+    let stop = search.stop_handle();
+    let base = AnalysisUpdate {
+        engine: "built-in".to_string(),
+        ..base
+    };
+    let mut last = update(&base, 0, 0, Duration::ZERO);
+    search.run_multipv(&position, &[], limits, lines, &mut |infos| {
+        let nodes = infos.last().map_or(0, |info| info.nodes);
+        last = update(&base, infos[0].depth, nodes, started.elapsed());
+        last.lines = search_lines(&position, infos);
+        // Nobody is listening any more
+        if sender.blocking_send(Ok(last.clone())).is_err() {
+            stop.store(true, Ordering::Relaxed);
+        }
+    });
+    last.time_ms = started.elapsed().as_millis() as u64;
+    last.done = true;
+    let _ = sender.blocking_send(Ok(last));
+}
+
+async fn analyse_uci(
+    engine: &EnginePool,
+    position: &Position,
+    base: AnalysisUpdate,
+    limits: Limits,
+    lines: usize,
+    sender: &UpdateSender,
+) -> Result<(), Status> {
+    let mut engine = engine
+        .acquire()
+        .await
+        .map_err(|err| Status::unavailable(format!("Engine is not available: {err}")))?;
+    let started = Instant::now();
+    let mut collector = LineCollector::new(lines);
+    let base = AnalysisUpdate {
+        engine: "uci".to_string(),
+        ..base
+    };
+    let mut last = update(&base, 0, 0, Duration::ZERO);
+    engine
+        .go(&position.to_fen(), &[], limits, lines, &mut |info| {
+            if let Some(infos) = collector.push(info.clone()) {
+                let nodes = infos.iter().filter_map(|info| info.nodes).max();
+                let depth = infos[0].depth.unwrap_or_default();
+                last = update(&base, depth, nodes.unwrap_or_default(), started.elapsed());
+                last.lines = uci_lines(position, &infos);
+                // Intermediate updates are skipped rather than holding up the engine
+                let _ = sender.try_send(Ok(last.clone()));
+            }
+        })
+        .await
+        .map_err(|err| Status::internal(format!("Engine failed: {err}")))?;
+    last.time_ms = started.elapsed().as_millis() as u64;
+    last.done = true;
+    let _ = sender.send(Ok(last)).await;
+    Ok(())
+}
+
+#[tonic::async_trait]
+impl Analysis for AnalysisService {
+    type AnalyzeStream = Pin<Box<dyn Stream<Item = Result<AnalysisUpdate, Status>> + Send>>;
+
+    async fn analyze(
+        &self,
+        request: Request<AnalyzeRequest>,
+    ) -> Result<Response<Self::AnalyzeStream>, Status> {
+        log_request("Analyze", &request);
+        authenticated_user(&request)?;
+        let r = request.into_inner();
+        // Also refused when the engine could not search it: a king missing or the side that just
+        // moved in check
+        let position = Position::from_fen(&r.fen)
+            .map_err(|_| Status::invalid_argument("Invalid FEN or illegal position"))?;
+        let limits = limits(&r);
+        // An engine shows no more lines than there are moves
+        let legal = position.legal_moves().len();
+        let lines = (r.multipv as usize).clamp(1, MAX_LINES).min(legal.max(1));
+        let base = AnalysisUpdate {
+            book_moves: self.book_moves(&position),
+            tablebase: self.tablebase_result(&position),
+            ..Default::default()
+        };
+
+        let (sender, receiver) = mpsc::channel(16);
+        match self.engine.clone() {
+            // With no legal moves there is nothing to ask the external engine
+            Some(engine) if legal > 0 => {
+                tokio::spawn(async move {
+                    let result =
+                        analyse_uci(&engine, &position, base, limits, lines, &sender).await;
+                    if let Err(status) = result {
+                        let _ = sender.send(Err(status)).await;
+                    }
+                });
+            }
+            _ => {
+                tokio::task::spawn_blocking(move || {
+                    analyse_built_in(position, base, limits, lines, sender)
+                });
+            }
+        }
+        let stream = ReceiverStream::new(receiver);
+        Ok(Response::new(Box::pin(stream) as Self::AnalyzeStream))
+    }
+
+    async fn get_game_analysis(
+        &self,
+        request: Request<GameAnalysisRequest>,
+    ) -> Result<Response<GameAnalysis>, Status> {
+        log_request("GetGameAnalysis", &request);
+        authenticated_user(&request)?;
+        let r = request.into_inner();
+        let game_id = match r.match_id.parse::<i32>() {
+            Ok(id) => id,
+            Err(_) => return Err(Status::invalid_argument("Invalid match id")),
+        };
+        let game_row = game_query::Query::find_game_by_id(&self.db_connection, game_id)
+            .await
+            .map_err(|_| Status::internal("Could not load game"))?
+            .ok_or_else(|| Status::not_found("Game not found"))?;
+        if game_row.state != "finished" {
+            return Err(Status::failed_precondition("Game is not finished"));
+        }
+        let analysis = query::Query::find_analysis_by_game(&self.db_connection, game_id)
+            .await
+            .map_err(|_| Status::internal("Could not load analysis"))?;
+        let mut reply = GameAnalysis {
+            match_id: r.match_id,
+            state: "pending".to_string(),
+            eco: game_row.eco.clone().unwrap_or_default(),
+            opening: game_row.opening.clone().unwrap_or_default(),
+            ..Default::default()
+        };
+        let analysis = match analysis {
+            Some(analysis) => analysis,
+            None => return Ok(Response::new(reply)),
+        };
+        // Still pending for clients while a failed run waits to be tried again
+        if analysis.state != "retrying" {
+            reply.state = analysis.state.clone();
+        }
+        reply.engine = analysis.engine.clone();
+        if analysis.state != "done" {
+            return Ok(Response::new(reply));
+        }
+
+        let moves = history::moves(&self.db_connection, game_id)
+            .await
+            .map_err(|_| Status::internal("Could not load moves"))?;
+        let moves =
+            Position::parse_game(&moves).map_err(|_| Status::internal("Could not replay game"))?;
+        let evaluations = report::decode(&moves, &analysis.evaluations, &analysis.best_moves)
+            .ok_or_else(|| Status::internal("Could not read analysis"))?;
+        let report = report::build(&moves, &evaluations);
+        // The game is in the book up to the first move that is not
+        let mut in_book = self.book.is_some();
+        let positions = report::positions(&moves);
+        reply.moves = report
+            .moves
+            .iter()
+            .enumerate()
+            .map(|(ply, mv)| MoveAnalysis {
+                ply: ply as u32 + 1,
+                san: mv.san.clone(),
+                score: Some(match mv.score {
+                    Score::Centipawns(centipawns) => move_analysis::Score::Centipawns(centipawns),
+                    Score::Mate(moves) => move_analysis::Score::Mate(moves),
+                }),
+                best: mv.best.clone().unwrap_or_default(),
+                classification: mv.classification.as_str().to_string(),
+                loss: mv.loss,
+                book: {
+                    in_book = in_book && self.book_moves(&positions[ply]).contains(&mv.san);
+                    in_book
+                },
+            })
+            .collect();
+        reply.white = Some(to_player(&report.white));
+        reply.black = Some(to_player(&report.black));
+        let mut tags = self.pgn_tags(&game_row).await?;
+        if let (Some(eco), Some(opening)) = (&game_row.eco, &game_row.opening) {
+            tags.push(("ECO", eco.clone()));
+            tags.push(("Opening", opening.clone()));
+        }
+        tags.push(("Annotator", analysis.engine.clone()));
+        reply.pgn = pgn::write(&tags, &report::annotate(&report), result(&game_row));
+        Ok(Response::new(reply))
+    }
+}
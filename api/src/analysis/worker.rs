@@ -0,0 +1,217 @@
+// Background job analysing every finished game once: the engine evaluates each position and
+// the report built from that is stored in `game_analysis`. Games are picked up from the
+// database, so those finished while the server was down are analysed after a restart. A game
+// the engine fails on is tried again a few times with growing pauses, then given up on.
+use std::time::Duration;
+
+use chrono::{NaiveDateTime, Utc};
+use sea_orm::DatabaseConnection;
+use tonic::Status;
+
+use entity::entities::{game, game_analysis};
+use service::game_analysis::{mutation, query};
+
+use super::{
+    lines::search_score,
+    report::{self, Evaluation},
+};
+use crate::chess::{
+    engine::{
+        position::{Move, Position, START_FEN},
+        search::{Limits, Search},
+    },
+    history,
+    uci::{EnginePool, Score},
+};
+use crate::puzzle::generator;
+
+const INTERVAL: Duration = Duration::from_secs(10);
+const GAMES_PER_RUN: u64 = 5;
+const TT_ENTRIES: usize = 1 << 18;
+const MAX_ATTEMPTS: i32 = 5;
+// Before the first retry, doubled for every one after
+const RETRY_MINUTES: i64 = 1;
+// Per position
+const LIMITS: Limits = Limits {
+    depth: Some(10),
+    nodes: Some(300_000),
+    time: Some(Duration::from_millis(300)),
+    noise: 0,
+};
+
+pub async fn run(db: DatabaseConnection, engine: Option<EnginePool>) {
+    let mut interval = tokio::time::interval(INTERVAL);
+    loop {
+        interval.tick().await;
+        let now = Utc::now().naive_utc();
+        let games = match query::Query::find_unanalysed_games(&db, now, GAMES_PER_RUN).await {
+            Ok(games) => games,
+            Err(err) => {
+                println!("Error: could not load games to analyse: {err}");
+                continue;
+            }
+        };
+        for (game_row, previous) in games {
+            if let Err(err) = analyse(&db, engine.as_ref(), &game_row, previous.as_ref()).await {
+                println!("Error: could not analyse game {}: {err}", game_row.id);
+            }
+        }
+    }
+}
+
+// When a game whose analysis failed for the given time is tried again, none once it is given up on
+fn retry_at(attempts: i32, now: NaiveDateTime) -> Option<NaiveDateTime> {
+    if attempts >= MAX_ATTEMPTS {
+        return None;
+    }
+    Some(now + chrono::Duration::minutes(RETRY_MINUTES << (attempts - 1)))
+}
+
+fn evaluate_built_in(moves: &[Move]) -> Vec<Evaluation> {
+    let mut search = Search::new(TT_ENTRIES);
+    let mut previous = Vec::new();
+    report::positions(moves)
+        .iter()
+        .map(|position| {
+            let evaluation = Evaluation::terminal(position).unwrap_or_else(|| {
+                let info = search.run(position, &previous, LIMITS, &mut |_| {});
+                Evaluation {
+                    score: info
+                        .as_ref()
+                        .map_or(Score::Centipawns(0), |info| search_score(info.score)),
+                    best: info.and_then(|info| info.best()),
+                }
+            });
+            previous.push(position.key());
+            evaluation
+        })
+        .collect()
+}
+
+async fn evaluate_uci(engine: &EnginePool, moves: &[Move]) -> Result<Vec<Evaluation>, Status> {
+    let uci: Vec<String> = moves.iter().map(Move::uci).collect();
+    let mut engine = engine
+        .acquire()
+        .await
+        .map_err(|err| Status::unavailable(format!("Engine is not available: {err}")))?;
+    let mut evaluations = Vec::new();
+    for (ply, position) in report::positions(moves).iter().enumerate() {
+        if let Some(evaluation) = Evaluation::terminal(position) {
+            evaluations.push(evaluation);
+            continue;
+        }
+        let mut score = Score::Centipawns(0);
+        let best = engine
+            .go(START_FEN, &uci[..ply], LIMITS, 1, &mut |info| {
+                if let Some(info_score) = info.score {
+                    score = info_score;
+                }
+            })
+            .await
+            .map_err(|err| Status::internal(format!("Engine failed: {err}")))?;
+        evaluations.push(Evaluation {
+            score,
+            best: best.best.and_then(|best| position.parse_uci(&best)),
+        });
+    }
+    Ok(evaluations)
+}
+
+async fn analyse(
+    db: &DatabaseConnection,
+    engine: Option<&EnginePool>,
+    game_row: &game::Model,
+    previous: Option<&game_analysis::Model>,
+) -> Result<(), Status> {
+    let mut analysis = game_analysis::Model {
+        id: 0,
+        game_id: game_row.id,
+        state: "failed".to_string(),
+        engine: "built-in".to_string(),
+        evaluations: String::new(),
+        best_moves: String::new(),
+        white_accuracy: None,
+        black_accuracy: None,
+        white_acpl: None,
+        black_acpl: None,
+        created_at: Default::default(),
+        attempts: previous.map_or(0, |previous| previous.attempts),
+        retry_at: None,
+    };
+    let mut mined = None;
+    let mut failure = None;
+    let moves = history::moves(db, game_row.id)
+        .await
+        .map_err(|_| Status::internal("Could not load moves"))?;
+    // A game that cannot be replayed is stored as failed so it is not tried again
+    if let Ok(moves) = Position::parse_game(&moves) {
+        let evaluations = match engine {
+            Some(engine) => {
+                analysis.engine = "uci".to_string();
+                evaluate_uci(engine, &moves).await
+            }
+            None => {
+                let moves = moves.clone();
+                tokio::task::spawn_blocking(move || evaluate_built_in(&moves))
+                    .await
+                    .map_err(|_| Status::internal("Engine failed"))
+            }
+        };
+        match evaluations {
+            Ok(evaluations) => {
+                let report = report::build(&moves, &evaluations);
+                let (scores, best_moves) = report::encode(&moves, &evaluations);
+                analysis.state = "done".to_string();
+                analysis.evaluations = scores;
+                analysis.best_moves = best_moves;
+                analysis.white_accuracy = Some(report.white.accuracy);
+                analysis.black_accuracy = Some(report.black.accuracy);
+                analysis.white_acpl = Some(report.white.acpl);
+                analysis.black_acpl = Some(report.black.acpl);
+                mined = Some((moves, evaluations));
+            }
+            // The engine may be back later, so the game is tried again after a pause
+            Err(err) => {
+                analysis.attempts += 1;
+                analysis.retry_at = retry_at(analysis.attempts, Utc::now().naive_utc());
+                if analysis.retry_at.is_some() {
+                    analysis.state = "retrying".to_string();
+                }
+                failure = Some(err);
+            }
+        }
+    }
+    let stored = match previous {
+        Some(previous) => mutation::Mutation::update_analysis(db, previous.id, analysis).await,
+        None => mutation::Mutation::create_analysis(db, analysis).await,
+    };
+    stored.map_err(|_| Status::internal("Could not store analysis"))?;
+    if let Some(err) = failure {
+        return Err(err);
+    }
+    // The analysis is kept when no puzzles could be mined
+    if let Some((moves, evaluations)) = mined {
+        if let Err(err) = generator::mine(db, game_row, moves, evaluations).await {
+            println!(
+                "Error: could not mine puzzles from game {}: {err}",
+                game_row.id
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_backs_off_then_gives_up() {
+        let now = NaiveDateTime::default();
+        let after = |attempts| retry_at(attempts, now).map(|at| (at - now).num_minutes());
+        assert_eq!(after(1), Some(1));
+        assert_eq!(after(2), Some(2));
+        assert_eq!(after(4), Some(8));
+        assert_eq!(after(MAX_ATTEMPTS), None);
+    }
+}
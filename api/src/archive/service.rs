@@ -0,0 +1,336 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, NaiveDateTime};
+use sea_orm::{DatabaseConnection, DbErr};
+use tonic::{Request, Response, Status};
+
+use entity::entities::{game, time_control, users};
+use service::{
+    game::query::{self, ArchiveOrder, GameFilter, Outcome, Side},
+    game_move::query as game_move_query,
+    ratings::pool::Pool,
+    time_control::query as time_control_query,
+    users::query as user_query,
+};
+
+use super::{
+    game_archive_server::GameArchive, ArchivedGame, GetGameRequest, ListGamesRequest,
+    ListGamesResponse,
+};
+use crate::auth::interceptor::{authenticated_user, log_request};
+use crate::chess::history;
+use crate::paging::page_size;
+
+pub struct GameArchiveService {
+    pub db_connection: DatabaseConnection,
+}
+
+fn not_empty(text: &str) -> Option<String> {
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+fn timestamp(seconds: i64) -> Result<Option<NaiveDateTime>, Status> {
+    if seconds == 0 {
+        return Ok(None);
+    }
+    DateTime::from_timestamp(seconds, 0)
+        .map(|date| Some(date.naive_utc()))
+        .ok_or_else(|| Status::invalid_argument("Invalid date"))
+}
+
+// Everything but the players, which need looking up
+fn filter(
+    r: &ListGamesRequest,
+    time_controls: &[time_control::Model],
+) -> Result<GameFilter, Status> {
+    let side = match r.color.as_str() {
+        "" => None,
+        "white" => Some(Side::White),
+        "black" => Some(Side::Black),
+        _ => return Err(Status::invalid_argument("Unknown color")),
+    };
+    let outcome = match r.result.as_str() {
+        "" => None,
+        "win" => Some(Outcome::Win),
+        "draw" => Some(Outcome::Draw),
+        "loss" => Some(Outcome::Loss),
+        _ => return Err(Status::invalid_argument("Unknown result")),
+    };
+    let mut pools = Vec::new();
+    for pool in &r.pools {
+        pools.push(
+            Pool::parse(pool).ok_or_else(|| Status::invalid_argument("Unknown rating pool"))?,
+        );
+    }
+    // No time control of the pools matches nothing rather than everything
+    let mut time_controls: Vec<i32> = time_controls
+        .iter()
+        .filter(|time_control| pools.contains(&Pool::from_time(time_control.time)))
+        .map(|time_control| time_control.id)
+        .collect();
+    if !pools.is_empty() && time_controls.is_empty() {
+        time_controls.push(0);
+    }
+    let since = timestamp(r.since)?;
+    let until = timestamp(r.until)?;
+    if let (Some(since), Some(until)) = (since, until) {
+        if until <= since {
+            return Err(Status::invalid_argument("Invalid date range"));
+        }
+    }
+    Ok(GameFilter {
+        player_id: 0,
+        opponent_id: None,
+        side,
+        outcome,
+        time_controls,
+        eco: not_empty(&r.eco),
+        opening: not_empty(&r.opening),
+        since,
+        until,
+        rated: r.rated,
+    })
+}
+
+fn order(r: &ListGamesRequest) -> Result<ArchiveOrder, Status> {
+    match r.sort.as_str() {
+        "" | "newest" => Ok(ArchiveOrder::NewestFirst),
+        "oldest" => Ok(ArchiveOrder::OldestFirst),
+        _ => Err(Status::invalid_argument("Unknown sort")),
+    }
+}
+
+// `plies` is the number of moves of the game
+fn to_archived_game(
+    game_row: &game::Model,
+    plies: i64,
+    usernames: &HashMap<i32, String>,
+    time_controls: &[time_control::Model],
+) -> ArchivedGame {
+    let username = |id| usernames.get(&id).cloned().unwrap_or_default();
+    let time_control = time_controls
+        .iter()
+        .find(|time_control| time_control.id == game_row.time_control);
+    ArchivedGame {
+        id: game_row.id,
+        white: username(game_row.player_white),
+        black: username(game_row.player_black),
+        result: game_row.result.clone().unwrap_or_default(),
+        rated: game_row.rated,
+        time_control: time_control
+            .map(|time_control| time_control.display_name.clone())
+            .unwrap_or_default(),
+        pool: time_control
+            .map(|time_control| Pool::from_time(time_control.time).as_str().to_string())
+            .unwrap_or_default(),
+        eco: game_row.eco.clone().unwrap_or_default(),
+        opening: game_row.opening.clone().unwrap_or_default(),
+        started_at: game_row.created_at.and_utc().timestamp(),
+        finished_at: game_row
+            .updated_at
+            .unwrap_or(game_row.created_at)
+            .and_utc()
+            .timestamp(),
+        plies: plies as i32,
+        moves: String::new(),
+        fen: String::new(),
+    }
+}
+
+async fn find_player(db: &DatabaseConnection, username: &str) -> Result<users::Model, Status> {
+    user_query::Query::find_user_by_username(db, username)
+        .await
+        .map_err(|_| Status::internal("Could not load player"))?
+        .filter(|user| user.deleted_at.is_none())
+        .ok_or_else(|| Status::not_found("Player not found"))
+}
+
+async fn usernames(
+    db: &DatabaseConnection,
+    games: &[game::Model],
+) -> Result<HashMap<i32, String>, DbErr> {
+    let ids = games
+        .iter()
+        .flat_map(|game_row| [game_row.player_white, game_row.player_black])
+        .collect();
+    Ok(user_query::Query::find_users_by_ids(db, ids)
+        .await?
+        .into_iter()
+        .map(|user| (user.id, user.username))
+        .collect())
+}
+
+#[tonic::async_trait]
+impl GameArchive for GameArchiveService {
+    async fn list_games(
+        &self,
+        request: Request<ListGamesRequest>,
+    ) -> Result<Response<ListGamesResponse>, Status> {
+        log_request("ListGames", &request);
+        let user = authenticated_user(&request)?;
+        let r = request.into_inner();
+        let db = &self.db_connection;
+        let time_controls = time_control_query::Query::find_time_controls(db)
+            .await
+            .map_err(|_| Status::internal("Could not load time controls"))?;
+        let mut filter = filter(&r, &time_controls)?;
+        let order = order(&r)?;
+        let page_size = page_size(r.page_size)?;
+        if r.cursor < 0 {
+            return Err(Status::invalid_argument("Invalid cursor"));
+        }
+        filter.player_id = match r.player.as_str() {
+            "" => user.id,
+            player => find_player(db, player).await?.id,
+        };
+        if !r.opponent.is_empty() {
+            filter.opponent_id = Some(find_player(db, &r.opponent).await?.id);
+        }
+
+        let total = query::Query::count_archived_games(db, &filter)
+            .await
+            .map_err(|_| Status::internal("Could not count games"))?;
+        // One more than asked for tells whether there is another page
+        let mut games = query::Query::find_archived_games(
+            db,
+            &filter,
+            order,
+            (r.cursor > 0).then_some(r.cursor),
+            page_size as u64 + 1,
+        )
+        .await
+        .map_err(|_| Status::internal("Could not load games"))?;
+        let next_cursor = if games.len() > page_size as usize {
+            games.truncate(page_size as usize);
+            games.last().map_or(0, |last| last.id)
+        } else {
+            0
+        };
+        let usernames = usernames(db, &games)
+            .await
+            .map_err(|_| Status::internal("Could not load players"))?;
+        let plies = game_move_query::Query::count_moves(
+            db,
+            games.iter().map(|game_row| game_row.id).collect(),
+        )
+        .await
+        .map_err(|_| Status::internal("Could not load moves"))?;
+        Ok(Response::new(ListGamesResponse {
+            games: games
+                .iter()
+                .map(|game_row| {
+                    let plies = plies.get(&game_row.id).copied().unwrap_or(0);
+                    to_archived_game(game_row, plies, &usernames, &time_controls)
+                })
+                .collect(),
+            total,
+            next_cursor,
+        }))
+    }
+
+    async fn get_game(
+        &self,
+        request: Request<GetGameRequest>,
+    ) -> Result<Response<ArchivedGame>, Status> {
+        log_request("GetGame", &request);
+        authenticated_user(&request)?;
+        let r = request.into_inner();
+        let db = &self.db_connection;
+        // Games still being played are for spectating, with its delay
+        let game_row = query::Query::find_game_by_id(db, r.game_id)
+            .await
+            .map_err(|_| Status::internal("Could not load game"))?
+            .filter(|game_row| game_row.result.is_some())
+            .ok_or_else(|| Status::not_found("Game not found"))?;
+        let time_control =
+            time_control_query::Query::find_time_control_by_id(db, game_row.time_control)
+                .await
+                .map_err(|_| Status::internal("Could not load time control"))?;
+        let usernames = usernames(db, std::slice::from_ref(&game_row))
+            .await
+            .map_err(|_| Status::internal("Could not load players"))?;
+        let moves = history::moves(db, game_row.id)
+            .await
+            .map_err(|_| Status::internal("Could not load moves"))?;
+        let plies = moves.split_whitespace().count() as i64;
+        let mut archived = to_archived_game(&game_row, plies, &usernames, time_control.as_slice());
+        archived.moves = moves;
+        archived.fen = game_row.board;
+        Ok(Response::new(archived))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time_control(id: i32, time: i32) -> time_control::Model {
+        time_control::Model {
+            id,
+            display_name: format!("{} min", time / 60),
+            time,
+            created_at: Default::default(),
+            updated_at: None,
+        }
+    }
+
+    #[test]
+    fn test_filter() {
+        let time_controls = [
+            time_control(1, 60),
+            time_control(2, 300),
+            time_control(3, 600),
+        ];
+        let r = ListGamesRequest {
+            color: "black".to_string(),
+            result: "loss".to_string(),
+            pools: vec!["blitz".to_string(), "bullet".to_string()],
+            eco: "B2".to_string(),
+            since: 1_720_000_000,
+            rated: Some(false),
+            ..Default::default()
+        };
+        let parsed = filter(&r, &time_controls).unwrap();
+        assert_eq!(parsed.side, Some(Side::Black));
+        assert_eq!(parsed.outcome, Some(Outcome::Loss));
+        assert_eq!(parsed.time_controls, vec![1, 2]);
+        assert_eq!(parsed.eco.as_deref(), Some("B2"));
+        assert_eq!(parsed.opening, None);
+        assert_eq!(parsed.since.unwrap().and_utc().timestamp(), 1_720_000_000);
+        assert_eq!(parsed.until, None);
+        assert_eq!(parsed.rated, Some(false));
+
+        // A pool without time controls matches no game
+        let r = ListGamesRequest {
+            pools: vec!["correspondence".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(filter(&r, &time_controls).unwrap().time_controls, vec![0]);
+        assert!(filter(&ListGamesRequest::default(), &time_controls)
+            .unwrap()
+            .time_controls
+            .is_empty());
+
+        for r in [
+            ListGamesRequest {
+                color: "red".to_string(),
+                ..Default::default()
+            },
+            ListGamesRequest {
+                result: "won".to_string(),
+                ..Default::default()
+            },
+            ListGamesRequest {
+                pools: vec!["hyper".to_string()],
+                ..Default::default()
+            },
+            ListGamesRequest {
+                since: 1_720_000_000,
+                until: 1_710_000_000,
+                ..Default::default()
+            },
+        ] {
+            assert!(filter(&r, &time_controls).is_err());
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use tonic::{service::Interceptor, Request, Status};
+
+use super::{
+    session::RevokedSessions,
+    token::{self, TokenKind},
+};
+
+// Inserted into the request extensions once the access token has been validated
+#[derive(Clone, Copy, Debug)]
+pub struct AuthenticatedUser {
+    pub id: i32,
+    pub session_id: i32,
+}
+
+#[derive(Clone)]
+pub struct AuthInterceptor {
+    pub revoked_sessions: RevokedSessions,
+}
+
+impl Interceptor for AuthInterceptor {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        let header = match request.metadata().get("authorization") {
+            Some(header) => header
+                .to_str()
+                .map_err(|_| Status::unauthenticated("Invalid authorization header"))?,
+            None => return Err(Status::unauthenticated("Missing authorization header")),
+        };
+        let token = header.strip_prefix("Bearer ").unwrap_or(header);
+        let claims = token::validate(token, TokenKind::Access)?;
+        if self.revoked_sessions.is_revoked(claims.sid) {
+            return Err(Status::unauthenticated("Session has been revoked"));
+        }
+        request.extensions_mut().insert(AuthenticatedUser {
+            id: claims.sub,
+            session_id: claims.sid,
+        });
+        Ok(request)
+    }
+}
+
+pub fn authenticated_user<T>(request: &Request<T>) -> Result<AuthenticatedUser, Status> {
+    request
+        .extensions()
+        .get::<AuthenticatedUser>()
+        .copied()
+        .ok_or_else(|| Status::unauthenticated("Missing authenticated user"))
+}
+
+// The method and, once authenticated, the caller. Never the request itself: its metadata has
+// the bearer token and Login and Register carry passwords.
+pub fn log_request<T>(method: &str, request: &Request<T>) {
+    match request.extensions().get::<AuthenticatedUser>() {
+        Some(user) => println!("Got a request: {method} from user {}", user.id),
+        None => println!("Got a request: {method}"),
+    }
+}
@@ -1,3 +1,6 @@
-
+pub mod interceptor;
+pub mod password;
 pub mod service;
+pub mod session;
+pub mod token;
 tonic::include_proto!("auth"); // The string specified here must match the proto package name
@@ -0,0 +1,56 @@
+// Passwords are stored as Argon2 hashes in PHC string format. Hashing is slow on purpose, so it
+// runs on the blocking thread pool instead of holding up the requests sharing its thread.
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use tonic::Status;
+
+pub async fn hash(password: &str) -> Result<String, Status> {
+    let password = password.to_owned();
+    tokio::task::spawn_blocking(move || {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+    })
+    .await
+    .ok()
+    .and_then(Result::ok)
+    .ok_or_else(|| Status::internal("Could not hash password"))
+}
+
+// Accounts without a password, bots and deleted ones, cannot be logged into
+pub async fn verify(password: &str, stored: &str) -> bool {
+    if stored.is_empty() {
+        return false;
+    }
+    let (password, stored) = (password.to_owned(), stored.to_owned());
+    tokio::task::spawn_blocking(move || match PasswordHash::new(&stored) {
+        Ok(hash) => Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok(),
+        Err(_) => false,
+    })
+    .await
+    .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_hash_and_verify() {
+        let stored = hash("correct horse").await.unwrap();
+        assert_ne!(stored, "correct horse");
+        assert!(verify("correct horse", &stored).await);
+        assert!(!verify("battery staple", &stored).await);
+        // Salted, so the same password hashes differently
+        assert_ne!(hash("correct horse").await.unwrap(), stored);
+
+        // Anything not hashed is never accepted
+        assert!(!verify("hunter2", "hunter2").await);
+        assert!(!verify("", "").await);
+    }
+}
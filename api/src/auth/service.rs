@@ -1,14 +1,28 @@
-use sea_orm::DatabaseConnection;
+use chrono::{Duration, Utc};
+use sea_orm::{DatabaseConnection, SqlErr};
 use tonic::{Request, Response, Status};
 
+use entity::entities::{sessions, users};
+use service::sessions::{mutation as session_mutation, query as session_query};
 use service::users::{mutation, query};
-use entity::entities::users;
 
-use super::{auth_server::Auth, LoginRequest, LoginResponse, RegisterRequest, RegisterResponse};
+use crate::rate_limit::{client::TrustedProxies, login::LoginThrottle};
+
+use super::{
+    auth_server::Auth,
+    interceptor::log_request,
+    password,
+    session::RevokedSessions,
+    token::{self, TokenKind, TokenPair},
+    LoginRequest, LoginResponse, RefreshRequest, RegisterRequest, RegisterResponse,
+};
 
 pub struct AuthService {
     pub db_connection: DatabaseConnection,
-} 
+    pub login_throttle: LoginThrottle,
+    pub revoked_sessions: RevokedSessions,
+    pub trusted_proxies: TrustedProxies,
+}
 
 impl RegisterRequest {
     pub fn into(self) -> users::Model {
@@ -18,52 +32,206 @@ impl RegisterRequest {
             password: self.password,
             created_at: Default::default(),
             updated_at: None,
+            display_name: None,
+            country: None,
+            bio: None,
+            avatar_url: None,
+            deleted_at: None,
+            bot_level: None,
+        }
+    }
+}
+
+impl From<TokenPair> for LoginResponse {
+    fn from(tokens: TokenPair) -> Self {
+        LoginResponse {
+            token: tokens.access_token,
+            refresh_token: tokens.refresh_token,
+            expires_in: tokens.expires_in,
         }
     }
 }
 
+// Device and address shown when listing sessions
+struct ClientInfo {
+    device: Option<String>,
+    ip_address: Option<String>,
+}
+
+impl ClientInfo {
+    fn from_request<T>(request: &Request<T>, trusted_proxies: &TrustedProxies) -> ClientInfo {
+        let metadata = request.metadata();
+        let device = metadata
+            .get("user-agent")
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string());
+        let forwarded = metadata
+            .get("x-forwarded-for")
+            .and_then(|v| v.to_str().ok());
+        let ip_address = trusted_proxies
+            .client_ip(request.remote_addr().map(|addr| addr.ip()), forwarded)
+            .map(|ip| ip.to_string());
+        ClientInfo { device, ip_address }
+    }
+}
+
+// Names deleted accounts are renamed to
+fn is_reserved(username: &str) -> bool {
+    username
+        .to_lowercase()
+        .starts_with(mutation::DELETED_PREFIX)
+}
+
+fn session_expiry() -> chrono::NaiveDateTime {
+    (Utc::now() + Duration::seconds(token::refresh_token_ttl() as i64)).naive_utc()
+}
+
+impl AuthService {
+    async fn start_session(&self, user_id: i32, client: ClientInfo) -> Result<TokenPair, Status> {
+        let session = session_mutation::Mutation::create_session(
+            &self.db_connection,
+            sessions::Model {
+                id: 0,
+                user_id,
+                device: client.device,
+                ip_address: client.ip_address,
+                created_at: Default::default(),
+                last_seen_at: Default::default(),
+                expires_at: session_expiry(),
+                revoked_at: None,
+                refresh_generation: 0,
+            },
+        )
+        .await
+        .map_err(|_| Status::internal("Could not create session"))?;
+        token::issue_tokens(user_id, session.id, session.refresh_generation)
+    }
+}
+
 #[tonic::async_trait]
 impl Auth for AuthService {
     async fn login(
         &self,
         request: Request<LoginRequest>,
     ) -> Result<Response<LoginResponse>, Status> {
-        println!("Got a request: {:?}", request);
+        log_request("Login", &request);
+        let client = ClientInfo::from_request(&request, &self.trusted_proxies);
         let r = request.into_inner();
-        let db_result = query::Query::find_user_by_username_password(
-            &self.db_connection,
-            &r.username,
-            &r.password,
-        )
-        .await;
+        let ip_address = client.ip_address.clone();
+        self.login_throttle
+            .reserve(&r.username, ip_address.as_deref())
+            .await?;
+        let db_result = query::Query::find_user_for_login(&self.db_connection, &r.username).await;
         if db_result.is_err() {
             return Err(Status::invalid_argument("Invalid username or password"));
         }
-        let db_result = db_result.unwrap();
-        if db_result.is_none() {
-            return Err(Status::invalid_argument("Invalid username or password"));
-        }
-        let db_result = db_result.unwrap();
-        let response = LoginResponse {
-            token: db_result.id.to_string(),
+        let db_result = match db_result.unwrap() {
+            Some(user) if password::verify(&r.password, &user.password).await => user,
+            _ => return Err(Status::invalid_argument("Invalid username or password")),
         };
-        Ok(Response::new(response))
+        self.login_throttle
+            .record_success(&r.username, ip_address.as_deref())
+            .await;
+        let tokens = self.start_session(db_result.id, client).await?;
+        Ok(Response::new(tokens.into()))
     }
 
     async fn register(
         &self,
         request: Request<RegisterRequest>,
     ) -> Result<Response<RegisterResponse>, Status> {
-        println!("Got a request: {:?}", request);
+        log_request("Register", &request);
+        let client = ClientInfo::from_request(&request, &self.trusted_proxies);
         let r = request.into_inner();
-        let db_result = mutation::Mutation::create_user(&self.db_connection, r.into()).await;
-        if db_result.is_err() {
+        if is_reserved(&r.username) {
+            return Err(Status::invalid_argument("Username is not available"));
+        }
+        let existing = query::Query::find_user_by_username(&self.db_connection, &r.username)
+            .await
+            .map_err(|_| Status::internal("Could not load user"))?;
+        if existing.is_some() {
+            return Err(Status::already_exists("Username is already taken"));
+        }
+        let mut form_data = r.into();
+        form_data.password = password::hash(&form_data.password).await?;
+        let db_result = mutation::Mutation::create_user(&self.db_connection, form_data).await;
+        if let Err(err) = db_result {
+            // Lost a race with a concurrent registration of the same name
+            if let Some(SqlErr::UniqueConstraintViolation(_)) = err.sql_err() {
+                return Err(Status::already_exists("Username is already taken"));
+            }
             return Err(Status::invalid_argument("Invalid username or password"));
         }
         let db_result = db_result.unwrap();
+        let tokens = self.start_session(db_result.id, client).await?;
         let response = RegisterResponse {
-            token: db_result.id.to_string(),
+            token: tokens.access_token,
+            refresh_token: tokens.refresh_token,
+            expires_in: tokens.expires_in,
         };
         Ok(tonic::Response::new(response))
     }
+
+    async fn refresh(
+        &self,
+        request: Request<RefreshRequest>,
+    ) -> Result<Response<LoginResponse>, Status> {
+        log_request("Refresh", &request);
+        let r = request.into_inner();
+        let claims = token::validate(&r.refresh_token, TokenKind::Refresh)?;
+        let user = query::Query::find_user_by_id(&self.db_connection, claims.sub)
+            .await
+            .map_err(|_| Status::internal("Could not load user"))?;
+        match user {
+            Some(user) if user.deleted_at.is_none() => {}
+            _ => return Err(Status::unauthenticated("Invalid or expired token")),
+        }
+        let session = session_query::Query::find_session_by_id(&self.db_connection, claims.sid)
+            .await
+            .map_err(|_| Status::internal("Could not load session"))?;
+        match session {
+            Some(session)
+                if session.user_id == claims.sub
+                    && session.revoked_at.is_none()
+                    && session.expires_at > Utc::now().naive_utc() => {}
+            _ => return Err(Status::unauthenticated("Session has been revoked")),
+        }
+        // Each refresh token is good for one refresh. One used again was copied, so the session
+        // is ended for whoever holds it.
+        let rotated = session_mutation::Mutation::rotate_refresh(
+            &self.db_connection,
+            claims.sid,
+            claims.gen,
+            session_expiry(),
+        )
+        .await
+        .map_err(|_| Status::internal("Could not update session"))?;
+        if !rotated {
+            let ids = session_mutation::Mutation::revoke_session(
+                &self.db_connection,
+                claims.sid,
+                claims.sub,
+            )
+            .await
+            .map_err(|_| Status::internal("Could not revoke session"))?;
+            self.revoked_sessions.revoke(&ids);
+            return Err(Status::unauthenticated("Session has been revoked"));
+        }
+        let tokens = token::issue_tokens(claims.sub, claims.sid, claims.gen + 1)?;
+        Ok(Response::new(tokens.into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deleted_account_names_are_reserved() {
+        assert!(is_reserved("deleted_42"));
+        assert!(is_reserved("Deleted_42"));
+        assert!(is_reserved("DELETED_"));
+        assert!(!is_reserved("deleted"));
+        assert!(!is_reserved("undeleted_42"));
+    }
 }
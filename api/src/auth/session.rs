@@ -0,0 +1,48 @@
+use std::collections::HashSet;
+use std::sync::{Arc, RwLock};
+
+// Interceptors are synchronous, so revocations are mirrored in memory for them to check.
+// It's loaded from the sessions table on startup and updated whenever a session is revoked.
+#[derive(Clone, Default)]
+pub struct RevokedSessions {
+    ids: Arc<RwLock<HashSet<i32>>>,
+}
+
+impl RevokedSessions {
+    pub fn new(ids: Vec<i32>) -> RevokedSessions {
+        RevokedSessions {
+            ids: Arc::new(RwLock::new(ids.into_iter().collect())),
+        }
+    }
+
+    pub fn revoke(&self, ids: &[i32]) {
+        if let Ok(mut revoked) = self.ids.write() {
+            revoked.extend(ids.iter().copied());
+        }
+    }
+
+    pub fn is_revoked(&self, id: i32) -> bool {
+        match self.ids.read() {
+            Ok(revoked) => revoked.contains(&id),
+            // A poisoned lock means revocations may have been lost, fail closed
+            Err(_) => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_revoked_sessions() {
+        let revoked = RevokedSessions::new(vec![1]);
+        assert!(revoked.is_revoked(1));
+        assert!(!revoked.is_revoked(2));
+
+        let shared = revoked.clone();
+        shared.revoke(&[2, 3]);
+        assert!(revoked.is_revoked(2));
+        assert!(revoked.is_revoked(3));
+    }
+}
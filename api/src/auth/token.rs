@@ -0,0 +1,141 @@
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tonic::Status;
+
+const DEFAULT_ACCESS_TOKEN_TTL: u64 = 15 * 60;
+const DEFAULT_REFRESH_TOKEN_TTL: u64 = 30 * 24 * 60 * 60;
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenKind {
+    Access,
+    Refresh,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Claims {
+    // User id
+    pub sub: i32,
+    // Session id, see the sessions table
+    pub sid: i32,
+    // The session's refresh generation the token was issued for, only checked on refresh
+    #[serde(default)]
+    pub gen: i32,
+    pub kind: TokenKind,
+    pub iat: u64,
+    pub exp: u64,
+}
+
+pub struct TokenPair {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub expires_in: u64,
+}
+
+fn secret() -> Result<String, Status> {
+    match env::var("JWT_SECRET") {
+        Ok(secret) if !secret.is_empty() => Ok(secret),
+        _ => Err(Status::internal("JWT_SECRET is not configured")),
+    }
+}
+
+fn ttl(var: &str, default: u64) -> u64 {
+    env::var(var)
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .unwrap_or(default)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn sign(
+    user_id: i32,
+    session_id: i32,
+    generation: i32,
+    kind: TokenKind,
+    ttl: u64,
+) -> Result<String, Status> {
+    let iat = now();
+    let claims = Claims {
+        sub: user_id,
+        sid: session_id,
+        gen: generation,
+        kind,
+        iat,
+        exp: iat + ttl,
+    };
+    encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(secret()?.as_bytes()),
+    )
+    .map_err(|_| Status::internal("Could not sign token"))
+}
+
+pub fn refresh_token_ttl() -> u64 {
+    ttl("REFRESH_TOKEN_TTL", DEFAULT_REFRESH_TOKEN_TTL)
+}
+
+pub fn issue_tokens(user_id: i32, session_id: i32, generation: i32) -> Result<TokenPair, Status> {
+    let access_ttl = ttl("ACCESS_TOKEN_TTL", DEFAULT_ACCESS_TOKEN_TTL);
+    Ok(TokenPair {
+        access_token: sign(
+            user_id,
+            session_id,
+            generation,
+            TokenKind::Access,
+            access_ttl,
+        )?,
+        refresh_token: sign(
+            user_id,
+            session_id,
+            generation,
+            TokenKind::Refresh,
+            refresh_token_ttl(),
+        )?,
+        expires_in: access_ttl,
+    })
+}
+
+// Checks signature and expiry, and that an access token is not used as a refresh token or vice versa
+pub fn validate(token: &str, kind: TokenKind) -> Result<Claims, Status> {
+    let data = decode::<Claims>(
+        token,
+        &DecodingKey::from_secret(secret()?.as_bytes()),
+        &Validation::default(),
+    )
+    .map_err(|_| Status::unauthenticated("Invalid or expired token"))?;
+    if data.claims.kind != kind {
+        return Err(Status::unauthenticated("Invalid or expired token"));
+    }
+    Ok(data.claims)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_issue_and_validate_tokens() {
+        env::set_var("JWT_SECRET", "test-secret");
+        let tokens = issue_tokens(42, 7, 3).unwrap();
+        let claims = validate(&tokens.access_token, TokenKind::Access).unwrap();
+        assert_eq!(claims.sub, 42);
+        assert_eq!(claims.sid, 7);
+        let claims = validate(&tokens.refresh_token, TokenKind::Refresh).unwrap();
+        assert_eq!(claims.sub, 42);
+        assert_eq!(claims.sid, 7);
+        assert_eq!(claims.gen, 3);
+
+        assert!(validate(&tokens.access_token, TokenKind::Refresh).is_err());
+        assert!(validate(&tokens.refresh_token, TokenKind::Access).is_err());
+        assert!(validate("42", TokenKind::Access).is_err());
+    }
+}
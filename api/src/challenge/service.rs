@@ -0,0 +1,406 @@
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::Utc;
+use sea_orm::{DatabaseConnection, TransactionTrait};
+use tokio::sync::mpsc;
+use tokio_stream::{wrappers::ReceiverStream, Stream};
+use tonic::{Request, Response, Status};
+
+use entity::entities::{challenge, game};
+use service::{
+    challenges::{mutation, query},
+    time_control::query as time_control_query,
+    users::query as user_query,
+};
+
+use super::{
+    challenges_server::Challenges, AcceptChallengeResponse, Challenge, ChallengeRequest,
+    ColorChoice, CreateChallengeRequest, ListChallengesRequest, ListChallengesResponse,
+    StreamChallengesRequest, Variant,
+};
+use crate::auth::interceptor::{authenticated_user, log_request};
+use crate::chess::{
+    bot, events::GameEvents, lifecycle, pieces::Color, polyglot::OpeningBook, syzygy::Tablebase,
+    uci::EnginePool,
+};
+
+// Unanswered challenges expire after this long
+const CHALLENGE_TIMEOUT: i64 = 10 * 60;
+const EXPIRY_INTERVAL: Duration = Duration::from_secs(30);
+
+type ChallengeSender = mpsc::Sender<Result<Challenge, Status>>;
+
+fn color_to_str(color: ColorChoice) -> &'static str {
+    match color {
+        ColorChoice::Random => "random",
+        ColorChoice::White => "w",
+        ColorChoice::Black => "b",
+    }
+}
+
+fn color_from_str(color: &str) -> ColorChoice {
+    match color {
+        "w" => ColorChoice::White,
+        "b" => ColorChoice::Black,
+        _ => ColorChoice::Random,
+    }
+}
+
+fn variant_to_str(variant: Variant) -> &'static str {
+    match variant {
+        Variant::Standard => "standard",
+        Variant::Chess960 => "chess960",
+    }
+}
+
+fn variant_from_str(variant: &str) -> Variant {
+    match variant {
+        "chess960" => Variant::Chess960,
+        _ => Variant::Standard,
+    }
+}
+
+// Live challenge updates per user, for everyone with an open StreamChallenges call
+#[derive(Clone, Default)]
+pub struct ChallengeHub {
+    subscribers: Arc<Mutex<HashMap<i32, Vec<ChallengeSender>>>>,
+}
+
+impl ChallengeHub {
+    fn subscribe(&self, user_id: i32, sender: ChallengeSender) {
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.entry(user_id).or_default().push(sender);
+        }
+    }
+
+    // Both sides of a challenge see every change to it
+    fn publish(&self, challenge: &challenge::Model, message: &Challenge) {
+        let mut subscribers = match self.subscribers.lock() {
+            Ok(subscribers) => subscribers,
+            Err(_) => return,
+        };
+        for user_id in [challenge.challenger, challenge.challenged] {
+            if let Some(senders) = subscribers.get_mut(&user_id) {
+                // Slow clients miss updates rather than hold up everyone else
+                senders.retain(|sender| {
+                    !sender.is_closed() && sender.try_send(Ok(message.clone())).is_ok()
+                });
+                if senders.is_empty() {
+                    subscribers.remove(&user_id);
+                }
+            }
+        }
+    }
+
+    pub async fn run_expiry(self, db: DatabaseConnection) {
+        let mut interval = tokio::time::interval(EXPIRY_INTERVAL);
+        loop {
+            interval.tick().await;
+            let expired =
+                match mutation::Mutation::expire_challenges(&db, Utc::now().naive_utc()).await {
+                    Ok(expired) => expired,
+                    Err(err) => {
+                        println!("Error: could not expire challenges: {err}");
+                        continue;
+                    }
+                };
+            for mut challenge in expired {
+                challenge.state = "expired".to_string();
+                let message = to_message(&db, &challenge).await;
+                self.publish(&challenge, &message);
+            }
+        }
+    }
+}
+
+async fn username(db: &DatabaseConnection, user_id: i32) -> String {
+    match user_query::Query::find_user_by_id(db, user_id).await {
+        Ok(Some(user)) => user.username,
+        _ => String::new(),
+    }
+}
+
+async fn to_message(db: &DatabaseConnection, challenge: &challenge::Model) -> Challenge {
+    Challenge {
+        id: challenge.id,
+        challenger: username(db, challenge.challenger).await,
+        challenged: username(db, challenge.challenged).await,
+        time_control_id: challenge.time_control,
+        color: color_from_str(&challenge.color) as i32,
+        rated: challenge.rated,
+        variant: variant_from_str(&challenge.variant) as i32,
+        state: challenge.state.clone(),
+        match_id: challenge
+            .game_id
+            .map(|id| id.to_string())
+            .unwrap_or_default(),
+        created_at: challenge.created_at.and_utc().timestamp(),
+        expires_at: challenge.expires_at.and_utc().timestamp(),
+    }
+}
+
+pub struct ChallengeService {
+    pub db_connection: DatabaseConnection,
+    pub hub: ChallengeHub,
+    pub events: GameEvents,
+    pub engine: Option<EnginePool>,
+    pub book: Option<OpeningBook>,
+    pub tablebase: Option<Tablebase>,
+}
+
+impl ChallengeService {
+    async fn find_pending_challenge(&self, id: i32) -> Result<challenge::Model, Status> {
+        let db_result = query::Query::find_challenge_by_id(&self.db_connection, id)
+            .await
+            .map_err(|_| Status::internal("Could not load challenge"))?;
+        match db_result {
+            Some(challenge)
+                if challenge.state == "pending"
+                    && challenge.expires_at > Utc::now().naive_utc() =>
+            {
+                Ok(challenge)
+            }
+            Some(_) => Err(Status::failed_precondition(
+                "Challenge is no longer pending",
+            )),
+            None => Err(Status::not_found("Challenge not found")),
+        }
+    }
+
+    async fn answer(&self, id: i32, state: &str) -> Result<challenge::Model, Status> {
+        let db_result = mutation::Mutation::answer_challenge(&self.db_connection, id, state)
+            .await
+            .map_err(|_| Status::internal("Could not update challenge"))?;
+        match db_result {
+            Some(challenge) => Ok(challenge),
+            None => Err(Status::failed_precondition(
+                "Challenge is no longer pending",
+            )),
+        }
+    }
+
+    // Starts the game, returns the updated challenge and the game with the challenger's color.
+    // The challenge only counts as accepted once its game exists.
+    async fn accept(
+        &self,
+        challenge: challenge::Model,
+    ) -> Result<(Challenge, game::Model, Color), Status> {
+        let txn = self
+            .db_connection
+            .begin()
+            .await
+            .map_err(|_| Status::internal("Could not accept challenge"))?;
+        let challenge = mutation::Mutation::answer_challenge(&txn, challenge.id, "accepted")
+            .await
+            .map_err(|_| Status::internal("Could not update challenge"))?
+            .ok_or_else(|| Status::failed_precondition("Challenge is no longer pending"))?;
+        let challenger_color = match color_from_str(&challenge.color) {
+            ColorChoice::White => Color::White,
+            ColorChoice::Black => Color::Black,
+            ColorChoice::Random if rand::random::<bool>() => Color::White,
+            ColorChoice::Random => Color::Black,
+        };
+        let (white, black) = match challenger_color {
+            Color::White => (challenge.challenger, challenge.challenged),
+            Color::Black => (challenge.challenged, challenge.challenger),
+        };
+        let game_row = lifecycle::start_game(
+            &txn,
+            white,
+            black,
+            challenge.time_control,
+            challenge.rated,
+            None,
+        )
+        .await?;
+        let challenge = mutation::Mutation::set_challenge_game(&txn, challenge.id, game_row.id)
+            .await
+            .map_err(|_| Status::internal("Could not update challenge"))?;
+        txn.commit()
+            .await
+            .map_err(|_| Status::internal("Could not accept challenge"))?;
+        let message = self.publish(&challenge).await;
+
+        // A bot playing white makes the first move
+        bot::respond(
+            self.db_connection.clone(),
+            self.events.clone(),
+            self.engine.clone(),
+            self.book.clone(),
+            self.tablebase.clone(),
+            game_row.id,
+        );
+        Ok((message, game_row, challenger_color))
+    }
+
+    async fn publish(&self, challenge: &challenge::Model) -> Challenge {
+        let message = to_message(&self.db_connection, challenge).await;
+        self.hub.publish(challenge, &message);
+        message
+    }
+}
+
+#[tonic::async_trait]
+impl Challenges for ChallengeService {
+    type StreamChallengesStream = Pin<Box<dyn Stream<Item = Result<Challenge, Status>> + Send>>;
+
+    async fn create_challenge(
+        &self,
+        request: Request<CreateChallengeRequest>,
+    ) -> Result<Response<Challenge>, Status> {
+        log_request("CreateChallenge", &request);
+        let user = authenticated_user(&request)?;
+        let r = request.into_inner();
+        let color = ColorChoice::try_from(r.color)
+            .map_err(|_| Status::invalid_argument("Invalid color"))?;
+        let variant = Variant::try_from(r.variant)
+            .map_err(|_| Status::invalid_argument("Invalid variant"))?;
+        if variant != Variant::Standard {
+            return Err(Status::unimplemented("Only standard chess is supported"));
+        }
+        let opponent = user_query::Query::find_user_by_username(&self.db_connection, &r.username)
+            .await
+            .map_err(|_| Status::internal("Could not load user"))?;
+        let opponent = match opponent {
+            Some(opponent) if opponent.deleted_at.is_none() => opponent,
+            _ => return Err(Status::not_found("User not found")),
+        };
+        if opponent.id == user.id {
+            return Err(Status::invalid_argument("Cannot challenge yourself"));
+        }
+        let time_control = time_control_query::Query::find_time_control_by_id(
+            &self.db_connection,
+            r.time_control_id,
+        )
+        .await
+        .map_err(|_| Status::internal("Could not load time control"))?;
+        if time_control.is_none() {
+            return Err(Status::invalid_argument("Unknown time control"));
+        }
+
+        let challenge = mutation::Mutation::create_challenge(
+            &self.db_connection,
+            challenge::Model {
+                id: 0,
+                challenger: user.id,
+                challenged: opponent.id,
+                time_control: r.time_control_id,
+                color: color_to_str(color).to_string(),
+                rated: r.rated,
+                variant: variant_to_str(variant).to_string(),
+                state: "pending".to_string(),
+                game_id: None,
+                created_at: Default::default(),
+                expires_at: Utc::now().naive_utc() + chrono::Duration::seconds(CHALLENGE_TIMEOUT),
+                updated_at: None,
+            },
+        )
+        .await
+        .map_err(|_| Status::internal("Could not create challenge"))?;
+        if opponent.bot_level.is_some() {
+            // Bots take every challenge straight away
+            let (message, _, _) = self.accept(challenge).await?;
+            return Ok(Response::new(message));
+        }
+        Ok(Response::new(self.publish(&challenge).await))
+    }
+
+    async fn accept_challenge(
+        &self,
+        request: Request<ChallengeRequest>,
+    ) -> Result<Response<AcceptChallengeResponse>, Status> {
+        log_request("AcceptChallenge", &request);
+        let user = authenticated_user(&request)?;
+        let r = request.into_inner();
+        let challenge = self.find_pending_challenge(r.challenge_id).await?;
+        if challenge.challenged != user.id {
+            return Err(Status::permission_denied(
+                "Challenge was sent to someone else",
+            ));
+        }
+        let (_, game_row, challenger_color) = self.accept(challenge).await?;
+
+        Ok(Response::new(AcceptChallengeResponse {
+            match_id: game_row.id.to_string(),
+            color: challenger_color.opponent().as_str().to_string(),
+        }))
+    }
+
+    async fn decline_challenge(
+        &self,
+        request: Request<ChallengeRequest>,
+    ) -> Result<Response<Challenge>, Status> {
+        log_request("DeclineChallenge", &request);
+        let user = authenticated_user(&request)?;
+        let r = request.into_inner();
+        let challenge = self.find_pending_challenge(r.challenge_id).await?;
+        if challenge.challenged != user.id {
+            return Err(Status::permission_denied(
+                "Challenge was sent to someone else",
+            ));
+        }
+        let challenge = self.answer(challenge.id, "declined").await?;
+        Ok(Response::new(self.publish(&challenge).await))
+    }
+
+    async fn cancel_challenge(
+        &self,
+        request: Request<ChallengeRequest>,
+    ) -> Result<Response<Challenge>, Status> {
+        log_request("CancelChallenge", &request);
+        let user = authenticated_user(&request)?;
+        let r = request.into_inner();
+        let challenge = self.find_pending_challenge(r.challenge_id).await?;
+        if challenge.challenger != user.id {
+            return Err(Status::permission_denied(
+                "Challenge was sent by someone else",
+            ));
+        }
+        let challenge = self.answer(challenge.id, "cancelled").await?;
+        Ok(Response::new(self.publish(&challenge).await))
+    }
+
+    async fn list_challenges(
+        &self,
+        request: Request<ListChallengesRequest>,
+    ) -> Result<Response<ListChallengesResponse>, Status> {
+        log_request("ListChallenges", &request);
+        let user = authenticated_user(&request)?;
+        let db_result = query::Query::find_pending_challenges_by_user(
+            &self.db_connection,
+            user.id,
+            Utc::now().naive_utc(),
+        )
+        .await
+        .map_err(|_| Status::internal("Could not load challenges"))?;
+        let mut challenges = Vec::new();
+        for challenge in db_result {
+            challenges.push(to_message(&self.db_connection, &challenge).await);
+        }
+        Ok(Response::new(ListChallengesResponse { challenges }))
+    }
+
+    async fn stream_challenges(
+        &self,
+        request: Request<StreamChallengesRequest>,
+    ) -> Result<Response<Self::StreamChallengesStream>, Status> {
+        log_request("StreamChallenges", &request);
+        let user = authenticated_user(&request)?;
+        let pending = query::Query::find_pending_challenges_by_user(
+            &self.db_connection,
+            user.id,
+            Utc::now().naive_utc(),
+        )
+        .await
+        .map_err(|_| Status::internal("Could not load challenges"))?;
+        let (sender, receiver) = mpsc::channel(pending.len() + 16);
+        for challenge in pending {
+            let _ = sender.try_send(Ok(to_message(&self.db_connection, &challenge).await));
+        }
+        self.hub.subscribe(user.id, sender);
+        Ok(Response::new(Box::pin(ReceiverStream::new(receiver))))
+    }
+}
@@ -155,7 +155,6 @@ impl Board {
         fen
     }
 
-
     pub fn print_board(&self) -> String {
         let mut board_str = String::new();
         for sq in self.squares.iter().enumerate().rev() {
@@ -0,0 +1,247 @@
+// Computer opponents. Each level has its own account (`users.bot_level`), humans challenge it
+// like anyone else and it answers through the same game lifecycle, with moves picked by the
+// engine in `engine::search`. Weaker levels search less and get noisier evaluations. When the
+// server has an external UCI engine, it plays as one more bot above the built-in levels. With an
+// opening book every level plays from it for as long as the game stays in it, and in positions the
+// endgame tablebases have every level plays their best move.
+use std::time::Duration;
+
+use sea_orm::{DatabaseConnection, DbErr};
+use tonic::Status;
+
+use entity::entities::users;
+use service::{
+    game::query as game_query,
+    users::{mutation as user_mutation, query as user_query},
+};
+
+use super::{
+    engine::{
+        position::{Move, Position, START_FEN},
+        search::{Limits, Search},
+    },
+    events::GameEvents,
+    history, lifecycle,
+    pieces::Color,
+    polyglot::OpeningBook,
+    syzygy::Tablebase,
+    uci::{self, EnginePool},
+};
+
+const TT_ENTRIES: usize = 1 << 16;
+
+const fn level(depth: i32, nodes: u64, millis: u64, noise: i32) -> Limits {
+    Limits {
+        depth: Some(depth),
+        nodes: Some(nodes),
+        time: Some(Duration::from_millis(millis)),
+        noise,
+    }
+}
+
+// From the weakest, levels are numbered from 1
+const LEVELS: [Limits; 8] = [
+    level(1, 1_000, 200, 400),
+    level(1, 2_000, 200, 200),
+    level(2, 5_000, 300, 120),
+    level(3, 20_000, 500, 60),
+    level(4, 50_000, 800, 30),
+    level(6, 200_000, 1_000, 10),
+    level(10, 1_000_000, 2_000, 0),
+    level(64, 5_000_000, 4_000, 0),
+];
+
+// The external engine's bot
+pub const ENGINE_LEVEL: i32 = LEVELS.len() as i32 + 1;
+
+pub fn limits(bot_level: i32) -> Limits {
+    LEVELS[(bot_level.clamp(1, LEVELS.len() as i32) - 1) as usize]
+}
+
+pub fn username(bot_level: i32) -> String {
+    if bot_level == ENGINE_LEVEL {
+        return "bot-engine".to_string();
+    }
+    format!("bot-level-{}", bot_level)
+}
+
+// Creates the accounts of the levels that do not have one yet, run at startup
+pub async fn ensure_bots(db: &DatabaseConnection, engine: bool) -> Result<(), DbErr> {
+    let top = if engine {
+        ENGINE_LEVEL
+    } else {
+        LEVELS.len() as i32
+    };
+    for bot_level in 1..=top {
+        let name = username(bot_level);
+        match user_query::Query::find_user_by_username(db, &name).await? {
+            Some(user) if user.bot_level.is_none() => {
+                println!("Error: {name} is taken by a player, that bot is not available");
+            }
+            Some(_) => {}
+            None => {
+                user_mutation::Mutation::create_user(
+                    db,
+                    users::Model {
+                        id: 0,
+                        username: name,
+                        password: String::new(),
+                        created_at: Default::default(),
+                        updated_at: None,
+                        display_name: None,
+                        country: None,
+                        bio: None,
+                        avatar_url: None,
+                        deleted_at: None,
+                        bot_level: Some(bot_level),
+                    },
+                )
+                .await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// The bot's move in `position`, `previous` are the keys of the positions before it
+pub fn choose_move(position: &Position, previous: &[u64], bot_level: i32) -> Option<Move> {
+    Search::new(TT_ENTRIES)
+        .run(position, previous, limits(bot_level), &mut |_| {})
+        .and_then(|info| info.best())
+}
+
+// Plays for the bot in the background if it is its turn in the game
+pub fn respond(
+    db: DatabaseConnection,
+    events: GameEvents,
+    engine: Option<EnginePool>,
+    book: Option<OpeningBook>,
+    tablebase: Option<Tablebase>,
+    game_id: i32,
+) {
+    tokio::spawn(async move {
+        let (engine, book, tablebase) = (engine.as_ref(), book.as_ref(), tablebase.as_ref());
+        if let Err(err) = play(&db, &events, engine, book, tablebase, game_id).await {
+            println!("Error: bot could not move in game {game_id}: {err}");
+        }
+    });
+}
+
+// Asks the external engine, which is given the whole game so it can see repetitions
+async fn engine_move(
+    engine: &EnginePool,
+    position: &Position,
+    moves: &str,
+) -> Result<Option<Move>, Status> {
+    let moves = uci::uci_moves(moves).map_err(|_| Status::internal("Could not replay game"))?;
+    let limits = Limits {
+        time: Some(engine.move_time()),
+        ..Default::default()
+    };
+    let mut engine = engine
+        .acquire()
+        .await
+        .map_err(|err| Status::unavailable(format!("Engine is not available: {err}")))?;
+    let best = engine
+        .go(START_FEN, &moves, limits, 1, &mut |_| {})
+        .await
+        .map_err(|err| Status::internal(format!("Engine failed: {err}")))?;
+    match best.best {
+        Some(best) => position
+            .parse_uci(&best)
+            .map(Some)
+            .ok_or_else(|| Status::internal("Engine played an illegal move")),
+        None => Ok(None),
+    }
+}
+
+async fn play(
+    db: &DatabaseConnection,
+    events: &GameEvents,
+    engine: Option<&EnginePool>,
+    book: Option<&OpeningBook>,
+    tablebase: Option<&Tablebase>,
+    game_id: i32,
+) -> Result<(), Status> {
+    let game_row = game_query::Query::find_game_by_id(db, game_id)
+        .await
+        .map_err(|_| Status::internal("Could not load game"))?
+        .ok_or_else(|| Status::not_found("Game not found"))?;
+    if game_row.state != "active" {
+        return Ok(());
+    }
+    let turn = Color::from_str(&game_row.turn).unwrap_or(Color::White);
+    let player = match turn {
+        Color::White => game_row.player_white,
+        Color::Black => game_row.player_black,
+    };
+    let bot_level = match user_query::Query::find_user_by_id(db, player)
+        .await
+        .map_err(|_| Status::internal("Could not load user"))?
+        .and_then(|user| user.bot_level)
+    {
+        Some(bot_level) => bot_level,
+        None => return Ok(()),
+    };
+
+    // The engine keeps its own board, replayed from the moves so far
+    let moves = history::moves(db, game_row.id)
+        .await
+        .map_err(|_| Status::internal("Could not load moves"))?;
+    let (position, previous) =
+        Position::from_moves(&moves).map_err(|_| Status::internal("Could not replay game"))?;
+    let book_move = book.and_then(|book| book.pick(&position));
+    let tablebase_move = tablebase
+        .and_then(|tablebase| tablebase.best_moves(&position))
+        .and_then(|moves| moves.first().copied());
+    let best = match engine {
+        _ if book_move.is_some() => book_move,
+        _ if tablebase_move.is_some() => tablebase_move,
+        Some(engine) if bot_level == ENGINE_LEVEL => engine_move(engine, &position, &moves).await?,
+        // Without the external engine its bot plays as the strongest built-in level
+        _ => {
+            let position = position.clone();
+            tokio::task::spawn_blocking(move || choose_move(&position, &previous, bot_level))
+                .await
+                .map_err(|_| Status::internal("Engine failed"))?
+        }
+    };
+
+    let best = match best {
+        Some(best) => best,
+        // Checkmated or stalemated
+        None => {
+            if let Some((result, termination)) = lifecycle::outcome(&position) {
+                lifecycle::finish_game(db, events, &game_row, result, termination).await?;
+            }
+            return Ok(());
+        }
+    };
+    let next = position.play(best);
+    let game_move = lifecycle::record_move(db, events, &game_row, &position, best, &next).await?;
+    lifecycle::end_if_over(db, events, &game_row, tablebase, &game_move, &next).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_levels_get_stronger() {
+        assert_eq!(limits(0), limits(1));
+        assert_eq!(limits(ENGINE_LEVEL), limits(8));
+        assert_eq!(username(ENGINE_LEVEL), "bot-engine");
+        for pair in LEVELS.windows(2) {
+            assert!(pair[0].depth <= pair[1].depth);
+            assert!(pair[0].noise >= pair[1].noise);
+        }
+    }
+
+    #[test]
+    fn test_bot_takes_a_free_queen() {
+        let position = Position::from_fen("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1").unwrap();
+        let best = choose_move(&position, &[], 5).unwrap();
+        assert_eq!(position.san(best), "Rxd5");
+    }
+}
@@ -0,0 +1,163 @@
+// Flags players who run out of time without moving. A move made after the flag fell is caught
+// when it is recorded, this catches the player who stops moving altogether.
+use std::time::Duration;
+
+use chrono::{NaiveDateTime, Utc};
+use sea_orm::DatabaseConnection;
+use tonic::Status;
+
+use entity::entities::{game, game_move};
+use service::{game::query as game_query, game_move::query as game_move_query};
+
+use super::{events::GameEvents, lifecycle, pieces::Color};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+// Milliseconds the side to move has left at `now`, negative once its flag fell. `last_moves`
+// are the game's last two moves or fewer. None when the clock is not known, as for back-filled
+// moves.
+pub fn time_left(
+    game_row: &game::Model,
+    last_moves: &[game_move::Model],
+    now: NaiveDateTime,
+) -> Option<i64> {
+    let plies = last_moves.iter().map(|mv| mv.ply).max().unwrap_or(0);
+    let next = plies + 1;
+    // The clock stopped by the mover's previous move, or the starting clock
+    let left = match last_moves.iter().find(|mv| mv.ply == next - 2) {
+        Some(own) => own.clock_remaining?,
+        None if next > 2 => return None,
+        None if next % 2 == 1 => game_row.white_time * 1000,
+        None => game_row.black_time * 1000,
+    };
+    let since = last_moves
+        .iter()
+        .find(|mv| mv.ply == plies)
+        .map_or(game_row.created_at, |mv| mv.created_at);
+    Some(left as i64 - (now - since).num_milliseconds().max(0))
+}
+
+async fn flag_if_out_of_time(
+    db: &DatabaseConnection,
+    events: &GameEvents,
+    game_row: &game::Model,
+) -> Result<(), Status> {
+    let last_moves = game_move_query::Query::find_last_moves(db, game_row.id, 2)
+        .await
+        .map_err(|_| Status::internal("Could not load moves"))?;
+    match time_left(game_row, &last_moves, Utc::now().naive_utc()) {
+        Some(left) if left <= 0 => {
+            let turn = Color::from_str(&game_row.turn).unwrap_or(Color::White);
+            lifecycle::finish_game(
+                db,
+                events,
+                game_row,
+                lifecycle::win_for(&turn.opponent()),
+                lifecycle::TIMEOUT,
+            )
+            .await?;
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+pub async fn run(db: DatabaseConnection, events: GameEvents) {
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        let games = match game_query::Query::find_active_games(&db).await {
+            Ok(games) => games,
+            Err(err) => {
+                println!("Error: could not load active games: {err}");
+                continue;
+            }
+        };
+        for game_row in games {
+            if let Err(err) = flag_if_out_of_time(&db, &events, &game_row).await {
+                println!(
+                    "Error: could not check the clock of game {}: {err}",
+                    game_row.id
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    fn at(seconds: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 8, 1)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap()
+            + chrono::Duration::seconds(seconds as i64)
+    }
+
+    fn game() -> game::Model {
+        game::Model {
+            id: 1,
+            player_white: 7,
+            player_black: 9,
+            time_control: 1,
+            board: String::new(),
+            turn: "white".to_string(),
+            black_time: 60,
+            white_time: 60,
+            state: "active".to_string(),
+            created_at: at(0),
+            updated_at: None,
+            rated: true,
+            result: None,
+            white_clock: None,
+            black_clock: None,
+            eco: None,
+            opening: None,
+            termination: None,
+            replay_failed: false,
+        }
+    }
+
+    fn played(ply: i32, seconds: u32, clock_remaining: Option<i32>) -> game_move::Model {
+        game_move::Model {
+            id: 0,
+            game_id: 1,
+            ply,
+            san: String::new(),
+            uci: None,
+            fen: None,
+            zobrist: None,
+            material: None,
+            placement: None,
+            clock_remaining,
+            time_spent: None,
+            created_at: at(seconds),
+        }
+    }
+
+    #[test]
+    fn test_flag_without_a_move() {
+        // White never makes a first move
+        assert_eq!(time_left(&game(), &[], at(30)), Some(30_000));
+        assert_eq!(time_left(&game(), &[], at(61)), Some(-1_000));
+
+        // Black to move with its full minute, white has 50 seconds left
+        let moves = [played(1, 10, Some(50_000))];
+        assert_eq!(time_left(&game(), &moves, at(70)), Some(0));
+
+        // White to move with the 50 seconds it had after its first move
+        let moves = [played(2, 20, Some(50_000)), played(1, 10, Some(50_000))];
+        assert_eq!(time_left(&game(), &moves, at(40)), Some(30_000));
+        assert_eq!(time_left(&game(), &moves, at(75)), Some(-5_000));
+    }
+
+    #[test]
+    fn test_unknown_clock() {
+        let moves = [played(6, 20, None), played(5, 10, None)];
+        assert_eq!(time_left(&game(), &moves, at(1000)), None);
+    }
+}
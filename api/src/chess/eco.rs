@@ -0,0 +1,120 @@
+// Opening names from the ECO (Encyclopaedia of Chess Openings) codes. The bundled table has the
+// main lines of every code from A00 to E99, one per row as code, name and moves in SAN separated
+// by tabs. Lines are matched by the position they reach, so a game that gets there in another
+// move order is named the same.
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use sea_orm::{DatabaseConnection, DbErr};
+
+use service::{
+    game::{mutation, query},
+    game_move::query as game_move_query,
+};
+
+use super::{engine::position::Position, history};
+
+const TABLE: &str = include_str!("eco.tsv");
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Opening {
+    pub eco: String,
+    pub name: String,
+}
+
+// Positions the lines end in. A row that is not legal is a mistake in the table, it is left out.
+fn positions() -> &'static HashMap<u64, Opening> {
+    static POSITIONS: OnceLock<HashMap<u64, Opening>> = OnceLock::new();
+    POSITIONS.get_or_init(|| {
+        let mut positions = HashMap::new();
+        for row in TABLE.lines() {
+            let mut fields = row.split('\t');
+            let (eco, name, moves) = match (fields.next(), fields.next(), fields.next()) {
+                (Some(eco), Some(name), Some(moves)) => (eco, name, moves),
+                _ => continue,
+            };
+            if let Ok((position, _)) = Position::from_moves(moves) {
+                positions.entry(position.key()).or_insert(Opening {
+                    eco: eco.to_string(),
+                    name: name.to_string(),
+                });
+            }
+        }
+        positions
+    })
+}
+
+pub fn lookup(key: u64) -> Option<&'static Opening> {
+    positions().get(&key)
+}
+
+// The last named position the game went through
+pub fn classify(moves: &str) -> Option<&'static Opening> {
+    history::replay(moves)
+        .iter()
+        .rev()
+        .find_map(|(_, position)| lookup(position.key()))
+}
+
+// Names the games that were played before openings were stored. Run at startup.
+pub async fn classify_games(db: &DatabaseConnection) -> Result<(), DbErr> {
+    let games = query::Query::find_unclassified_games(db).await?;
+    let ids = games.iter().map(|game_row| game_row.id).collect();
+    let move_lists = game_move_query::Query::find_move_lists(db, ids).await?;
+    for game_row in games {
+        let moves = move_lists.get(&game_row.id).map_or("", String::as_str);
+        if let Some(opening) = classify(moves) {
+            mutation::Mutation::set_game_opening(
+                db,
+                game_row.id,
+                opening.eco.clone(),
+                opening.name.clone(),
+            )
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_line_is_named() {
+        let rows = TABLE.lines().filter(|row| !row.is_empty()).count();
+        assert_eq!(positions().len(), rows);
+    }
+
+    #[test]
+    fn test_every_code_is_in_the_table() {
+        let codes: std::collections::HashSet<&str> = TABLE
+            .lines()
+            .filter_map(|row| row.split('\t').next())
+            .collect();
+        for volume in ['A', 'B', 'C', 'D', 'E'] {
+            for number in 0..100 {
+                assert!(codes.contains(format!("{volume}{number:02}").as_str()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_classify() {
+        let berlin = classify("e4 e5 Nf3 Nc6 Bb5 Nf6 d3").unwrap();
+        assert_eq!(
+            (berlin.eco.as_str(), berlin.name.as_str()),
+            ("C65", "Ruy Lopez: Berlin Defense")
+        );
+        let open = classify("e4 e5 Nf3 Nc6 Bb5 Nf6 O-O Nxe4").unwrap();
+        assert_eq!(open.eco, "C67");
+        // The Sämisch Nimzo-Indian reached from the English
+        let nimzo = classify("c4 e6 Nc3 Nf6 d4 Bb4 a3").unwrap();
+        assert_eq!(
+            (nimzo.eco.as_str(), nimzo.name.as_str()),
+            ("E24", "Nimzo-Indian Defense: Sämisch Variation")
+        );
+        assert_eq!(classify("h4").unwrap().name, "Kádas Opening");
+        assert_eq!(classify(""), None);
+    }
+}
@@ -0,0 +1,268 @@
+// Handcrafted evaluation in centipawns from the side to move's point of view: material,
+// piece-square tables (king tapered between middlegame and endgame) and a few pawn and rook
+// terms. Tables are laid out like the board, a8 first, from White's side.
+use super::position::{file, rank, Position};
+use crate::chess::pieces::{Color, Kind};
+
+const BISHOP_PAIR: i32 = 30;
+const DOUBLED_PAWN: i32 = -15;
+const ISOLATED_PAWN: i32 = -12;
+// By rank from the pawn's own side
+const PASSED_PAWN: [i32; 8] = [0, 5, 10, 20, 35, 60, 100, 0];
+const ROOK_OPEN_FILE: i32 = 15;
+const ROOK_HALF_OPEN_FILE: i32 = 8;
+const TEMPO: i32 = 10;
+// Game phase from the pieces left, 24 with all of them on the board
+const MAX_PHASE: i32 = 24;
+
+#[rustfmt::skip]
+const PAWN: [i32; 64] = [
+     0,   0,   0,   0,   0,   0,   0,   0,
+    50,  50,  50,  50,  50,  50,  50,  50,
+    10,  10,  20,  30,  30,  20,  10,  10,
+     5,   5,  10,  25,  25,  10,   5,   5,
+     0,   0,   0,  20,  20,   0,   0,   0,
+     5,  -5, -10,   0,   0, -10,  -5,   5,
+     5,  10,  10, -20, -20,  10,  10,   5,
+     0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const KNIGHT: [i32; 64] = [
+   -50, -40, -30, -30, -30, -30, -40, -50,
+   -40, -20,   0,   0,   0,   0, -20, -40,
+   -30,   0,  10,  15,  15,  10,   0, -30,
+   -30,   5,  15,  20,  20,  15,   5, -30,
+   -30,   0,  15,  20,  20,  15,   0, -30,
+   -30,   5,  10,  15,  15,  10,   5, -30,
+   -40, -20,   0,   5,   5,   0, -20, -40,
+   -50, -40, -30, -30, -30, -30, -40, -50,
+];
+
+#[rustfmt::skip]
+const BISHOP: [i32; 64] = [
+   -20, -10, -10, -10, -10, -10, -10, -20,
+   -10,   0,   0,   0,   0,   0,   0, -10,
+   -10,   0,   5,  10,  10,   5,   0, -10,
+   -10,   5,   5,  10,  10,   5,   5, -10,
+   -10,   0,  10,  10,  10,  10,   0, -10,
+   -10,  10,  10,  10,  10,  10,  10, -10,
+   -10,   5,   0,   0,   0,   0,   5, -10,
+   -20, -10, -10, -10, -10, -10, -10, -20,
+];
+
+#[rustfmt::skip]
+const ROOK: [i32; 64] = [
+     0,   0,   0,   0,   0,   0,   0,   0,
+     5,  10,  10,  10,  10,  10,  10,   5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+     0,   0,   0,   5,   5,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const QUEEN: [i32; 64] = [
+   -20, -10, -10,  -5,  -5, -10, -10, -20,
+   -10,   0,   0,   0,   0,   0,   0, -10,
+   -10,   0,   5,   5,   5,   5,   0, -10,
+    -5,   0,   5,   5,   5,   5,   0,  -5,
+     0,   0,   5,   5,   5,   5,   0,  -5,
+   -10,   5,   5,   5,   5,   5,   0, -10,
+   -10,   0,   5,   0,   0,   0,   0, -10,
+   -20, -10, -10,  -5,  -5, -10, -10, -20,
+];
+
+#[rustfmt::skip]
+const KING_MIDDLEGAME: [i32; 64] = [
+   -30, -40, -40, -50, -50, -40, -40, -30,
+   -30, -40, -40, -50, -50, -40, -40, -30,
+   -30, -40, -40, -50, -50, -40, -40, -30,
+   -30, -40, -40, -50, -50, -40, -40, -30,
+   -20, -30, -30, -40, -40, -30, -30, -20,
+   -10, -20, -20, -20, -20, -20, -20, -10,
+    20,  20,   0,   0,   0,   0,  20,  20,
+    20,  30,  10,   0,   0,  10,  30,  20,
+];
+
+#[rustfmt::skip]
+const KING_ENDGAME: [i32; 64] = [
+   -50, -40, -30, -20, -20, -30, -40, -50,
+   -30, -20, -10,   0,   0, -10, -20, -30,
+   -30, -10,  20,  30,  30,  20, -10, -30,
+   -30, -10,  30,  40,  40,  30, -10, -30,
+   -30, -10,  30,  40,  40,  30, -10, -30,
+   -30, -10,  20,  30,  30,  20, -10, -30,
+   -30, -30,   0,   0,   0,   0, -30, -30,
+   -50, -30, -30, -30, -30, -30, -30, -50,
+];
+
+pub fn piece_value(kind: Kind) -> i32 {
+    match kind {
+        Kind::Pawn => 100,
+        Kind::Knight => 320,
+        Kind::Bishop => 330,
+        Kind::Rook => 500,
+        Kind::Queen => 900,
+        Kind::King => 0,
+    }
+}
+
+fn phase_weight(kind: Kind) -> i32 {
+    match kind {
+        Kind::Knight | Kind::Bishop => 1,
+        Kind::Rook => 2,
+        Kind::Queen => 4,
+        _ => 0,
+    }
+}
+
+// Tables are written for White, Black reads them upside down
+fn table_index(square: usize, color: Color) -> usize {
+    match color {
+        Color::White => square,
+        Color::Black => square ^ 56,
+    }
+}
+
+// Pawns per file for each side
+fn pawn_files(position: &Position) -> [[i32; 8]; 2] {
+    let mut files = [[0; 8]; 2];
+    for square in 0..64 {
+        if let Some(piece) = position.piece_at(square) {
+            if piece.kind == Kind::Pawn {
+                files[piece.color as usize][file(square)] += 1;
+            }
+        }
+    }
+    files
+}
+
+fn is_passed(position: &Position, square: usize, color: Color) -> bool {
+    let own_rank = rank(square) as i32;
+    (0..64).all(|other| match position.piece_at(other) {
+        Some(piece) if piece.kind == Kind::Pawn && piece.color != color => {
+            let ahead = match color {
+                Color::White => rank(other) as i32 > own_rank,
+                Color::Black => (rank(other) as i32) < own_rank,
+            };
+            !(ahead && file(other).abs_diff(file(square)) <= 1)
+        }
+        _ => true,
+    })
+}
+
+// Score for White
+fn white_score(position: &Position) -> i32 {
+    let files = pawn_files(position);
+    let mut score = 0;
+    let mut phase = 0;
+    let mut bishops = [0; 2];
+    let mut king_middlegame = 0;
+    let mut king_endgame = 0;
+    for square in 0..64 {
+        let piece = match position.piece_at(square) {
+            Some(piece) => piece,
+            None => continue,
+        };
+        let side = piece.color as usize;
+        let index = table_index(square, piece.color);
+        let mut value = piece_value(piece.kind);
+        phase += phase_weight(piece.kind);
+        match piece.kind {
+            Kind::Pawn => {
+                value += PAWN[index];
+                let pawn_file = file(square);
+                if files[side][pawn_file] > 1 {
+                    value += DOUBLED_PAWN;
+                }
+                let neighbours = [pawn_file.wrapping_sub(1), pawn_file + 1]
+                    .iter()
+                    .filter(|&&f| f < 8)
+                    .map(|&f| files[side][f])
+                    .sum::<i32>();
+                if neighbours == 0 {
+                    value += ISOLATED_PAWN;
+                }
+                if is_passed(position, square, piece.color) {
+                    let own_rank = match piece.color {
+                        Color::White => rank(square),
+                        Color::Black => 7 - rank(square),
+                    };
+                    value += PASSED_PAWN[own_rank];
+                }
+            }
+            Kind::Knight => value += KNIGHT[index],
+            Kind::Bishop => {
+                value += BISHOP[index];
+                bishops[side] += 1;
+            }
+            Kind::Rook => {
+                value += ROOK[index];
+                let rook_file = file(square);
+                if files[side][rook_file] == 0 {
+                    value += if files[1 - side][rook_file] == 0 {
+                        ROOK_OPEN_FILE
+                    } else {
+                        ROOK_HALF_OPEN_FILE
+                    };
+                }
+            }
+            Kind::Queen => value += QUEEN[index],
+            Kind::King => {
+                let sign = if piece.color == Color::White { 1 } else { -1 };
+                king_middlegame += sign * KING_MIDDLEGAME[index];
+                king_endgame += sign * KING_ENDGAME[index];
+            }
+        }
+        if piece.color == Color::White {
+            score += value;
+        } else {
+            score -= value;
+        }
+    }
+    let phase = phase.min(MAX_PHASE);
+    score += (king_middlegame * phase + king_endgame * (MAX_PHASE - phase)) / MAX_PHASE;
+    if bishops[Color::White as usize] >= 2 {
+        score += BISHOP_PAIR;
+    }
+    if bishops[Color::Black as usize] >= 2 {
+        score -= BISHOP_PAIR;
+    }
+    score
+}
+
+pub fn evaluate(position: &Position) -> i32 {
+    let score = white_score(position);
+    match position.turn() {
+        Color::White => score + TEMPO,
+        Color::Black => -score + TEMPO,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_start_position_is_balanced() {
+        assert_eq!(evaluate(&Position::new()), TEMPO);
+    }
+
+    #[test]
+    fn test_mirrored_positions_score_the_same() {
+        let white = Position::from_fen("4k3/pp6/8/3N4/8/8/5PPP/R5K1 w - - 0 1").unwrap();
+        let black = Position::from_fen("r5k1/5ppp/8/8/3n4/8/PP6/4K3 b - - 0 1").unwrap();
+        assert_eq!(evaluate(&white), evaluate(&black));
+        assert!(evaluate(&white) > 500);
+    }
+
+    #[test]
+    fn test_passed_pawn_is_worth_more() {
+        let passed = Position::from_fen("4k3/8/8/3P4/8/8/8/4K3 w - - 0 1").unwrap();
+        let blocked = Position::from_fen("4k3/3p4/8/3P4/8/8/8/4K3 w - - 0 1").unwrap();
+        assert!(evaluate(&passed) > evaluate(&blocked) + piece_value(Kind::Pawn));
+    }
+}
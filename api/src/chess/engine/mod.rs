@@ -0,0 +1,5 @@
+pub mod eval;
+pub mod position;
+pub mod search;
+pub mod tt;
+pub mod zobrist;
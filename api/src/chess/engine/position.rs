@@ -0,0 +1,825 @@
+// Full chess rules for the engine: legal move generation, FEN and SAN. Squares are indexed like
+// `Board`, 0 is a8 and 63 is h1, see `Square::to_1d_arr_coordinates`.
+use super::super::{
+    game::GameError,
+    pieces::{Color, Kind, Piece},
+    square::Square,
+};
+
+pub const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+const WHITE_KING_SIDE: u8 = 1;
+const WHITE_QUEEN_SIDE: u8 = 2;
+const BLACK_KING_SIDE: u8 = 4;
+const BLACK_QUEEN_SIDE: u8 = 8;
+
+const KNIGHT_STEPS: [(i32, i32); 8] = [
+    (1, 2),
+    (2, 1),
+    (2, -1),
+    (1, -2),
+    (-1, -2),
+    (-2, -1),
+    (-2, 1),
+    (-1, 2),
+];
+const KING_STEPS: [(i32, i32); 8] = [
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+];
+const BISHOP_RAYS: [(i32, i32); 4] = [(1, 1), (-1, 1), (-1, -1), (1, -1)];
+const ROOK_RAYS: [(i32, i32); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+const PROMOTIONS: [Kind; 4] = [Kind::Queen, Kind::Rook, Kind::Bishop, Kind::Knight];
+
+pub fn file(square: usize) -> usize {
+    square % 8
+}
+
+pub fn rank(square: usize) -> usize {
+    7 - square / 8
+}
+
+// The square `df` files and `dr` ranks away, if it is on the board
+pub fn offset(square: usize, df: i32, dr: i32) -> Option<usize> {
+    let file = file(square) as i32 + df;
+    let rank = rank(square) as i32 + dr;
+    if (0..8).contains(&file) && (0..8).contains(&rank) {
+        Some(((7 - rank) * 8 + file) as usize)
+    } else {
+        None
+    }
+}
+
+pub fn square_name(square: usize) -> String {
+    Square::new_from_1d_arr_coordinates(square).to_san()
+}
+
+pub fn parse_square(name: &str) -> Option<usize> {
+    if name.len() != 2 {
+        return None;
+    }
+    Square::from_san_str(name).map(|square| square.to_1d_arr_coordinates())
+}
+
+fn kind_char(kind: Kind) -> char {
+    Piece::new(Color::White, kind).as_char()
+}
+
+// Castling is the king moving two squares, en passant a pawn capturing onto the empty en
+// passant square
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Move {
+    pub from: usize,
+    pub to: usize,
+    pub promotion: Option<Kind>,
+}
+
+impl Move {
+    pub fn new(from: usize, to: usize) -> Move {
+        Move {
+            from,
+            to,
+            promotion: None,
+        }
+    }
+
+    // Coordinate notation as used by UCI, e.g. e2e4 or e7e8q
+    pub fn uci(&self) -> String {
+        let mut uci = square_name(self.from) + &square_name(self.to);
+        if let Some(kind) = self.promotion {
+            uci.push(kind_char(kind).to_ascii_lowercase());
+        }
+        uci
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Position {
+    squares: [Option<Piece>; 64],
+    turn: Color,
+    castling: u8,
+    en_passant: Option<usize>,
+    half_move: u32,
+    full_move: u32,
+    key: u64,
+}
+
+impl Position {
+    pub fn new() -> Position {
+        Position::from_fen(START_FEN).unwrap()
+    }
+
+    pub fn from_fen(fen: &str) -> Result<Position, GameError> {
+        let mut fields = fen.split_whitespace();
+        let mut squares = [None; 64];
+        let placement = fields.next().ok_or(GameError)?;
+        let ranks: Vec<&str> = placement.split('/').collect();
+        if ranks.len() != 8 {
+            return Err(GameError);
+        }
+        for (row, pieces) in ranks.iter().enumerate() {
+            let mut file = 0;
+            for c in pieces.chars() {
+                if let Some(empty) = c.to_digit(10) {
+                    file += empty as usize;
+                } else {
+                    let piece = Piece::from_char(c).ok_or(GameError)?;
+                    if file >= 8 {
+                        return Err(GameError);
+                    }
+                    squares[row * 8 + file] = Some(piece);
+                    file += 1;
+                }
+            }
+            if file != 8 {
+                return Err(GameError);
+            }
+        }
+        let turn = Color::from_str(fields.next().ok_or(GameError)?).ok_or(GameError)?;
+        let mut castling = 0;
+        for c in fields.next().unwrap_or("-").chars() {
+            castling |= match c {
+                'K' => WHITE_KING_SIDE,
+                'Q' => WHITE_QUEEN_SIDE,
+                'k' => BLACK_KING_SIDE,
+                'q' => BLACK_QUEEN_SIDE,
+                '-' => 0,
+                _ => return Err(GameError),
+            };
+        }
+        let en_passant = match fields.next().unwrap_or("-") {
+            "-" => None,
+            name => Some(parse_square(name).ok_or(GameError)?),
+        };
+        let half_move = fields
+            .next()
+            .unwrap_or("0")
+            .parse()
+            .map_err(|_| GameError)?;
+        let full_move = fields
+            .next()
+            .unwrap_or("1")
+            .parse()
+            .map_err(|_| GameError)?;
+        let mut position = Position {
+            squares,
+            turn,
+            castling,
+            en_passant,
+            half_move,
+            full_move,
+            key: 0,
+        };
+        // One king each, and the side that just moved cannot be left in check
+        for color in [Color::White, Color::Black] {
+            let kings = squares
+                .iter()
+                .filter(|p| **p == Some(Piece::new(color, Kind::King)))
+                .count();
+            if kings != 1 {
+                return Err(GameError);
+            }
+        }
+        if position.is_attacked(position.king(turn.opponent()), turn) {
+            return Err(GameError);
+        }
+        position.castling &= position.possible_castling();
+        position.key = super::zobrist::key(&position);
+        Ok(position)
+    }
+
+    // Replays moves given in SAN (or coordinates) from the starting position, with the keys of
+    // the positions before the last one for repetitions
+    pub fn from_moves(moves: &str) -> Result<(Position, Vec<u64>), GameError> {
+        let mut position = Position::new();
+        let mut previous = Vec::new();
+        for san in moves.split_whitespace() {
+            let mv = position.parse_move(san).ok_or(GameError)?;
+            previous.push(position.key());
+            position = position.play(mv);
+        }
+        Ok((position, previous))
+    }
+
+    // The SAN moves of a game played from the start separated by spaces, as in `game_move`
+    pub fn parse_game(moves: &str) -> Result<Vec<Move>, GameError> {
+        let mut position = Position::new();
+        let mut parsed = Vec::new();
+        for san in moves.split_whitespace() {
+            let mv = position.parse_move(san).ok_or(GameError)?;
+            parsed.push(mv);
+            position = position.play(mv);
+        }
+        Ok(parsed)
+    }
+
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+        for row in 0..8 {
+            let mut empty = 0;
+            for file in 0..8 {
+                match self.squares[row * 8 + file] {
+                    Some(piece) => {
+                        if empty > 0 {
+                            fen.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        fen.push(piece.as_char());
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+            if row < 7 {
+                fen.push('/');
+            }
+        }
+        let mut castling = String::new();
+        for (right, c) in [
+            (WHITE_KING_SIDE, 'K'),
+            (WHITE_QUEEN_SIDE, 'Q'),
+            (BLACK_KING_SIDE, 'k'),
+            (BLACK_QUEEN_SIDE, 'q'),
+        ] {
+            if self.castling & right != 0 {
+                castling.push(c);
+            }
+        }
+        if castling.is_empty() {
+            castling.push('-');
+        }
+        format!(
+            "{} {} {} {} {} {}",
+            fen,
+            self.turn.as_str(),
+            castling,
+            self.en_passant.map_or("-".to_string(), square_name),
+            self.half_move,
+            self.full_move
+        )
+    }
+
+    pub fn piece_at(&self, square: usize) -> Option<Piece> {
+        self.squares[square]
+    }
+
+    pub fn turn(&self) -> Color {
+        self.turn
+    }
+
+    // Zobrist hash, see `zobrist::key`
+    pub fn key(&self) -> u64 {
+        self.key
+    }
+
+    // Bits of the castling rights still available, K Q k q from the lowest
+    pub fn castling(&self) -> u8 {
+        self.castling
+    }
+
+    pub fn en_passant(&self) -> Option<usize> {
+        self.en_passant
+    }
+
+    // Whether a pawn of the side to move can actually take en passant
+    pub fn en_passant_capturable(&self) -> bool {
+        let target = match self.en_passant {
+            Some(target) => target,
+            None => return false,
+        };
+        let behind = if self.turn == Color::White { -1 } else { 1 };
+        [-1, 1].iter().any(|&df| {
+            offset(target, df, behind)
+                .is_some_and(|from| self.squares[from] == Some(Piece::new(self.turn, Kind::Pawn)))
+        })
+    }
+
+    pub fn half_move(&self) -> u32 {
+        self.half_move
+    }
+
+    pub fn king(&self, color: Color) -> usize {
+        self.squares
+            .iter()
+            .position(|p| *p == Some(Piece::new(color, Kind::King)))
+            .unwrap_or(0)
+    }
+
+    pub fn in_check(&self) -> bool {
+        self.is_attacked(self.king(self.turn), self.turn.opponent())
+    }
+
+    // Rights whose king and rook are still at home
+    fn possible_castling(&self) -> u8 {
+        let at = |square: usize, color: Color, kind: Kind| {
+            self.squares[square] == Some(Piece::new(color, kind))
+        };
+        let mut rights = 0;
+        if at(60, Color::White, Kind::King) {
+            if at(63, Color::White, Kind::Rook) {
+                rights |= WHITE_KING_SIDE;
+            }
+            if at(56, Color::White, Kind::Rook) {
+                rights |= WHITE_QUEEN_SIDE;
+            }
+        }
+        if at(4, Color::Black, Kind::King) {
+            if at(7, Color::Black, Kind::Rook) {
+                rights |= BLACK_KING_SIDE;
+            }
+            if at(0, Color::Black, Kind::Rook) {
+                rights |= BLACK_QUEEN_SIDE;
+            }
+        }
+        rights
+    }
+
+    pub fn is_attacked(&self, square: usize, by: Color) -> bool {
+        let is = |from: Option<usize>, kind: Kind| {
+            from.is_some_and(|from| self.squares[from] == Some(Piece::new(by, kind)))
+        };
+        // A white pawn attacks upwards, so it stands one rank below
+        let pawn_rank = if by == Color::White { -1 } else { 1 };
+        if is(offset(square, -1, pawn_rank), Kind::Pawn)
+            || is(offset(square, 1, pawn_rank), Kind::Pawn)
+        {
+            return true;
+        }
+        if KNIGHT_STEPS
+            .iter()
+            .any(|&(df, dr)| is(offset(square, df, dr), Kind::Knight))
+        {
+            return true;
+        }
+        if KING_STEPS
+            .iter()
+            .any(|&(df, dr)| is(offset(square, df, dr), Kind::King))
+        {
+            return true;
+        }
+        for (rays, kind) in [(BISHOP_RAYS, Kind::Bishop), (ROOK_RAYS, Kind::Rook)] {
+            for (df, dr) in rays {
+                let mut current = square;
+                while let Some(next) = offset(current, df, dr) {
+                    if let Some(piece) = self.squares[next] {
+                        if piece.color == by && (piece.kind == kind || piece.kind == Kind::Queen) {
+                            return true;
+                        }
+                        break;
+                    }
+                    current = next;
+                }
+            }
+        }
+        false
+    }
+
+    fn push_pawn_moves(&self, moves: &mut Vec<Move>, from: usize, to: usize) {
+        if rank(to) == 0 || rank(to) == 7 {
+            for kind in PROMOTIONS {
+                moves.push(Move {
+                    from,
+                    to,
+                    promotion: Some(kind),
+                });
+            }
+        } else {
+            moves.push(Move::new(from, to));
+        }
+    }
+
+    // Moves that follow the piece rules but may leave the king in check. With `captures_only`
+    // quiet moves are skipped, promotions count as captures.
+    pub fn pseudo_moves(&self, captures_only: bool) -> Vec<Move> {
+        let mut moves = Vec::with_capacity(48);
+        let us = self.turn;
+        for from in 0..64 {
+            let piece = match self.squares[from] {
+                Some(piece) if piece.color == us => piece,
+                _ => continue,
+            };
+            let target = |to: usize| match self.squares[to] {
+                None => Some(false),
+                Some(other) if other.color != us => Some(true),
+                Some(_) => None,
+            };
+            match piece.kind {
+                Kind::Pawn => {
+                    let forward = if us == Color::White { 1 } else { -1 };
+                    let home = if us == Color::White { 1 } else { 6 };
+                    if let Some(to) = offset(from, 0, forward) {
+                        let promotes = rank(to) == 0 || rank(to) == 7;
+                        if self.squares[to].is_none() {
+                            if !captures_only || promotes {
+                                self.push_pawn_moves(&mut moves, from, to);
+                            }
+                            if let Some(double) = offset(from, 0, 2 * forward) {
+                                if !captures_only
+                                    && rank(from) == home
+                                    && self.squares[double].is_none()
+                                {
+                                    moves.push(Move::new(from, double));
+                                }
+                            }
+                        }
+                    }
+                    for df in [-1, 1] {
+                        if let Some(to) = offset(from, df, forward) {
+                            let enemy = self.squares[to].is_some_and(|p| p.color != us);
+                            if enemy || self.en_passant == Some(to) {
+                                self.push_pawn_moves(&mut moves, from, to);
+                            }
+                        }
+                    }
+                }
+                Kind::Knight | Kind::King => {
+                    let steps = if piece.kind == Kind::Knight {
+                        KNIGHT_STEPS
+                    } else {
+                        KING_STEPS
+                    };
+                    for (df, dr) in steps {
+                        if let Some(to) = offset(from, df, dr) {
+                            match target(to) {
+                                Some(true) => moves.push(Move::new(from, to)),
+                                Some(false) if !captures_only => moves.push(Move::new(from, to)),
+                                _ => {}
+                            }
+                        }
+                    }
+                }
+                Kind::Bishop | Kind::Rook | Kind::Queen => {
+                    let rays: &[(i32, i32)] = match piece.kind {
+                        Kind::Bishop => &BISHOP_RAYS,
+                        Kind::Rook => &ROOK_RAYS,
+                        _ => &KING_STEPS,
+                    };
+                    for &(df, dr) in rays {
+                        let mut current = from;
+                        while let Some(to) = offset(current, df, dr) {
+                            match target(to) {
+                                Some(true) => {
+                                    moves.push(Move::new(from, to));
+                                    break;
+                                }
+                                Some(false) => {
+                                    if !captures_only {
+                                        moves.push(Move::new(from, to));
+                                    }
+                                }
+                                None => break,
+                            }
+                            current = to;
+                        }
+                    }
+                }
+            }
+        }
+        if !captures_only {
+            self.push_castling(&mut moves);
+        }
+        moves
+    }
+
+    fn push_castling(&self, moves: &mut Vec<Move>) {
+        let (king, king_side, queen_side) = match self.turn {
+            Color::White => (60, WHITE_KING_SIDE, WHITE_QUEEN_SIDE),
+            Color::Black => (4, BLACK_KING_SIDE, BLACK_QUEEN_SIDE),
+        };
+        if self.castling & (king_side | queen_side) == 0 || self.in_check() {
+            return;
+        }
+        let them = self.turn.opponent();
+        let empty = |squares: &[usize]| squares.iter().all(|&s| self.squares[s].is_none());
+        let safe = |squares: &[usize]| squares.iter().all(|&s| !self.is_attacked(s, them));
+        if self.castling & king_side != 0
+            && empty(&[king + 1, king + 2])
+            && safe(&[king + 1, king + 2])
+        {
+            moves.push(Move::new(king, king + 2));
+        }
+        if self.castling & queen_side != 0
+            && empty(&[king - 1, king - 2, king - 3])
+            && safe(&[king - 1, king - 2])
+        {
+            moves.push(Move::new(king, king - 2));
+        }
+    }
+
+    pub fn legal_moves(&self) -> Vec<Move> {
+        self.filter_legal(self.pseudo_moves(false))
+    }
+
+    pub fn legal_captures(&self) -> Vec<Move> {
+        self.filter_legal(self.pseudo_moves(true))
+    }
+
+    fn filter_legal(&self, moves: Vec<Move>) -> Vec<Move> {
+        let us = self.turn;
+        moves
+            .into_iter()
+            .filter(|&mv| {
+                let next = self.play(mv);
+                !next.is_attacked(next.king(us), us.opponent())
+            })
+            .collect()
+    }
+
+    pub fn is_capture(&self, mv: Move) -> bool {
+        self.squares[mv.to].is_some() || self.is_en_passant(mv)
+    }
+
+    fn is_en_passant(&self, mv: Move) -> bool {
+        self.en_passant == Some(mv.to)
+            && self.squares[mv.from].is_some_and(|p| p.kind == Kind::Pawn)
+            && file(mv.from) != file(mv.to)
+    }
+
+    fn is_castling(&self, mv: Move) -> bool {
+        self.squares[mv.from].is_some_and(|p| p.kind == Kind::King)
+            && file(mv.from).abs_diff(file(mv.to)) == 2
+    }
+
+    // The captured piece, if any
+    pub fn captured(&self, mv: Move) -> Option<Piece> {
+        if self.is_en_passant(mv) {
+            Some(Piece::new(self.turn.opponent(), Kind::Pawn))
+        } else {
+            self.squares[mv.to]
+        }
+    }
+
+    // The position after `mv`, which must be at least pseudo legal
+    pub fn play(&self, mv: Move) -> Position {
+        let mut next = self.clone();
+        let piece = match self.squares[mv.from] {
+            Some(piece) => piece,
+            None => return next,
+        };
+        let capture = self.is_capture(mv);
+        if self.is_en_passant(mv) {
+            let behind = if piece.color == Color::White { -1 } else { 1 };
+            if let Some(taken) = offset(mv.to, 0, behind) {
+                next.squares[taken] = None;
+            }
+        }
+        if self.is_castling(mv) {
+            let (rook_from, rook_to) = if mv.to > mv.from {
+                (mv.from + 3, mv.from + 1)
+            } else {
+                (mv.from - 4, mv.from - 1)
+            };
+            next.squares[rook_to] = next.squares[rook_from].take();
+        }
+        next.squares[mv.to] = match mv.promotion {
+            Some(kind) => Some(Piece::new(piece.color, kind)),
+            None => Some(piece),
+        };
+        next.squares[mv.from] = None;
+
+        for square in [mv.from, mv.to] {
+            next.castling &= match square {
+                60 => !(WHITE_KING_SIDE | WHITE_QUEEN_SIDE),
+                63 => !WHITE_KING_SIDE,
+                56 => !WHITE_QUEEN_SIDE,
+                4 => !(BLACK_KING_SIDE | BLACK_QUEEN_SIDE),
+                7 => !BLACK_KING_SIDE,
+                0 => !BLACK_QUEEN_SIDE,
+                _ => !0,
+            };
+        }
+        next.en_passant = None;
+        if piece.kind == Kind::Pawn && rank(mv.from).abs_diff(rank(mv.to)) == 2 {
+            next.en_passant = Some((mv.from + mv.to) / 2);
+        }
+        next.half_move = if piece.kind == Kind::Pawn || capture {
+            0
+        } else {
+            self.half_move + 1
+        };
+        if self.turn == Color::Black {
+            next.full_move += 1;
+        }
+        next.turn = self.turn.opponent();
+        next.key = super::zobrist::key(&next);
+        next
+    }
+
+    // Passing the turn, for null move pruning
+    pub fn play_null(&self) -> Position {
+        let mut next = self.clone();
+        next.en_passant = None;
+        next.turn = self.turn.opponent();
+        next.key = super::zobrist::key(&next);
+        next
+    }
+
+    // SAN without the check or mate suffix
+    fn san_body(&self, mv: Move, legal: &[Move]) -> String {
+        let piece = match self.squares[mv.from] {
+            Some(piece) => piece,
+            None => return String::new(),
+        };
+        if self.is_castling(mv) {
+            return if mv.to > mv.from { "O-O" } else { "O-O-O" }.to_string();
+        }
+        let capture = self.is_capture(mv);
+        let mut san = String::new();
+        if piece.kind == Kind::Pawn {
+            if capture {
+                san.push(square_name(mv.from).remove(0));
+                san.push('x');
+            }
+            san.push_str(&square_name(mv.to));
+            if let Some(kind) = mv.promotion {
+                san.push('=');
+                san.push(kind_char(kind));
+            }
+            return san;
+        }
+        san.push(kind_char(piece.kind));
+        let rivals: Vec<&Move> = legal
+            .iter()
+            .filter(|other| {
+                other.to == mv.to
+                    && other.from != mv.from
+                    && self.squares[other.from] == Some(piece)
+            })
+            .collect();
+        if !rivals.is_empty() {
+            let name = square_name(mv.from);
+            if rivals.iter().all(|other| file(other.from) != file(mv.from)) {
+                san.push_str(&name[..1]);
+            } else if rivals.iter().all(|other| rank(other.from) != rank(mv.from)) {
+                san.push_str(&name[1..]);
+            } else {
+                san.push_str(&name);
+            }
+        }
+        if capture {
+            san.push('x');
+        }
+        san.push_str(&square_name(mv.to));
+        san
+    }
+
+    pub fn san(&self, mv: Move) -> String {
+        let mut san = self.san_body(mv, &self.legal_moves());
+        let next = self.play(mv);
+        if next.in_check() {
+            san.push(if next.legal_moves().is_empty() {
+                '#'
+            } else {
+                '+'
+            });
+        }
+        san
+    }
+
+    // Coordinate notation as used by UCI, e.g. e2e4 or e7e8q
+    pub fn parse_uci(&self, uci: &str) -> Option<Move> {
+        if !uci.is_ascii() || !(4..=5).contains(&uci.len()) {
+            return None;
+        }
+        let from = parse_square(&uci[0..2])?;
+        let to = parse_square(&uci[2..4])?;
+        let promotion = match uci[4..].chars().next() {
+            Some(c) => Some(Piece::from_char(c)?.kind),
+            None => None,
+        };
+        let mv = Move {
+            from,
+            to,
+            promotion,
+        };
+        self.legal_moves().into_iter().find(|legal| *legal == mv)
+    }
+
+    // Accepts SAN, with or without check marks and annotations, as well as the long forms the
+    // game service takes such as Qe2e3 or e2e4
+    pub fn parse_move(&self, text: &str) -> Option<Move> {
+        let text = text
+            .trim_end_matches(&['+', '#', '!', '?'][..])
+            .replace("0-0-0", "O-O-O")
+            .replace("0-0", "O-O");
+        let legal = self.legal_moves();
+        if let Some(mv) = legal.iter().find(|&&mv| self.san_body(mv, &legal) == text) {
+            return Some(*mv);
+        }
+        let long: String = text
+            .chars()
+            .filter(|c| !matches!(c, 'x' | '-' | '=' | 'N' | 'B' | 'R' | 'Q' | 'K'))
+            .collect();
+        let promotion = text
+            .chars()
+            .last()
+            .filter(|c| matches!(c, 'N' | 'B' | 'R' | 'Q'));
+        let mut uci = long;
+        if let Some(kind) = promotion {
+            uci.push(kind.to_ascii_lowercase());
+        }
+        self.parse_uci(&uci)
+    }
+
+    // Kings only, or a single minor piece against a bare king
+    pub fn insufficient_material(&self) -> bool {
+        let pieces: Vec<Piece> = self
+            .squares
+            .iter()
+            .flatten()
+            .filter(|p| p.kind != Kind::King)
+            .copied()
+            .collect();
+        match pieces.as_slice() {
+            [] => true,
+            [piece] => matches!(piece.kind, Kind::Knight | Kind::Bishop),
+            _ => false,
+        }
+    }
+}
+
+impl Default for Position {
+    fn default() -> Self {
+        Position::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn perft(position: &Position, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        position
+            .legal_moves()
+            .into_iter()
+            .map(|mv| perft(&position.play(mv), depth - 1))
+            .sum()
+    }
+
+    #[test]
+    fn test_perft_start_position() {
+        let position = Position::new();
+        assert_eq!(perft(&position, 1), 20);
+        assert_eq!(perft(&position, 3), 8902);
+    }
+
+    #[test]
+    fn test_perft_tricky_positions() {
+        // Positions 2, 3 and 4 from https://www.chessprogramming.org/Perft_Results
+        let kiwipete = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        assert_eq!(perft(&Position::from_fen(kiwipete).unwrap(), 2), 2039);
+        let endgame = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+        assert_eq!(perft(&Position::from_fen(endgame).unwrap(), 3), 2812);
+        let promotions = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+        assert_eq!(perft(&Position::from_fen(promotions).unwrap(), 3), 9467);
+    }
+
+    #[test]
+    fn test_fen_round_trip() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        assert_eq!(Position::from_fen(fen).unwrap().to_fen(), fen);
+        assert_eq!(Position::new().to_fen(), START_FEN);
+        assert!(Position::from_fen("8/8/8/8/8/8/8/8 w - - 0 1").is_err());
+    }
+
+    #[test]
+    fn test_san() {
+        let position = Position::from_moves("e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O")
+            .unwrap()
+            .0;
+        assert_eq!(
+            position.to_fen(),
+            "r1bqkb1r/1ppp1ppp/p1n2n2/4p3/B3P3/5N2/PPPP1PPP/RNBQ1RK1 b kq - 3 5"
+        );
+        let position = Position::from_fen("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
+        let check = position.parse_move("Ra8").unwrap();
+        assert_eq!(position.san(check), "Ra8+");
+        let rooks = Position::from_fen("4k3/8/8/8/8/8/8/R4RK1 w - - 0 1").unwrap();
+        assert_eq!(rooks.san(rooks.parse_move("a1d1").unwrap()), "Rad1");
+        let mate = Position::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        assert_eq!(mate.san(mate.parse_move("Ra8").unwrap()), "Ra8#");
+    }
+
+    #[test]
+    fn test_en_passant_and_promotion() {
+        let position = Position::from_moves("e4 a6 e5 d5").unwrap().0;
+        let capture = position.parse_move("exd6").unwrap();
+        let next = position.play(capture);
+        assert_eq!(next.piece_at(parse_square("d5").unwrap()), None);
+        let position = Position::from_fen("8/P6k/8/8/8/8/8/K7 w - - 0 1").unwrap();
+        let promote = position.parse_move("a8=Q").unwrap();
+        assert_eq!(promote.promotion, Some(Kind::Queen));
+        assert_eq!(position.parse_move("a7a8q"), Some(promote));
+    }
+}
@@ -0,0 +1,580 @@
+// Alpha-beta (principal variation search) with iterative deepening, a transposition table,
+// null move pruning and a quiescence search over captures. Moves are tried hash move first,
+// then captures by most valuable victim, promotions, killer moves and history.
+use std::cmp::Reverse;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use std::time::{Duration, Instant};
+
+use super::{
+    eval::{evaluate, piece_value},
+    position::{Move, Position},
+    tt::{Bound, Entry, TranspositionTable},
+};
+use crate::chess::pieces::Kind;
+
+pub const MATE: i32 = 32_000;
+const INFINITY: i32 = MATE + 1;
+pub const MAX_PLY: usize = 64;
+const DEFAULT_TT_ENTRIES: usize = 1 << 18;
+// How often the clock is looked at
+const CHECK_EVERY: u64 = 1024;
+const NULL_MOVE_REDUCTION: i32 = 2;
+
+pub fn is_mate_score(score: i32) -> bool {
+    score.abs() >= MATE - MAX_PLY as i32
+}
+
+// Moves until mate for a mate score, negative when the side to move is getting mated
+pub fn mate_in(score: i32) -> Option<i32> {
+    if !is_mate_score(score) {
+        return None;
+    }
+    let moves = (MATE - score.abs() + 1) / 2;
+    Some(if score > 0 { moves } else { -moves })
+}
+
+// Mate scores are stored relative to the node so they stay right when found at another ply
+fn score_to_tt(score: i32, ply: usize) -> i32 {
+    if score >= MATE - MAX_PLY as i32 {
+        score + ply as i32
+    } else if score <= -(MATE - MAX_PLY as i32) {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+fn score_from_tt(score: i32, ply: usize) -> i32 {
+    if score >= MATE - MAX_PLY as i32 {
+        score - ply as i32
+    } else if score <= -(MATE - MAX_PLY as i32) {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+// Any of them stops the search, at least one full iteration is always finished
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Limits {
+    pub depth: Option<i32>,
+    pub nodes: Option<u64>,
+    pub time: Option<Duration>,
+    // Up to this many centipawns are added to or taken off every evaluation, to play worse
+    pub noise: i32,
+}
+
+// Result of one finished iteration, `score` is for the side to move
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchInfo {
+    pub depth: i32,
+    pub score: i32,
+    pub nodes: u64,
+    pub pv: Vec<Move>,
+}
+
+impl SearchInfo {
+    pub fn best(&self) -> Option<Move> {
+        self.pv.first().copied()
+    }
+}
+
+pub struct Search {
+    tt: TranspositionTable,
+    limits: Limits,
+    started: Instant,
+    nodes: u64,
+    completed: i32,
+    stopped: bool,
+    // Set from another thread to end the search early
+    stop: Arc<AtomicBool>,
+    noise_seed: u64,
+    // Root moves left out, so multi-PV can search for the next best line
+    excluded: Vec<Move>,
+    // Keys of the positions before the current one, game history first, for repetitions
+    keys: Vec<u64>,
+    killers: [[Option<Move>; 2]; MAX_PLY],
+    history: Box<[[i32; 64]; 64]>,
+    pv: Box<[[Option<Move>; MAX_PLY]; MAX_PLY]>,
+    pv_len: [usize; MAX_PLY],
+}
+
+impl Default for Search {
+    fn default() -> Self {
+        Search::new(DEFAULT_TT_ENTRIES)
+    }
+}
+
+impl Search {
+    pub fn new(tt_entries: usize) -> Search {
+        Search {
+            tt: TranspositionTable::new(tt_entries),
+            limits: Limits::default(),
+            started: Instant::now(),
+            nodes: 0,
+            completed: 0,
+            stopped: false,
+            stop: Arc::new(AtomicBool::new(false)),
+            noise_seed: 0,
+            excluded: Vec::new(),
+            keys: Vec::new(),
+            killers: [[None; 2]; MAX_PLY],
+            history: Box::new([[0; 64]; 64]),
+            pv: Box::new([[None; MAX_PLY]; MAX_PLY]),
+            pv_len: [0; MAX_PLY],
+        }
+    }
+
+    pub fn stop_handle(&self) -> Arc<AtomicBool> {
+        self.stop.clone()
+    }
+
+    // Searches `position` reached after the positions with keys `previous`, calling `report`
+    // after every finished iteration. Returns the last finished one, None without legal moves.
+    pub fn run(
+        &mut self,
+        position: &Position,
+        previous: &[u64],
+        limits: Limits,
+        report: &mut dyn FnMut(&SearchInfo),
+    ) -> Option<SearchInfo> {
+        self.run_multipv(position, previous, limits, 1, &mut |lines| {
+            report(&lines[0])
+        })
+        .into_iter()
+        .next()
+    }
+
+    // Like `run` with the best `lines` root moves each given its own line, best first. Every
+    // line of an iteration is searched before `report` gets them.
+    pub fn run_multipv(
+        &mut self,
+        position: &Position,
+        previous: &[u64],
+        limits: Limits,
+        lines: usize,
+        report: &mut dyn FnMut(&[SearchInfo]),
+    ) -> Vec<SearchInfo> {
+        self.limits = limits;
+        self.started = Instant::now();
+        self.nodes = 0;
+        self.completed = 0;
+        self.stopped = false;
+        self.noise_seed = if limits.noise > 0 { rand::random() } else { 0 };
+        self.keys = previous.to_vec();
+        self.killers = [[None; 2]; MAX_PLY];
+        self.history.iter_mut().for_each(|row| row.fill(0));
+        let legal = position.legal_moves().len();
+        if legal == 0 {
+            return Vec::new();
+        }
+        let lines = lines.clamp(1, legal);
+
+        let max_depth = limits
+            .depth
+            .unwrap_or(MAX_PLY as i32)
+            .clamp(1, MAX_PLY as i32 - 1);
+        let mut result = Vec::new();
+        'deepening: for depth in 1..=max_depth {
+            let mut infos: Vec<SearchInfo> = Vec::with_capacity(lines);
+            for _ in 0..lines {
+                self.excluded = infos.iter().filter_map(SearchInfo::best).collect();
+                let score = self.negamax(position, depth, 0, -INFINITY, INFINITY, false);
+                if self.stopped {
+                    break 'deepening;
+                }
+                infos.push(SearchInfo {
+                    depth,
+                    score,
+                    nodes: self.nodes,
+                    pv: self.pv[0][..self.pv_len[0]]
+                        .iter()
+                        .flatten()
+                        .copied()
+                        .collect(),
+                });
+            }
+            self.completed = depth;
+            infos.sort_by_key(|info| Reverse(info.score));
+            report(&infos);
+            let score = infos[0].score;
+            result = infos;
+            // Nothing to gain from searching a forced mate any deeper
+            if is_mate_score(score) && MATE - score.abs() <= depth {
+                break;
+            }
+            if self.out_of_time() {
+                break;
+            }
+        }
+        self.excluded.clear();
+        result
+    }
+
+    fn out_of_time(&self) -> bool {
+        self.limits
+            .time
+            .is_some_and(|time| self.started.elapsed() >= time)
+    }
+
+    fn should_stop(&mut self) -> bool {
+        if self.stopped {
+            return true;
+        }
+        if self.completed == 0 {
+            return false;
+        }
+        let nodes_spent = self.limits.nodes.is_some_and(|nodes| self.nodes >= nodes);
+        let interrupted = self.nodes % CHECK_EVERY == 0
+            && (self.out_of_time() || self.stop.load(Ordering::Relaxed));
+        if nodes_spent || interrupted {
+            self.stopped = true;
+        }
+        self.stopped
+    }
+
+    fn evaluate(&self, position: &Position) -> i32 {
+        let score = evaluate(position);
+        if self.limits.noise <= 0 {
+            return score;
+        }
+        // The same position gets the same noise during a search, so the table stays consistent
+        let hash = (position.key() ^ self.noise_seed).wrapping_mul(0x2545_f491_4f6c_dd1d);
+        let spread = 2 * self.limits.noise as u64 + 1;
+        score + ((hash >> 32) % spread) as i32 - self.limits.noise
+    }
+
+    fn is_repetition(&self, position: &Position) -> bool {
+        self.keys
+            .iter()
+            .rev()
+            .take(position.half_move() as usize)
+            .any(|&key| key == position.key())
+    }
+
+    fn has_pieces(position: &Position) -> bool {
+        (0..64).any(|square| {
+            position.piece_at(square).is_some_and(|piece| {
+                piece.color == position.turn() && !matches!(piece.kind, Kind::Pawn | Kind::King)
+            })
+        })
+    }
+
+    fn order(
+        &self,
+        position: &Position,
+        moves: Vec<Move>,
+        hash_move: Option<Move>,
+        ply: usize,
+    ) -> Vec<Move> {
+        let mut scored: Vec<(i32, Move)> = moves
+            .into_iter()
+            .map(|mv| {
+                let score = if Some(mv) == hash_move {
+                    1_000_000
+                } else if let Some(victim) = position.captured(mv) {
+                    let attacker = position
+                        .piece_at(mv.from)
+                        .map_or(0, |p| piece_value(p.kind));
+                    100_000 + 10 * piece_value(victim.kind) - attacker / 10
+                } else if let Some(kind) = mv.promotion {
+                    90_000 + piece_value(kind)
+                } else if ply < MAX_PLY && self.killers[ply][0] == Some(mv) {
+                    80_000
+                } else if ply < MAX_PLY && self.killers[ply][1] == Some(mv) {
+                    79_000
+                } else {
+                    self.history[mv.from][mv.to]
+                };
+                (score, mv)
+            })
+            .collect();
+        scored.sort_by_key(|(score, _)| Reverse(*score));
+        scored.into_iter().map(|(_, mv)| mv).collect()
+    }
+
+    fn negamax(
+        &mut self,
+        position: &Position,
+        depth: i32,
+        ply: usize,
+        mut alpha: i32,
+        beta: i32,
+        null_allowed: bool,
+    ) -> i32 {
+        self.pv_len[ply] = ply;
+        if self.should_stop() {
+            return 0;
+        }
+        self.nodes += 1;
+        if ply > 0
+            && (position.half_move() >= 100
+                || position.insufficient_material()
+                || self.is_repetition(position))
+        {
+            return 0;
+        }
+        if ply >= MAX_PLY - 1 {
+            return self.evaluate(position);
+        }
+        let in_check = position.in_check();
+        let depth = if in_check { depth + 1 } else { depth };
+        if depth <= 0 {
+            return self.quiescence(position, ply, alpha, beta);
+        }
+
+        let key = position.key();
+        let entry = self.tt.probe(key);
+        if let Some(entry) = entry {
+            if ply > 0 && entry.depth >= depth {
+                let score = score_from_tt(entry.score, ply);
+                match entry.bound {
+                    Bound::Exact => return score,
+                    Bound::Lower if score >= beta => return score,
+                    Bound::Upper if score <= alpha => return score,
+                    _ => {}
+                }
+            }
+        }
+
+        let pv_node = beta - alpha > 1;
+        if null_allowed
+            && !pv_node
+            && !in_check
+            && depth > NULL_MOVE_REDUCTION
+            && Search::has_pieces(position)
+            && self.evaluate(position) >= beta
+        {
+            self.keys.push(key);
+            let score = -self.negamax(
+                &position.play_null(),
+                depth - 1 - NULL_MOVE_REDUCTION,
+                ply + 1,
+                -beta,
+                -beta + 1,
+                false,
+            );
+            self.keys.pop();
+            if self.stopped {
+                return 0;
+            }
+            if score >= beta && !is_mate_score(score) {
+                return beta;
+            }
+        }
+
+        let mut moves = position.legal_moves();
+        if moves.is_empty() {
+            return if in_check { -MATE + ply as i32 } else { 0 };
+        }
+        if ply == 0 {
+            moves.retain(|mv| !self.excluded.contains(mv));
+        }
+        let moves = self.order(position, moves, entry.and_then(|e| e.best), ply);
+        let original_alpha = alpha;
+        let mut best_score = -INFINITY;
+        let mut best_move = None;
+        self.keys.push(key);
+        for (i, mv) in moves.into_iter().enumerate() {
+            let next = position.play(mv);
+            let score = if i == 0 {
+                -self.negamax(&next, depth - 1, ply + 1, -beta, -alpha, true)
+            } else {
+                let score = -self.negamax(&next, depth - 1, ply + 1, -alpha - 1, -alpha, true);
+                if score > alpha && score < beta {
+                    -self.negamax(&next, depth - 1, ply + 1, -beta, -alpha, true)
+                } else {
+                    score
+                }
+            };
+            if self.stopped {
+                self.keys.pop();
+                return 0;
+            }
+            if score > best_score {
+                best_score = score;
+                best_move = Some(mv);
+            }
+            if score > alpha {
+                alpha = score;
+                self.pv[ply][ply] = Some(mv);
+                for i in ply + 1..self.pv_len[ply + 1] {
+                    self.pv[ply][i] = self.pv[ply + 1][i];
+                }
+                self.pv_len[ply] = self.pv_len[ply + 1].max(ply + 1);
+            }
+            if alpha >= beta {
+                if !position.is_capture(mv) {
+                    if self.killers[ply][0] != Some(mv) {
+                        self.killers[ply][1] = self.killers[ply][0];
+                        self.killers[ply][0] = Some(mv);
+                    }
+                    self.history[mv.from][mv.to] += depth * depth;
+                }
+                break;
+            }
+        }
+        self.keys.pop();
+
+        let bound = if best_score >= beta {
+            Bound::Lower
+        } else if best_score > original_alpha {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        self.tt.store(Entry {
+            key,
+            depth,
+            score: score_to_tt(best_score, ply),
+            bound,
+            best: best_move,
+        });
+        best_score
+    }
+
+    // Only captures and promotions, so the evaluation is not taken in the middle of an
+    // exchange. In check every evasion is tried.
+    fn quiescence(&mut self, position: &Position, ply: usize, mut alpha: i32, beta: i32) -> i32 {
+        if self.should_stop() {
+            return 0;
+        }
+        self.nodes += 1;
+        let in_check = position.in_check();
+        if ply >= MAX_PLY - 1 {
+            return self.evaluate(position);
+        }
+        let moves = if in_check {
+            let moves = position.legal_moves();
+            if moves.is_empty() {
+                return -MATE + ply as i32;
+            }
+            moves
+        } else {
+            let stand_pat = self.evaluate(position);
+            if stand_pat >= beta {
+                return stand_pat;
+            }
+            alpha = alpha.max(stand_pat);
+            position.legal_captures()
+        };
+        let mut best = if in_check { -INFINITY } else { alpha };
+        for mv in self.order(position, moves, None, MAX_PLY) {
+            let score = -self.quiescence(&position.play(mv), ply + 1, -beta, -alpha);
+            if self.stopped {
+                return 0;
+            }
+            if score > best {
+                best = score;
+            }
+            if score >= beta {
+                return score;
+            }
+            alpha = alpha.max(score);
+        }
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search(fen: &str, depth: i32) -> SearchInfo {
+        let position = Position::from_fen(fen).unwrap();
+        let limits = Limits {
+            depth: Some(depth),
+            ..Default::default()
+        };
+        Search::new(1 << 16)
+            .run(&position, &[], limits, &mut |_| {})
+            .unwrap()
+    }
+
+    #[test]
+    fn test_finds_mate_in_one() {
+        let fen = "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4";
+        let info = search(fen, 3);
+        let position = Position::from_fen(fen).unwrap();
+        assert_eq!(position.san(info.best().unwrap()), "Qxf7#");
+        // Mate on the next ply
+        assert_eq!(info.score, MATE - 1);
+    }
+
+    #[test]
+    fn test_finds_mate_in_two() {
+        // 1. Nf6+ gxf6 2. Bxf7#
+        let fen = "r2qkb1r/pp2nppp/3p4/2pNN1B1/2BnP3/3P4/PPP2PPP/R2bK2R w KQkq - 1 1";
+        let info = search(fen, 4);
+        let position = Position::from_fen(fen).unwrap();
+        assert_eq!(position.san(info.best().unwrap()), "Nf6+");
+        assert_eq!(info.score, MATE - 3);
+    }
+
+    #[test]
+    fn test_takes_hanging_queen() {
+        let fen = "4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1";
+        let info = search(fen, 2);
+        let position = Position::from_fen(fen).unwrap();
+        assert_eq!(position.san(info.best().unwrap()), "Rxd5");
+    }
+
+    #[test]
+    fn test_stalemate_and_checkmate_have_no_move() {
+        let stalemate = Position::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        let mut search = Search::new(1 << 10);
+        assert_eq!(
+            search.run(&stalemate, &[], Limits::default(), &mut |_| {}),
+            None
+        );
+    }
+
+    #[test]
+    fn test_multipv_lines_are_distinct_and_sorted() {
+        let position = Position::new();
+        let limits = Limits {
+            depth: Some(3),
+            ..Default::default()
+        };
+        let mut reports = 0;
+        let lines = Search::new(1 << 16).run_multipv(&position, &[], limits, 3, &mut |lines| {
+            assert_eq!(lines.len(), 3);
+            reports += 1;
+        });
+        assert_eq!(reports, 3);
+        assert_eq!(lines.len(), 3);
+        assert!(lines.windows(2).all(|pair| pair[0].score >= pair[1].score));
+        assert_ne!(lines[0].best(), lines[1].best());
+        assert_ne!(lines[1].best(), lines[2].best());
+        assert_ne!(lines[0].best(), lines[2].best());
+        // Never more lines than legal moves
+        let king = Position::from_fen("k7/8/8/8/8/8/8/K7 w - - 0 1").unwrap();
+        let lines = Search::new(1 << 10).run_multipv(&king, &[], limits, 10, &mut |_| {});
+        assert_eq!(lines.len(), 3);
+    }
+
+    #[test]
+    fn test_mate_in() {
+        assert_eq!(mate_in(MATE - 1), Some(1));
+        assert_eq!(mate_in(MATE - 3), Some(2));
+        assert_eq!(mate_in(-(MATE - 2)), Some(-1));
+        assert_eq!(mate_in(150), None);
+    }
+
+    #[test]
+    fn test_node_limit_still_gives_a_move() {
+        let position = Position::new();
+        let limits = Limits {
+            nodes: Some(1),
+            ..Default::default()
+        };
+        let info = Search::new(1 << 10)
+            .run(&position, &[], limits, &mut |_| {})
+            .unwrap();
+        assert_eq!(info.depth, 1);
+        assert!(info.best().is_some());
+    }
+}
@@ -0,0 +1,84 @@
+// Transposition table: search results by position key, so positions reached again through a
+// different move order are not searched twice and the best move found is tried first.
+use super::position::Move;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bound {
+    Exact,
+    // The score is at least this (a beta cutoff)
+    Lower,
+    // The score is at most this (nothing beat alpha)
+    Upper,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Entry {
+    pub key: u64,
+    pub depth: i32,
+    pub score: i32,
+    pub bound: Bound,
+    pub best: Option<Move>,
+}
+
+pub struct TranspositionTable {
+    entries: Vec<Option<Entry>>,
+}
+
+impl TranspositionTable {
+    // Rounded down to a power of two entries
+    pub fn new(entries: usize) -> TranspositionTable {
+        let size = entries.max(1).next_power_of_two();
+        let size = if size > entries.max(1) {
+            size / 2
+        } else {
+            size
+        };
+        TranspositionTable {
+            entries: vec![None; size],
+        }
+    }
+
+    fn index(&self, key: u64) -> usize {
+        (key as usize) & (self.entries.len() - 1)
+    }
+
+    pub fn probe(&self, key: u64) -> Option<Entry> {
+        self.entries[self.index(key)].filter(|entry| entry.key == key)
+    }
+
+    // A deeper result for the same position is kept, anything else is replaced
+    pub fn store(&mut self, entry: Entry) {
+        let index = self.index(entry.key);
+        match self.entries[index] {
+            Some(old) if old.key == entry.key && old.depth > entry.depth => {}
+            _ => self.entries[index] = Some(entry),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(key: u64, depth: i32) -> Entry {
+        Entry {
+            key,
+            depth,
+            score: 0,
+            bound: Bound::Exact,
+            best: None,
+        }
+    }
+
+    #[test]
+    fn test_deeper_entry_is_kept() {
+        let mut table = TranspositionTable::new(1000);
+        table.store(entry(7, 5));
+        table.store(entry(7, 3));
+        assert_eq!(table.probe(7).map(|e| e.depth), Some(5));
+        // Another position in the same slot replaces it
+        table.store(entry(7 + 512, 1));
+        assert_eq!(table.probe(7), None);
+        assert_eq!(table.probe(7 + 512).map(|e| e.depth), Some(1));
+    }
+}
@@ -0,0 +1,84 @@
+// Zobrist hashing: every piece on a square, castling right, en passant file and the side to
+// move has a fixed random key, a position's key is the xor of the keys of what is in it.
+use super::position::{file, Position};
+use crate::chess::pieces::{Color, Kind};
+
+const PIECE_KEYS: usize = 12 * 64;
+const CASTLING_KEYS: usize = PIECE_KEYS;
+const EN_PASSANT_KEYS: usize = CASTLING_KEYS + 4;
+const TURN_KEY: usize = EN_PASSANT_KEYS + 8;
+
+// splitmix64 from a fixed seed, so keys are the same on every run and can be stored
+const fn keys() -> [u64; TURN_KEY + 1] {
+    let mut keys = [0; TURN_KEY + 1];
+    let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+    let mut i = 0;
+    while i < keys.len() {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        keys[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    keys
+}
+
+static KEYS: [u64; TURN_KEY + 1] = keys();
+
+fn piece_index(color: Color, kind: Kind) -> usize {
+    let kind = match kind {
+        Kind::Pawn => 0,
+        Kind::Knight => 1,
+        Kind::Bishop => 2,
+        Kind::Rook => 3,
+        Kind::Queen => 4,
+        Kind::King => 5,
+    };
+    match color {
+        Color::White => kind,
+        Color::Black => kind + 6,
+    }
+}
+
+// The en passant file only counts when the capture is possible, so positions that cannot be
+// told apart hash the same
+pub fn key(position: &Position) -> u64 {
+    let mut key = 0;
+    for square in 0..64 {
+        if let Some(piece) = position.piece_at(square) {
+            key ^= KEYS[piece_index(piece.color, piece.kind) * 64 + square];
+        }
+    }
+    for right in 0..4 {
+        if position.castling() & (1 << right) != 0 {
+            key ^= KEYS[CASTLING_KEYS + right];
+        }
+    }
+    if let Some(square) = position.en_passant() {
+        if position.en_passant_capturable() {
+            key ^= KEYS[EN_PASSANT_KEYS + file(square)];
+        }
+    }
+    if position.turn() == Color::Black {
+        key ^= KEYS[TURN_KEY];
+    }
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transpositions_hash_the_same() {
+        let a = Position::from_moves("Nf3 Nf6 Nc3 Nc6").unwrap().0;
+        let b = Position::from_moves("Nc3 Nc6 Nf3 Nf6").unwrap().0;
+        assert_eq!(a.key(), b.key());
+        assert_ne!(a.key(), Position::new().key());
+        // Nobody can take en passant after 1. e4, so it is the same position as 1. e3 e6 2. e4
+        let double = Position::from_moves("e4").unwrap().0;
+        let from_fen = Position::from_fen(&double.to_fen().replace(" e3 ", " - ")).unwrap();
+        assert_eq!(double.key(), from_fen.key());
+    }
+}
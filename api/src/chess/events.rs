@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::broadcast;
+
+// Events buffered per game for slow watchers before they start missing some
+const CHANNEL_CAPACITY: usize = 64;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum GameEvent {
+    // `ply` is the number of half moves played, including this one
+    Move {
+        ply: usize,
+        san: String,
+        fen: String,
+    },
+    Finished {
+        result: String,
+    },
+}
+
+// Live events per game, for players and spectators watching it
+#[derive(Clone, Default)]
+pub struct GameEvents {
+    channels: Arc<Mutex<HashMap<i32, broadcast::Sender<GameEvent>>>>,
+}
+
+impl GameEvents {
+    pub fn subscribe(&self, game_id: i32) -> Option<broadcast::Receiver<GameEvent>> {
+        let mut channels = self.channels.lock().ok()?;
+        let sender = channels
+            .entry(game_id)
+            .or_insert_with(|| broadcast::channel(CHANNEL_CAPACITY).0);
+        Some(sender.subscribe())
+    }
+
+    pub fn publish(&self, game_id: i32, event: GameEvent) {
+        let mut channels = match self.channels.lock() {
+            Ok(channels) => channels,
+            Err(_) => return,
+        };
+        let finished = matches!(event, GameEvent::Finished { .. });
+        if let Some(sender) = channels.get(&game_id) {
+            // Fails only when nobody is watching
+            if sender.send(event).is_err() || finished {
+                channels.remove(&game_id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_game_events() {
+        let events = GameEvents::default();
+        // Nobody watching
+        events.publish(
+            1,
+            GameEvent::Finished {
+                result: "1-0".to_string(),
+            },
+        );
+        let mut receiver = events.subscribe(1).unwrap();
+        let mut other_game = events.subscribe(2).unwrap();
+        let move_event = GameEvent::Move {
+            ply: 1,
+            san: "e4".to_string(),
+            fen: "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1".to_string(),
+        };
+        events.publish(1, move_event.clone());
+        assert_eq!(receiver.try_recv().unwrap(), move_event);
+        assert!(other_game.try_recv().is_err());
+
+        events.publish(
+            1,
+            GameEvent::Finished {
+                result: "1-0".to_string(),
+            },
+        );
+        assert!(matches!(
+            receiver.try_recv().unwrap(),
+            GameEvent::Finished { .. }
+        ));
+        // The channel is dropped once the game is over
+        assert!(events.channels.lock().unwrap().get(&1).is_none());
+    }
+}
@@ -79,6 +79,8 @@ impl Game {
                         'Q' => castling_rights.1 = Some(Piece::new(Color::White, Kind::Queen)),
                         'k' => castling_rights.2 = Some(Piece::new(Color::Black, Kind::King)),
                         'q' => castling_rights.3 = Some(Piece::new(Color::Black, Kind::Queen)),
+                        // No castling rights, as `to_fen` writes them
+                        '-' => {}
                         _ => return Err(GameError),
                     }
                 }
@@ -166,6 +168,10 @@ impl Game {
         self.board.print_board()
     }
 
+    pub fn turn(&self) -> Color {
+        self.turn
+    }
+
     fn can_castle(&self, color: &Color, kind: &Kind) -> bool {
         if let Some((wk, wq, bk, bq)) = self.castling {
             match color {
@@ -236,6 +242,10 @@ mod tests {
             ))
         );
         assert_eq!(game.en_passant, None);
+
+        let fen = "4k3/8/8/8/8/8/8/4K2R b - - 3 40";
+        let game = Game::from_fen(fen).unwrap();
+        assert_eq!(game.to_fen(), fen);
     }
 
     #[test]
@@ -0,0 +1,148 @@
+// Per-move history in `game_move`, the one list of moves of a game: besides the SAN every move
+// gets its UCI form, the position after it with its Zobrist key, and the clock. Positions come
+// from the engine's board, moves back-filled from before it checked them may lack them.
+use chrono::{NaiveDateTime, Utc};
+use sea_orm::{ConnectionTrait, DatabaseConnection, DbErr};
+use tonic::Status;
+
+use entity::entities::{game, game_move};
+use service::{
+    game::mutation as game_mutation,
+    game_move::{mutation, query},
+};
+
+use super::{
+    engine::position::{Move, Position},
+    material,
+};
+
+// Each move with the position after it, up to the first one that cannot be replayed
+pub fn replay(moves: &str) -> Vec<(Move, Position)> {
+    let mut position = Position::new();
+    let mut replayed = Vec::new();
+    for san in moves.split_whitespace() {
+        let mv = match position.parse_move(san) {
+            Some(mv) => mv,
+            None => break,
+        };
+        position = position.play(mv);
+        replayed.push((mv, position.clone()));
+    }
+    replayed
+}
+
+// The SAN moves of the game so far separated by spaces, as `Position::from_moves` reads them
+pub async fn moves<C: ConnectionTrait>(db: &C, game_id: i32) -> Result<String, DbErr> {
+    Ok(query::Query::find_move_lists(db, vec![game_id])
+        .await?
+        .remove(&game_id)
+        .unwrap_or_default())
+}
+
+fn time_spent(since: NaiveDateTime, now: NaiveDateTime) -> i32 {
+    (now - since).num_milliseconds().clamp(0, i32::MAX as i64) as i32
+}
+
+// The row for `mv`, written `san`, played next in the game and leading to `after`. Worked out
+// before the game row is locked, saving it fails if another move got in first.
+pub async fn next_move(
+    db: &DatabaseConnection,
+    game_row: &game::Model,
+    san: &str,
+    mv: Move,
+    after: &Position,
+) -> Result<game_move::Model, Status> {
+    let previous = query::Query::find_last_moves(db, game_row.id, 2)
+        .await
+        .map_err(|_| Status::internal("Could not load moves"))?;
+    let ply = previous.first().map_or(1, |last| last.ply + 1);
+    let now = Utc::now().naive_utc();
+    let since = previous
+        .iter()
+        .find(|mv| mv.ply == ply - 1)
+        .map_or(game_row.created_at, |mv| mv.created_at);
+    let spent = time_spent(since, now);
+    // What the mover had left after their previous move, unknown for back-filled moves
+    let left = match previous.iter().find(|mv| mv.ply == ply - 2) {
+        Some(own) => own.clock_remaining,
+        None if ply % 2 == 1 => Some(game_row.white_time * 1000),
+        None => Some(game_row.black_time * 1000),
+    };
+
+    Ok(game_move::Model {
+        clock_remaining: left.map(|left| left.saturating_sub(spent).max(0)),
+        time_spent: Some(spent),
+        created_at: now,
+        san: san.to_string(),
+        ..positioned(game_row.id, ply, mv, after)
+    })
+}
+
+// What replaying tells about move number `ply`, the rest of the row is left as it is
+fn positioned(game_id: i32, ply: i32, mv: Move, position: &Position) -> game_move::Model {
+    game_move::Model {
+        id: 0,
+        game_id,
+        ply,
+        san: String::new(),
+        uci: Some(mv.uci()),
+        fen: Some(position.to_fen()),
+        zobrist: Some(position.key() as i64),
+        material: Some(material::signature(position)),
+        placement: Some(material::placement(position)),
+        clock_remaining: None,
+        time_spent: None,
+        created_at: Default::default(),
+    }
+}
+
+// Moves back-filled by the migrations only have their SAN or lack the material columns, this
+// adds the rest. Run at startup. A game that cannot be replayed to the end is marked so it is
+// reported once rather than on every start.
+pub async fn complete_backfill(db: &DatabaseConnection) -> Result<(), DbErr> {
+    for game_id in query::Query::find_games_missing_positions(db).await? {
+        let moves = moves(db, game_id).await?;
+        let replayed = replay(&moves);
+        if replayed.len() < moves.split_whitespace().count() {
+            println!(
+                "Error: game {game_id} can only be replayed up to ply {}",
+                replayed.len()
+            );
+            game_mutation::Mutation::mark_replay_failed(db, game_id).await?;
+        }
+        for (i, (mv, position)) in replayed.into_iter().enumerate() {
+            mutation::Mutation::set_move_position(
+                db,
+                positioned(game_id, i as i32 + 1, mv, &position),
+            )
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_replay_stops_at_unknown_move() {
+        let replayed = replay("e4 e5 Nf3 Ke7 Ke3");
+        let uci: Vec<String> = replayed.iter().map(|(mv, _)| mv.uci()).collect();
+        assert_eq!(uci, vec!["e2e4", "e7e5", "g1f3", "e8e7"]);
+        assert_eq!(
+            replayed[3].1.key(),
+            Position::from_moves("e4 e5 Nf3 Ke7").unwrap().0.key()
+        );
+        assert!(replay("").is_empty());
+    }
+
+    #[test]
+    fn test_time_spent() {
+        let now = Utc::now().naive_utc();
+        assert_eq!(time_spent(now - Duration::milliseconds(1500), now), 1500);
+        // A clock that went backwards costs nothing
+        assert_eq!(time_spent(now + Duration::seconds(1), now), 0);
+    }
+}
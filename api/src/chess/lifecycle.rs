@@ -0,0 +1,295 @@
+use sea_orm::{ConnectionTrait, DatabaseConnection, SqlErr, TransactionTrait};
+use tonic::Status;
+
+use entity::entities::{game, game_move};
+use service::{
+    game::mutation, game_move::query as game_move_query, ratings::mutation as rating_mutation,
+    time_control::query as time_control_query,
+};
+
+use super::{
+    eco,
+    engine::position::{Move, Position},
+    events::{GameEvent, GameEvents},
+    game::Game,
+    history,
+    pieces::Color,
+    syzygy::{Tablebase, Wdl},
+};
+use crate::{explorer, stats, tournament};
+
+pub const DRAW: &str = "1/2-1/2";
+// How a game ended, stored with its result
+pub const CHECKMATE: &str = "checkmate";
+pub const STALEMATE: &str = "stalemate";
+pub const RESIGNATION: &str = "resignation";
+pub const TIMEOUT: &str = "timeout";
+pub const ADJUDICATION: &str = "adjudication";
+// Tablebase draws are only declared when both players have at least this long left, with less
+// the game can still be lost on time
+const ADJUDICATION_MIN_CLOCK_MS: i32 = 30_000;
+// Off until the table decoding has been checked against real .rtbw/.rtbz files, a wrong probe
+// would end games that are not drawn
+const TABLEBASE_ADJUDICATION: bool = false;
+
+pub fn win_for(color: &Color) -> &'static str {
+    match color {
+        Color::White => "1-0",
+        Color::Black => "0-1",
+    }
+}
+
+// Starting clocks in seconds that replace the time control's, e.g. for tiebreak games
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Clock {
+    pub white: i32,
+    pub black: i32,
+}
+
+// Every way a game can start (seeks, challenges, tournaments) goes through here
+pub async fn start_game<C: ConnectionTrait>(
+    db: &C,
+    player_white: i32,
+    player_black: i32,
+    time_control_id: i32,
+    rated: bool,
+    clock: Option<Clock>,
+) -> Result<game::Model, Status> {
+    let time_control = time_control_query::Query::find_time_control_by_id(db, time_control_id)
+        .await
+        .map_err(|_| Status::internal("Could not load time control"))?
+        .ok_or_else(|| Status::not_found("Time control not found"))?;
+    let board = Game::new();
+    mutation::Mutation::create_game(
+        db,
+        game::Model {
+            id: 0,
+            player_white,
+            player_black,
+            time_control: time_control.id,
+            board: board.to_fen(),
+            turn: board.turn().as_str().to_string(),
+            black_time: clock.map_or(time_control.time, |c| c.black),
+            white_time: clock.map_or(time_control.time, |c| c.white),
+            state: "active".to_string(),
+            created_at: Default::default(),
+            updated_at: None,
+            rated,
+            result: None,
+            white_clock: clock.map(|c| c.white),
+            black_clock: clock.map(|c| c.black),
+            eco: None,
+            opening: None,
+            termination: None,
+            replay_failed: false,
+        },
+    )
+    .await
+    .map_err(|_| Status::internal("Could not create game"))
+}
+
+// Saves a move played by either side from `before` to `after` and tells everyone watching the
+// game
+pub async fn record_move(
+    db: &DatabaseConnection,
+    events: &GameEvents,
+    game_row: &game::Model,
+    before: &Position,
+    mv: Move,
+    after: &Position,
+) -> Result<game_move::Model, Status> {
+    let san = before.san(mv);
+    let fen = after.to_fen();
+    let game_move = history::next_move(db, game_row, &san, mv, after).await?;
+    // Named after the last position of the table the game went through
+    let opening = game_move
+        .zobrist
+        .and_then(|key| eco::lookup(key as u64))
+        .filter(|opening| {
+            game_row.eco.as_ref() != Some(&opening.eco)
+                || game_row.opening.as_ref() != Some(&opening.name)
+        });
+    let saved = mutation::Mutation::update_game_position(
+        db,
+        game_row.id,
+        fen.clone(),
+        after.turn().as_str().to_string(),
+        game_move.clone(),
+    )
+    .await;
+    match saved {
+        Ok(Some(_)) => {}
+        // Another move was saved first or the game ended in the meantime
+        Ok(None) => return Err(Status::failed_precondition("Game has changed, try again")),
+        Err(err) => {
+            return Err(match err.sql_err() {
+                Some(SqlErr::UniqueConstraintViolation(_)) => {
+                    Status::failed_precondition("Game has changed, try again")
+                }
+                _ => Status::internal("Could not save move"),
+            })
+        }
+    }
+    if let Some(opening) = opening {
+        mutation::Mutation::set_game_opening(
+            db,
+            game_row.id,
+            opening.eco.clone(),
+            opening.name.clone(),
+        )
+        .await
+        .map_err(|_| Status::internal("Could not save opening"))?;
+    }
+    events.publish(
+        game_row.id,
+        GameEvent::Move {
+            ply: game_move.ply as usize,
+            san,
+            fen,
+        },
+    );
+    Ok(game_move)
+}
+
+// Draws the game after `last_move` when the tablebases say it is drawn, returns whether it did
+pub async fn adjudicate(
+    db: &DatabaseConnection,
+    events: &GameEvents,
+    game_row: &game::Model,
+    tablebase: Option<&Tablebase>,
+    last_move: &game_move::Model,
+) -> Result<bool, Status> {
+    let Some(tablebase) = tablebase.filter(|_| TABLEBASE_ADJUDICATION) else {
+        return Ok(false);
+    };
+    let position = match last_move.fen.as_deref().map(Position::from_fen) {
+        Some(Ok(position)) => position,
+        _ => return Ok(false),
+    };
+    if tablebase.probe_wdl(&position) != Some(Wdl::Draw) {
+        return Ok(false);
+    }
+    // The mover's clock is on the move itself, the opponent's on the move before
+    let previous =
+        game_move_query::Query::find_moves(db, game_row.id, last_move.ply - 1, last_move.ply - 1)
+            .await
+            .map_err(|_| Status::internal("Could not load moves"))?;
+    let clocks = [
+        last_move.clock_remaining,
+        previous.first().and_then(|mv| mv.clock_remaining),
+    ];
+    if clocks
+        .iter()
+        .any(|clock| clock.map_or(true, |clock| clock < ADJUDICATION_MIN_CLOCK_MS))
+    {
+        return Ok(false);
+    }
+    finish_game(db, events, game_row, DRAW, ADJUDICATION).await?;
+    Ok(true)
+}
+
+// Checkmate or stalemate for whoever is to move in `after`, with the result
+pub fn outcome(after: &Position) -> Option<(&'static str, &'static str)> {
+    if !after.legal_moves().is_empty() {
+        return None;
+    }
+    Some(if after.in_check() {
+        (win_for(&after.turn().opponent()), CHECKMATE)
+    } else {
+        (DRAW, STALEMATE)
+    })
+}
+
+// Ends the game when `last_move`, leading to `after`, ended it: made after the flag fell,
+// checkmate, stalemate or a tablebase draw. Returns whether it did.
+pub async fn end_if_over(
+    db: &DatabaseConnection,
+    events: &GameEvents,
+    game_row: &game::Model,
+    tablebase: Option<&Tablebase>,
+    last_move: &game_move::Model,
+    after: &Position,
+) -> Result<bool, Status> {
+    // The mover's flag fell before the move was made, it loses on time instead
+    if last_move.clock_remaining == Some(0) {
+        finish_game(db, events, game_row, win_for(&after.turn()), TIMEOUT).await?;
+        return Ok(true);
+    }
+    if let Some((result, termination)) = outcome(after) {
+        finish_game(db, events, game_row, result, termination).await?;
+        return Ok(true);
+    }
+    adjudicate(db, events, game_row, tablebase, last_move).await
+}
+
+// Every way a game can end goes through here, so ratings are updated exactly once
+pub async fn finish_game(
+    db: &DatabaseConnection,
+    events: &GameEvents,
+    game_row: &game::Model,
+    result: &str,
+    termination: &str,
+) -> Result<game::Model, Status> {
+    let (plies, pool) = stats::record::plies_and_pool(db, game_row)
+        .await
+        .map_err(|_| Status::internal("Could not load game"))?;
+    // Player stats, ratings and the opening explorer are updated with the result, so they cannot
+    // drift from the games
+    let txn = db
+        .begin()
+        .await
+        .map_err(|_| Status::internal("Could not finish game"))?;
+    let finished = mutation::Mutation::finish_game(
+        &txn,
+        game_row.id,
+        result.to_string(),
+        termination.to_string(),
+    )
+    .await
+    .map_err(|_| Status::internal("Could not finish game"))?
+    .ok_or_else(|| Status::failed_precondition("Game is not active"))?;
+    stats::record::add_game(&txn, &finished, plies, pool)
+        .await
+        .map_err(|_| Status::internal("Could not update player stats"))?;
+    if finished.rated {
+        rating_mutation::Mutation::apply_game_result(&txn, &finished)
+            .await
+            .map_err(|_| Status::internal("Could not update ratings"))?;
+    }
+    explorer::record::record_game(&txn, &finished)
+        .await
+        .map_err(|_| Status::internal("Could not update the opening explorer"))?;
+    txn.commit()
+        .await
+        .map_err(|_| Status::internal("Could not finish game"))?;
+    events.publish(
+        finished.id,
+        GameEvent::Finished {
+            result: result.to_string(),
+        },
+    );
+    if let Err(err) = tournament::director::on_game_finished(db, &finished).await {
+        println!(
+            "Error: could not update tournament for game {}: {err}",
+            finished.id
+        );
+    }
+    Ok(finished)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outcome() {
+        // Fool's mate
+        let (mated, _) = Position::from_moves("f3 e5 g4 Qh4#").unwrap();
+        assert_eq!(outcome(&mated), Some(("0-1", CHECKMATE)));
+        let stalemate = Position::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(outcome(&stalemate), Some((DRAW, STALEMATE)));
+        let (check, _) = Position::from_moves("e4 f5 Qh5+").unwrap();
+        assert_eq!(outcome(&check), None);
+        assert_eq!(outcome(&Position::new()), None);
+    }
+}
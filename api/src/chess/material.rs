@@ -0,0 +1,165 @@
+// What is stored with every move in `game_move` so games can be searched by position: the
+// material signature, e.g. "KRBvKR" for a king, rook and bishop against a king and rook, and
+// the placement, the 64 squares from a8 to h1 as piece letters with '.' for empty ones.
+// Patterns of pieces on given squares become SQL LIKE patterns over the placement.
+use super::{
+    engine::position::{parse_square, Position},
+    pieces::{Color, Kind, Piece},
+};
+
+// Strongest first, the order of each side in a signature
+const ORDER: [Kind; 6] = [
+    Kind::King,
+    Kind::Queen,
+    Kind::Rook,
+    Kind::Bishop,
+    Kind::Knight,
+    Kind::Pawn,
+];
+
+fn side(counts: &[usize; 6]) -> String {
+    ORDER
+        .iter()
+        .zip(counts)
+        .flat_map(|(&kind, &count)| {
+            std::iter::repeat(Piece::new(Color::White, kind).as_char()).take(count)
+        })
+        .collect()
+}
+
+fn index(kind: Kind) -> usize {
+    ORDER.iter().position(|&k| k == kind).unwrap_or_default()
+}
+
+pub fn signature(position: &Position) -> String {
+    let mut white = [0; 6];
+    let mut black = [0; 6];
+    for square in 0..64 {
+        match position.piece_at(square) {
+            Some(piece) if piece.color == Color::White => white[index(piece.kind)] += 1,
+            Some(piece) => black[index(piece.kind)] += 1,
+            None => {}
+        }
+    }
+    format!("{}v{}", side(&white), side(&black))
+}
+
+pub fn placement(position: &Position) -> String {
+    (0..64)
+        .map(|square| {
+            position
+                .piece_at(square)
+                .map_or('.', |piece| piece.as_char())
+        })
+        .collect()
+}
+
+// Reads what people type, "R+B vs R", "KRB v KR" or "krbvkr", as a signature. Kings are
+// added when left out, and pawns are only there when they are named.
+pub fn parse_signature(text: &str) -> Option<String> {
+    let text = text.to_uppercase().replace("VS", "V");
+    let (white, black) = text.split_once('V')?;
+    let mut sides = [[0; 6], [0; 6]];
+    for (counts, pieces) in sides.iter_mut().zip([white, black]) {
+        for c in pieces.chars().filter(|c| !c.is_whitespace() && *c != '+') {
+            let piece = Piece::from_char(c)?;
+            counts[index(piece.kind)] += 1;
+        }
+        match counts[0] {
+            0 => counts[0] = 1,
+            1 => {}
+            _ => return None,
+        }
+    }
+    Some(format!("{}v{}", side(&sides[0]), side(&sides[1])))
+}
+
+// The same material with the colors swapped
+pub fn mirror(signature: &str) -> String {
+    match signature.split_once('v') {
+        Some((white, black)) => format!("{black}v{white}"),
+        None => signature.to_string(),
+    }
+}
+
+// Squares with what has to be on them, separated by spaces or commas: a piece letter as in
+// FEN then the square, e.g. "Kg1 Rf1 pf7", or '-' and the square for one that has to be empty.
+// Squares not named can have anything on them.
+pub fn parse_pattern(text: &str) -> Option<String> {
+    let mut pattern = vec!['_'; 64];
+    let mut named = false;
+    for entry in text
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|entry| !entry.is_empty())
+    {
+        let mut chars = entry.chars();
+        let wanted = match chars.next()? {
+            '-' => '.',
+            c => Piece::from_char(c)?.as_char(),
+        };
+        let square = parse_square(chars.as_str())?;
+        if pattern[square] != '_' && pattern[square] != wanted {
+            return None;
+        }
+        pattern[square] = wanted;
+        named = true;
+    }
+    named.then(|| pattern.into_iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signature_and_placement() {
+        let position = Position::from_fen("8/8/4k3/8/3r4/8/2B5/R3K3 w - - 0 60").unwrap();
+        assert_eq!(signature(&position), "KRBvKR");
+        assert_eq!(
+            signature(&Position::new()),
+            "KQRRBBNNPPPPPPPPvKQRRBBNNPPPPPPPP"
+        );
+
+        let placement = placement(&Position::new());
+        assert_eq!(placement.len(), 64);
+        assert!(placement.starts_with("rnbqkbnrpppppppp........"));
+        assert!(placement.ends_with("PPPPPPPPRNBQKBNR"));
+    }
+
+    #[test]
+    fn test_parse_signature() {
+        assert_eq!(parse_signature("R+B vs R").unwrap(), "KRBvKR");
+        assert_eq!(parse_signature("KBR v KR").unwrap(), "KRBvKR");
+        assert_eq!(parse_signature("qvrp").unwrap(), "KQvKRP");
+        assert_eq!(parse_signature("K vs K").unwrap(), "KvK");
+        assert!(parse_signature("R+B").is_none());
+        assert!(parse_signature("R+X vs R").is_none());
+        assert!(parse_signature("KK vs K").is_none());
+        assert_eq!(mirror("KRBvKR"), "KRvKRB");
+    }
+
+    #[test]
+    fn test_parse_pattern() {
+        let pattern = parse_pattern("Kg1, Rf1 pf7 -e4").unwrap();
+        assert_eq!(pattern.len(), 64);
+        assert_eq!(&pattern[13..14], "p");
+        assert_eq!(&pattern[36..37], ".");
+        assert_eq!(&pattern[61..63], "RK");
+        assert_eq!(pattern.chars().filter(|&c| c == '_').count(), 60);
+
+        let start = placement(&Position::new());
+        let matches = |pattern: &str| {
+            pattern
+                .chars()
+                .zip(start.chars())
+                .all(|(p, c)| p == '_' || p == c)
+        };
+        assert!(matches(&parse_pattern("Ke1 ke8 -e4").unwrap()));
+        assert!(!matches(&parse_pattern("Ke1 Pe4").unwrap()));
+
+        assert!(parse_pattern("").is_none());
+        assert!(parse_pattern("Ke9").is_none());
+        assert!(parse_pattern("Xe4").is_none());
+        assert!(parse_pattern("Ke1 Qe1").is_none());
+    }
+}
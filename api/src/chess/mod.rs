@@ -1,7 +1,19 @@
 pub mod board;
+pub mod bot;
 pub mod chess_move;
+pub mod clock;
+pub mod eco;
+pub mod engine;
+pub mod events;
 pub mod game;
+pub mod history;
+pub mod lifecycle;
+pub mod material;
+pub mod pgn;
 pub mod pieces;
+pub mod polyglot;
 pub mod service;
 pub mod square;
+pub mod syzygy;
+pub mod uci;
 tonic::include_proto!("chessgame"); // The string specified here must match the proto package name
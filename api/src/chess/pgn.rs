@@ -0,0 +1,105 @@
+// Writing games in Portable Game Notation, with optional annotation glyphs and comments
+const LINE_WIDTH: usize = 80;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PgnMove {
+    pub san: String,
+    // Numeric annotation glyph, e.g. 2 for "?" and 4 for "??"
+    pub nag: Option<u8>,
+    pub comment: Option<String>,
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// `tags` are written in the order given, the seven tag roster first by convention
+pub fn write(tags: &[(&str, String)], moves: &[PgnMove], result: &str) -> String {
+    let mut pgn = String::new();
+    for (name, value) in tags {
+        pgn.push_str(&format!("[{} \"{}\"]\n", name, escape(value)));
+    }
+    pgn.push('\n');
+
+    let mut tokens = Vec::new();
+    let mut after_comment = false;
+    for (ply, mv) in moves.iter().enumerate() {
+        let number = ply / 2 + 1;
+        if ply % 2 == 0 {
+            tokens.push(format!("{number}."));
+        } else if after_comment {
+            // Black's move needs its number again once something came in between
+            tokens.push(format!("{number}..."));
+        }
+        tokens.push(mv.san.clone());
+        if let Some(nag) = mv.nag {
+            tokens.push(format!("${nag}"));
+        }
+        after_comment = false;
+        if let Some(comment) = &mv.comment {
+            tokens.push(format!("{{ {} }}", comment.replace('}', ")")));
+            after_comment = true;
+        }
+    }
+    tokens.push(result.to_string());
+
+    let mut line = String::new();
+    for token in tokens {
+        if !line.is_empty() && line.len() + 1 + token.len() > LINE_WIDTH {
+            pgn.push_str(&line);
+            pgn.push('\n');
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&token);
+    }
+    pgn.push_str(&line);
+    pgn.push('\n');
+    pgn
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plain(san: &str) -> PgnMove {
+        PgnMove {
+            san: san.to_string(),
+            nag: None,
+            comment: None,
+        }
+    }
+
+    #[test]
+    fn test_write_annotated_game() {
+        let mut moves: Vec<PgnMove> = ["e4", "e5", "Qh5", "Nc6", "Bc4", "Nf6", "Qxf7#"]
+            .iter()
+            .map(|san| plain(san))
+            .collect();
+        moves[5].nag = Some(4);
+        moves[5].comment = Some("Blunder. g6 was best.".to_string());
+        moves[6].comment = Some("[%eval #0]".to_string());
+        let tags = [
+            ("Event", "Casual \"blitz\"".to_string()),
+            ("Result", "1-0".to_string()),
+        ];
+        let pgn = write(&tags, &moves, "1-0");
+        assert_eq!(
+            pgn,
+            "[Event \"Casual \\\"blitz\\\"\"]\n[Result \"1-0\"]\n\n\
+             1. e4 e5 2. Qh5 Nc6 3. Bc4 Nf6 $4 { Blunder. g6 was best. } 4. Qxf7#\n\
+             { [%eval #0] } 1-0\n"
+        );
+        assert!(pgn.lines().all(|line| line.len() <= LINE_WIDTH));
+    }
+
+    #[test]
+    fn test_black_move_after_comment_is_numbered() {
+        let mut moves = vec![plain("d4"), plain("d5")];
+        moves[0].comment = Some("[%eval 0.2]".to_string());
+        let pgn = write(&[], &moves, "*");
+        assert_eq!(pgn, "\n1. d4 { [%eval 0.2] } 1... d5 *\n");
+    }
+}
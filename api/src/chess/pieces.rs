@@ -1,6 +1,6 @@
 use super::square::Square;
 
-#[derive(PartialEq, Clone, Copy, Debug)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
 pub struct Piece {
     pub color: Color,
     pub kind: Kind,
@@ -179,7 +179,7 @@ impl Piece {
     }
 }
 
-#[derive(PartialEq, Clone, Copy, Debug)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
 pub enum Color {
     White,
     Black,
@@ -200,9 +200,16 @@ impl Color {
             _ => None,
         }
     }
+
+    pub fn opponent(&self) -> Color {
+        match self {
+            Color::White => Color::Black,
+            Color::Black => Color::White,
+        }
+    }
 }
 
-#[derive(PartialEq, Clone, Copy, Debug)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
 pub enum Kind {
     Pawn,
     Knight,
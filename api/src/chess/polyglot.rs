@@ -0,0 +1,457 @@
+// Polyglot opening books: a file of 16 byte big-endian entries, each a position key, a move,
+// its weight and a learn value, sorted by key. Keys xor the format's 781 Random64 numbers, which
+// are not bundled with the server. POLYGLOT_KEYS_PATH points at a file with them as hexadecimal
+// numbers in order, e.g. the array from the format description pasted as is, and they are
+// checked against the known key of the starting position. POLYGLOT_BOOK_PATH is the book.
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::{env, fs, io};
+
+use rand::Rng;
+use sea_orm::{DatabaseConnection, DbErr};
+
+use service::{
+    game::query as game_query, game_move::query as game_move_query, ratings::mutation::white_score,
+    users::query as user_query,
+};
+
+use super::{
+    engine::position::{file, rank, Move, Position},
+    history,
+    pieces::{Color, Kind, Piece},
+};
+
+const RANDOM_COUNT: usize = 781;
+const CASTLING_KEYS: usize = 768;
+const EN_PASSANT_KEYS: usize = 772;
+const TURN_KEY: usize = 780;
+// From the format description, to tell the right numbers from any others
+const START_KEY: u64 = 0x463b_9618_1691_fc9c;
+const ENTRY_SIZE: usize = 16;
+// Books built from our games stop here, like the explorer
+const MAX_PLIES: usize = 50;
+const GAMES_PAGE: u64 = 500;
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+pub struct Keys(Vec<u64>);
+
+impl Keys {
+    // Every 16 digit hexadecimal number in the text, with or without 0x and C suffixes
+    pub fn parse(text: &str) -> io::Result<Keys> {
+        let values: Vec<u64> = text
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .map(|token| {
+                token
+                    .trim_start_matches("0x")
+                    .trim_start_matches("0X")
+                    .trim_end_matches(&['U', 'L', 'u', 'l'][..])
+            })
+            .filter(|token| token.len() == 16)
+            .filter_map(|token| u64::from_str_radix(token, 16).ok())
+            .collect();
+        if values.len() != RANDOM_COUNT {
+            return Err(invalid("Expected the 781 Polyglot Random64 numbers"));
+        }
+        let keys = Keys(values);
+        if keys.hash(&Position::new()) != START_KEY {
+            return Err(invalid("Not the Polyglot Random64 numbers"));
+        }
+        Ok(keys)
+    }
+
+    pub fn load(path: &str) -> io::Result<Keys> {
+        Keys::parse(&fs::read_to_string(path)?)
+    }
+
+    // Like `zobrist::key` but with Polyglot's numbers and layout
+    pub fn hash(&self, position: &Position) -> u64 {
+        let mut key = 0;
+        for square in 0..64 {
+            if let Some(piece) = position.piece_at(square) {
+                let kind = match piece.kind {
+                    Kind::Pawn => 0,
+                    Kind::Knight => 1,
+                    Kind::Bishop => 2,
+                    Kind::Rook => 3,
+                    Kind::Queen => 4,
+                    Kind::King => 5,
+                };
+                let kind = 2 * kind + (piece.color == Color::White) as usize;
+                key ^= self.0[64 * kind + 8 * rank(square) + file(square)];
+            }
+        }
+        // K Q k q, in the same order as `Position::castling`
+        for right in 0..4 {
+            if position.castling() & (1 << right) != 0 {
+                key ^= self.0[CASTLING_KEYS + right];
+            }
+        }
+        if let Some(square) = position.en_passant() {
+            if position.en_passant_capturable() {
+                key ^= self.0[EN_PASSANT_KEYS + file(square)];
+            }
+        }
+        if position.turn() == Color::White {
+            key ^= self.0[TURN_KEY];
+        }
+        key
+    }
+}
+
+fn is_castling(position: &Position, mv: Move) -> bool {
+    position.piece_at(mv.from).map(|piece| piece.kind) == Some(Kind::King)
+        && file(mv.from).abs_diff(file(mv.to)) == 2
+}
+
+// Squares as file and rank from 0, castling as the king taking its own rook
+pub fn encode_move(position: &Position, mv: Move) -> u16 {
+    let to = match is_castling(position, mv) {
+        true if file(mv.to) > file(mv.from) => mv.to + 1,
+        true => mv.to - 2,
+        false => mv.to,
+    };
+    let promotion = match mv.promotion {
+        Some(Kind::Knight) => 1,
+        Some(Kind::Bishop) => 2,
+        Some(Kind::Rook) => 3,
+        Some(Kind::Queen) => 4,
+        _ => 0,
+    };
+    (file(to) | rank(to) << 3 | file(mv.from) << 6 | rank(mv.from) << 9 | promotion << 12) as u16
+}
+
+// None for a move that is not legal in `position`
+pub fn decode_move(position: &Position, raw: u16) -> Option<Move> {
+    let square = |bits: u16| (7 - ((bits >> 3) & 7) as usize) * 8 + (bits & 7) as usize;
+    let from = square(raw >> 6);
+    let mut to = square(raw);
+    let king = position.piece_at(from)?;
+    if king.kind == Kind::King && position.piece_at(to) == Some(Piece::new(king.color, Kind::Rook))
+    {
+        to = if to > from { from + 2 } else { from - 2 };
+    }
+    let promotion = match (raw >> 12) & 7 {
+        1 => Some(Kind::Knight),
+        2 => Some(Kind::Bishop),
+        3 => Some(Kind::Rook),
+        4 => Some(Kind::Queen),
+        _ => None,
+    };
+    let mv = Move {
+        from,
+        to,
+        promotion,
+    };
+    position
+        .legal_moves()
+        .into_iter()
+        .find(|legal| *legal == mv)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub key: u64,
+    pub mv: u16,
+    pub weight: u16,
+    pub learn: u32,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Book {
+    entries: Vec<Entry>,
+}
+
+impl Book {
+    // A trailing partial entry is ignored
+    pub fn from_bytes(bytes: &[u8]) -> Book {
+        let mut entries: Vec<Entry> = bytes
+            .chunks_exact(ENTRY_SIZE)
+            .map(|entry| Entry {
+                key: u64::from_be_bytes(entry[0..8].try_into().unwrap()),
+                mv: u16::from_be_bytes(entry[8..10].try_into().unwrap()),
+                weight: u16::from_be_bytes(entry[10..12].try_into().unwrap()),
+                learn: u32::from_be_bytes(entry[12..16].try_into().unwrap()),
+            })
+            .collect();
+        // Lookups need them sorted, books are but nothing checks it
+        entries.sort_by_key(|entry| entry.key);
+        Book { entries }
+    }
+
+    pub fn load(path: &str) -> io::Result<Book> {
+        Ok(Book::from_bytes(&fs::read(path)?))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.entries.len() * ENTRY_SIZE);
+        for entry in &self.entries {
+            bytes.extend_from_slice(&entry.key.to_be_bytes());
+            bytes.extend_from_slice(&entry.mv.to_be_bytes());
+            bytes.extend_from_slice(&entry.weight.to_be_bytes());
+            bytes.extend_from_slice(&entry.learn.to_be_bytes());
+        }
+        bytes
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn entries(&self, key: u64) -> &[Entry] {
+        let start = self.entries.partition_point(|entry| entry.key < key);
+        let end = self.entries.partition_point(|entry| entry.key <= key);
+        &self.entries[start..end]
+    }
+}
+
+// Weights from results, as Polyglot's make-book counts them: 2 for every game the side that
+// played the move won and 1 for every draw
+#[derive(Default)]
+pub struct BookBuilder {
+    weights: HashMap<(u64, u16), u64>,
+}
+
+impl BookBuilder {
+    pub fn add_game(&mut self, keys: &Keys, moves: &str, result: &str) {
+        let white = match white_score(result) {
+            Some(white) => white,
+            None => return,
+        };
+        let mut position = Position::new();
+        for (mv, next) in history::replay(moves).into_iter().take(MAX_PLIES) {
+            let score = match position.turn() {
+                Color::White => white,
+                Color::Black => 1.0 - white,
+            };
+            let weight = self
+                .weights
+                .entry((keys.hash(&position), encode_move(&position, mv)))
+                .or_default();
+            *weight += (2.0 * score) as u64;
+            position = next;
+        }
+    }
+
+    // Moves that were never won or drawn are left out. Weights are scaled down to fit in 16 bits.
+    pub fn build(self) -> Book {
+        let max = self.weights.values().copied().max().unwrap_or(0);
+        let mut entries: Vec<Entry> = self
+            .weights
+            .into_iter()
+            .filter(|&(_, weight)| weight > 0)
+            .map(|((key, mv), weight)| Entry {
+                key,
+                mv,
+                weight: if max > u16::MAX as u64 {
+                    (weight * u16::MAX as u64 / max).max(1) as u16
+                } else {
+                    weight as u16
+                },
+                learn: 0,
+            })
+            .collect();
+        entries.sort_by(|a, b| a.key.cmp(&b.key).then(b.weight.cmp(&a.weight)));
+        Book { entries }
+    }
+}
+
+// A book from every finished game between people
+pub async fn build_from_games(db: &DatabaseConnection, keys: &Keys) -> Result<Book, DbErr> {
+    let bots: Vec<i32> = user_query::Query::find_bots(db)
+        .await?
+        .into_iter()
+        .map(|bot| bot.id)
+        .collect();
+    let mut builder = BookBuilder::default();
+    let mut after_id = 0;
+    loop {
+        let games = game_query::Query::find_finished_games(db, after_id, GAMES_PAGE).await?;
+        let last = match games.last() {
+            Some(last) => last.id,
+            None => return Ok(builder.build()),
+        };
+        let ids = games.iter().map(|game_row| game_row.id).collect();
+        let move_lists = game_move_query::Query::find_move_lists(db, ids).await?;
+        for game_row in &games {
+            if bots.contains(&game_row.player_white) || bots.contains(&game_row.player_black) {
+                continue;
+            }
+            if let (Some(result), Some(moves)) = (&game_row.result, move_lists.get(&game_row.id)) {
+                builder.add_game(keys, moves, result);
+            }
+        }
+        after_id = last;
+    }
+}
+
+#[derive(Clone)]
+pub struct OpeningBook {
+    keys: Arc<Keys>,
+    book: Arc<Book>,
+}
+
+impl OpeningBook {
+    pub fn new(keys: Keys, book: Book) -> OpeningBook {
+        OpeningBook {
+            keys: Arc::new(keys),
+            book: Arc::new(book),
+        }
+    }
+
+    // No book is used unless both POLYGLOT_KEYS_PATH and POLYGLOT_BOOK_PATH are set
+    pub fn from_env() -> Option<OpeningBook> {
+        let keys_path = env::var("POLYGLOT_KEYS_PATH")
+            .ok()
+            .filter(|s| !s.is_empty())?;
+        let book_path = env::var("POLYGLOT_BOOK_PATH")
+            .ok()
+            .filter(|s| !s.is_empty())?;
+        let loaded = Keys::load(&keys_path).and_then(|keys| Ok((keys, Book::load(&book_path)?)));
+        match loaded {
+            Ok((keys, book)) => {
+                println!("Loaded {} opening book entries", book.len());
+                Some(OpeningBook::new(keys, book))
+            }
+            Err(err) => {
+                println!("Error: could not load the opening book: {err}");
+                None
+            }
+        }
+    }
+
+    // The legal book moves in `position` with their weights, heaviest first
+    pub fn moves(&self, position: &Position) -> Vec<(Move, u16)> {
+        let mut moves: Vec<(Move, u16)> = self
+            .book
+            .entries(self.keys.hash(position))
+            .iter()
+            .filter_map(|entry| Some((decode_move(position, entry.mv)?, entry.weight)))
+            .collect();
+        moves.sort_by_key(|&(_, weight)| Reverse(weight));
+        moves
+    }
+
+    // A book move picked at random in proportion to the weights
+    pub fn pick(&self, position: &Position) -> Option<Move> {
+        let moves = self.moves(position);
+        let total: u32 = moves.iter().map(|&(_, weight)| weight as u32).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut left = rand::thread_rng().gen_range(0..total);
+        for (mv, weight) in moves {
+            if left < weight as u32 {
+                return Some(mv);
+            }
+            left -= weight as u32;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Not Polyglot's numbers, enough to check the layout
+    fn test_keys() -> Keys {
+        let mut state: u64 = 1;
+        Keys(
+            (0..RANDOM_COUNT)
+                .map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    state
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_keys_are_checked() {
+        let text: String = test_keys()
+            .0
+            .iter()
+            .map(|key| format!("U64(0x{key:016X}),\n"))
+            .collect();
+        let err = Keys::parse(&text).err().unwrap();
+        assert_eq!(err.to_string(), "Not the Polyglot Random64 numbers");
+        assert!(Keys::parse("0x9D39247E33776D41").is_err());
+    }
+
+    #[test]
+    fn test_hash_ignores_move_order_and_impossible_en_passant() {
+        let keys = test_keys();
+        let a = Position::from_moves("Nf3 Nf6 Nc3 Nc6").unwrap().0;
+        let b = Position::from_moves("Nc3 Nc6 Nf3 Nf6").unwrap().0;
+        assert_eq!(keys.hash(&a), keys.hash(&b));
+        let e4 = Position::from_moves("e4").unwrap().0;
+        let without = Position::from_fen(&e4.to_fen().replace(" e3 ", " - ")).unwrap();
+        assert_eq!(keys.hash(&e4), keys.hash(&without));
+        // Here black can take en passant
+        let capturable = Position::from_moves("e4 d5 e5 f5").unwrap().0;
+        let without = Position::from_fen(&capturable.to_fen().replace(" f6 ", " - ")).unwrap();
+        assert_ne!(keys.hash(&capturable), keys.hash(&without));
+    }
+
+    #[test]
+    fn test_move_encoding() {
+        let position = Position::new();
+        let e4 = position.parse_move("e4").unwrap();
+        // e2 is file 4 rank 1, e4 file 4 rank 3
+        assert_eq!(encode_move(&position, e4), 4 | 3 << 3 | 4 << 6 | 1 << 9);
+        assert_eq!(decode_move(&position, encode_move(&position, e4)), Some(e4));
+
+        let castle = Position::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        let short = castle.parse_move("O-O").unwrap();
+        // e1h1
+        assert_eq!(encode_move(&castle, short), 7 | 4 << 6);
+        assert_eq!(decode_move(&castle, 7 | 4 << 6), Some(short));
+        let long = castle.parse_move("O-O-O").unwrap();
+        assert_eq!(decode_move(&castle, encode_move(&castle, long)), Some(long));
+
+        let promotion = Position::from_fen("8/P7/8/8/8/8/8/k6K w - - 0 1").unwrap();
+        let queen = promotion.parse_move("a8=Q").unwrap();
+        assert_eq!(encode_move(&promotion, queen) >> 12, 4);
+        assert_eq!(
+            decode_move(&promotion, encode_move(&promotion, queen)),
+            Some(queen)
+        );
+        // Not legal
+        assert_eq!(decode_move(&position, 4 | 4 << 3 | 4 << 6 | 1 << 9), None);
+    }
+
+    #[test]
+    fn test_build_and_read_book() {
+        let keys = test_keys();
+        let mut builder = BookBuilder::default();
+        builder.add_game(&keys, "e4 e5 Nf3", "1-0");
+        builder.add_game(&keys, "e4 c5", "1/2-1/2");
+        builder.add_game(&keys, "d4 d5", "0-1");
+        builder.add_game(&keys, "c4", "*");
+        let book = builder.build();
+        let bytes = book.to_bytes();
+        assert_eq!(bytes.len(), book.len() * ENTRY_SIZE);
+        let read = Book::from_bytes(&bytes);
+        assert_eq!(read, book);
+
+        let book = OpeningBook::new(keys, read);
+        let position = Position::new();
+        let moves: Vec<(String, u16)> = book
+            .moves(&position)
+            .into_iter()
+            .map(|(mv, weight)| (position.san(mv), weight))
+            .collect();
+        // e4 won once and drew once, d4 lost, c4 has no result
+        assert_eq!(moves, vec![("e4".to_string(), 3)]);
+        assert_eq!(book.pick(&position), position.parse_move("e4"));
+        let after_d4 = Position::from_moves("d4").unwrap().0;
+        assert_eq!(book.moves(&after_d4).len(), 1);
+        assert_eq!(book.pick(&Position::from_moves("a3").unwrap().0), None);
+    }
+}
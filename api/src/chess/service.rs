@@ -1,12 +1,56 @@
+use entity::entities::game as game_entity;
 use sea_orm::DatabaseConnection;
 use tonic::{Request, Response, Status};
 
-use super::{chess_game_server::ChessGame, game::Game, pieces::Color, MoveRequest, MoveResponse};
+use service::{game::query, users::query as user_query};
 
-static GAME_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+use super::{
+    bot, chess_game_server::ChessGame, engine::position::Position, events::GameEvents, lifecycle,
+    pieces::Color, polyglot::OpeningBook, syzygy::Tablebase, uci::EnginePool, Bot, ListBotsRequest,
+    ListBotsResponse, MoveRequest, MoveResponse, ResignRequest, ResignResponse,
+};
+use crate::auth::interceptor::{authenticated_user, log_request};
 
 pub struct ChessGameService {
     pub db_connection: DatabaseConnection,
+    pub events: GameEvents,
+    pub engine: Option<EnginePool>,
+    pub book: Option<OpeningBook>,
+    pub tablebase: Option<Tablebase>,
+}
+
+impl ChessGameService {
+    async fn find_active_game(&self, match_id: &str) -> Result<game_entity::Model, Status> {
+        let game_id = match match_id.parse::<i32>() {
+            Ok(id) => id,
+            Err(_) => return Err(Status::invalid_argument("Invalid match id")),
+        };
+        let db_result = query::Query::find_game_by_id(&self.db_connection, game_id)
+            .await
+            .map_err(|_| Status::internal("Could not load game"))?;
+        let game_row = match db_result {
+            Some(game_row) => game_row,
+            None => return Err(Status::not_found("Game not found")),
+        };
+        if game_row.state != "active" {
+            return Err(Status::failed_precondition("Game is not active"));
+        }
+        Ok(game_row)
+    }
+}
+
+// The side a user plays comes from the token, never from the request.
+// When someone plays both sides it is whoever is to move.
+fn player_color(game_row: &game_entity::Model, user_id: i32, turn: Color) -> Result<Color, Status> {
+    if user_id == game_row.player_white && user_id == game_row.player_black {
+        Ok(turn)
+    } else if user_id == game_row.player_white {
+        Ok(Color::White)
+    } else if user_id == game_row.player_black {
+        Ok(Color::Black)
+    } else {
+        Err(Status::permission_denied("Not a player in this game"))
+    }
 }
 
 #[tonic::async_trait]
@@ -15,26 +59,102 @@ impl ChessGame for ChessGameService {
         &self,
         request: Request<MoveRequest>,
     ) -> Result<Response<MoveResponse>, Status> {
-        println!("Got a request: {:?}", request);
+        log_request("MovePiece", &request);
+        let user = authenticated_user(&request)?;
         let r = request.into_inner();
-        let color = Color::from_str(r.player_color.as_str());
-        if color.is_none() {
-            return Err(Status::invalid_argument("Invalid color"));
-        }
-        let game_parse = Game::from_fen(GAME_FEN);
-        if game_parse.is_err() {
-            return Err(Status::invalid_argument("Invalid FEN"));
+        let game_row = self.find_active_game(&r.match_id).await?;
+        let position =
+            Position::from_fen(&game_row.board).map_err(|_| Status::internal("Invalid FEN"))?;
+        let color = player_color(&game_row, user.id, position.turn())?;
+        // Only legal moves by the side to move are played
+        let played = match position.parse_move(&r.pgn_move) {
+            Some(mv) if color == position.turn() => Some(mv),
+            _ => None,
+        };
+        let mut board_state = game_row.board.clone();
+
+        if let Some(mv) = played {
+            let next = position.play(mv);
+            board_state = next.to_fen();
+            let game_move = lifecycle::record_move(
+                &self.db_connection,
+                &self.events,
+                &game_row,
+                &position,
+                mv,
+                &next,
+            )
+            .await?;
+            let finished = lifecycle::end_if_over(
+                &self.db_connection,
+                &self.events,
+                &game_row,
+                self.tablebase.as_ref(),
+                &game_move,
+                &next,
+            )
+            .await?;
+            // Bots answer in the background
+            if !finished {
+                bot::respond(
+                    self.db_connection.clone(),
+                    self.events.clone(),
+                    self.engine.clone(),
+                    self.book.clone(),
+                    self.tablebase.clone(),
+                    game_row.id,
+                );
+            }
         }
-        let mut game = game_parse.unwrap();
-        let success = game.play_move(&color.unwrap(), &r.pgn_move);
-        let board_state = game.to_fen();
 
         let reply = MoveResponse {
             match_id: r.match_id,
-            success,
+            success: played.is_some(),
             board_state,
         };
 
         Ok(Response::new(reply))
     }
+    async fn resign(
+        &self,
+        request: Request<ResignRequest>,
+    ) -> Result<Response<ResignResponse>, Status> {
+        log_request("Resign", &request);
+        let user = authenticated_user(&request)?;
+        let r = request.into_inner();
+        let game_row = self.find_active_game(&r.match_id).await?;
+        let turn = Color::from_str(&game_row.turn).unwrap_or(Color::White);
+        let color = player_color(&game_row, user.id, turn)?;
+        let result = lifecycle::win_for(&color.opponent());
+        lifecycle::finish_game(
+            &self.db_connection,
+            &self.events,
+            &game_row,
+            result,
+            lifecycle::RESIGNATION,
+        )
+        .await?;
+
+        Ok(Response::new(ResignResponse {
+            match_id: r.match_id,
+            result: result.to_string(),
+        }))
+    }
+
+    async fn list_bots(
+        &self,
+        request: Request<ListBotsRequest>,
+    ) -> Result<Response<ListBotsResponse>, Status> {
+        log_request("ListBots", &request);
+        let bots = user_query::Query::find_bots(&self.db_connection)
+            .await
+            .map_err(|_| Status::internal("Could not load bots"))?
+            .into_iter()
+            .map(|user| Bot {
+                username: user.username,
+                level: user.bot_level.unwrap_or_default(),
+            })
+            .collect();
+        Ok(Response::new(ListBotsResponse { bots }))
+    }
 }
@@ -11,10 +11,10 @@ impl Square {
     }
 
     pub fn new_from_1d_arr_coordinates(coordinates: usize) -> Square {
-      Square {
-        file: coordinates % 8,
-        rank: 7 - (coordinates / 8),
-      }
+        Square {
+            file: coordinates % 8,
+            rank: 7 - (coordinates / 8),
+        }
     }
 
     pub fn to_1d_arr_coordinates(&self) -> usize {
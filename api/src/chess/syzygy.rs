@@ -0,0 +1,1352 @@
+// Syzygy endgame tablebases. For positions with few enough pieces they say whether the side to
+// move wins, draws or loses with perfect play (WDL, the .rtbw files) and how many plies it is
+// from there to the next capture or pawn move (DTZ, the .rtbz files). SYZYGY_PATH is the
+// directory with the files, each table is read into memory the first time a position needs it.
+// The tables know nothing of castling, positions where it is still possible are not probed.
+// Decoding follows the layout the files are read with in Stockfish's tbprobe.
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::{env, fs, io};
+
+use super::{
+    engine::position::{Move, Position},
+    pieces::{Color, Kind, Piece},
+};
+
+// The largest tables there are
+pub const MAX_PIECES: usize = 7;
+const WDL_MAGIC: [u8; 4] = [0x71, 0xe8, 0x23, 0x5d];
+const DTZ_MAGIC: [u8; 4] = [0xd7, 0x66, 0x0c, 0xa5];
+// Flags of a file
+const SPLIT: u8 = 1;
+const HAS_PAWNS: u8 = 2;
+// Flags of each part of a table
+const STM: u8 = 1;
+const MAPPED: u8 = 2;
+const WIN_PLIES: u8 = 4;
+const LOSS_PLIES: u8 = 8;
+const WIDE: u8 = 16;
+const SINGLE_VALUE: u8 = 128;
+// Leaves of the symbol tree have no right child
+const NO_SYMBOL: usize = 0xfff;
+// Ways to place the leading pieces of a table without pawns, three unique pieces or two kings
+const UNIQUE_LEADERS: u64 = 31_332;
+const KING_PAIRS: u64 = 462;
+const NAME_ORDER: [(Kind, char); 6] = [
+    (Kind::King, 'K'),
+    (Kind::Queen, 'Q'),
+    (Kind::Rook, 'R'),
+    (Kind::Bishop, 'B'),
+    (Kind::Knight, 'N'),
+    (Kind::Pawn, 'P'),
+];
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+// With perfect play, for the side to move. Cursed wins and blessed losses are the wins and
+// losses the fifty-move rule turns into draws.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Wdl {
+    Loss,
+    BlessedLoss,
+    Draw,
+    CursedWin,
+    Win,
+}
+
+impl Wdl {
+    fn from_value(value: i32) -> Wdl {
+        match value {
+            ..=-2 => Wdl::Loss,
+            -1 => Wdl::BlessedLoss,
+            0 => Wdl::Draw,
+            1 => Wdl::CursedWin,
+            _ => Wdl::Win,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Wdl::Loss => "loss",
+            Wdl::BlessedLoss => "blessed-loss",
+            Wdl::Draw => "draw",
+            Wdl::CursedWin => "cursed-win",
+            Wdl::Win => "win",
+        }
+    }
+}
+
+// Squares are numbered from a1 to h8 here, as in the files, and from a8 on our boards
+fn file_of(square: usize) -> usize {
+    square & 7
+}
+
+fn rank_of(square: usize) -> usize {
+    square >> 3
+}
+
+// Positive above the a1-h8 diagonal, negative below it
+fn off_diagonal(square: usize) -> i32 {
+    rank_of(square) as i32 - file_of(square) as i32
+}
+
+// Pieces are coded 1 to 6 from pawn to king, plus 8 for black
+fn piece_code(piece: Piece) -> u8 {
+    let kind = match piece.kind {
+        Kind::Pawn => 1,
+        Kind::Knight => 2,
+        Kind::Bishop => 3,
+        Kind::Rook => 4,
+        Kind::Queen => 5,
+        Kind::King => 6,
+    };
+    match piece.color {
+        Color::White => kind,
+        Color::Black => kind | 8,
+    }
+}
+
+fn code_at(position: &Position, square: usize) -> Option<u8> {
+    position.piece_at(square ^ 56).map(piece_code)
+}
+
+fn piece_count(position: &Position) -> usize {
+    (0..64)
+        .filter(|&square| position.piece_at(square).is_some())
+        .count()
+}
+
+// A side's pieces the way table names have them, e.g. KRP
+fn side_name(position: &Position, color: Color) -> String {
+    let pieces: Vec<Piece> = (0..64)
+        .filter_map(|square| position.piece_at(square))
+        .filter(|piece| piece.color == color)
+        .collect();
+    NAME_ORDER
+        .iter()
+        .flat_map(|&(kind, letter)| {
+            let count = pieces.iter().filter(|piece| piece.kind == kind).count();
+            std::iter::repeat(letter).take(count)
+        })
+        .collect()
+}
+
+fn read_u16(bytes: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn read_u32_be(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn read_u64_be(bytes: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(bytes.get(at..at + 8)?.try_into().ok()?))
+}
+
+// Tables for turning a position into its index, the same for every file
+struct Indices {
+    // Ways to choose k of n squares
+    binomial: [[u64; 64]; 6],
+    // Squares below the a1-h8 diagonal, 0 to 27
+    below_diagonal: [u64; 64],
+    // The a1-d1-d4 triangle, 0 to 9 with the diagonal last
+    triangle: [usize; 64],
+    // The 462 ways to place both kings with the first one in the triangle
+    king_pairs: [[u64; 64]; 10],
+    // Squares a2 to h7, higher nearer the edge and the second rank. The leading pawn is the
+    // highest one.
+    pawn_order: [usize; 64],
+    // By number of leading pawns
+    lead_pawn_index: [[u64; 64]; 6],
+    lead_pawns_size: [[u64; 4]; 6],
+}
+
+fn indices() -> &'static Indices {
+    static INDICES: OnceLock<Indices> = OnceLock::new();
+    INDICES.get_or_init(|| {
+        let mut binomial = [[0; 64]; 6];
+        binomial[0][0] = 1;
+        for n in 1..64 {
+            for k in 0..6.min(n + 1) {
+                let with = if k > 0 { binomial[k - 1][n - 1] } else { 0 };
+                let without = if k < n { binomial[k][n - 1] } else { 0 };
+                binomial[k][n] = with + without;
+            }
+        }
+
+        let mut below_diagonal = [0; 64];
+        let mut code = 0;
+        for (square, value) in below_diagonal.iter_mut().enumerate() {
+            if off_diagonal(square) < 0 {
+                *value = code;
+                code += 1;
+            }
+        }
+
+        let mut triangle = [0; 64];
+        let mut diagonal = Vec::new();
+        let mut code = 0;
+        for square in (0..=27).filter(|&square| file_of(square) <= 3) {
+            match off_diagonal(square) {
+                0 => diagonal.push(square),
+                off if off < 0 => {
+                    triangle[square] = code;
+                    code += 1;
+                }
+                _ => {}
+            }
+        }
+        for square in diagonal {
+            triangle[square] = code;
+            code += 1;
+        }
+
+        // With the first king on the diagonal the other one is not above it, and positions
+        // with both on the diagonal come last
+        let mut king_pairs = [[0; 64]; 10];
+        let mut both_on_diagonal = Vec::new();
+        let mut code = 0;
+        for (first, pairs) in king_pairs.iter_mut().enumerate() {
+            // b1 is the only square in the triangle coded 0
+            for king in (0..=27).filter(|&king| triangle[king] == first && (first > 0 || king == 1))
+            {
+                for (other, pair) in pairs.iter_mut().enumerate() {
+                    let near = file_of(king).abs_diff(file_of(other)) <= 1
+                        && rank_of(king).abs_diff(rank_of(other)) <= 1;
+                    if near || (off_diagonal(king) == 0 && off_diagonal(other) > 0) {
+                        continue;
+                    }
+                    if off_diagonal(king) == 0 && off_diagonal(other) == 0 {
+                        both_on_diagonal.push((first, other));
+                    } else {
+                        *pair = code;
+                        code += 1;
+                    }
+                }
+            }
+        }
+        for (first, other) in both_on_diagonal {
+            king_pairs[first][other] = code;
+            code += 1;
+        }
+
+        let mut pawn_order = [0; 64];
+        for file in 0..4 {
+            for rank in 1..7 {
+                let placed = file * 6 + rank - 1;
+                pawn_order[rank * 8 + file] = 47 - 2 * placed;
+                pawn_order[rank * 8 + (7 - file)] = 46 - 2 * placed;
+            }
+        }
+        let mut lead_pawn_index = [[0; 64]; 6];
+        let mut lead_pawns_size = [[0; 4]; 6];
+        for leading in 1..6 {
+            for (file, size) in lead_pawns_size[leading].iter_mut().enumerate() {
+                let mut index = 0;
+                for rank in 1..7 {
+                    let square = rank * 8 + file;
+                    lead_pawn_index[leading][square] = index;
+                    index += binomial[leading - 1][pawn_order[square]];
+                }
+                *size = index;
+            }
+        }
+
+        Indices {
+            binomial,
+            below_diagonal,
+            triangle,
+            king_pairs,
+            pawn_order,
+            lead_pawn_index,
+            lead_pawns_size,
+        }
+    })
+}
+
+// What a table's name says about its pieces, white's are before the v
+struct Material {
+    pieces: usize,
+    has_pawns: bool,
+    // Some side has exactly one piece of a kind other than the king
+    unique: bool,
+    // Pawns of the leading color, the one with fewer of them, then of the other one
+    pawns: [usize; 2],
+    symmetric: bool,
+}
+
+impl Material {
+    fn from_name(name: &str) -> Option<Material> {
+        let (white, black) = name.split_once('v')?;
+        let valid =
+            |side: &str| side.starts_with('K') && side.chars().all(|c| "KQRBNP".contains(c));
+        if !valid(white) || !valid(black) || white.len() + black.len() > MAX_PIECES {
+            return None;
+        }
+        let count = |side: &str, letter: char| side.chars().filter(|&c| c == letter).count();
+        let (white_pawns, black_pawns) = (count(white, 'P'), count(black, 'P'));
+        let lead_white = black_pawns == 0 || (white_pawns > 0 && black_pawns >= white_pawns);
+        Some(Material {
+            pieces: white.len() + black.len(),
+            has_pawns: white_pawns + black_pawns > 0,
+            unique: [white, black]
+                .iter()
+                .any(|side| "QRBNP".chars().any(|letter| count(side, letter) == 1)),
+            pawns: if lead_white {
+                [white_pawns, black_pawns]
+            } else {
+                [black_pawns, white_pawns]
+            },
+            symmetric: white == black,
+        })
+    }
+}
+
+// One part of a table: a side to move and, with pawns, a file of the leading pawn
+#[derive(Default)]
+struct Pairs {
+    flags: u8,
+    pieces: [u8; MAX_PIECES],
+    // Pieces are indexed in groups, e.g. the leading pieces and then each kind of the others
+    group_len: [usize; MAX_PIECES + 1],
+    group_index: [u64; MAX_PIECES + 1],
+    // Values are compressed into blocks, with a sparse index entry every `span` positions
+    block_size: usize,
+    span: u64,
+    sparse_index: usize,
+    sparse_index_size: usize,
+    block_lengths: usize,
+    block_lengths_size: usize,
+    blocks: usize,
+    num_blocks: usize,
+    // Huffman coded symbols, each a value or a pair of symbols. Also the value of single
+    // valued parts.
+    min_sym_len: u8,
+    lowest_sym: usize,
+    base: Vec<u64>,
+    sym_len: Vec<u8>,
+    tree: usize,
+    // Where each result's values start in a DTZ table's map
+    map_index: [usize; 4],
+}
+
+// A symbol's two halves, for leaves the value and NO_SYMBOL
+fn children(bytes: &[u8], tree: usize, symbol: usize) -> Option<(usize, usize)> {
+    let pair = bytes.get(tree + 3 * symbol..tree + 3 * symbol + 3)?;
+    let (a, b, c) = (pair[0] as usize, pair[1] as usize, pair[2] as usize);
+    Some((((b & 0xf) << 8) | a, (c << 4) | (b >> 4)))
+}
+
+impl Pairs {
+    fn set_groups(&mut self, material: &Material, order: [u8; 2], file: usize) {
+        let indices = indices();
+        let mut first_len: i32 = if material.has_pawns {
+            0
+        } else if material.unique {
+            3
+        } else {
+            2
+        };
+        let mut n = 0;
+        self.group_len[0] = 1;
+        for i in 1..material.pieces {
+            first_len -= 1;
+            if first_len > 0 || self.pieces[i] == self.pieces[i - 1] {
+                self.group_len[n] += 1;
+            } else {
+                n += 1;
+                self.group_len[n] = 1;
+            }
+        }
+        n += 1;
+        self.group_len[n] = 0;
+
+        // Groups are not necessarily indexed in order, `order` says where the leading one and
+        // the other side's pawns go
+        let both_pawns = material.has_pawns && material.pawns[1] > 0;
+        let mut next = if both_pawns { 2 } else { 1 };
+        let mut free = 64 - self.group_len[0] - if both_pawns { self.group_len[1] } else { 0 };
+        let mut index = 1;
+        let mut k = 0;
+        while next < n || k == order[0] as usize || k == order[1] as usize {
+            if k == order[0] as usize {
+                self.group_index[0] = index;
+                index *= if material.has_pawns {
+                    indices.lead_pawns_size[self.group_len[0]][file]
+                } else if material.unique {
+                    UNIQUE_LEADERS
+                } else {
+                    KING_PAIRS
+                };
+            } else if k == order[1] as usize {
+                self.group_index[1] = index;
+                index *= indices.binomial[self.group_len[1]][48 - self.group_len[0]];
+            } else {
+                self.group_index[next] = index;
+                index *= indices.binomial[self.group_len[next]][free];
+                free -= self.group_len[next];
+                next += 1;
+            }
+            k += 1;
+        }
+        self.group_index[n] = index;
+    }
+
+    fn set_sizes(&mut self, bytes: &[u8], at: usize) -> Option<usize> {
+        self.flags = *bytes.get(at)?;
+        if self.flags & SINGLE_VALUE != 0 {
+            self.min_sym_len = *bytes.get(at + 1)?;
+            return Some(at + 2);
+        }
+        let size = self.group_index[self.group_len.iter().position(|&len| len == 0)?];
+        self.block_size = 1usize.checked_shl(*bytes.get(at + 1)? as u32)?;
+        self.span = 1u64.checked_shl(*bytes.get(at + 2)? as u32)?;
+        self.sparse_index_size = size.div_ceil(self.span) as usize;
+        let padding = *bytes.get(at + 3)? as usize;
+        self.num_blocks = read_u32(bytes, at + 4)? as usize;
+        self.block_lengths_size = self.num_blocks + padding;
+        let max_sym_len = *bytes.get(at + 8)?;
+        self.min_sym_len = *bytes.get(at + 9)?;
+        self.lowest_sym = at + 10;
+
+        // Longer codes have lower values, base[i] is the lowest code of length min + i padded
+        // to 64 bits
+        let lengths = max_sym_len.checked_sub(self.min_sym_len)? as usize + 1;
+        self.base = vec![0; lengths];
+        for i in (0..lengths - 1).rev() {
+            let lowest = read_u16(bytes, self.lowest_sym + 2 * i)? as u64;
+            let next_lowest = read_u16(bytes, self.lowest_sym + 2 * (i + 1))? as u64;
+            self.base[i] = self.base[i + 1]
+                .wrapping_add(lowest)
+                .wrapping_sub(next_lowest)
+                / 2;
+        }
+        for (i, base) in self.base.iter_mut().enumerate() {
+            let shift = 64u32.checked_sub(i as u32 + self.min_sym_len as u32)?;
+            *base = base.checked_shl(shift).unwrap_or(0);
+        }
+
+        let at = self.lowest_sym + 2 * lengths;
+        let symbols = read_u16(bytes, at)? as usize;
+        self.tree = at + 2;
+        self.sym_len = vec![0; symbols];
+        let mut visited = vec![false; symbols];
+        for symbol in 0..symbols {
+            if !visited[symbol] {
+                self.sym_len[symbol] = self.symbol_length(bytes, symbol, &mut visited)?;
+            }
+        }
+        Some(self.tree + 3 * symbols + (symbols & 1))
+    }
+
+    // How many values a symbol stands for, minus one
+    fn symbol_length(&mut self, bytes: &[u8], symbol: usize, visited: &mut [bool]) -> Option<u8> {
+        visited[symbol] = true;
+        let (left, right) = children(bytes, self.tree, symbol)?;
+        if right == NO_SYMBOL {
+            return Some(0);
+        }
+        for child in [left, right] {
+            if !*visited.get(child)? {
+                self.sym_len[child] = self.symbol_length(bytes, child, visited)?;
+            }
+        }
+        Some(
+            self.sym_len[left]
+                .wrapping_add(self.sym_len[right])
+                .wrapping_add(1),
+        )
+    }
+}
+
+// Only DTZ tables have a map, from the stored values to distances, for each result
+fn set_dtz_map(bytes: &[u8], parts: &mut [Pairs], mut at: usize) -> Option<usize> {
+    let map = at;
+    for pairs in parts.iter_mut().filter(|pairs| pairs.flags & MAPPED != 0) {
+        if pairs.flags & WIDE != 0 {
+            at += at & 1;
+            for index in pairs.map_index.iter_mut() {
+                *index = (at - map) / 2 + 1;
+                at += 2 * read_u16(bytes, at)? as usize + 2;
+            }
+        } else {
+            for index in pairs.map_index.iter_mut() {
+                *index = at - map + 1;
+                at += *bytes.get(at)? as usize + 1;
+            }
+        }
+    }
+    Some(at + (at & 1))
+}
+
+struct Table {
+    bytes: Vec<u8>,
+    material: Material,
+    dtz: bool,
+    // By side to move, then by the file of the leading pawn. Symmetric WDL tables and DTZ
+    // tables have one side only.
+    parts: Vec<Vec<Pairs>>,
+    map: usize,
+}
+
+impl Table {
+    fn parse(bytes: Vec<u8>, name: &str, dtz: bool) -> io::Result<Table> {
+        let material = Material::from_name(name).ok_or_else(|| invalid("Not a table name"))?;
+        let magic = if dtz { DTZ_MAGIC } else { WDL_MAGIC };
+        if bytes.len() < 5 || bytes[..4] != magic {
+            return Err(invalid("Not a Syzygy table"));
+        }
+        let flags = bytes[4];
+        if (flags & HAS_PAWNS != 0) != material.has_pawns
+            || (flags & SPLIT != 0) == material.symmetric
+        {
+            return Err(invalid("Table does not match its name"));
+        }
+        let mut table = Table {
+            bytes,
+            material,
+            dtz,
+            parts: Vec::new(),
+            map: 0,
+        };
+        table
+            .read_parts()
+            .ok_or_else(|| invalid("Truncated table"))?;
+        Ok(table)
+    }
+
+    fn read_parts(&mut self) -> Option<()> {
+        let bytes = &self.bytes;
+        let material = &self.material;
+        let sides = if !self.dtz && !material.symmetric {
+            2
+        } else {
+            1
+        };
+        let files = if material.has_pawns { 4 } else { 1 };
+        let both_pawns = material.has_pawns && material.pawns[1] > 0;
+        let mut parts: Vec<Vec<Pairs>> = (0..sides)
+            .map(|_| (0..files).map(|_| Pairs::default()).collect())
+            .collect();
+
+        let mut at = 5;
+        for file in 0..files {
+            let first = *bytes.get(at)?;
+            let second = if both_pawns {
+                *bytes.get(at + 1)?
+            } else {
+                0xff
+            };
+            let order = [[first & 0xf, second & 0xf], [first >> 4, second >> 4]];
+            at += 1 + both_pawns as usize;
+            for k in 0..material.pieces {
+                let byte = *bytes.get(at)?;
+                parts[0][file].pieces[k] = byte & 0xf;
+                if sides == 2 {
+                    parts[1][file].pieces[k] = byte >> 4;
+                }
+                at += 1;
+            }
+            for (side, part) in parts.iter_mut().enumerate() {
+                part[file].set_groups(material, order[side], file);
+            }
+        }
+        at += at & 1;
+        for file in 0..files {
+            for part in parts.iter_mut() {
+                at = part[file].set_sizes(bytes, at)?;
+            }
+        }
+        let map = at;
+        if self.dtz {
+            at = set_dtz_map(bytes, &mut parts[0], at)?;
+        }
+        for file in 0..files {
+            for part in parts.iter_mut() {
+                part[file].sparse_index = at;
+                at += 6 * part[file].sparse_index_size;
+            }
+        }
+        for file in 0..files {
+            for part in parts.iter_mut() {
+                part[file].block_lengths = at;
+                at += 2 * part[file].block_lengths_size;
+            }
+        }
+        for file in 0..files {
+            for part in parts.iter_mut() {
+                let pairs = &mut part[file];
+                at = (at + 63) & !63;
+                pairs.blocks = at;
+                at += pairs.num_blocks * pairs.block_size;
+                if pairs.num_blocks > 0 && at > bytes.len() {
+                    return None;
+                }
+            }
+        }
+        self.parts = parts;
+        self.map = map;
+        Some(())
+    }
+
+    // Where the position is in the table: the side to move in the table's colors, the file of
+    // the leading pawn and the index. With `flip` black has the table's white pieces.
+    fn index(&self, position: &Position, flip: bool) -> Option<(usize, usize, u64)> {
+        let indices = indices();
+        let flip_color = if flip { 8 } else { 0 };
+        let flip_squares = if flip { 56 } else { 0 };
+        let stm = (flip != (position.turn() == Color::Black)) as usize;
+        let mut squares = Vec::with_capacity(MAX_PIECES);
+        let mut pieces = Vec::with_capacity(MAX_PIECES);
+
+        // With pawns the table is split by the file of the leading one, which comes first
+        let lead = self
+            .material
+            .has_pawns
+            .then(|| self.parts[0][0].pieces[0] ^ flip_color);
+        let mut file = 0;
+        if let Some(lead) = lead {
+            for square in (0..64).filter(|&square| code_at(position, square) == Some(lead)) {
+                squares.push(square ^ flip_squares);
+                pieces.push(lead ^ flip_color);
+            }
+            let leader = (0..squares.len()).max_by_key(|&i| indices.pawn_order[squares[i]])?;
+            squares.swap(0, leader);
+            file = file_of(squares[0]).min(7 - file_of(squares[0]));
+        }
+        let lead_pawns = squares.len();
+        for square in 0..64 {
+            match code_at(position, square) {
+                Some(code) if Some(code) != lead => {
+                    squares.push(square ^ flip_squares);
+                    pieces.push(code ^ flip_color);
+                }
+                _ => {}
+            }
+        }
+        if squares.len() != self.material.pieces {
+            return None;
+        }
+
+        // The pieces in the order the part has them
+        let pairs = &self.parts[stm % self.parts.len()][file];
+        for i in lead_pawns..squares.len() - 1 {
+            if let Some(j) = (i + 1..squares.len()).find(|&j| pieces[j] == pairs.pieces[i]) {
+                pieces.swap(i, j);
+                squares.swap(i, j);
+            }
+        }
+        // The leading piece goes to files a to d, without pawns also to ranks 1 to 4 and below
+        // the a1-h8 diagonal
+        if file_of(squares[0]) > 3 {
+            squares.iter_mut().for_each(|square| *square ^= 7);
+        }
+        let mut index = if self.material.has_pawns {
+            let index = indices.lead_pawn_index[lead_pawns][squares[0]];
+            squares[1..lead_pawns].sort_by_key(|&square| indices.pawn_order[square]);
+            index
+                + (1..lead_pawns)
+                    .map(|i| indices.binomial[i][indices.pawn_order[squares[i]]])
+                    .sum::<u64>()
+        } else {
+            if rank_of(squares[0]) > 3 {
+                squares.iter_mut().for_each(|square| *square ^= 56);
+            }
+            if let Some(i) = (0..pairs.group_len[0]).find(|&i| off_diagonal(squares[i]) != 0) {
+                if off_diagonal(squares[i]) > 0 {
+                    for square in squares[i..].iter_mut() {
+                        *square = ((*square >> 3) | (*square << 3)) & 63;
+                    }
+                }
+            }
+            if self.material.unique {
+                unique_index(&squares)
+            } else {
+                indices.king_pairs[indices.triangle[squares[0]]][squares[1]]
+            }
+        };
+
+        // Then the other groups, each as a combination of the squares the earlier ones left
+        index *= pairs.group_index[0];
+        let mut start = pairs.group_len[0];
+        let mut remaining_pawns = self.material.has_pawns && self.material.pawns[1] > 0;
+        let mut next = 1;
+        while pairs.group_len[next] != 0 {
+            let len = pairs.group_len[next];
+            let group = squares.get_mut(start..start + len)?;
+            group.sort_unstable();
+            let mut combination = 0;
+            for i in 0..len {
+                let square = squares[start + i];
+                let taken = squares[..start].iter().filter(|&&s| s < square).count();
+                let skipped = if remaining_pawns { 8 } else { 0 };
+                combination += indices.binomial[i + 1][square - taken - skipped];
+            }
+            remaining_pawns = false;
+            index += combination * pairs.group_index[next];
+            start += len;
+            next += 1;
+        }
+        Some((stm, file, index))
+    }
+
+    // The value stored for position `index` of a part
+    fn value(&self, pairs: &Pairs, index: u64) -> Option<usize> {
+        if pairs.flags & SINGLE_VALUE != 0 {
+            return Some(pairs.min_sym_len as usize);
+        }
+        let bytes = &self.bytes;
+        let entry = pairs.sparse_index + 6 * (index / pairs.span) as usize;
+        let mut block = read_u32(bytes, entry)? as usize;
+        // The entry is for the position in the middle of its span
+        let mut offset = read_u16(bytes, entry + 4)? as i64 + (index % pairs.span) as i64
+            - (pairs.span / 2) as i64;
+        let block_length = |block: usize| {
+            (block < pairs.block_lengths_size)
+                .then(|| read_u16(bytes, pairs.block_lengths + 2 * block))
+                .flatten()
+                .map(|length| length as i64 + 1)
+        };
+        while offset < 0 {
+            block = block.checked_sub(1)?;
+            offset += block_length(block)?;
+        }
+        while offset >= block_length(block)? {
+            offset -= block_length(block)?;
+            block += 1;
+        }
+
+        let min = pairs.min_sym_len as u32;
+        let mut at = pairs.blocks + block * pairs.block_size;
+        let mut buffer = read_u64_be(bytes, at)?;
+        let mut buffered: u32 = 64;
+        at += 8;
+        let mut symbol = loop {
+            let mut len = 0;
+            while buffer < *pairs.base.get(len)? {
+                len += 1;
+            }
+            let shift = 64u32.checked_sub(len as u32 + min)?;
+            let code = (buffer - pairs.base[len]).checked_shr(shift).unwrap_or(0);
+            let symbol = code as usize + read_u16(bytes, pairs.lowest_sym + 2 * len)? as usize;
+            let length = *pairs.sym_len.get(symbol)? as i64 + 1;
+            if offset < length {
+                break symbol;
+            }
+            offset -= length;
+            let used = len as u32 + min;
+            buffer = buffer.checked_shl(used).unwrap_or(0);
+            buffered = buffered.checked_sub(used)?;
+            if buffered <= 32 {
+                buffered += 32;
+                buffer |= (read_u32_be(bytes, at)? as u64) << (64 - buffered);
+                at += 4;
+            }
+        };
+        // Down the pairs to the value at the offset
+        while *pairs.sym_len.get(symbol)? != 0 {
+            let (left, right) = children(bytes, pairs.tree, symbol)?;
+            let left_length = *pairs.sym_len.get(left)? as i64 + 1;
+            if offset < left_length {
+                symbol = left;
+            } else {
+                offset -= left_length;
+                symbol = right;
+            }
+        }
+        Some(children(bytes, pairs.tree, symbol)?.0)
+    }
+
+    // Stored distances are turned into plies from a win or loss, for result `wdl`
+    fn dtz_value(&self, file: usize, value: usize, wdl: i32) -> Option<i32> {
+        let pairs = &self.parts[0][file];
+        let mut value = value;
+        if pairs.flags & MAPPED != 0 {
+            let map_index = pairs.map_index[[1, 3, 0, 2, 0][(wdl + 2) as usize]];
+            value = if pairs.flags & WIDE != 0 {
+                read_u16(&self.bytes, self.map + 2 * (map_index + value))? as usize
+            } else {
+                *self.bytes.get(self.map + map_index + value)? as usize
+            };
+        }
+        let in_moves = match wdl {
+            2 => pairs.flags & WIN_PLIES == 0,
+            -2 => pairs.flags & LOSS_PLIES == 0,
+            _ => true,
+        };
+        let value = value as i32;
+        Some(if in_moves { 2 * value + 1 } else { value + 1 })
+    }
+}
+
+fn unique_index(squares: &[usize]) -> u64 {
+    let indices = indices();
+    let (a, b, c) = (squares[0], squares[1], squares[2]);
+    let adjust_b = (b > a) as usize;
+    let adjust_c = (c > a) as usize + (c > b) as usize;
+    let rank = |square: usize| rank_of(square) as u64;
+    if off_diagonal(a) != 0 {
+        (indices.triangle[a] as u64 * 63 + (b - adjust_b) as u64) * 62 + (c - adjust_c) as u64
+    } else if off_diagonal(b) != 0 {
+        (6 * 63 + rank(a) * 28 + indices.below_diagonal[b]) * 62 + (c - adjust_c) as u64
+    } else if off_diagonal(c) != 0 {
+        6 * 63 * 62
+            + 4 * 28 * 62
+            + rank(a) * 7 * 28
+            + (rank(b) - adjust_b as u64) * 28
+            + indices.below_diagonal[c]
+    } else {
+        6 * 63 * 62
+            + 4 * 28 * 62
+            + 4 * 7 * 28
+            + rank(a) * 7 * 6
+            + (rank(b) - adjust_b as u64) * 6
+            + (rank(c) - adjust_c as u64)
+    }
+}
+
+// DTZ of a position whose best move captures or moves a pawn
+fn before_zeroing(wdl: i32) -> i32 {
+    match wdl {
+        2 => 1,
+        1 => 101,
+        -1 => -101,
+        -2 => -1,
+        _ => 0,
+    }
+}
+
+struct Files {
+    dir: PathBuf,
+    names: HashSet<String>,
+    max_pieces: usize,
+    // None for files that could not be read, so they are not tried again
+    loaded: Mutex<HashMap<String, Option<Arc<Table>>>>,
+}
+
+#[derive(Clone)]
+pub struct Tablebase {
+    files: Arc<Files>,
+}
+
+impl Tablebase {
+    pub fn open(dir: &Path) -> io::Result<Tablebase> {
+        let mut names = HashSet::new();
+        let mut max_pieces = 0;
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let (Some(stem), Some(extension)) = (
+                path.file_stem().and_then(|stem| stem.to_str()),
+                path.extension().and_then(|extension| extension.to_str()),
+            ) else {
+                continue;
+            };
+            if extension != "rtbw" && extension != "rtbz" {
+                continue;
+            }
+            if let Some(material) = Material::from_name(stem) {
+                max_pieces = max_pieces.max(material.pieces);
+                names.insert(format!("{stem}.{extension}"));
+            }
+        }
+        Ok(Tablebase {
+            files: Arc::new(Files {
+                dir: dir.to_path_buf(),
+                names,
+                max_pieces,
+                loaded: Mutex::new(HashMap::new()),
+            }),
+        })
+    }
+
+    // No tablebase is used unless SYZYGY_PATH is set
+    pub fn from_env() -> Option<Tablebase> {
+        let path = env::var("SYZYGY_PATH").ok().filter(|s| !s.is_empty())?;
+        match Tablebase::open(Path::new(&path)) {
+            Ok(tablebase) => {
+                println!(
+                    "Found {} tablebase files for up to {} pieces",
+                    tablebase.files.names.len(),
+                    tablebase.files.max_pieces
+                );
+                Some(tablebase)
+            }
+            Err(err) => {
+                println!("Error: could not open the tablebases: {err}");
+                None
+            }
+        }
+    }
+
+    // Whether the position can be in the tables: few enough pieces and no castling
+    pub fn covers(&self, position: &Position) -> bool {
+        position.castling() == 0 && piece_count(position) <= self.files.max_pieces
+    }
+
+    fn load(&self, name: &str, dtz: bool) -> Option<Arc<Table>> {
+        let file = format!("{name}.{}", if dtz { "rtbz" } else { "rtbw" });
+        if !self.files.names.contains(&file) {
+            return None;
+        }
+        let mut loaded = self.files.loaded.lock().unwrap();
+        if let Some(table) = loaded.get(&file) {
+            return table.clone();
+        }
+        let path = self.files.dir.join(&file);
+        let table = match fs::read(&path).and_then(|bytes| Table::parse(bytes, name, dtz)) {
+            Ok(table) => Some(Arc::new(table)),
+            Err(err) => {
+                println!("Error: could not read {}: {err}", path.display());
+                None
+            }
+        };
+        loaded.insert(file, table.clone());
+        table
+    }
+
+    // The table with the position's material, and whether black has its white pieces
+    fn table(&self, position: &Position, dtz: bool) -> Option<(Arc<Table>, bool)> {
+        let white = side_name(position, Color::White);
+        let black = side_name(position, Color::Black);
+        if let Some(table) = self.load(&format!("{white}v{black}"), dtz) {
+            // Symmetric tables are stored for white to move only
+            let flip = table.material.symmetric && position.turn() == Color::Black;
+            return Some((table, flip));
+        }
+        let table = self.load(&format!("{black}v{white}"), dtz)?;
+        Some((table, true))
+    }
+
+    // From -2 for a loss to 2 for a win, as stored
+    fn wdl_table(&self, position: &Position) -> Option<i32> {
+        if piece_count(position) == 2 {
+            return Some(0);
+        }
+        let (table, flip) = self.table(position, false)?;
+        let (stm, file, index) = table.index(position, flip)?;
+        let pairs = &table.parts[stm % table.parts.len()][file];
+        Some(table.value(pairs, index)? as i32 - 2)
+    }
+
+    // None without the table, Some(None) when it only has the other side to move
+    fn dtz_table(&self, position: &Position, wdl: i32) -> Option<Option<i32>> {
+        let (table, flip) = self.table(position, true)?;
+        let (stm, file, index) = table.index(position, flip)?;
+        let pairs = &table.parts[0][file];
+        let stored = (pairs.flags & STM) as usize == stm
+            || (table.material.symmetric && !table.material.has_pawns);
+        if !stored {
+            return Some(None);
+        }
+        let value = table.value(pairs, index)?;
+        table.dtz_value(file, value, wdl).map(Some)
+    }
+
+    // The result and whether the best move captures or moves a pawn. Captures are searched
+    // first because tables may store anything for positions where one wins, and they do not
+    // know about en passant.
+    fn search(&self, position: &Position, with_pawn_moves: bool) -> Option<(i32, bool)> {
+        let moves = position.legal_moves();
+        let mut best = -2;
+        let mut searched = 0;
+        for &mv in &moves {
+            let pawn = position
+                .piece_at(mv.from)
+                .is_some_and(|piece| piece.kind == Kind::Pawn);
+            if !(position.is_capture(mv) || with_pawn_moves && pawn) {
+                continue;
+            }
+            searched += 1;
+            let (value, _) = self.search(&position.play(mv), false)?;
+            if -value > best {
+                best = -value;
+                if best == 2 {
+                    return Some((best, true));
+                }
+            }
+        }
+        let all_searched = searched > 0 && searched == moves.len();
+        let value = if all_searched {
+            best
+        } else {
+            self.wdl_table(position)?
+        };
+        if best >= value {
+            return Some((best, best > 0 || all_searched));
+        }
+        Some((value, false))
+    }
+
+    fn dtz(&self, position: &Position) -> Option<i32> {
+        let (wdl, zeroing) = self.search(position, true)?;
+        if wdl == 0 {
+            return Some(0);
+        }
+        if zeroing {
+            return Some(before_zeroing(wdl));
+        }
+        if let Some(dtz) = self.dtz_table(position, wdl)? {
+            let fifty = if wdl.abs() == 1 { 100 } else { 0 };
+            return Some((dtz + fifty) * wdl.signum());
+        }
+        // Stored for the other side to move, one ply further on
+        let mut best = None;
+        for mv in position.legal_moves() {
+            let zeroing = position.is_capture(mv)
+                || position
+                    .piece_at(mv.from)
+                    .is_some_and(|piece| piece.kind == Kind::Pawn);
+            let next = position.play(mv);
+            let dtz = if zeroing {
+                -before_zeroing(self.search(&next, false)?.0)
+            } else {
+                let dtz = -self.dtz(&next)?;
+                dtz + dtz.signum()
+            };
+            if dtz.signum() == wdl.signum() && best.map_or(true, |best| dtz < best) {
+                best = Some(dtz);
+            }
+        }
+        // Without moves it is checkmate
+        Some(best.unwrap_or(-1))
+    }
+
+    pub fn probe_wdl(&self, position: &Position) -> Option<Wdl> {
+        if !self.covers(position) {
+            return None;
+        }
+        self.search(position, false)
+            .map(|(wdl, _)| Wdl::from_value(wdl))
+    }
+
+    // Plies to the next capture or pawn move with perfect play, positive when the side to move
+    // wins and zero for draws. Can be one more than that where the table counts in moves.
+    pub fn probe_dtz(&self, position: &Position) -> Option<i32> {
+        if !self.covers(position) {
+            return None;
+        }
+        self.dtz(position)
+    }
+
+    // The moves that keep the best result, quickest to the next capture or pawn move when
+    // winning and slowest when losing. Moves are only told apart by result without DTZ tables.
+    pub fn best_moves(&self, position: &Position) -> Option<Vec<Move>> {
+        if !self.covers(position) {
+            return None;
+        }
+        let mut ranked = Vec::new();
+        for mv in position.legal_moves() {
+            let next = position.play(mv);
+            let wdl = -self.search(&next, false)?.0;
+            let dtz = if next.half_move() == 0 {
+                Some(before_zeroing(wdl))
+            } else {
+                self.dtz(&next).map(|dtz| -dtz + (-dtz).signum())
+            };
+            let mates = next.in_check() && next.legal_moves().is_empty();
+            let dtz = if mates { Some(1) } else { dtz };
+            ranked.push((mv, wdl, dtz));
+        }
+        let with_dtz = ranked.iter().all(|&(_, _, dtz)| dtz.is_some());
+        let rank = |&(_, wdl, dtz): &(Move, i32, Option<i32>)| {
+            let dtz = if with_dtz { dtz.unwrap_or(0) } else { 0 };
+            (wdl, -dtz)
+        };
+        let best = ranked.iter().map(rank).max()?;
+        Some(
+            ranked
+                .iter()
+                .filter(|&entry| rank(entry) == best)
+                .map(|&(mv, _, _)| mv)
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAWN: u8 = 1;
+    const ROOK: u8 = 4;
+    const QUEEN: u8 = 5;
+    const KING: u8 = 6;
+    const BLACK: u8 = 8;
+    const BLOCK: usize = 64;
+
+    // A value for every position, or one byte per position in blocks of 64
+    enum Part {
+        Single(u8),
+        Bytes(Vec<u8>),
+    }
+
+    // Not tables from the generator, but laid out like them. Every part has its pieces in the
+    // same order, parts go by file and then by side to move.
+    fn write_table(
+        magic: [u8; 4],
+        flags: u8,
+        pieces: &[u8],
+        files: usize,
+        parts: &[(u8, Part)],
+    ) -> Vec<u8> {
+        let mut bytes = magic.to_vec();
+        bytes.push(flags);
+        for _ in 0..files {
+            bytes.push(0);
+            bytes.extend(pieces.iter().map(|&piece| piece | (piece << 4)));
+        }
+        if bytes.len() % 2 == 1 {
+            bytes.push(0);
+        }
+        for (flags, part) in parts {
+            match part {
+                Part::Single(value) => bytes.extend([flags | SINGLE_VALUE, *value]),
+                Part::Bytes(values) => {
+                    // 64 byte blocks, a sparse entry every 64 positions, no padding
+                    bytes.extend([*flags, 6, 6, 0]);
+                    bytes.extend((values.len().div_ceil(BLOCK) as u32).to_le_bytes());
+                    // Every symbol is 8 bits long and stands for its own value
+                    bytes.extend([8, 8, 0, 0]);
+                    bytes.extend(256u16.to_le_bytes());
+                    for symbol in 0..256 {
+                        bytes.extend([symbol as u8, 0xf0, 0xff]);
+                    }
+                }
+            }
+        }
+        let values = || {
+            parts.iter().filter_map(|(_, part)| match part {
+                Part::Bytes(values) => Some(values),
+                Part::Single(_) => None,
+            })
+        };
+        for values in values() {
+            for span in 0..values.len().div_ceil(BLOCK) {
+                let middle = span * BLOCK + BLOCK / 2;
+                bytes.extend(((middle / BLOCK) as u32).to_le_bytes());
+                bytes.extend(((middle % BLOCK) as u16).to_le_bytes());
+            }
+        }
+        for values in values() {
+            for block in values.chunks(BLOCK) {
+                bytes.extend((block.len() as u16 - 1).to_le_bytes());
+            }
+        }
+        for values in values() {
+            bytes.resize(bytes.len().next_multiple_of(64), 0);
+            bytes.extend(values);
+            bytes.resize(bytes.len().next_multiple_of(BLOCK), 0);
+        }
+        // The decoder reads ahead
+        bytes.extend([0; 8]);
+        bytes
+    }
+
+    fn tablebase(test: &str, files: &[(&str, Vec<u8>)]) -> Tablebase {
+        let dir = env::temp_dir().join(format!("syzygy-{test}-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for (name, bytes) in files {
+            fs::write(dir.join(name), bytes).unwrap();
+        }
+        Tablebase::open(&dir).unwrap()
+    }
+
+    // White always wins, whoever is to move
+    fn won_for_white(pieces: &[u8]) -> Vec<u8> {
+        let parts = [(0, Part::Single(4)), (0, Part::Single(0))];
+        write_table(WDL_MAGIC, SPLIT, pieces, 1, &parts)
+    }
+
+    fn queen_tables(test: &str) -> Tablebase {
+        let dtz = write_table(
+            DTZ_MAGIC,
+            SPLIT,
+            &[KING, QUEEN, KING | BLACK],
+            1,
+            &[(WIN_PLIES, Part::Single(5))],
+        );
+        tablebase(
+            test,
+            &[
+                ("KQvK.rtbw", won_for_white(&[KING, QUEEN, KING | BLACK])),
+                ("KQvK.rtbz", dtz),
+                ("KRvK.rtbw", won_for_white(&[KING, ROOK, KING | BLACK])),
+                (
+                    "KQvKR.rtbw",
+                    won_for_white(&[KING, QUEEN, KING | BLACK, ROOK | BLACK]),
+                ),
+            ],
+        )
+    }
+
+    fn position(fen: &str) -> Position {
+        Position::from_fen(fen).unwrap()
+    }
+
+    #[test]
+    fn test_index_tables() {
+        let indices = indices();
+        let pairs = indices.king_pairs.iter().flatten().max().unwrap();
+        assert_eq!(pairs + 1, KING_PAIRS);
+        assert_eq!(indices.below_diagonal.iter().max(), Some(&27));
+        assert_eq!(indices.triangle.iter().max(), Some(&9));
+        assert_eq!(indices.pawn_order.iter().max(), Some(&47));
+        assert_eq!(indices.binomial[2][5], 10);
+        assert_eq!(indices.lead_pawns_size[1], [6; 4]);
+    }
+
+    #[test]
+    fn test_mirrored_positions_have_the_same_index() {
+        let queens = queen_tables("mirror");
+        let index = |fen: &str| {
+            let position = position(fen);
+            let (table, flip) = queens.table(&position, false).unwrap();
+            table.index(&position, flip).unwrap()
+        };
+        let original = index("8/8/6k1/8/2R5/8/8/1K6 w - - 0 1");
+        assert!(original.2 < UNIQUE_LEADERS);
+        // Across the d/e line, the 4/5 line, the a1-h8 diagonal and with colors swapped
+        assert_eq!(index("8/8/1k6/8/5R2/8/8/6K1 w - - 0 1"), original);
+        assert_eq!(index("1K6/8/8/2R5/8/6k1/8/8 w - - 0 1"), original);
+        assert_eq!(index("8/5k2/8/8/8/3R4/K7/8 w - - 0 1"), original);
+        assert_eq!(index("1k6/8/8/2r5/8/6K1/8/8 b - - 0 1"), original);
+        assert_ne!(index("8/8/6k1/8/2R5/8/8/1K6 b - - 0 1"), original);
+
+        let pawns = tablebase(
+            "pawns",
+            &[(
+                "KPvK.rtbw",
+                write_table(
+                    WDL_MAGIC,
+                    SPLIT | HAS_PAWNS,
+                    &[PAWN, KING, KING | BLACK],
+                    4,
+                    &[
+                        (0, Part::Single(4)),
+                        (0, Part::Single(2)),
+                        (0, Part::Single(4)),
+                        (0, Part::Single(2)),
+                        (0, Part::Single(4)),
+                        (0, Part::Single(2)),
+                        (0, Part::Single(4)),
+                        (0, Part::Single(2)),
+                    ],
+                ),
+            )],
+        );
+        let index = |fen: &str| {
+            let position = position(fen);
+            let (table, flip) = pawns.table(&position, false).unwrap();
+            table.index(&position, flip).unwrap()
+        };
+        let original = index("8/8/4k3/8/8/1P6/8/2K5 w - - 0 1");
+        assert_eq!(original.1, 1);
+        assert_eq!(index("8/8/3k4/8/8/6P1/8/5K2 w - - 0 1"), original);
+        assert_eq!(index("2k5/8/1p6/8/8/4K3/8/8 b - - 0 1"), original);
+        assert_eq!(
+            pawns.probe_wdl(&position("8/8/4k3/8/8/1P6/8/2K5 b - - 0 1")),
+            Some(Wdl::Draw)
+        );
+    }
+
+    #[test]
+    fn test_compressed_values() {
+        let size = UNIQUE_LEADERS as usize;
+        let white: Vec<u8> = (0..size).map(|i| (i % 5) as u8).collect();
+        let black: Vec<u8> = (0..size).map(|i| (i * 7 % 251) as u8).collect();
+        let bytes = write_table(
+            WDL_MAGIC,
+            SPLIT,
+            &[KING, ROOK, KING | BLACK],
+            1,
+            &[
+                (0, Part::Bytes(white.clone())),
+                (0, Part::Bytes(black.clone())),
+            ],
+        );
+        let table = Table::parse(bytes, "KRvK", false).unwrap();
+        for (side, values) in [white, black].iter().enumerate() {
+            for (index, &value) in values.iter().enumerate() {
+                let pairs = &table.parts[side][0];
+                assert_eq!(table.value(pairs, index as u64), Some(value as usize));
+            }
+        }
+
+        let tablebase = tablebase("compressed", &[("KRvK.rtbw", table.bytes.clone())]);
+        let position = position("8/8/6k1/8/2R5/8/8/1K6 w - - 0 1");
+        let (_, _, index) = table.index(&position, false).unwrap();
+        let expected = Wdl::from_value((index % 5) as i32 - 2);
+        assert_eq!(tablebase.probe_wdl(&position), Some(expected));
+    }
+
+    #[test]
+    fn test_tables_are_checked() {
+        let bytes = won_for_white(&[KING, QUEEN, KING | BLACK]);
+        assert!(Table::parse(bytes.clone(), "KQvK", false).is_ok());
+        assert!(Table::parse(bytes.clone(), "KQvK", true).is_err());
+        assert!(Table::parse(bytes.clone(), "KPvK", false).is_err());
+        assert!(Table::parse(bytes[..5].to_vec(), "KQvK", false).is_err());
+        assert!(Material::from_name("KQvKX").is_none());
+        assert!(Material::from_name("KQQQQvKQQ").is_none());
+    }
+
+    #[test]
+    fn test_probe_wdl() {
+        let tablebase = queen_tables("wdl");
+        let win = position("8/8/8/8/8/2k5/8/K6Q w - - 0 1");
+        assert_eq!(tablebase.probe_wdl(&win), Some(Wdl::Win));
+        let loss = position("8/8/8/8/8/2k5/8/K6Q b - - 0 1");
+        assert_eq!(tablebase.probe_wdl(&loss), Some(Wdl::Loss));
+        // The table says white wins, but black takes the queen first
+        let capture = position("r3k3/8/8/8/Q7/8/8/7K b - - 0 1");
+        assert_eq!(tablebase.probe_wdl(&capture), Some(Wdl::Win));
+        assert_eq!(
+            tablebase.probe_wdl(&position("8/8/8/4k3/8/8/8/K7 w - - 0 1")),
+            Some(Wdl::Draw)
+        );
+        // No table, too many pieces, castling
+        assert_eq!(
+            tablebase.probe_wdl(&position("8/8/8/4k3/8/8/8/KN6 w - - 0 1")),
+            None
+        );
+        assert_eq!(
+            tablebase.probe_wdl(&position("r3k3/8/8/8/Q7/8/8/R6K b - - 0 1")),
+            None
+        );
+        assert_eq!(
+            tablebase.probe_wdl(&position("4k3/8/8/8/8/8/8/R3K3 w Q - 0 1")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_probe_dtz() {
+        let tablebase = queen_tables("dtz");
+        let win = position("8/8/8/8/8/2k5/8/K6Q w - - 0 1");
+        assert_eq!(tablebase.probe_dtz(&win), Some(6));
+        // Only stored with white to move, found one ply further on
+        let loss = position("8/8/8/8/8/2k5/8/K6Q b - - 0 1");
+        assert_eq!(tablebase.probe_dtz(&loss), Some(-7));
+        let capture = position("r3k3/8/8/8/Q7/8/8/7K b - - 0 1");
+        assert_eq!(tablebase.probe_dtz(&capture), Some(1));
+    }
+
+    #[test]
+    fn test_best_moves() {
+        let tablebase = queen_tables("best");
+        let mate = position("k7/8/1K6/8/8/8/8/2Q5 w - - 0 1");
+        let moves = tablebase.best_moves(&mate).unwrap();
+        let moves: Vec<String> = moves.into_iter().map(|mv| mate.san(mv)).collect();
+        assert_eq!(moves, vec!["Qc8#".to_string()]);
+        let capture = position("r3k3/8/8/8/Q7/8/8/7K b - - 0 1");
+        let moves = tablebase.best_moves(&capture).unwrap();
+        assert_eq!(moves, vec![capture.parse_move("Rxa4").unwrap()]);
+    }
+}
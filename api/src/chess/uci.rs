@@ -0,0 +1,509 @@
+// Talks to external engines (e.g. a locally installed Stockfish) over the Universal Chess
+// Interface. Engine processes are started on demand and kept in a pool, at most `pool_size`
+// run at once and a finished search hands its process to the next one.
+use std::env;
+use std::io;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::timeout;
+
+use super::engine::{
+    position::{Move, Position},
+    search::Limits,
+};
+use super::game::GameError;
+
+const DEFAULT_POOL_SIZE: usize = 2;
+const DEFAULT_MOVE_TIME_MS: u64 = 1000;
+// How long an engine has to answer, on top of the time it was given to think
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EngineConfig {
+    pub path: String,
+    pub args: Vec<String>,
+    // Sent as `setoption` once the engine is started, e.g. ("Threads", "2")
+    pub options: Vec<(String, String)>,
+    pub pool_size: usize,
+    // How long the engine thinks about a bot move
+    pub move_time: Duration,
+}
+
+impl EngineConfig {
+    // No external engine is used unless UCI_ENGINE_PATH is set. UCI_ENGINE_ARGS is split on
+    // whitespace and UCI_ENGINE_OPTIONS is a comma separated list such as Threads=2,Hash=64
+    pub fn from_env() -> Option<EngineConfig> {
+        let path = env::var("UCI_ENGINE_PATH").ok().filter(|s| !s.is_empty())?;
+        let args = env::var("UCI_ENGINE_ARGS")
+            .map(|s| s.split_whitespace().map(String::from).collect())
+            .unwrap_or_default();
+        let options = env::var("UCI_ENGINE_OPTIONS")
+            .map(|s| parse_options(&s))
+            .unwrap_or_default();
+        let pool_size = env::var("UCI_ENGINE_POOL_SIZE")
+            .ok()
+            .and_then(|s| s.parse::<usize>().ok())
+            .filter(|&size| size > 0)
+            .unwrap_or(DEFAULT_POOL_SIZE);
+        let move_time = env::var("UCI_ENGINE_MOVE_TIME_MS")
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok())
+            .unwrap_or(DEFAULT_MOVE_TIME_MS);
+        Some(EngineConfig {
+            path,
+            args,
+            options,
+            pool_size,
+            move_time: Duration::from_millis(move_time),
+        })
+    }
+}
+
+fn parse_options(options: &str) -> Vec<(String, String)> {
+    options
+        .split(',')
+        .filter_map(|option| option.split_once('='))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .filter(|(name, _)| !name.is_empty())
+        .collect()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Score {
+    Centipawns(i32),
+    // Moves until mate, negative when the side to move is getting mated
+    Mate(i32),
+}
+
+// One `info` line, only the fields we use
+#[derive(Clone, Debug, PartialEq)]
+pub struct Info {
+    pub depth: Option<i32>,
+    pub multipv: usize,
+    pub score: Option<Score>,
+    pub nodes: Option<u64>,
+    pub pv: Vec<String>,
+}
+
+pub fn parse_info(line: &str) -> Option<Info> {
+    let mut tokens = line.split_whitespace();
+    if tokens.next() != Some("info") {
+        return None;
+    }
+    let mut info = Info {
+        depth: None,
+        multipv: 1,
+        score: None,
+        nodes: None,
+        pv: Vec::new(),
+    };
+    while let Some(token) = tokens.next() {
+        match token {
+            "depth" => info.depth = tokens.next().and_then(|s| s.parse().ok()),
+            "multipv" => info.multipv = tokens.next().and_then(|s| s.parse().ok()).unwrap_or(1),
+            "nodes" => info.nodes = tokens.next().and_then(|s| s.parse().ok()),
+            "score" => {
+                let kind = tokens.next();
+                let value = tokens.next().and_then(|s| s.parse().ok());
+                info.score = match (kind, value) {
+                    (Some("cp"), Some(value)) => Some(Score::Centipawns(value)),
+                    (Some("mate"), Some(value)) => Some(Score::Mate(value)),
+                    _ => None,
+                };
+            }
+            // Free text, runs to the end of the line
+            "string" => break,
+            // Always the last field
+            "pv" => {
+                info.pv = tokens.map(String::from).collect();
+                break;
+            }
+            _ => {}
+        }
+    }
+    Some(info)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BestMove {
+    // None when the side to move has no legal moves
+    pub best: Option<String>,
+    pub ponder: Option<String>,
+}
+
+pub fn parse_bestmove(line: &str) -> Option<BestMove> {
+    let mut tokens = line.split_whitespace();
+    if tokens.next() != Some("bestmove") {
+        return None;
+    }
+    let best = tokens
+        .next()
+        .filter(|&best| best != "(none)" && best != "0000")
+        .map(String::from);
+    let ponder = match tokens.next() {
+        Some("ponder") => tokens.next().map(String::from),
+        _ => None,
+    };
+    Some(BestMove { best, ponder })
+}
+
+// The game's moves in coordinate notation, for `position ... moves`
+pub fn uci_moves(moves: &str) -> Result<Vec<String>, GameError> {
+    Ok(Position::parse_game(moves)?.iter().map(Move::uci).collect())
+}
+
+fn go_command(limits: Limits) -> String {
+    let mut command = String::from("go");
+    if let Some(depth) = limits.depth {
+        command.push_str(&format!(" depth {depth}"));
+    }
+    if let Some(nodes) = limits.nodes {
+        command.push_str(&format!(" nodes {nodes}"));
+    }
+    if let Some(time) = limits.time {
+        command.push_str(&format!(" movetime {}", time.as_millis()));
+    }
+    // Never search forever
+    if limits == Limits::default() {
+        command.push_str(&format!(" movetime {DEFAULT_MOVE_TIME_MS}"));
+    }
+    command
+}
+
+pub struct Engine {
+    // Killed when dropped
+    _child: Child,
+    stdin: ChildStdin,
+    stdout: Lines<BufReader<ChildStdout>>,
+    // Set while a `go` has not seen its `bestmove`, such an engine is not reused
+    searching: bool,
+}
+
+impl Engine {
+    pub async fn start(config: &EngineConfig) -> io::Result<Engine> {
+        let mut child = Command::new(&config.path)
+            .args(&config.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()?;
+        let stdin = child.stdin.take().ok_or_else(|| broken("no stdin"))?;
+        let stdout = child.stdout.take().ok_or_else(|| broken("no stdout"))?;
+        let mut engine = Engine {
+            _child: child,
+            stdin,
+            stdout: BufReader::new(stdout).lines(),
+            searching: false,
+        };
+        engine.send("uci").await?;
+        engine.wait_for("uciok", RESPONSE_TIMEOUT).await?;
+        for (name, value) in &config.options {
+            engine
+                .send(&format!("setoption name {name} value {value}"))
+                .await?;
+        }
+        engine.ready().await?;
+        Ok(engine)
+    }
+
+    async fn send(&mut self, command: &str) -> io::Result<()> {
+        self.stdin.write_all(command.as_bytes()).await?;
+        self.stdin.write_all(b"\n").await?;
+        self.stdin.flush().await
+    }
+
+    async fn read_line(&mut self, wait: Duration) -> io::Result<String> {
+        match timeout(wait, self.stdout.next_line()).await {
+            Ok(Ok(Some(line))) => Ok(line),
+            Ok(Ok(None)) => Err(broken("engine exited")),
+            Ok(Err(err)) => Err(err),
+            Err(_) => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "engine did not answer",
+            )),
+        }
+    }
+
+    async fn wait_for(&mut self, expected: &str, wait: Duration) -> io::Result<()> {
+        while self.read_line(wait).await?.trim() != expected {}
+        Ok(())
+    }
+
+    async fn ready(&mut self) -> io::Result<()> {
+        self.send("isready").await?;
+        self.wait_for("readyok", RESPONSE_TIMEOUT).await
+    }
+
+    // Searches the position after `moves` are played from `fen` for the best `multipv` lines,
+    // `report` gets every `info`
+    pub async fn go(
+        &mut self,
+        fen: &str,
+        moves: &[String],
+        limits: Limits,
+        multipv: usize,
+        report: &mut (dyn FnMut(&Info) + Send),
+    ) -> io::Result<BestMove> {
+        self.searching = true;
+        self.send(&format!("setoption name MultiPV value {}", multipv.max(1)))
+            .await?;
+        self.send("ucinewgame").await?;
+        self.ready().await?;
+        let mut position = format!("position fen {fen}");
+        if !moves.is_empty() {
+            position.push_str(" moves ");
+            position.push_str(&moves.join(" "));
+        }
+        self.send(&position).await?;
+        self.send(&go_command(limits)).await?;
+
+        let wait = limits.time.unwrap_or_default() + RESPONSE_TIMEOUT;
+        loop {
+            let line = self.read_line(wait).await?;
+            if let Some(best) = parse_bestmove(&line) {
+                self.searching = false;
+                return Ok(best);
+            }
+            if let Some(info) = parse_info(&line) {
+                report(&info);
+            }
+        }
+    }
+}
+
+fn broken(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, message.to_string())
+}
+
+#[derive(Clone)]
+pub struct EnginePool {
+    config: Arc<EngineConfig>,
+    idle: Arc<Mutex<Vec<Engine>>>,
+    permits: Arc<Semaphore>,
+}
+
+impl EnginePool {
+    pub fn new(config: EngineConfig) -> EnginePool {
+        EnginePool {
+            permits: Arc::new(Semaphore::new(config.pool_size)),
+            config: Arc::new(config),
+            idle: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    pub fn from_env() -> Option<EnginePool> {
+        EngineConfig::from_env().map(EnginePool::new)
+    }
+
+    pub fn move_time(&self) -> Duration {
+        self.config.move_time
+    }
+
+    // Waits for a free slot, then reuses an idle engine or starts a new one
+    pub async fn acquire(&self) -> io::Result<PooledEngine> {
+        let permit = self
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|_| broken("engine pool closed"))?;
+        let idle = self.idle.lock().unwrap().pop();
+        let engine = match idle {
+            Some(engine) => engine,
+            None => Engine::start(&self.config).await?,
+        };
+        Ok(PooledEngine {
+            engine: Some(engine),
+            idle: self.idle.clone(),
+            _permit: permit,
+        })
+    }
+}
+
+// Goes back to the pool when dropped, unless it failed or was dropped in the middle of a search
+pub struct PooledEngine {
+    engine: Option<Engine>,
+    idle: Arc<Mutex<Vec<Engine>>>,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl PooledEngine {
+    pub async fn go(
+        &mut self,
+        fen: &str,
+        moves: &[String],
+        limits: Limits,
+        multipv: usize,
+        report: &mut (dyn FnMut(&Info) + Send),
+    ) -> io::Result<BestMove> {
+        let engine = self
+            .engine
+            .as_mut()
+            .ok_or_else(|| broken("engine failed"))?;
+        let result = engine.go(fen, moves, limits, multipv, report).await;
+        if result.is_err() {
+            self.engine = None;
+        }
+        result
+    }
+}
+
+impl Drop for PooledEngine {
+    fn drop(&mut self) {
+        if let Some(engine) = self.engine.take().filter(|engine| !engine.searching) {
+            self.idle.lock().unwrap().push(engine);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::engine::position::START_FEN;
+
+    // A scripted stand-in for a real engine
+    const FAKE_ENGINE: &str = r#"
+while read -r line; do
+  case "$line" in
+    uci) echo "id name Fake"; echo "uciok" ;;
+    isready) echo "readyok" ;;
+    go*)
+      echo "info depth 1 score cp 25 nodes 20 pv e2e4 e7e5"
+      echo "info string thinking"
+      echo "info depth 2 multipv 1 score mate 3 nodes 80 pv d2d4 d7d5"
+      echo "bestmove d2d4 ponder d7d5" ;;
+    quit) exit 0 ;;
+  esac
+done
+"#;
+
+    fn fake_config(script: &str) -> EngineConfig {
+        EngineConfig {
+            path: "sh".to_string(),
+            args: vec!["-c".to_string(), script.to_string()],
+            options: vec![("Threads".to_string(), "1".to_string())],
+            pool_size: 1,
+            move_time: Duration::from_millis(100),
+        }
+    }
+
+    #[test]
+    fn test_parse_info() {
+        let info = parse_info(
+            "info depth 12 seldepth 18 multipv 2 score cp -31 nodes 120345 nps 900000 pv g8f6 c2c4",
+        )
+        .unwrap();
+        assert_eq!(info.depth, Some(12));
+        assert_eq!(info.multipv, 2);
+        assert_eq!(info.score, Some(Score::Centipawns(-31)));
+        assert_eq!(info.nodes, Some(120345));
+        assert_eq!(info.pv, vec!["g8f6", "c2c4"]);
+        let info = parse_info("info depth 5 score mate -2 lowerbound pv h7h8q").unwrap();
+        assert_eq!(info.score, Some(Score::Mate(-2)));
+        assert_eq!(
+            parse_info("info string pv is not a pv").unwrap().pv.len(),
+            0
+        );
+        assert_eq!(parse_info("bestmove e2e4"), None);
+    }
+
+    #[test]
+    fn test_parse_bestmove() {
+        assert_eq!(
+            parse_bestmove("bestmove e7e8q ponder a2a1"),
+            Some(BestMove {
+                best: Some("e7e8q".to_string()),
+                ponder: Some("a2a1".to_string()),
+            })
+        );
+        assert_eq!(parse_bestmove("bestmove (none)").unwrap().best, None);
+        assert_eq!(parse_bestmove("info depth 1"), None);
+    }
+
+    #[test]
+    fn test_uci_moves_and_options() {
+        assert_eq!(
+            uci_moves("e4 e5 Nf3 Nc6 Bb5 a6 O-O").unwrap(),
+            vec!["e2e4", "e7e5", "g1f3", "b8c6", "f1b5", "a7a6", "e1g1"]
+        );
+        assert!(uci_moves("e4 e4").is_err());
+        assert_eq!(
+            parse_options("Threads=2, Hash = 64,bad"),
+            vec![
+                ("Threads".to_string(), "2".to_string()),
+                ("Hash".to_string(), "64".to_string())
+            ]
+        );
+        assert_eq!(go_command(Limits::default()), "go movetime 1000");
+        let limits = Limits {
+            depth: Some(8),
+            time: Some(Duration::from_millis(300)),
+            ..Default::default()
+        };
+        assert_eq!(go_command(limits), "go depth 8 movetime 300");
+    }
+
+    #[tokio::test]
+    async fn test_pool_reuses_engine() {
+        let pool = EnginePool::new(fake_config(FAKE_ENGINE));
+        let mut infos = Vec::new();
+        let best = {
+            let mut engine = pool.acquire().await.unwrap();
+            let moves = vec!["e2e4".to_string()];
+            engine
+                .go(START_FEN, &moves, Limits::default(), 1, &mut |info| {
+                    infos.push(info.clone())
+                })
+                .await
+                .unwrap()
+        };
+        assert_eq!(best.best.as_deref(), Some("d2d4"));
+        assert_eq!(best.ponder.as_deref(), Some("d7d5"));
+        assert_eq!(infos.len(), 3);
+        assert_eq!(infos[2].score, Some(Score::Mate(3)));
+        assert_eq!(pool.idle.lock().unwrap().len(), 1);
+
+        // The idle engine answers the next search
+        let mut engine = pool.acquire().await.unwrap();
+        assert!(pool.idle.lock().unwrap().is_empty());
+        let best = engine
+            .go(START_FEN, &[], Limits::default(), 1, &mut |_| {})
+            .await
+            .unwrap();
+        assert_eq!(best.best.as_deref(), Some("d2d4"));
+    }
+
+    #[tokio::test]
+    async fn test_failed_engine_is_dropped() {
+        // Answers the handshake, then exits when asked to search
+        let script = r#"
+while read -r line; do
+  case "$line" in
+    uci) echo "uciok" ;;
+    isready) echo "readyok" ;;
+    go*) exit 1 ;;
+  esac
+done
+"#;
+        let pool = EnginePool::new(fake_config(script));
+        let mut engine = pool.acquire().await.unwrap();
+        let result = engine
+            .go(START_FEN, &[], Limits::default(), 1, &mut |_| {})
+            .await;
+        assert!(result.is_err());
+        drop(engine);
+        assert!(pool.idle.lock().unwrap().is_empty());
+
+        let missing = EngineConfig {
+            path: "/nonexistent/engine".to_string(),
+            ..fake_config("")
+        };
+        assert!(EnginePool::new(missing).acquire().await.is_err());
+    }
+}
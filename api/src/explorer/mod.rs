@@ -0,0 +1,3 @@
+pub mod record;
+pub mod service;
+tonic::include_proto!("explorer"); // The string specified here must match the proto package name
@@ -0,0 +1,181 @@
+// Keeps `opening_move` up to date: every finished game adds its first moves, keyed by the
+// position they were played in so that transpositions are counted together.
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
+use sea_orm::{ConnectionTrait, DatabaseConnection, DbErr, TransactionTrait};
+use std::collections::HashSet;
+
+use entity::entities::{game, opening_move};
+use service::{
+    game::query as game_query,
+    openings::{mutation, query},
+    ratings::{glicko2::Glicko2, mutation::white_score, pool::Pool, query as rating_query},
+    time_control::query as time_control_query,
+    users::query as user_query,
+};
+
+use crate::chess::{engine::position::Position, history};
+
+// Deeper than any opening line, the explorer is not meant for the endgame
+const MAX_PLIES: usize = 50;
+pub const BAND_WIDTH: i32 = 200;
+const BACKFILL_PAGE: u64 = 100;
+
+pub fn rating_band(rating: f64) -> i32 {
+    (rating as i32).div_euclid(BAND_WIDTH) * BAND_WIDTH
+}
+
+pub fn month(date: NaiveDate) -> NaiveDate {
+    date.with_day(1).unwrap_or(date)
+}
+
+// What a game adds to the counts, nothing when it has no result. `rating` is the average of
+// both players. A move played again from the same position, e.g. after a repetition, is
+// counted once for the game.
+pub fn game_moves(
+    moves: &str,
+    result: &str,
+    pool: Pool,
+    rating: f64,
+    finished_at: NaiveDateTime,
+) -> Vec<opening_move::Model> {
+    let score = match white_score(result) {
+        Some(score) => score,
+        None => return Vec::new(),
+    };
+    let mut position = Position::new();
+    let mut rows = Vec::new();
+    let mut seen = HashSet::new();
+    for (mv, after) in history::replay(moves).into_iter().take(MAX_PLIES) {
+        if seen.insert((position.key(), mv.uci())) {
+            rows.push(opening_move::Model {
+                id: 0,
+                zobrist: position.key() as i64,
+                uci: mv.uci(),
+                san: position.san(mv),
+                pool: pool.as_str().to_string(),
+                rating_band: rating_band(rating),
+                month: month(finished_at.date()),
+                games: 1,
+                white_wins: (score == 1.0) as i32,
+                draws: (score == 0.5) as i32,
+                black_wins: (score == 0.0) as i32,
+                rating_sum: rating.round() as i64,
+            });
+        }
+        position = after;
+    }
+    rows
+}
+
+// After a rated game its new rating, otherwise the current one
+async fn player_rating<C: ConnectionTrait>(
+    db: &C,
+    user_id: i32,
+    game_row: &game::Model,
+    pool: Pool,
+) -> Result<f64, DbErr> {
+    if game_row.rated {
+        if let Some(entry) =
+            rating_query::Query::find_rating_after_game(db, user_id, game_row.id).await?
+        {
+            return Ok(entry.rating);
+        }
+    }
+    Ok(rating_query::Query::find_rating(db, user_id, pool)
+        .await?
+        .map_or(Glicko2::default().rating, |rating| rating.rating))
+}
+
+// Called once for every finished game, after its ratings were updated. `db` is the transaction
+// that stores its result, so the counts cannot miss a game.
+pub async fn record_game<C: ConnectionTrait + TransactionTrait>(
+    db: &C,
+    game_row: &game::Model,
+) -> Result<(), DbErr> {
+    let result = match &game_row.result {
+        Some(result) => result,
+        None => return Ok(()),
+    };
+    // Bots do not play the openings people come to study
+    for user_id in [game_row.player_white, game_row.player_black] {
+        match user_query::Query::find_user_by_id(db, user_id).await? {
+            Some(user) if user.bot_level.is_none() => {}
+            _ => return Ok(()),
+        }
+    }
+    let time_control =
+        time_control_query::Query::find_time_control_by_id(db, game_row.time_control)
+            .await?
+            .ok_or(DbErr::Custom("Cannot find time control.".to_owned()))?;
+    let pool = Pool::from_time(time_control.time);
+    let white = player_rating(db, game_row.player_white, game_row, pool).await?;
+    let black = player_rating(db, game_row.player_black, game_row, pool).await?;
+
+    let finished_at = game_row.updated_at.unwrap_or(game_row.created_at);
+    let moves = history::moves(db, game_row.id).await?;
+    let rows = game_moves(&moves, result, pool, (white + black) / 2.0, finished_at);
+    if rows.is_empty() {
+        return Ok(());
+    }
+    mutation::Mutation::add_game(db, rows).await
+}
+
+// Counts the games finished before the explorer existed. Only runs while nothing is counted, so
+// a backfill that was interrupted is started over by emptying `opening_move`.
+pub async fn backfill(db: &DatabaseConnection) -> Result<(), DbErr> {
+    if query::Query::has_openings(db).await? {
+        return Ok(());
+    }
+    let mut after_id = 0;
+    loop {
+        let games = game_query::Query::find_finished_games(db, after_id, BACKFILL_PAGE).await?;
+        let last = match games.last() {
+            Some(last) => last.id,
+            None => return Ok(()),
+        };
+        for game_row in &games {
+            record_game(db, game_row).await?;
+        }
+        after_id = last;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_game_moves_are_keyed_by_position_before() {
+        let finished_at = NaiveDate::from_ymd_opt(2024, 7, 20)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap();
+        let rows = game_moves("e4 e5 Nf3", "0-1", Pool::Blitz, 1650.4, finished_at);
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0].zobrist, Position::new().key() as i64);
+        assert_eq!(rows[0].uci, "e2e4");
+        assert_eq!(
+            rows[2].zobrist,
+            Position::from_moves("e4 e5").unwrap().0.key() as i64
+        );
+        assert_eq!(rows[2].san, "Nf3");
+        assert_eq!(rows[2].pool, "blitz");
+        assert_eq!(rows[2].rating_band, 1600);
+        assert_eq!(rows[2].rating_sum, 1650);
+        assert_eq!(rows[2].month, NaiveDate::from_ymd_opt(2024, 7, 1).unwrap());
+        assert_eq!(
+            (rows[2].white_wins, rows[2].draws, rows[2].black_wins),
+            (0, 0, 1)
+        );
+
+        assert!(game_moves("e4 e5", "*", Pool::Blitz, 1500.0, finished_at).is_empty());
+        // Repeating the same four moves adds each of them once
+        let repeated = "Nf3 Nf6 Ng1 Ng8 ".repeat(20);
+        let rows = game_moves(&repeated, "1/2-1/2", Pool::Rapid, 1500.0, finished_at);
+        assert_eq!(
+            rows.iter().map(|row| row.san.as_str()).collect::<Vec<_>>(),
+            vec!["Nf3", "Nf6", "Ng1", "Ng8"]
+        );
+        assert!(rows.iter().all(|row| row.games == 1 && row.draws == 1));
+    }
+}
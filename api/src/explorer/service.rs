@@ -0,0 +1,181 @@
+use chrono::{DateTime, NaiveDate};
+use sea_orm::DatabaseConnection;
+use tonic::{Request, Response, Status};
+
+use service::{
+    openings::query::{self, MoveStats, OpeningFilter},
+    ratings::pool::Pool,
+};
+
+use super::{
+    explorer_server::Explorer,
+    record::{month, rating_band},
+    Opening, OpeningMove, OpeningRequest,
+};
+use crate::auth::interceptor::{authenticated_user, log_request};
+use crate::chess::{eco, engine::position::Position};
+
+pub struct ExplorerService {
+    pub db_connection: DatabaseConnection,
+}
+
+fn position(r: &OpeningRequest) -> Result<Position, Status> {
+    match (r.fen.is_empty(), r.moves.is_empty()) {
+        (true, true) => Ok(Position::new()),
+        (false, true) => {
+            Position::from_fen(&r.fen).map_err(|_| Status::invalid_argument("Invalid FEN"))
+        }
+        (true, false) => Position::from_moves(&r.moves)
+            .map(|(position, _)| position)
+            .map_err(|_| Status::invalid_argument("Invalid moves")),
+        (false, false) => Err(Status::invalid_argument(
+            "Only one of FEN and moves can be set",
+        )),
+    }
+}
+
+fn month_of(timestamp: i64) -> Result<Option<NaiveDate>, Status> {
+    if timestamp == 0 {
+        return Ok(None);
+    }
+    let date = DateTime::from_timestamp(timestamp, 0)
+        .ok_or_else(|| Status::invalid_argument("Invalid date"))?;
+    Ok(Some(month(date.date_naive())))
+}
+
+fn filter(r: &OpeningRequest) -> Result<OpeningFilter, Status> {
+    let mut pools = Vec::new();
+    for pool in &r.pools {
+        match Pool::parse(pool) {
+            Some(pool) => pools.push(pool.as_str().to_string()),
+            None => return Err(Status::invalid_argument("Unknown rating pool")),
+        }
+    }
+    if r.min_rating < 0 || r.max_rating < 0 || (r.max_rating > 0 && r.max_rating < r.min_rating) {
+        return Err(Status::invalid_argument("Invalid rating range"));
+    }
+    Ok(OpeningFilter {
+        pools,
+        min_band: (r.min_rating > 0).then(|| rating_band(r.min_rating as f64)),
+        max_band: (r.max_rating > 0).then(|| rating_band(r.max_rating as f64)),
+        since: month_of(r.since)?,
+        until: month_of(r.until)?,
+    })
+}
+
+fn percent(count: i64, games: i64) -> f64 {
+    if games == 0 {
+        0.0
+    } else {
+        100.0 * count as f64 / games as f64
+    }
+}
+
+fn to_opening(position: &Position, moves: Vec<MoveStats>) -> Opening {
+    let games = moves.iter().map(|mv| mv.games).sum();
+    let white = moves.iter().map(|mv| mv.white_wins).sum();
+    let draws = moves.iter().map(|mv| mv.draws).sum();
+    let black = moves.iter().map(|mv| mv.black_wins).sum();
+    let named = eco::lookup(position.key());
+    Opening {
+        fen: position.to_fen(),
+        eco: named.map(|named| named.eco.clone()).unwrap_or_default(),
+        name: named.map(|named| named.name.clone()).unwrap_or_default(),
+        games: games as u64,
+        white: percent(white, games),
+        draws: percent(draws, games),
+        black: percent(black, games),
+        moves: moves
+            .into_iter()
+            .map(|mv| OpeningMove {
+                games: mv.games as u64,
+                white: percent(mv.white_wins, mv.games),
+                draws: percent(mv.draws, mv.games),
+                black: percent(mv.black_wins, mv.games),
+                average_rating: (mv.rating_sum as f64 / mv.games.max(1) as f64).round() as i32,
+                uci: mv.uci,
+                san: mv.san,
+            })
+            .collect(),
+    }
+}
+
+#[tonic::async_trait]
+impl Explorer for ExplorerService {
+    async fn get_opening(
+        &self,
+        request: Request<OpeningRequest>,
+    ) -> Result<Response<Opening>, Status> {
+        log_request("GetOpening", &request);
+        authenticated_user(&request)?;
+        let r = request.into_inner();
+        let position = position(&r)?;
+        let filter = filter(&r)?;
+        let moves = query::Query::find_moves(&self.db_connection, position.key() as i64, &filter)
+            .await
+            .map_err(|_| Status::internal("Could not load opening"))?;
+        Ok(Response::new(to_opening(&position, moves)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(uci: &str, games: i64, white_wins: i64, draws: i64, rating_sum: i64) -> MoveStats {
+        MoveStats {
+            uci: uci.to_string(),
+            san: uci.to_string(),
+            games,
+            white_wins,
+            draws,
+            black_wins: games - white_wins - draws,
+            rating_sum,
+        }
+    }
+
+    #[test]
+    fn test_opening_percentages() {
+        let opening = to_opening(
+            &Position::new(),
+            vec![stats("e2e4", 3, 2, 0, 4800), stats("d2d4", 1, 0, 1, 1900)],
+        );
+        assert_eq!(opening.games, 4);
+        assert_eq!(
+            (opening.white, opening.draws, opening.black),
+            (50.0, 25.0, 25.0)
+        );
+        assert_eq!(opening.moves[0].average_rating, 1600);
+        assert_eq!(opening.moves[1].draws, 100.0);
+        let empty = to_opening(&Position::from_moves("e4 c5").unwrap().0, Vec::new());
+        assert_eq!(empty.white, 0.0);
+        assert_eq!(empty.eco, "B20");
+    }
+
+    #[test]
+    fn test_filter_rounds_to_bands() {
+        let r = OpeningRequest {
+            min_rating: 1850,
+            max_rating: 2100,
+            pools: vec!["blitz".to_string()],
+            since: 1720000000,
+            ..Default::default()
+        };
+        let bands = filter(&r).unwrap();
+        assert_eq!((bands.min_band, bands.max_band), (Some(1800), Some(2000)));
+        assert_eq!(bands.since, NaiveDate::from_ymd_opt(2024, 7, 1));
+        assert_eq!(bands.until, None);
+
+        let r = OpeningRequest {
+            pools: vec!["hyperbullet".to_string()],
+            ..Default::default()
+        };
+        assert!(filter(&r).is_err());
+        let r = OpeningRequest {
+            fen: Position::new().to_fen(),
+            moves: "e4".to_string(),
+            ..Default::default()
+        };
+        assert!(position(&r).is_err());
+    }
+}
@@ -1,15 +1,65 @@
+// tonic::Status is large, but it is the error type of every handler and interceptor
+#![allow(clippy::result_large_err)]
+mod account;
+mod analysis;
+mod archive;
 mod auth;
+mod challenge;
 mod chess;
+mod explorer;
+mod matchmaking;
+mod paging;
+mod puzzle;
+mod rate_limit;
+mod rating;
+mod search;
+mod session;
+mod spectate;
+mod stats;
+mod tournament;
 mod db {
     pub mod connector;
 }
 
-use auth::{auth_server::AuthServer, service::AuthService};
-use chess::{chess_game_server::ChessGameServer, service::ChessGameService};
+use account::{account_server::AccountServer, service::AccountService};
+use analysis::{analysis_server::AnalysisServer, service::AnalysisService};
+use archive::{game_archive_server::GameArchiveServer, service::GameArchiveService};
+use auth::{
+    auth_server::AuthServer, interceptor::AuthInterceptor, service::AuthService,
+    session::RevokedSessions,
+};
+use challenge::{
+    challenges_server::ChallengesServer,
+    service::{ChallengeHub, ChallengeService},
+};
+use chess::{
+    chess_game_server::ChessGameServer, events::GameEvents, polyglot::OpeningBook,
+    service::ChessGameService, syzygy::Tablebase, uci::EnginePool,
+};
 use db::connector::{self};
+use explorer::{explorer_server::ExplorerServer, service::ExplorerService};
+use matchmaking::{
+    matchmaking_server::MatchmakingServer,
+    service::{Matchmaker, MatchmakingService},
+};
 use migration::{Migrator, MigratorTrait};
+use puzzle::{puzzles_server::PuzzlesServer, service::PuzzleService};
+use rate_limit::{
+    client::TrustedProxies,
+    layer::{Quota, RateLimitLayer},
+    login::LoginThrottle,
+    store::{AttemptStore, DatabaseStore, MemoryStore},
+};
+use rating::{ratings_server::RatingsServer, service::RatingService};
+use search::{position_search_server::PositionSearchServer, service::PositionSearchService};
+use service::sessions::query as session_query;
+use session::{service::SessionService, sessions_server::SessionsServer};
+use spectate::{delay::DelayPolicy, service::SpectateService, spectate_server::SpectateServer};
+use stats::{player_stats_server::PlayerStatsServer, service::PlayerStatsService};
 use std::env;
+use std::sync::Arc;
 use tonic::transport::Server;
+use tournament::{service::TournamentService, tournaments_server::TournamentsServer};
 const FILE_DESCRIPTOR_SET: &[u8] = tonic::include_file_descriptor_set!("chessbicos_descriptor");
 
 #[tokio::main]
@@ -18,13 +68,152 @@ async fn start() -> Result<(), Box<dyn std::error::Error>> {
     let addr = format!("[::0]:{}", port).parse()?;
     let db = connector::db_connector().await?;
     Migrator::up(&db, None).await?;
+    chess::history::complete_backfill(&db).await?;
+    chess::eco::classify_games(&db).await?;
+    explorer::record::backfill(&db).await?;
+    stats::record::backfill(&db).await?;
+    // An external UCI engine is only used when UCI_ENGINE_PATH points at one
+    let engine = EnginePool::from_env();
+    let book = OpeningBook::from_env();
+    let tablebase = Tablebase::from_env();
+    chess::bot::ensure_bots(&db, engine.is_some()).await?;
+    let revoked_sessions =
+        RevokedSessions::new(session_query::Query::find_revoked_unexpired_session_ids(&db).await?);
+    let auth_interceptor = AuthInterceptor {
+        revoked_sessions: revoked_sessions.clone(),
+    };
+
+    // Failed logins are only counted in memory unless RATE_LIMIT_STORE=database
+    let attempt_store: Arc<dyn AttemptStore> = match env::var("RATE_LIMIT_STORE").as_deref() {
+        Ok("database") => Arc::new(DatabaseStore {
+            db_connection: db.clone(),
+        }),
+        _ => Arc::new(MemoryStore::default()),
+    };
+    // Only these may tell the client's address in x-forwarded-for
+    let trusted_proxies = TrustedProxies::from_env();
+    let rate_limit_layer = RateLimitLayer::new()
+        .trusted_proxies(trusted_proxies.clone())
+        .limit(
+            "/chessgame.ChessGame/MovePiece",
+            Quota {
+                burst: 10,
+                per_second: 5.0,
+            },
+        )
+        .limit(
+            "/matchmaking.Matchmaking/Seek",
+            Quota {
+                burst: 5,
+                per_second: 1.0,
+            },
+        )
+        .limit(
+            "/challenge.Challenges/CreateChallenge",
+            Quota {
+                burst: 5,
+                per_second: 0.2,
+            },
+        )
+        .limit(
+            "/analysis.Analysis/Analyze",
+            Quota {
+                burst: 5,
+                per_second: 0.5,
+            },
+        )
+        .limit(
+            "/auth.Auth/Login",
+            Quota {
+                burst: 10,
+                per_second: 1.0,
+            },
+        )
+        .limit(
+            "/auth.Auth/Register",
+            Quota {
+                burst: 5,
+                per_second: 0.1,
+            },
+        );
 
     // Cloning is not a problem, https://github.com/SeaQL/sea-orm/discussions/2198#discussioncomment-9119481
+    let login_throttle = LoginThrottle::new(attempt_store);
     let auth_service = AuthService {
         db_connection: db.clone(),
+        login_throttle: login_throttle.clone(),
+        revoked_sessions: revoked_sessions.clone(),
+        trusted_proxies,
     };
+    let game_events = GameEvents::default();
+    tokio::spawn(chess::clock::run(db.clone(), game_events.clone()));
     let chess_game_service = ChessGameService {
         db_connection: db.clone(),
+        events: game_events.clone(),
+        engine: engine.clone(),
+        book: book.clone(),
+        tablebase: tablebase.clone(),
+    };
+    let spectate_service = SpectateService {
+        db_connection: db.clone(),
+        events: game_events.clone(),
+        delay: DelayPolicy::from_env(),
+    };
+    let matchmaker = Matchmaker::default();
+    tokio::spawn(matchmaker.clone().run(db.clone()));
+    let account_service = AccountService {
+        db_connection: db.clone(),
+        revoked_sessions: revoked_sessions.clone(),
+        login_throttle,
+        events: game_events.clone(),
+        matchmaker: matchmaker.clone(),
+    };
+    let matchmaking_service = MatchmakingService {
+        db_connection: db.clone(),
+        matchmaker,
+    };
+    let challenge_hub = ChallengeHub::default();
+    tokio::spawn(challenge_hub.clone().run_expiry(db.clone()));
+    let challenge_service = ChallengeService {
+        db_connection: db.clone(),
+        hub: challenge_hub,
+        events: game_events,
+        engine: engine.clone(),
+        book: book.clone(),
+        tablebase: tablebase.clone(),
+    };
+    tokio::spawn(analysis::worker::run(db.clone(), engine.clone()));
+    let analysis_service = AnalysisService {
+        db_connection: db.clone(),
+        engine,
+        book,
+        tablebase,
+    };
+    tokio::spawn(tournament::director::run(db.clone()));
+    let tournament_service = TournamentService {
+        db_connection: db.clone(),
+    };
+    let explorer_service = ExplorerService {
+        db_connection: db.clone(),
+    };
+    let puzzle_service = PuzzleService {
+        db_connection: db.clone(),
+    };
+    let game_archive_service = GameArchiveService {
+        db_connection: db.clone(),
+    };
+    let position_search_service = PositionSearchService {
+        db_connection: db.clone(),
+    };
+    let player_stats_service = PlayerStatsService {
+        db_connection: db.clone(),
+    };
+    let rating_service = RatingService {
+        db_connection: db.clone(),
+    };
+    let session_service = SessionService {
+        db_connection: db.clone(),
+        revoked_sessions,
     };
 
     let reflection_service = tonic_reflection::server::Builder::configure()
@@ -33,17 +222,91 @@ async fn start() -> Result<(), Box<dyn std::error::Error>> {
         .unwrap();
 
     Server::builder()
+        .layer(rate_limit_layer)
         .add_service(reflection_service)
-        .add_service(ChessGameServer::new(chess_game_service))
-        .add_service(AuthServer::new(auth_service))        
+        .add_service(ChessGameServer::with_interceptor(
+            chess_game_service,
+            auth_interceptor.clone(),
+        ))
+        .add_service(AccountServer::with_interceptor(
+            account_service,
+            auth_interceptor.clone(),
+        ))
+        .add_service(SessionsServer::with_interceptor(
+            session_service,
+            auth_interceptor.clone(),
+        ))
+        .add_service(RatingsServer::with_interceptor(
+            rating_service,
+            auth_interceptor.clone(),
+        ))
+        .add_service(MatchmakingServer::with_interceptor(
+            matchmaking_service,
+            auth_interceptor.clone(),
+        ))
+        .add_service(ChallengesServer::with_interceptor(
+            challenge_service,
+            auth_interceptor.clone(),
+        ))
+        .add_service(SpectateServer::with_interceptor(
+            spectate_service,
+            auth_interceptor.clone(),
+        ))
+        .add_service(TournamentsServer::with_interceptor(
+            tournament_service,
+            auth_interceptor.clone(),
+        ))
+        .add_service(AnalysisServer::with_interceptor(
+            analysis_service,
+            auth_interceptor.clone(),
+        ))
+        .add_service(ExplorerServer::with_interceptor(
+            explorer_service,
+            auth_interceptor.clone(),
+        ))
+        .add_service(PuzzlesServer::with_interceptor(
+            puzzle_service,
+            auth_interceptor.clone(),
+        ))
+        .add_service(PositionSearchServer::with_interceptor(
+            position_search_service,
+            auth_interceptor.clone(),
+        ))
+        .add_service(GameArchiveServer::with_interceptor(
+            game_archive_service,
+            auth_interceptor.clone(),
+        ))
+        .add_service(PlayerStatsServer::with_interceptor(
+            player_stats_service,
+            auth_interceptor,
+        ))
+        .add_service(AuthServer::new(auth_service))
         .serve(addr)
         .await?;
 
     Ok(())
 }
 
+// Writes a Polyglot book made from the stored games to the path given after `build-book`
+#[tokio::main]
+async fn build_book() -> Result<(), Box<dyn std::error::Error>> {
+    let path = env::args()
+        .nth(2)
+        .ok_or("Usage: chessbicos-server build-book <path>")?;
+    let keys_path = env::var("POLYGLOT_KEYS_PATH").map_err(|_| "POLYGLOT_KEYS_PATH is not set")?;
+    let keys = chess::polyglot::Keys::load(&keys_path)?;
+    let db = connector::db_connector().await?;
+    let book = chess::polyglot::build_from_games(&db, &keys).await?;
+    std::fs::write(&path, book.to_bytes())?;
+    println!("Wrote {} entries to {path}", book.len());
+    Ok(())
+}
+
 pub fn main() {
-    let result = start();
+    let result = match env::args().nth(1).as_deref() {
+        Some("build-book") => build_book(),
+        _ => start(),
+    };
 
     if let Some(err) = result.err() {
         println!("Error: {err}");
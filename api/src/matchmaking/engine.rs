@@ -0,0 +1,305 @@
+use std::time::{Duration, Instant};
+
+use crate::chess::pieces::Color;
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum ColorPreference {
+    Random,
+    White,
+    Black,
+}
+
+#[derive(Clone, Debug)]
+pub struct Seek {
+    pub id: u64,
+    pub user_id: i32,
+    pub time_control: i32,
+    pub rated: bool,
+    pub rating: f64,
+    pub color: ColorPreference,
+    // Games played as white minus games played as black, over the last few games
+    pub color_balance: i32,
+    pub created_at: Instant,
+}
+
+// Accepted rating difference, widening the longer a seek waits
+#[derive(Clone, Debug)]
+pub struct RangePolicy {
+    pub initial: f64,
+    pub step: f64,
+    pub every: Duration,
+    pub max: f64,
+}
+
+impl Default for RangePolicy {
+    fn default() -> Self {
+        RangePolicy {
+            initial: 100.0,
+            step: 50.0,
+            every: Duration::from_secs(5),
+            max: 500.0,
+        }
+    }
+}
+
+impl RangePolicy {
+    pub fn range(&self, waited: Duration) -> f64 {
+        let steps = waited.as_millis() / self.every.as_millis().max(1);
+        (self.initial + self.step * steps as f64).min(self.max)
+    }
+}
+
+#[derive(Debug)]
+pub struct Pairing {
+    pub white: Seek,
+    pub black: Seek,
+}
+
+#[derive(Default)]
+pub struct SeekPool {
+    pub policy: RangePolicy,
+    seeks: Vec<Seek>,
+    next_id: u64,
+}
+
+impl SeekPool {
+    // Each user has at most one seek, None if there already is one
+    pub fn add(&mut self, mut seek: Seek) -> Option<u64> {
+        if self.seeks.iter().any(|s| s.user_id == seek.user_id) {
+            return None;
+        }
+        self.next_id += 1;
+        seek.id = self.next_id;
+        self.seeks.push(seek);
+        Some(self.next_id)
+    }
+
+    pub fn cancel(&mut self, user_id: i32) -> Option<Seek> {
+        let index = self.seeks.iter().position(|s| s.user_id == user_id)?;
+        Some(self.seeks.remove(index))
+    }
+
+    pub fn remove(&mut self, id: u64) -> Option<Seek> {
+        let index = self.seeks.iter().position(|s| s.id == id)?;
+        Some(self.seeks.remove(index))
+    }
+
+    // Puts back a seek taken by `find_pairings`, keeping its id and its place by age
+    pub fn restore(&mut self, seek: Seek) {
+        let index = self
+            .seeks
+            .iter()
+            .position(|s| s.created_at > seek.created_at)
+            .unwrap_or(self.seeks.len());
+        self.seeks.insert(index, seek);
+    }
+
+    // Oldest seeks are paired first, each with the closest rated compatible opponent.
+    // `coin` settles the colors when nothing else does.
+    pub fn find_pairings(&mut self, now: Instant, mut coin: impl FnMut() -> bool) -> Vec<Pairing> {
+        let mut pairings = Vec::new();
+        let mut i = 0;
+        while i < self.seeks.len() {
+            let seek = &self.seeks[i];
+            let opponent = self
+                .seeks
+                .iter()
+                .enumerate()
+                .skip(i + 1)
+                .filter(|(_, other)| self.compatible(seek, other, now))
+                .min_by(|(_, a), (_, b)| {
+                    let da = (a.rating - seek.rating).abs();
+                    let db = (b.rating - seek.rating).abs();
+                    da.total_cmp(&db)
+                })
+                .map(|(j, _)| j);
+            match opponent {
+                Some(j) => {
+                    let b = self.seeks.remove(j);
+                    let a = self.seeks.remove(i);
+                    let (white, black) = match assign_colors(&a, &b, &mut coin) {
+                        Some(Color::White) => (a, b),
+                        _ => (b, a),
+                    };
+                    pairings.push(Pairing { white, black });
+                }
+                None => i += 1,
+            }
+        }
+        pairings
+    }
+
+    fn compatible(&self, a: &Seek, b: &Seek, now: Instant) -> bool {
+        if a.user_id == b.user_id || a.time_control != b.time_control || a.rated != b.rated {
+            return false;
+        }
+        if a.color != ColorPreference::Random && a.color == b.color {
+            return false;
+        }
+        // Both players have to accept the difference
+        let difference = (a.rating - b.rating).abs();
+        let range_a = self
+            .policy
+            .range(now.saturating_duration_since(a.created_at));
+        let range_b = self
+            .policy
+            .range(now.saturating_duration_since(b.created_at));
+        difference <= range_a.min(range_b)
+    }
+}
+
+// The color `a` plays. Explicit preferences win, then whoever played white less often
+// recently gets white.
+fn assign_colors(a: &Seek, b: &Seek, coin: &mut impl FnMut() -> bool) -> Option<Color> {
+    match (a.color, b.color) {
+        (ColorPreference::White, ColorPreference::White)
+        | (ColorPreference::Black, ColorPreference::Black) => None,
+        (ColorPreference::White, _) | (_, ColorPreference::Black) => Some(Color::White),
+        (ColorPreference::Black, _) | (_, ColorPreference::White) => Some(Color::Black),
+        (ColorPreference::Random, ColorPreference::Random) => {
+            if a.color_balance < b.color_balance {
+                Some(Color::White)
+            } else if a.color_balance > b.color_balance {
+                Some(Color::Black)
+            } else if coin() {
+                Some(Color::White)
+            } else {
+                Some(Color::Black)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seek(user_id: i32, rating: f64, created_at: Instant) -> Seek {
+        Seek {
+            id: 0,
+            user_id,
+            time_control: 1,
+            rated: true,
+            rating,
+            color: ColorPreference::Random,
+            color_balance: 0,
+            created_at,
+        }
+    }
+
+    #[test]
+    fn test_range_widens_over_time() {
+        let policy = RangePolicy::default();
+        assert_eq!(policy.range(Duration::from_secs(0)), 100.0);
+        assert_eq!(policy.range(Duration::from_secs(4)), 100.0);
+        assert_eq!(policy.range(Duration::from_secs(12)), 200.0);
+        assert_eq!(policy.range(Duration::from_secs(600)), 500.0);
+    }
+
+    #[test]
+    fn test_one_seek_per_user() {
+        let now = Instant::now();
+        let mut pool = SeekPool::default();
+        assert_eq!(pool.add(seek(1, 1500.0, now)), Some(1));
+        assert_eq!(pool.add(seek(1, 1500.0, now)), None);
+        assert_eq!(pool.add(seek(2, 1500.0, now)), Some(2));
+        assert_eq!(pool.cancel(1).map(|s| s.id), Some(1));
+        assert_eq!(pool.cancel(1).map(|s| s.id), None);
+        assert_eq!(pool.seeks.len(), 1);
+    }
+
+    #[test]
+    fn test_pairs_closest_rating_within_range() {
+        let now = Instant::now();
+        let mut pool = SeekPool::default();
+        pool.add(seek(1, 1500.0, now));
+        pool.add(seek(2, 1590.0, now));
+        pool.add(seek(3, 1520.0, now));
+        pool.add(seek(4, 2000.0, now));
+        let pairings = pool.find_pairings(now, || true);
+        assert_eq!(pairings.len(), 1);
+        let mut users = [pairings[0].white.user_id, pairings[0].black.user_id];
+        users.sort();
+        assert_eq!(users, [1, 3]);
+        assert_eq!(pool.seeks.len(), 2);
+    }
+
+    #[test]
+    fn test_restored_seek_keeps_its_place() {
+        let start = Instant::now();
+        let mut pool = SeekPool::default();
+        pool.add(seek(1, 1500.0, start));
+        pool.add(seek(2, 1500.0, start + Duration::from_secs(1)));
+        pool.add(seek(3, 2500.0, start + Duration::from_secs(2)));
+        let pairing = pool.find_pairings(start, || true).remove(0);
+        let first = match pairing.white.user_id {
+            1 => pairing.white,
+            _ => pairing.black,
+        };
+        pool.restore(first);
+        let users: Vec<(u64, i32)> = pool.seeks.iter().map(|s| (s.id, s.user_id)).collect();
+        assert_eq!(users, vec![(1, 1), (3, 3)]);
+    }
+
+    #[test]
+    fn test_range_widening_allows_pairing() {
+        let start = Instant::now();
+        let mut pool = SeekPool::default();
+        pool.add(seek(1, 1500.0, start));
+        pool.add(seek(2, 1750.0, start));
+        assert!(pool.find_pairings(start, || true).is_empty());
+        let later = start + Duration::from_secs(20);
+        assert_eq!(pool.find_pairings(later, || true).len(), 1);
+    }
+
+    #[test]
+    fn test_incompatible_seeks() {
+        let now = Instant::now();
+        let mut pool = SeekPool::default();
+        pool.add(seek(1, 1500.0, now));
+        let mut casual = seek(2, 1500.0, now);
+        casual.rated = false;
+        pool.add(casual);
+        let mut other_time_control = seek(3, 1500.0, now);
+        other_time_control.time_control = 2;
+        pool.add(other_time_control);
+        assert!(pool.find_pairings(now, || true).is_empty());
+
+        let mut pool = SeekPool::default();
+        let mut white = seek(1, 1500.0, now);
+        white.color = ColorPreference::White;
+        let mut also_white = seek(2, 1500.0, now);
+        also_white.color = ColorPreference::White;
+        pool.add(white);
+        pool.add(also_white);
+        assert!(pool.find_pairings(now, || true).is_empty());
+    }
+
+    #[test]
+    fn test_color_assignment() {
+        let now = Instant::now();
+        let mut pool = SeekPool::default();
+        let mut black = seek(1, 1500.0, now);
+        black.color = ColorPreference::Black;
+        pool.add(black);
+        pool.add(seek(2, 1500.0, now));
+        let pairings = pool.find_pairings(now, || true);
+        assert_eq!(pairings[0].white.user_id, 2);
+        assert_eq!(pairings[0].black.user_id, 1);
+
+        // Played white more often recently
+        let mut pool = SeekPool::default();
+        let mut often_white = seek(1, 1500.0, now);
+        often_white.color_balance = 3;
+        pool.add(often_white);
+        pool.add(seek(2, 1500.0, now));
+        let pairings = pool.find_pairings(now, || true);
+        assert_eq!(pairings[0].white.user_id, 2);
+
+        let mut pool = SeekPool::default();
+        pool.add(seek(1, 1500.0, now));
+        pool.add(seek(2, 1500.0, now));
+        let pairings = pool.find_pairings(now, || false);
+        assert_eq!(pairings[0].white.user_id, 2);
+    }
+}
@@ -0,0 +1,3 @@
+pub mod engine;
+pub mod service;
+tonic::include_proto!("matchmaking"); // The string specified here must match the proto package name
@@ -0,0 +1,315 @@
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use sea_orm::DatabaseConnection;
+use tokio::sync::{mpsc, Notify};
+use tokio_stream::{wrappers::ReceiverStream, Stream};
+use tonic::{Request, Response, Status};
+
+use service::{
+    game::query as game_query,
+    ratings::{glicko2::Glicko2, pool::Pool, query as rating_query},
+    time_control::query as time_control_query,
+    users::query as user_query,
+};
+
+use super::{
+    engine::{self, Pairing, Seek, SeekPool},
+    matchmaking_server::Matchmaking,
+    seek_event::Event,
+    CancelSeekRequest, CancelSeekResponse, Cancelled, ColorPreference, Matched, Queued, SeekEvent,
+    SeekRequest,
+};
+use crate::auth::interceptor::{authenticated_user, log_request};
+use crate::chess::{lifecycle, pieces::Color};
+
+// Recent games looked at when balancing colors
+const COLOR_HISTORY: u64 = 20;
+// Seeks are paired again this often, as their rating ranges widen
+const MATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+type EventSender = mpsc::Sender<Result<SeekEvent, Status>>;
+
+fn event(event: Event) -> Result<SeekEvent, Status> {
+    Ok(SeekEvent { event: Some(event) })
+}
+
+#[derive(Default)]
+struct Queue {
+    pool: SeekPool,
+    senders: HashMap<u64, EventSender>,
+}
+
+#[derive(Clone, Default)]
+pub struct Matchmaker {
+    queue: Arc<Mutex<Queue>>,
+    wake: Arc<Notify>,
+}
+
+impl Matchmaker {
+    // A new seek replaces the user's previous one
+    fn add(&self, seek: Seek, sender: EventSender) -> Result<(), Status> {
+        let user_id = seek.user_id;
+        let mut queue = self
+            .queue
+            .lock()
+            .map_err(|_| Status::internal("Matchmaking is unavailable"))?;
+        if let Some(previous) = queue.pool.cancel(user_id) {
+            if let Some(previous_sender) = queue.senders.remove(&previous.id) {
+                let _ = previous_sender.try_send(event(Event::Cancelled(Cancelled {})));
+            }
+        }
+        let seek_id = match queue.pool.add(seek) {
+            Some(seek_id) => seek_id,
+            None => return Err(Status::already_exists("Already seeking a game")),
+        };
+        let _ = sender.try_send(event(Event::Queued(Queued { seek_id })));
+        queue.senders.insert(seek_id, sender);
+        drop(queue);
+        self.wake.notify_one();
+        Ok(())
+    }
+
+    pub fn cancel(&self, user_id: i32) -> bool {
+        let mut queue = match self.queue.lock() {
+            Ok(queue) => queue,
+            Err(_) => return false,
+        };
+        match queue.pool.cancel(user_id) {
+            Some(seek) => {
+                if let Some(sender) = queue.senders.remove(&seek.id) {
+                    let _ = sender.try_send(event(Event::Cancelled(Cancelled {})));
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn take_pairings(&self) -> Vec<(Pairing, EventSender, EventSender)> {
+        let mut queue = match self.queue.lock() {
+            Ok(queue) => queue,
+            Err(_) => return Vec::new(),
+        };
+        // Clients that went away without cancelling
+        let closed: Vec<u64> = queue
+            .senders
+            .iter()
+            .filter(|(_, sender)| sender.is_closed())
+            .map(|(id, _)| *id)
+            .collect();
+        for id in closed {
+            queue.senders.remove(&id);
+            queue.pool.remove(id);
+        }
+        let pairings = queue
+            .pool
+            .find_pairings(Instant::now(), rand::random::<bool>);
+        let mut taken = Vec::new();
+        for pairing in pairings {
+            let white = queue.senders.remove(&pairing.white.id);
+            let black = queue.senders.remove(&pairing.black.id);
+            match (white, black) {
+                (Some(white), Some(black)) => taken.push((pairing, white, black)),
+                // The player still listening keeps waiting, the other seek is dropped
+                (white, black) => {
+                    for (seek, sender) in [(pairing.white, white), (pairing.black, black)] {
+                        if let Some(sender) = sender {
+                            queue.senders.insert(seek.id, sender);
+                            queue.pool.restore(seek);
+                        }
+                    }
+                }
+            }
+        }
+        taken
+    }
+
+    // Pairs seeks as soon as one is added, and periodically as the rating ranges widen
+    pub async fn run(self, db: DatabaseConnection) {
+        let mut interval = tokio::time::interval(MATCH_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = self.wake.notified() => {}
+            }
+            for (pairing, white, black) in self.take_pairings() {
+                start_game(&db, pairing, white, black).await;
+            }
+        }
+    }
+}
+
+async fn username(db: &DatabaseConnection, user_id: i32) -> String {
+    match user_query::Query::find_user_by_id(db, user_id).await {
+        Ok(Some(user)) => user.username,
+        _ => String::new(),
+    }
+}
+
+async fn start_game(
+    db: &DatabaseConnection,
+    pairing: Pairing,
+    white: EventSender,
+    black: EventSender,
+) {
+    let game_row = match lifecycle::start_game(
+        db,
+        pairing.white.user_id,
+        pairing.black.user_id,
+        pairing.white.time_control,
+        pairing.white.rated,
+        None,
+    )
+    .await
+    {
+        Ok(game_row) => game_row,
+        Err(status) => {
+            let _ = white.send(Err(status.clone())).await;
+            let _ = black.send(Err(status)).await;
+            return;
+        }
+    };
+    let matched = |color: Color, opponent: &Seek, name: String| {
+        event(Event::Matched(Matched {
+            match_id: game_row.id.to_string(),
+            color: color.as_str().to_string(),
+            opponent: name,
+            opponent_rating: opponent.rating,
+        }))
+    };
+    let white_name = username(db, pairing.white.user_id).await;
+    let black_name = username(db, pairing.black.user_id).await;
+    let _ = white
+        .send(matched(Color::White, &pairing.black, black_name))
+        .await;
+    let _ = black
+        .send(matched(Color::Black, &pairing.white, white_name))
+        .await;
+}
+
+pub struct MatchmakingService {
+    pub db_connection: DatabaseConnection,
+    pub matchmaker: Matchmaker,
+}
+
+impl MatchmakingService {
+    async fn color_balance(&self, user_id: i32) -> Result<i32, Status> {
+        let games = game_query::Query::find_recent_games_by_user(
+            &self.db_connection,
+            user_id,
+            COLOR_HISTORY,
+        )
+        .await
+        .map_err(|_| Status::internal("Could not load games"))?;
+        Ok(games
+            .iter()
+            .map(|game| if game.player_white == user_id { 1 } else { -1 })
+            .sum())
+    }
+}
+
+#[tonic::async_trait]
+impl Matchmaking for MatchmakingService {
+    type SeekStream = Pin<Box<dyn Stream<Item = Result<SeekEvent, Status>> + Send>>;
+
+    async fn seek(
+        &self,
+        request: Request<SeekRequest>,
+    ) -> Result<Response<Self::SeekStream>, Status> {
+        log_request("Seek", &request);
+        let user = authenticated_user(&request)?;
+        let r = request.into_inner();
+        let color = match ColorPreference::try_from(r.color) {
+            Ok(ColorPreference::Random) => engine::ColorPreference::Random,
+            Ok(ColorPreference::White) => engine::ColorPreference::White,
+            Ok(ColorPreference::Black) => engine::ColorPreference::Black,
+            Err(_) => return Err(Status::invalid_argument("Invalid color preference")),
+        };
+        let time_control = time_control_query::Query::find_time_control_by_id(
+            &self.db_connection,
+            r.time_control_id,
+        )
+        .await
+        .map_err(|_| Status::internal("Could not load time control"))?
+        .ok_or_else(|| Status::invalid_argument("Unknown time control"))?;
+        let pool = Pool::from_time(time_control.time);
+        let rating = rating_query::Query::find_rating(&self.db_connection, user.id, pool)
+            .await
+            .map_err(|_| Status::internal("Could not load rating"))?
+            .map(|rating| Glicko2::from(&rating))
+            .unwrap_or_default();
+        let color_balance = self.color_balance(user.id).await?;
+
+        let (sender, receiver) = mpsc::channel(4);
+        self.matchmaker.add(
+            Seek {
+                id: 0,
+                user_id: user.id,
+                time_control: time_control.id,
+                rated: r.rated,
+                rating: rating.rating,
+                color,
+                color_balance,
+                created_at: Instant::now(),
+            },
+            sender,
+        )?;
+        Ok(Response::new(Box::pin(ReceiverStream::new(receiver))))
+    }
+
+    async fn cancel_seek(
+        &self,
+        request: Request<CancelSeekRequest>,
+    ) -> Result<Response<CancelSeekResponse>, Status> {
+        log_request("CancelSeek", &request);
+        let user = authenticated_user(&request)?;
+        let cancelled = self.matchmaker.cancel(user.id);
+        Ok(Response::new(CancelSeekResponse { cancelled }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seek(user_id: i32) -> Seek {
+        Seek {
+            id: 0,
+            user_id,
+            time_control: 1,
+            rated: true,
+            rating: 1500.0,
+            color: engine::ColorPreference::Random,
+            color_balance: 0,
+            created_at: Instant::now(),
+        }
+    }
+
+    #[test]
+    fn test_seek_without_opponent_sender_is_kept() {
+        let matchmaker = Matchmaker::default();
+        let (sender, mut receiver) = mpsc::channel(4);
+        matchmaker.add(seek(1), sender).unwrap();
+        // A seek whose client is not known to the queue
+        matchmaker.queue.lock().unwrap().pool.add(seek(2));
+        assert!(matchmaker.take_pairings().is_empty());
+        let queue = matchmaker.queue.lock().unwrap();
+        assert!(queue.senders.contains_key(&1));
+        drop(queue);
+        assert!(matches!(
+            receiver.try_recv().unwrap().unwrap().event,
+            Some(Event::Queued(_))
+        ));
+
+        let (sender, _receiver) = mpsc::channel(4);
+        matchmaker.add(seek(3), sender).unwrap();
+        let pairings = matchmaker.take_pairings();
+        assert_eq!(pairings.len(), 1);
+        let mut users = [pairings[0].0.white.user_id, pairings[0].0.black.user_id];
+        users.sort();
+        assert_eq!(users, [1, 3]);
+    }
+}
@@ -0,0 +1,27 @@
+// Page sizes of the endpoints that list games page by page
+use tonic::Status;
+
+pub const DEFAULT_PAGE_SIZE: i32 = 20;
+pub const MAX_PAGE_SIZE: i32 = 100;
+
+// The page size asked for, the default when it is 0
+pub fn page_size(requested: i32) -> Result<i32, Status> {
+    match requested {
+        0 => Ok(DEFAULT_PAGE_SIZE),
+        size if (1..=MAX_PAGE_SIZE).contains(&size) => Ok(size),
+        _ => Err(Status::invalid_argument("Invalid page size")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_size() {
+        assert_eq!(page_size(0).unwrap(), DEFAULT_PAGE_SIZE);
+        assert_eq!(page_size(MAX_PAGE_SIZE).unwrap(), MAX_PAGE_SIZE);
+        assert!(page_size(MAX_PAGE_SIZE + 1).is_err());
+        assert!(page_size(-1).is_err());
+    }
+}
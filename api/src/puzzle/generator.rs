@@ -0,0 +1,310 @@
+// Puzzles mined from the blunders game analysis finds. A blunder that leaves the opponent
+// winning makes a puzzle: the position before it, the blunder, then the winning line the engine
+// finds for as long as each of the solver's moves is the only good one. Lines that never get
+// past the first move are dropped.
+use std::time::Duration;
+
+use sea_orm::DatabaseConnection;
+use tonic::Status;
+
+use entity::entities::{game, puzzle};
+use service::{puzzles::mutation, ratings::glicko2::Glicko2};
+
+use super::solution::Solution;
+use crate::analysis::report::{self, Classification, Evaluation};
+use crate::chess::{
+    engine::{
+        position::{Move, Position},
+        search::{is_mate_score, Limits, Search, SearchInfo},
+    },
+    pieces::Kind,
+    uci::Score,
+};
+
+// Centipawns for the side to move
+const WINNING: i32 = 300;
+const CRUSHING: i32 = 600;
+// How much worse the second best move has to be for the best one to be the only move
+const ONLY_MOVE_MARGIN: i32 = 200;
+const MAX_SOLVER_MOVES: usize = 4;
+// Before this ply a puzzle is from the opening
+const OPENING_PLIES: usize = 20;
+// With at most this many pieces other than kings and pawns it is from the endgame
+const ENDGAME_PIECES: usize = 4;
+const TT_ENTRIES: usize = 1 << 18;
+const LIMITS: Limits = Limits {
+    depth: Some(12),
+    nodes: Some(500_000),
+    time: Some(Duration::from_secs(1)),
+    noise: 0,
+};
+
+pub const THEMES: [&str; 13] = [
+    "mate",
+    "mateIn1",
+    "mateIn2",
+    "mateIn3",
+    "mateIn4",
+    "crushing",
+    "advantage",
+    "oneMove",
+    "short",
+    "long",
+    "opening",
+    "middlegame",
+    "endgame",
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Candidate {
+    // Index of the blunder in the game's moves
+    pub ply: usize,
+    pub solution: Solution,
+    pub themes: Vec<&'static str>,
+}
+
+fn is_winning(score: Score) -> bool {
+    match score {
+        Score::Centipawns(centipawns) => centipawns >= WINNING,
+        Score::Mate(moves) => moves > 0,
+    }
+}
+
+fn is_losing(score: Score) -> bool {
+    match score {
+        Score::Centipawns(centipawns) => centipawns <= -WINNING,
+        Score::Mate(moves) => moves <= 0,
+    }
+}
+
+// Blunders after which the opponent is winning, by players who were not lost already
+fn blunders(moves: &[Move], evaluations: &[Evaluation]) -> Vec<usize> {
+    report::build(moves, evaluations)
+        .moves
+        .iter()
+        .enumerate()
+        .filter(|&(ply, mv)| {
+            mv.classification == Classification::Blunder
+                && !is_losing(evaluations[ply].score)
+                && is_winning(evaluations[ply + 1].score)
+        })
+        .map(|(ply, _)| ply)
+        .collect()
+}
+
+fn only_move(infos: &[SearchInfo]) -> bool {
+    let best = infos[0].score;
+    match infos.get(1) {
+        None => true,
+        // Any mate is accepted when solving, so another one would do as well
+        Some(second) if is_mate_score(best) => !(is_mate_score(second.score) && second.score > 0),
+        Some(second) => second.score < WINNING && best - second.score >= ONLY_MOVE_MARGIN,
+    }
+}
+
+// The solver's line from `position` with the opponent's replies, ending with a solver's move,
+// and the score of the first move. None when the first move is not the only winning one.
+fn solve(search: &mut Search, position: &Position) -> Option<(Vec<Move>, i32)> {
+    let mut line = Vec::new();
+    let mut score = None;
+    let mut position = position.clone();
+    while line.len() < 2 * MAX_SOLVER_MOVES - 1 {
+        let infos = search.run_multipv(&position, &[], LIMITS, 2, &mut |_| {});
+        match infos.first() {
+            Some(best) if best.score >= WINNING && only_move(&infos) => {
+                score.get_or_insert(best.score);
+                let mv = best.best()?;
+                line.push(mv);
+                position = position.play(mv);
+            }
+            _ => break,
+        }
+        if position.legal_moves().is_empty() || line.len() == 2 * MAX_SOLVER_MOVES - 1 {
+            break;
+        }
+        let reply = search.run(&position, &[], LIMITS, &mut |_| {})?.best()?;
+        line.push(reply);
+        position = position.play(reply);
+    }
+    // A reply with no only move after it is left out
+    if line.len() % 2 == 0 {
+        line.pop();
+    }
+    Some((line, score?))
+}
+
+fn themes(ply: usize, solution: &Solution, score: i32) -> Vec<&'static str> {
+    let mut themes = Vec::new();
+    let mut end = solution.start.clone();
+    for &mv in &solution.moves {
+        end = end.play(mv);
+    }
+    let solver_moves = solution.moves.len() / 2;
+    if end.in_check() && end.legal_moves().is_empty() {
+        themes.push("mate");
+        themes.push(THEMES[solver_moves]);
+    } else if score >= CRUSHING {
+        themes.push("crushing");
+    } else {
+        themes.push("advantage");
+    }
+    themes.push(match solver_moves {
+        1 => "oneMove",
+        2 => "short",
+        _ => "long",
+    });
+    let pieces = (0..64)
+        .filter_map(|square| solution.start.piece_at(square))
+        .filter(|piece| !matches!(piece.kind, Kind::King | Kind::Pawn))
+        .count();
+    themes.push(if pieces <= ENDGAME_PIECES {
+        "endgame"
+    } else if ply < OPENING_PLIES {
+        "opening"
+    } else {
+        "middlegame"
+    });
+    themes
+}
+
+// `evaluations` has one entry per position of the game, as analysis makes them. Searches, so
+// this is slow.
+pub fn find(moves: &[Move], evaluations: &[Evaluation]) -> Vec<Candidate> {
+    let positions = report::positions(moves);
+    let mut search = Search::new(TT_ENTRIES);
+    blunders(moves, evaluations)
+        .into_iter()
+        .filter_map(|ply| {
+            let (line, score) = solve(&mut search, &positions[ply + 1])?;
+            let mut solution_moves = vec![moves[ply]];
+            solution_moves.extend(line);
+            let solution = Solution {
+                start: positions[ply].clone(),
+                moves: solution_moves,
+            };
+            Some(Candidate {
+                ply,
+                themes: themes(ply, &solution, score),
+                solution,
+            })
+        })
+        .collect()
+}
+
+// Adds the puzzles from an analysed game, returns how many were found
+pub async fn mine(
+    db: &DatabaseConnection,
+    game_row: &game::Model,
+    moves: Vec<Move>,
+    evaluations: Vec<Evaluation>,
+) -> Result<usize, Status> {
+    let candidates = tokio::task::spawn_blocking(move || find(&moves, &evaluations))
+        .await
+        .map_err(|_| Status::internal("Engine failed"))?;
+    let initial = Glicko2::default();
+    for candidate in &candidates {
+        mutation::Mutation::create_puzzle(
+            db,
+            puzzle::Model {
+                id: 0,
+                game_id: Some(game_row.id),
+                // Numbered from 1 like the moves in `game_move`
+                ply: Some(candidate.ply as i32 + 1),
+                fen: candidate.solution.start.to_fen(),
+                solution: Solution::to_uci(&candidate.solution.moves),
+                themes: candidate.themes.join(" "),
+                rating: initial.rating,
+                deviation: initial.deviation,
+                volatility: initial.volatility,
+                plays: 0,
+                created_at: Default::default(),
+            },
+        )
+        .await
+        .map_err(|_| Status::internal("Could not store puzzle"))?;
+    }
+    Ok(candidates.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::report::Evaluation;
+
+    fn evaluations(positions: &[Position], scores: &[Score]) -> Vec<Evaluation> {
+        positions
+            .iter()
+            .zip(scores)
+            .map(|(_, &score)| Evaluation { score, best: None })
+            .collect()
+    }
+
+    #[test]
+    fn test_blunders_that_leave_the_opponent_winning() {
+        // 1. e4 e5 2. Bc4 Nc6 3. Qh5 Nf6?? 4. Qxf7#
+        let moves = Position::parse_game("e4 e5 Bc4 Nc6 Qh5 Nf6 Qxf7#").unwrap();
+        let positions = report::positions(&moves);
+        let scores = [
+            Score::Centipawns(30),
+            Score::Centipawns(-30),
+            Score::Centipawns(30),
+            Score::Centipawns(-20),
+            Score::Centipawns(0),
+            Score::Centipawns(-20),
+            Score::Mate(1),
+            Score::Mate(0),
+        ];
+        let evaluations = evaluations(&positions, &scores);
+        assert_eq!(blunders(&moves, &evaluations), vec![5]);
+
+        let candidates = find(&moves, &evaluations);
+        assert_eq!(candidates.len(), 1);
+        let candidate = &candidates[0];
+        assert_eq!(candidate.solution.san(), vec!["Nf6", "Qxf7#"]);
+        assert_eq!(
+            candidate.themes,
+            vec!["mate", "mateIn1", "oneMove", "opening"]
+        );
+    }
+
+    #[test]
+    fn test_lost_positions_are_not_puzzles() {
+        // Black was getting mated anyway
+        let moves = Position::parse_game("e4 e5 Bc4 Nc6 Qh5 Nf6 Qxf7#").unwrap();
+        let positions = report::positions(&moves);
+        let mut scores = vec![Score::Centipawns(0); 5];
+        scores.extend([Score::Mate(-3), Score::Mate(1), Score::Mate(0)]);
+        assert!(blunders(&moves, &evaluations(&positions, &scores)).is_empty());
+    }
+
+    #[test]
+    fn test_only_moves() {
+        let info = |score| SearchInfo {
+            depth: 1,
+            score,
+            nodes: 0,
+            pv: Vec::new(),
+        };
+        assert!(only_move(&[info(900)]));
+        assert!(only_move(&[info(900), info(100)]));
+        assert!(!only_move(&[info(900), info(400)]));
+        assert!(!only_move(&[info(350), info(200)]));
+        let mate = crate::chess::engine::search::MATE - 3;
+        assert!(only_move(&[info(mate), info(900)]));
+        assert!(!only_move(&[info(mate), info(mate - 2)]));
+    }
+
+    #[test]
+    fn test_solve_stops_without_an_only_move() {
+        let mut search = Search::new(1 << 12);
+        // Only Rxd8 wins back the rook and the queen
+        let position = Position::from_fen("3q2k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1").unwrap();
+        let (line, score) = solve(&mut search, &position).unwrap();
+        assert_eq!(position.san(line[0]), "Rxd8#");
+        assert_eq!(line.len(), 1);
+        assert!(is_mate_score(score));
+        // Nothing wins here
+        let position = Position::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert!(solve(&mut search, &position).is_none());
+    }
+}
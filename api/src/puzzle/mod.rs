@@ -0,0 +1,4 @@
+pub mod generator;
+pub mod service;
+pub mod solution;
+tonic::include_proto!("puzzle"); // The string specified here must match the proto package name
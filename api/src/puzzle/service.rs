@@ -0,0 +1,192 @@
+use chrono::Utc;
+use sea_orm::DatabaseConnection;
+use tonic::{Request, Response, Status};
+
+use entity::entities::{puzzle, puzzle_rating};
+use service::{
+    puzzles::{mutation, query},
+    ratings::glicko2::Glicko2,
+};
+
+use super::{
+    generator::THEMES,
+    puzzles_server::Puzzles,
+    solution::{Progress, Solution},
+    GetDailyPuzzleRequest, GetPuzzleRatingRequest, GetPuzzleRequest, Puzzle, PuzzleRating,
+    SubmitPuzzleMovesRequest, SubmitPuzzleMovesResponse,
+};
+use crate::auth::interceptor::{authenticated_user, log_request};
+use crate::chess::pieces::Color;
+
+// Around the player's rating first, further away when nothing is left there
+const RATING_WINDOWS: [f64; 4] = [100.0, 200.0, 400.0, 800.0];
+
+pub struct PuzzleService {
+    pub db_connection: DatabaseConnection,
+}
+
+fn solution(puzzle_row: &puzzle::Model) -> Result<Solution, Status> {
+    Solution::parse(&puzzle_row.fen, &puzzle_row.solution)
+        .ok_or_else(|| Status::internal("Invalid puzzle"))
+}
+
+fn to_puzzle(puzzle_row: &puzzle::Model) -> Result<Puzzle, Status> {
+    let solution = solution(puzzle_row)?;
+    let opponent_move = solution.moves[0];
+    Ok(Puzzle {
+        id: puzzle_row.id,
+        fen: puzzle_row.fen.clone(),
+        opponent_move: solution.start.san(opponent_move),
+        opponent_move_uci: opponent_move.uci(),
+        color: match solution.solver() {
+            Color::White => "white",
+            Color::Black => "black",
+        }
+        .to_string(),
+        themes: puzzle_row
+            .themes
+            .split_whitespace()
+            .map(str::to_string)
+            .collect(),
+        rating: puzzle_row.rating.round() as i32,
+        plays: puzzle_row.plays,
+        game_id: puzzle_row.game_id.unwrap_or_default(),
+    })
+}
+
+fn to_rating(rating_row: Option<&puzzle_rating::Model>) -> PuzzleRating {
+    match rating_row {
+        Some(rating_row) => PuzzleRating {
+            rating: rating_row.rating,
+            deviation: rating_row.deviation,
+            attempts: rating_row.attempts,
+            solved: rating_row.solved,
+            provisional: Glicko2::from(rating_row).is_provisional(),
+        },
+        None => {
+            let initial = Glicko2::default();
+            PuzzleRating {
+                rating: initial.rating,
+                deviation: initial.deviation,
+                attempts: 0,
+                solved: 0,
+                provisional: true,
+            }
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl Puzzles for PuzzleService {
+    async fn get_puzzle(
+        &self,
+        request: Request<GetPuzzleRequest>,
+    ) -> Result<Response<Puzzle>, Status> {
+        log_request("GetPuzzle", &request);
+        let user = authenticated_user(&request)?;
+        let r = request.into_inner();
+        let theme = match r.theme.as_str() {
+            "" => None,
+            theme if THEMES.contains(&theme) => Some(theme),
+            _ => return Err(Status::invalid_argument("Unknown theme")),
+        };
+        let rating = query::Query::find_puzzle_rating(&self.db_connection, user.id)
+            .await
+            .map_err(|_| Status::internal("Could not load puzzle rating"))?
+            .map(|rating_row| rating_row.rating)
+            .unwrap_or(Glicko2::default().rating);
+        let windows = RATING_WINDOWS
+            .iter()
+            .map(|&window| (rating - window, rating + window))
+            .chain([(f64::MIN, f64::MAX)]);
+        for (min, max) in windows {
+            let found =
+                query::Query::find_unplayed_puzzle(&self.db_connection, user.id, min, max, theme)
+                    .await
+                    .map_err(|_| Status::internal("Could not load puzzle"))?;
+            if let Some(puzzle_row) = found {
+                return Ok(Response::new(to_puzzle(&puzzle_row)?));
+            }
+        }
+        Err(Status::not_found("No puzzle left"))
+    }
+
+    async fn get_daily_puzzle(
+        &self,
+        request: Request<GetDailyPuzzleRequest>,
+    ) -> Result<Response<Puzzle>, Status> {
+        log_request("GetDailyPuzzle", &request);
+        authenticated_user(&request)?;
+        let puzzle_row =
+            query::Query::find_daily_puzzle(&self.db_connection, Utc::now().date_naive())
+                .await
+                .map_err(|_| Status::internal("Could not load puzzle"))?
+                .ok_or_else(|| Status::not_found("No daily puzzle yet"))?;
+        Ok(Response::new(to_puzzle(&puzzle_row)?))
+    }
+
+    async fn submit_puzzle_moves(
+        &self,
+        request: Request<SubmitPuzzleMovesRequest>,
+    ) -> Result<Response<SubmitPuzzleMovesResponse>, Status> {
+        log_request("SubmitPuzzleMoves", &request);
+        let user = authenticated_user(&request)?;
+        let r = request.into_inner();
+        let puzzle_row = query::Query::find_puzzle_by_id(&self.db_connection, r.puzzle_id)
+            .await
+            .map_err(|_| Status::internal("Could not load puzzle"))?
+            .ok_or_else(|| Status::not_found("Puzzle not found"))?;
+        let solution = solution(&puzzle_row)?;
+        let progress = solution.check(&r.moves)?;
+        let mut response = SubmitPuzzleMovesResponse {
+            result: progress.as_str().to_string(),
+            ..Default::default()
+        };
+        match progress {
+            Progress::Continue(reply) => {
+                // The position the reply is played in
+                let mut position = solution.puzzle_position();
+                for san in &r.moves {
+                    let mv = position
+                        .parse_move(san)
+                        .ok_or_else(|| Status::invalid_argument("Illegal move"))?;
+                    position = position.play(mv);
+                }
+                response.reply = position.san(reply);
+                response.reply_uci = reply.uci();
+            }
+            Progress::Solved | Progress::Failed => {
+                let rated = mutation::Mutation::record_attempt(
+                    &self.db_connection,
+                    user.id,
+                    puzzle_row.id,
+                    progress == Progress::Solved,
+                )
+                .await
+                .map_err(|_| Status::internal("Could not record attempt"))?;
+                response.rated = rated.is_some();
+                let rating_row = match rated {
+                    Some(rating_row) => Some(rating_row),
+                    None => query::Query::find_puzzle_rating(&self.db_connection, user.id)
+                        .await
+                        .map_err(|_| Status::internal("Could not load puzzle rating"))?,
+                };
+                response.rating = Some(to_rating(rating_row.as_ref()));
+                response.solution = solution.san();
+            }
+        }
+        Ok(Response::new(response))
+    }
+
+    async fn get_puzzle_rating(
+        &self,
+        request: Request<GetPuzzleRatingRequest>,
+    ) -> Result<Response<PuzzleRating>, Status> {
+        log_request("GetPuzzleRating", &request);
+        let user = authenticated_user(&request)?;
+        let rating_row = query::Query::find_puzzle_rating(&self.db_connection, user.id)
+            .await
+            .map_err(|_| Status::internal("Could not load puzzle rating"))?;
+        Ok(Response::new(to_rating(rating_row.as_ref())))
+    }
+}
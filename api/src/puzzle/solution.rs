@@ -0,0 +1,168 @@
+// Puzzles are stored like lichess has them: the position before the opponent's move and the
+// solution in UCI starting with that move, then the solver's moves each answered by the
+// opponent. Solvers send their moves so far and every one has to be the solution's, except
+// that any mate ends the puzzle.
+use tonic::Status;
+
+use crate::chess::{
+    engine::position::{Move, Position},
+    pieces::Color,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Solution {
+    pub start: Position,
+    // The opponent's move first
+    pub moves: Vec<Move>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Progress {
+    // Right so far, the opponent answers with the move
+    Continue(Move),
+    Solved,
+    Failed,
+}
+
+impl Progress {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Progress::Continue(_) => "continue",
+            Progress::Solved => "solved",
+            Progress::Failed => "failed",
+        }
+    }
+}
+
+impl Solution {
+    pub fn parse(fen: &str, uci: &str) -> Option<Solution> {
+        let start = Position::from_fen(fen).ok()?;
+        let mut position = start.clone();
+        let mut moves = Vec::new();
+        for text in uci.split_whitespace() {
+            let mv = position.parse_uci(text)?;
+            position = position.play(mv);
+            moves.push(mv);
+        }
+        // At least the opponent's move and one of the solver's
+        (moves.len() >= 2).then_some(Solution { start, moves })
+    }
+
+    pub fn to_uci(moves: &[Move]) -> String {
+        moves.iter().map(Move::uci).collect::<Vec<_>>().join(" ")
+    }
+
+    // Where the solver takes over
+    pub fn puzzle_position(&self) -> Position {
+        self.start.play(self.moves[0])
+    }
+
+    pub fn solver(&self) -> Color {
+        self.start.turn().opponent()
+    }
+
+    pub fn san(&self) -> Vec<String> {
+        let mut position = self.start.clone();
+        let mut san = Vec::new();
+        for &mv in &self.moves {
+            san.push(position.san(mv));
+            position = position.play(mv);
+        }
+        san
+    }
+
+    // `attempt` has the solver's moves in SAN, each has to be legal in the position it is played in
+    pub fn check(&self, attempt: &[String]) -> Result<Progress, Status> {
+        if attempt.is_empty() {
+            return Err(Status::invalid_argument("No moves"));
+        }
+        let mut position = self.puzzle_position();
+        for (i, san) in attempt.iter().enumerate() {
+            let expected = self
+                .moves
+                .get(2 * i + 1)
+                .ok_or_else(|| Status::invalid_argument("Too many moves"))?;
+            let mv = position
+                .parse_move(san)
+                .ok_or_else(|| Status::invalid_argument("Illegal move"))?;
+            let next = position.play(mv);
+            let mates = next.in_check() && next.legal_moves().is_empty();
+            if mv != *expected && !mates {
+                return Ok(Progress::Failed);
+            }
+            let last = i == attempt.len() - 1;
+            let reply = match self.moves.get(2 * i + 2) {
+                Some(&reply) if !mates => reply,
+                _ if last => return Ok(Progress::Solved),
+                _ => return Err(Status::invalid_argument("Too many moves")),
+            };
+            if last {
+                return Ok(Progress::Continue(reply));
+            }
+            position = next.play(reply);
+        }
+        unreachable!("the last move of the attempt always returns")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn moves(san: &[&str]) -> Vec<String> {
+        san.iter().map(|san| san.to_string()).collect()
+    }
+
+    // Black takes on b2, then the solver has two moves with a reply in between
+    fn two_moves() -> Solution {
+        Solution::parse(
+            "r3k3/1pp2ppp/8/4N3/8/1q6/PP3PPP/2R1K3 b - - 0 1",
+            "b3b2 e5c6 b7c6 c1c6",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        let solution = two_moves();
+        assert_eq!(solution.solver(), Color::White);
+        assert_eq!(solution.san(), vec!["Qxb2", "Nc6", "bxc6", "Rxc6"]);
+        assert_eq!(Solution::to_uci(&solution.moves), "b3b2 e5c6 b7c6 c1c6");
+        assert_eq!(solution.puzzle_position().turn(), Color::White);
+        // Too short or illegal
+        assert!(Solution::parse("4k3/8/8/8/8/8/8/4K2R w - - 0 1", "h1h8").is_none());
+        assert!(Solution::parse("4k3/8/8/8/8/8/8/4K2R w - - 0 1", "h1h8 e8e7 h8h9").is_none());
+    }
+
+    #[test]
+    fn test_check_attempts() {
+        let solution = two_moves();
+        let reply = solution.moves[2];
+        assert_eq!(
+            solution.check(&moves(&["Nc6"])).unwrap(),
+            Progress::Continue(reply)
+        );
+        assert_eq!(
+            solution.check(&moves(&["Nc6", "Rxc6"])).unwrap(),
+            Progress::Solved
+        );
+        assert_eq!(
+            solution.check(&moves(&["Nc6", "Rc2"])).unwrap(),
+            Progress::Failed
+        );
+        assert_eq!(solution.check(&moves(&["Nxf7"])).unwrap(), Progress::Failed);
+        assert!(solution.check(&moves(&["Nc7"])).is_err());
+        assert!(solution.check(&moves(&["Nc6", "Rxc6", "Ke2"])).is_err());
+        assert!(solution.check(&[]).is_err());
+    }
+
+    #[test]
+    fn test_any_mate_solves() {
+        // Either rook mates on the eighth rank, the solution has the one on a1
+        let solution =
+            Solution::parse("6k1/5ppp/8/2p5/8/8/8/RR4K1 b - - 0 1", "c5c4 a1a8").unwrap();
+        assert_eq!(solution.check(&moves(&["Ra8#"])).unwrap(), Progress::Solved);
+        assert_eq!(solution.check(&moves(&["Rb8#"])).unwrap(), Progress::Solved);
+        assert_eq!(solution.check(&moves(&["Rd1"])).unwrap(), Progress::Failed);
+    }
+}
@@ -0,0 +1,90 @@
+// The address a request came from. Anyone can send `x-forwarded-for`, so it is only believed
+// when the connection itself comes from one of the proxies listed in TRUSTED_PROXIES
+// (comma separated addresses), and then read from the right, skipping those proxies.
+use std::env;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+#[derive(Clone, Debug, Default)]
+pub struct TrustedProxies {
+    addresses: Arc<Vec<IpAddr>>,
+}
+
+impl TrustedProxies {
+    pub fn new(addresses: Vec<IpAddr>) -> TrustedProxies {
+        TrustedProxies {
+            addresses: Arc::new(addresses),
+        }
+    }
+
+    pub fn from_env() -> TrustedProxies {
+        let addresses = env::var("TRUSTED_PROXIES")
+            .map(|list| {
+                list.split(',')
+                    .filter_map(|address| address.trim().parse().ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+        TrustedProxies::new(addresses)
+    }
+
+    fn is_trusted(&self, address: &IpAddr) -> bool {
+        self.addresses.contains(address)
+    }
+
+    // `peer` is the address of the connection, `forwarded` the `x-forwarded-for` header
+    pub fn client_ip(&self, peer: Option<IpAddr>, forwarded: Option<&str>) -> Option<IpAddr> {
+        let peer = peer?;
+        if !self.is_trusted(&peer) {
+            return Some(peer);
+        }
+        let mut client = peer;
+        for address in forwarded.unwrap_or_default().rsplit(',') {
+            match address.trim().parse::<IpAddr>() {
+                Ok(address) => {
+                    client = address;
+                    if !self.is_trusted(&address) {
+                        break;
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+        Some(client)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(address: &str) -> Option<IpAddr> {
+        address.parse().ok()
+    }
+
+    #[test]
+    fn test_forwarded_for_is_only_believed_from_trusted_proxies() {
+        let proxies = TrustedProxies::new(vec!["10.0.0.1".parse().unwrap()]);
+        // Straight from the client, whatever it claims
+        assert_eq!(
+            proxies.client_ip(ip("203.0.113.7"), Some("198.51.100.1")),
+            ip("203.0.113.7")
+        );
+        // Through the proxy, the address it added
+        assert_eq!(
+            proxies.client_ip(ip("10.0.0.1"), Some("198.51.100.1, 203.0.113.7")),
+            ip("203.0.113.7")
+        );
+        assert_eq!(proxies.client_ip(ip("10.0.0.1"), None), ip("10.0.0.1"));
+        assert_eq!(
+            proxies.client_ip(ip("10.0.0.1"), Some("garbage")),
+            ip("10.0.0.1")
+        );
+        // Nothing is trusted by default
+        assert_eq!(
+            TrustedProxies::default().client_ip(ip("10.0.0.1"), Some("198.51.100.1")),
+            ip("10.0.0.1")
+        );
+        assert_eq!(proxies.client_ip(None, Some("198.51.100.1")), None);
+    }
+}
@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use tonic::{body::BoxBody, transport::server::TcpConnectInfo, Status};
+use tower::{Layer, Service};
+
+use super::client::TrustedProxies;
+
+// Past this many entries buckets that are full again are dropped, as they carry no information,
+// then the ones of the clients seen least recently
+const MAX_TRACKED_BUCKETS: usize = 10_000;
+// Evicting down to this leaves room so it does not happen on every request
+const EVICT_TO: usize = MAX_TRACKED_BUCKETS * 9 / 10;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Quota {
+    // Requests that can be made back to back
+    pub burst: u32,
+    pub per_second: f64,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl Bucket {
+    fn is_full(&self, quota: &Quota, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.updated_at).as_secs_f64();
+        self.tokens + elapsed * quota.per_second >= quota.burst as f64
+    }
+
+    fn refill(&mut self, quota: &Quota, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * quota.per_second).min(quota.burst as f64);
+        self.updated_at = now;
+    }
+}
+
+#[derive(Default)]
+struct Limiter {
+    // gRPC method path, e.g. "/chessgame.ChessGame/MovePiece"
+    quotas: HashMap<String, Quota>,
+    buckets: HashMap<(String, String), Bucket>,
+}
+
+impl Limiter {
+    fn try_acquire(&mut self, path: &str, client: &str, now: Instant) -> bool {
+        let quota = match self.quotas.get(path) {
+            Some(quota) => *quota,
+            None => return true,
+        };
+        if self.buckets.len() >= MAX_TRACKED_BUCKETS {
+            self.evict(now);
+        }
+        let bucket = self
+            .buckets
+            .entry((path.to_string(), client.to_string()))
+            .or_insert(Bucket {
+                tokens: quota.burst as f64,
+                updated_at: now,
+            });
+        bucket.refill(&quota, now);
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    // `updated_at` is only moved by requests, so it is when the client was last seen
+    fn evict(&mut self, now: Instant) {
+        let quotas = &self.quotas;
+        self.buckets
+            .retain(|(path, _), bucket| match quotas.get(path) {
+                Some(quota) => !bucket.is_full(quota, now),
+                None => false,
+            });
+        if self.buckets.len() <= EVICT_TO {
+            return;
+        }
+        let mut seen: Vec<Instant> = self.buckets.values().map(|b| b.updated_at).collect();
+        let excess = seen.len() - EVICT_TO;
+        let (_, &mut cutoff, _) = seen.select_nth_unstable(excess - 1);
+        let mut to_remove = excess;
+        self.buckets.retain(|_, bucket| {
+            if to_remove > 0 && bucket.updated_at <= cutoff {
+                to_remove -= 1;
+                false
+            } else {
+                true
+            }
+        });
+    }
+}
+
+// Token bucket per client address and gRPC method, for any tonic service.
+// Methods without a quota are not limited.
+#[derive(Clone, Default)]
+pub struct RateLimitLayer {
+    limiter: Arc<Mutex<Limiter>>,
+    trusted_proxies: TrustedProxies,
+}
+
+impl RateLimitLayer {
+    pub fn new() -> RateLimitLayer {
+        RateLimitLayer::default()
+    }
+
+    pub fn limit(self, path: &str, quota: Quota) -> RateLimitLayer {
+        if let Ok(mut limiter) = self.limiter.lock() {
+            limiter.quotas.insert(path.to_string(), quota);
+        }
+        self
+    }
+
+    pub fn trusted_proxies(mut self, trusted_proxies: TrustedProxies) -> RateLimitLayer {
+        self.trusted_proxies = trusted_proxies;
+        self
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimit {
+            inner,
+            limiter: self.limiter.clone(),
+            trusted_proxies: self.trusted_proxies.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimit<S> {
+    inner: S,
+    limiter: Arc<Mutex<Limiter>>,
+    trusted_proxies: TrustedProxies,
+}
+
+fn client_key<B>(request: &http::Request<B>, trusted_proxies: &TrustedProxies) -> String {
+    let peer = request
+        .extensions()
+        .get::<TcpConnectInfo>()
+        .and_then(|info| info.remote_addr())
+        .map(|addr| addr.ip());
+    let forwarded = request
+        .headers()
+        .get("x-forwarded-for")
+        .and_then(|v| v.to_str().ok());
+    trusted_proxies
+        .client_ip(peer, forwarded)
+        .map(|ip| ip.to_string())
+        .unwrap_or_default()
+}
+
+impl<S, ReqBody> Service<http::Request<ReqBody>> for RateLimit<S>
+where
+    S: Service<http::Request<ReqBody>, Response = http::Response<BoxBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<ReqBody>) -> Self::Future {
+        let client = client_key(&request, &self.trusted_proxies);
+        let allowed = match self.limiter.lock() {
+            Ok(mut limiter) => limiter.try_acquire(request.uri().path(), &client, Instant::now()),
+            Err(_) => true,
+        };
+        if !allowed {
+            let response = Status::resource_exhausted("Rate limit exceeded").to_http();
+            return Box::pin(async move { Ok(response) });
+        }
+        Box::pin(self.inner.call(request))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    static MOVE_PIECE: &str = "/chessgame.ChessGame/MovePiece";
+
+    #[test]
+    fn test_limiter_token_bucket() {
+        let mut limiter = Limiter::default();
+        limiter.quotas.insert(
+            MOVE_PIECE.to_string(),
+            Quota {
+                burst: 2,
+                per_second: 1.0,
+            },
+        );
+        let now = Instant::now();
+        assert!(limiter.try_acquire(MOVE_PIECE, "10.0.0.1", now));
+        assert!(limiter.try_acquire(MOVE_PIECE, "10.0.0.1", now));
+        assert!(!limiter.try_acquire(MOVE_PIECE, "10.0.0.1", now));
+        // Separate bucket per client
+        assert!(limiter.try_acquire(MOVE_PIECE, "10.0.0.2", now));
+        // Unlimited method
+        assert!(limiter.try_acquire("/auth.Auth/Login", "10.0.0.1", now));
+
+        let later = now + Duration::from_millis(1100);
+        assert!(limiter.try_acquire(MOVE_PIECE, "10.0.0.1", later));
+        assert!(!limiter.try_acquire(MOVE_PIECE, "10.0.0.1", later));
+    }
+
+    #[test]
+    fn test_limiter_evicts_least_recently_seen() {
+        let mut limiter = Limiter::default();
+        limiter.quotas.insert(
+            MOVE_PIECE.to_string(),
+            Quota {
+                burst: 10,
+                per_second: 0.001,
+            },
+        );
+        let start = Instant::now();
+        // Every client keeps a bucket that is not full, as a spoofing client would
+        for i in 0..MAX_TRACKED_BUCKETS + 500 {
+            let now = start + Duration::from_millis(i as u64);
+            assert!(limiter.try_acquire(MOVE_PIECE, &format!("client-{i}"), now));
+            assert!(limiter.buckets.len() <= MAX_TRACKED_BUCKETS);
+        }
+        let last = format!("client-{}", MAX_TRACKED_BUCKETS + 499);
+        assert!(limiter
+            .buckets
+            .contains_key(&(MOVE_PIECE.to_string(), last)));
+        assert!(!limiter
+            .buckets
+            .contains_key(&(MOVE_PIECE.to_string(), "client-0".to_string())));
+    }
+}
@@ -0,0 +1,300 @@
+use chrono::{Duration, NaiveDateTime, Utc};
+use std::sync::Arc;
+use tonic::{metadata::MetadataValue, Status};
+
+use super::store::{AttemptRecord, AttemptStore, Reservation};
+
+#[derive(Clone, Debug)]
+pub struct BackoffPolicy {
+    // Failures allowed before the first lockout
+    pub free_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    // Failures older than this are forgotten
+    pub window: Duration,
+}
+
+impl BackoffPolicy {
+    pub fn per_username() -> BackoffPolicy {
+        BackoffPolicy {
+            free_attempts: 5,
+            base_delay: Duration::seconds(1),
+            max_delay: Duration::minutes(15),
+            window: Duration::minutes(15),
+        }
+    }
+
+    // Several people can share an address (NAT, offices), so it is more lenient
+    pub fn per_ip() -> BackoffPolicy {
+        BackoffPolicy {
+            free_attempts: 20,
+            base_delay: Duration::seconds(1),
+            max_delay: Duration::minutes(15),
+            window: Duration::minutes(15),
+        }
+    }
+
+    pub fn record_failure(
+        &self,
+        previous: Option<AttemptRecord>,
+        now: NaiveDateTime,
+    ) -> AttemptRecord {
+        let failures = match previous {
+            Some(record) if now - record.last_failure_at < self.window => record.failures + 1,
+            _ => 1,
+        };
+        let locked_until = if failures > self.free_attempts {
+            // Doubles with each failure past the free attempts
+            let exponent = (failures - self.free_attempts - 1).min(30);
+            let delay = self
+                .base_delay
+                .num_milliseconds()
+                .checked_mul(1 << exponent)
+                .map(Duration::milliseconds)
+                .unwrap_or(self.max_delay)
+                .min(self.max_delay);
+            Some(now + delay)
+        } else {
+            None
+        };
+        AttemptRecord {
+            failures,
+            last_failure_at: now,
+            locked_until,
+        }
+    }
+
+    pub fn lockout_remaining(
+        &self,
+        record: &AttemptRecord,
+        now: NaiveDateTime,
+    ) -> Option<Duration> {
+        match record.locked_until {
+            Some(locked_until) if locked_until > now => Some(locked_until - now),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct LoginThrottle {
+    pub store: Arc<dyn AttemptStore>,
+    pub username_policy: BackoffPolicy,
+    pub ip_policy: BackoffPolicy,
+}
+
+fn username_key(username: &str) -> String {
+    format!("user:{}", username.to_lowercase())
+}
+
+fn ip_key(ip_address: &str) -> String {
+    format!("ip:{}", ip_address)
+}
+
+fn account_key(user_id: i32) -> String {
+    format!("account:{}", user_id)
+}
+
+impl LoginThrottle {
+    pub fn new(store: Arc<dyn AttemptStore>) -> LoginThrottle {
+        LoginThrottle {
+            store,
+            username_policy: BackoffPolicy::per_username(),
+            ip_policy: BackoffPolicy::per_ip(),
+        }
+    }
+
+    fn keys(&self, username: &str, ip_address: Option<&str>) -> Vec<(String, &BackoffPolicy)> {
+        let mut keys = vec![(username_key(username), &self.username_policy)];
+        if let Some(ip_address) = ip_address {
+            keys.push((ip_key(ip_address), &self.ip_policy));
+        }
+        keys
+    }
+
+    // Counts the attempt as a failure before the password is checked, so parallel attempts
+    // can't all get through on the same count. Store errors are logged and the attempt allowed,
+    // so a database outage doesn't lock everyone out.
+    pub async fn reserve(&self, username: &str, ip_address: Option<&str>) -> Result<(), Status> {
+        self.reserve_keys(self.keys(username, ip_address)).await
+    }
+
+    // For checks of the password of someone already logged in, like changing it. They are
+    // counted per account, so a stolen session cannot be used to guess it.
+    pub async fn reserve_account(&self, user_id: i32) -> Result<(), Status> {
+        self.reserve_keys(vec![(account_key(user_id), &self.username_policy)])
+            .await
+    }
+
+    pub async fn record_account_success(&self, user_id: i32) {
+        if let Err(err) = self.store.remove(&account_key(user_id)).await {
+            println!("Error: could not reset login attempts: {err}");
+        }
+    }
+
+    async fn reserve_keys(&self, keys: Vec<(String, &BackoffPolicy)>) -> Result<(), Status> {
+        let now = Utc::now().naive_utc();
+        let mut reserved = Vec::new();
+        let mut remaining: Option<Duration> = None;
+        for (key, policy) in keys {
+            match self.store.reserve(&key, policy, now).await {
+                Ok(Reservation::Reserved) => reserved.push(key),
+                Ok(Reservation::Locked(locked_until)) => {
+                    let lockout = (locked_until - now).max(Duration::zero());
+                    remaining = Some(remaining.map_or(lockout, |r| r.max(lockout)));
+                }
+                Err(err) => println!("Error: could not reserve login attempt: {err}"),
+            }
+        }
+        match remaining {
+            Some(remaining) => {
+                // A refused attempt doesn't count against the other keys
+                for key in reserved {
+                    self.release(&key).await;
+                }
+                Err(too_many_attempts(remaining))
+            }
+            None => Ok(()),
+        }
+    }
+
+    // The username counter is reset. The address counter only gets this attempt back, otherwise
+    // an attacker could clear it by logging into an account of their own.
+    pub async fn record_success(&self, username: &str, ip_address: Option<&str>) {
+        if let Err(err) = self.store.remove(&username_key(username)).await {
+            println!("Error: could not reset login attempts: {err}");
+        }
+        if let Some(ip_address) = ip_address {
+            self.release(&ip_key(ip_address)).await;
+        }
+    }
+
+    async fn release(&self, key: &str) {
+        if let Err(err) = self.store.release(key).await {
+            println!("Error: could not release login attempt: {err}");
+        }
+    }
+}
+
+fn too_many_attempts(remaining: Duration) -> Status {
+    let seconds = (remaining.num_milliseconds() + 999) / 1000;
+    let mut status = Status::resource_exhausted(format!(
+        "Too many login attempts, try again in {} seconds",
+        seconds
+    ));
+    if let Ok(value) = MetadataValue::try_from(seconds.to_string()) {
+        status.metadata_mut().insert("retry-after", value);
+    }
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rate_limit::store::MemoryStore;
+
+    fn policy() -> BackoffPolicy {
+        BackoffPolicy {
+            free_attempts: 2,
+            base_delay: Duration::seconds(1),
+            max_delay: Duration::seconds(10),
+            window: Duration::minutes(5),
+        }
+    }
+
+    #[test]
+    fn test_backoff_doubles_and_caps() {
+        let policy = policy();
+        let now = Utc::now().naive_utc();
+        let mut record = None;
+        let mut delays = Vec::new();
+        for _ in 0..8 {
+            let next = policy.record_failure(record, now);
+            delays.push(next.locked_until.map(|l| (l - now).num_seconds()));
+            record = Some(next);
+        }
+        assert_eq!(
+            delays,
+            vec![
+                None,
+                None,
+                Some(1),
+                Some(2),
+                Some(4),
+                Some(8),
+                Some(10),
+                Some(10)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_backoff_window_resets_failures() {
+        let policy = policy();
+        let now = Utc::now().naive_utc();
+        let record = AttemptRecord {
+            failures: 10,
+            last_failure_at: now - Duration::minutes(6),
+            locked_until: None,
+        };
+        let next = policy.record_failure(Some(record), now);
+        assert_eq!(next.failures, 1);
+        assert_eq!(next.locked_until, None);
+        assert_eq!(policy.lockout_remaining(&next, now), None);
+    }
+
+    fn throttle() -> LoginThrottle {
+        LoginThrottle {
+            store: Arc::new(MemoryStore::default()),
+            username_policy: policy(),
+            ip_policy: BackoffPolicy::per_ip(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_login_throttle_locks_and_resets() {
+        let throttle = throttle();
+        // Three failed attempts, the third one starts the lockout
+        for _ in 0..3 {
+            assert!(throttle.reserve("Bob", Some("10.0.0.1")).await.is_ok());
+        }
+        let err = throttle.reserve("bob", Some("10.0.0.2")).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::ResourceExhausted);
+        assert!(err.metadata().get("retry-after").is_some());
+        // Other accounts from the same address are not affected yet
+        assert!(throttle.reserve("alice", Some("10.0.0.1")).await.is_ok());
+        throttle.record_success("alice", Some("10.0.0.1")).await;
+
+        throttle.record_success("BOB", Some("10.0.0.1")).await;
+        assert!(throttle.reserve("bob", Some("10.0.0.1")).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_account_checks_are_counted_per_user() {
+        let throttle = throttle();
+        for _ in 0..3 {
+            assert!(throttle.reserve_account(7).await.is_ok());
+        }
+        let err = throttle.reserve_account(7).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::ResourceExhausted);
+        assert!(throttle.reserve_account(8).await.is_ok());
+        throttle.record_account_success(7).await;
+        assert!(throttle.reserve_account(7).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_parallel_attempts_share_one_count() {
+        let throttle = throttle();
+        let attempts = (0..10).map(|_| {
+            let throttle = throttle.clone();
+            tokio::spawn(async move { throttle.reserve("bob", None).await.is_ok() })
+        });
+        let mut allowed = 0;
+        for attempt in attempts {
+            if attempt.await.unwrap() {
+                allowed += 1;
+            }
+        }
+        assert_eq!(allowed, 3);
+    }
+}
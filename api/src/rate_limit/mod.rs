@@ -0,0 +1,4 @@
+pub mod client;
+pub mod layer;
+pub mod login;
+pub mod store;
@@ -0,0 +1,208 @@
+use chrono::{Duration, NaiveDateTime};
+use sea_orm::{DatabaseConnection, DbErr};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use super::login::BackoffPolicy;
+use service::login_attempts::{mutation, query};
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct AttemptRecord {
+    pub failures: u32,
+    pub last_failure_at: NaiveDateTime,
+    pub locked_until: Option<NaiveDateTime>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Reservation {
+    Reserved,
+    Locked(NaiveDateTime),
+}
+
+// Where failed login counters are kept. The in-memory store is lost on restart,
+// the database store survives restarts and is shared between server instances.
+#[tonic::async_trait]
+pub trait AttemptStore: Send + Sync {
+    // Counts an attempt as a failure before the password is checked, unless the key is locked.
+    // Reading and updating the counter is one step, so parallel attempts can't share a count.
+    async fn reserve(
+        &self,
+        key: &str,
+        policy: &BackoffPolicy,
+        now: NaiveDateTime,
+    ) -> Result<Reservation, DbErr>;
+    // Takes back a reserved attempt that succeeded
+    async fn release(&self, key: &str) -> Result<(), DbErr>;
+    async fn remove(&self, key: &str) -> Result<(), DbErr>;
+}
+
+// How often expired counters are dropped from memory
+const SWEEP_INTERVAL_MINUTES: i64 = 1;
+
+struct MemoryEntry {
+    record: AttemptRecord,
+    // After this the record is neither locked nor inside its window, so it can be forgotten
+    expires_at: NaiveDateTime,
+}
+
+#[derive(Default)]
+struct MemoryRecords {
+    entries: HashMap<String, MemoryEntry>,
+    swept_at: Option<NaiveDateTime>,
+}
+
+impl MemoryRecords {
+    fn sweep(&mut self, now: NaiveDateTime) {
+        if self
+            .swept_at
+            .is_some_and(|swept_at| now - swept_at < Duration::minutes(SWEEP_INTERVAL_MINUTES))
+        {
+            return;
+        }
+        self.entries.retain(|_, entry| entry.expires_at > now);
+        self.swept_at = Some(now);
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryStore {
+    records: Mutex<MemoryRecords>,
+}
+
+impl MemoryStore {
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, MemoryRecords>, DbErr> {
+        self.records
+            .lock()
+            .map_err(|_| DbErr::Custom("Attempt store lock poisoned.".to_owned()))
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.lock().map_or(0, |records| records.entries.len())
+    }
+}
+
+#[tonic::async_trait]
+impl AttemptStore for MemoryStore {
+    async fn reserve(
+        &self,
+        key: &str,
+        policy: &BackoffPolicy,
+        now: NaiveDateTime,
+    ) -> Result<Reservation, DbErr> {
+        let mut records = self.lock()?;
+        records.sweep(now);
+        let previous = records.entries.get(key).map(|entry| entry.record.clone());
+        if let Some(lockout) = previous
+            .as_ref()
+            .and_then(|r| policy.lockout_remaining(r, now))
+        {
+            return Ok(Reservation::Locked(now + lockout));
+        }
+        let record = policy.record_failure(previous, now);
+        let expires_at = record
+            .locked_until
+            .map_or(now + policy.window, |locked_until| {
+                locked_until.max(now + policy.window)
+            });
+        records
+            .entries
+            .insert(key.to_string(), MemoryEntry { record, expires_at });
+        Ok(Reservation::Reserved)
+    }
+
+    async fn release(&self, key: &str) -> Result<(), DbErr> {
+        let mut records = self.lock()?;
+        if let Some(entry) = records.entries.get_mut(key) {
+            entry.record.failures = entry.record.failures.saturating_sub(1);
+        }
+        Ok(())
+    }
+
+    async fn remove(&self, key: &str) -> Result<(), DbErr> {
+        let mut records = self.lock()?;
+        records.entries.remove(key);
+        Ok(())
+    }
+}
+
+pub struct DatabaseStore {
+    pub db_connection: DatabaseConnection,
+}
+
+#[tonic::async_trait]
+impl AttemptStore for DatabaseStore {
+    async fn reserve(
+        &self,
+        key: &str,
+        policy: &BackoffPolicy,
+        now: NaiveDateTime,
+    ) -> Result<Reservation, DbErr> {
+        let reserved = mutation::Mutation::reserve_attempt(
+            &self.db_connection,
+            key,
+            now,
+            now - policy.window,
+            policy.free_attempts.min(i32::MAX as u32) as i32,
+            policy.base_delay.num_milliseconds(),
+            policy.max_delay.num_milliseconds(),
+        )
+        .await?;
+        if reserved.is_some() {
+            return Ok(Reservation::Reserved);
+        }
+        let db_result = query::Query::find_attempts_by_key(&self.db_connection, key).await?;
+        // The row can only be missing if it was reset in the meantime
+        Ok(Reservation::Locked(
+            db_result
+                .and_then(|attempts| attempts.locked_until)
+                .unwrap_or(now),
+        ))
+    }
+
+    async fn release(&self, key: &str) -> Result<(), DbErr> {
+        mutation::Mutation::release_attempt(&self.db_connection, key).await?;
+        Ok(())
+    }
+
+    async fn remove(&self, key: &str) -> Result<(), DbErr> {
+        mutation::Mutation::delete_attempts(&self.db_connection, key).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[tokio::test]
+    async fn test_memory_store_forgets_expired_records() {
+        let store = MemoryStore::default();
+        let policy = BackoffPolicy::per_username();
+        let now = Utc::now().naive_utc();
+        store.reserve("user:old", &policy, now).await.unwrap();
+        assert_eq!(store.len(), 1);
+
+        let later = now + policy.window + Duration::seconds(1);
+        store.reserve("user:new", &policy, later).await.unwrap();
+        assert_eq!(store.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_memory_store_release_gives_back_an_attempt() {
+        let store = MemoryStore::default();
+        let policy = BackoffPolicy::per_username();
+        let now = Utc::now().naive_utc();
+        for _ in 0..policy.free_attempts {
+            store.reserve("ip:1", &policy, now).await.unwrap();
+            store.release("ip:1").await.unwrap();
+        }
+        // Every attempt was given back, so this one is still free
+        store.reserve("ip:1", &policy, now).await.unwrap();
+        assert_eq!(
+            store.reserve("ip:1", &policy, now).await.unwrap(),
+            Reservation::Reserved
+        );
+    }
+}
@@ -0,0 +1,2 @@
+pub mod service;
+tonic::include_proto!("rating"); // The string specified here must match the proto package name
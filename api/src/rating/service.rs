@@ -0,0 +1,92 @@
+use sea_orm::DatabaseConnection;
+use tonic::{Request, Response, Status};
+
+use entity::entities::{rating, users};
+use service::{
+    ratings::{glicko2::Glicko2, pool::Pool, query},
+    users::query as user_query,
+};
+
+use super::{
+    ratings_server::Ratings, GetRatingHistoryRequest, GetRatingHistoryResponse, GetRatingsRequest,
+    GetRatingsResponse, Rating, RatingHistoryEntry,
+};
+use crate::auth::interceptor::log_request;
+
+pub struct RatingService {
+    pub db_connection: DatabaseConnection,
+}
+
+impl Rating {
+    fn new(pool: Pool, rating: Option<&rating::Model>) -> Rating {
+        let glicko2 = rating.map(Glicko2::from).unwrap_or_default();
+        Rating {
+            pool: pool.as_str().to_string(),
+            rating: glicko2.rating,
+            deviation: glicko2.deviation,
+            games: rating.map(|r| r.games).unwrap_or(0),
+            provisional: glicko2.is_provisional(),
+        }
+    }
+}
+
+impl RatingService {
+    async fn find_user(&self, username: &str) -> Result<users::Model, Status> {
+        let db_result = user_query::Query::find_user_by_username(&self.db_connection, username)
+            .await
+            .map_err(|_| Status::internal("Could not load user"))?;
+        match db_result {
+            Some(user) => Ok(user),
+            None => Err(Status::not_found("User not found")),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl Ratings for RatingService {
+    async fn get_ratings(
+        &self,
+        request: Request<GetRatingsRequest>,
+    ) -> Result<Response<GetRatingsResponse>, Status> {
+        log_request("GetRatings", &request);
+        let r = request.into_inner();
+        let user = self.find_user(&r.username).await?;
+        let db_result = query::Query::find_ratings_by_user(&self.db_connection, user.id)
+            .await
+            .map_err(|_| Status::internal("Could not load ratings"))?;
+        let ratings = Pool::ALL
+            .into_iter()
+            .map(|pool| {
+                let rating = db_result.iter().find(|r| r.pool == pool.as_str());
+                Rating::new(pool, rating)
+            })
+            .collect();
+        Ok(Response::new(GetRatingsResponse { ratings }))
+    }
+
+    async fn get_rating_history(
+        &self,
+        request: Request<GetRatingHistoryRequest>,
+    ) -> Result<Response<GetRatingHistoryResponse>, Status> {
+        log_request("GetRatingHistory", &request);
+        let r = request.into_inner();
+        let pool = match Pool::parse(&r.pool) {
+            Some(pool) => pool,
+            None => return Err(Status::invalid_argument("Unknown rating pool")),
+        };
+        let user = self.find_user(&r.username).await?;
+        let db_result = query::Query::find_rating_history(&self.db_connection, user.id, pool)
+            .await
+            .map_err(|_| Status::internal("Could not load rating history"))?;
+        let entries = db_result
+            .into_iter()
+            .map(|entry| RatingHistoryEntry {
+                game_id: entry.game_id.unwrap_or_default(),
+                rating: entry.rating,
+                deviation: entry.deviation,
+                created_at: entry.created_at.and_utc().timestamp(),
+            })
+            .collect();
+        Ok(Response::new(GetRatingHistoryResponse { entries }))
+    }
+}
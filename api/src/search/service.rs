@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+
+use sea_orm::DatabaseConnection;
+use tonic::{Request, Response, Status};
+
+use service::{
+    game::query as game_query,
+    game_move::query::{self, PositionFilter},
+    users::query as user_query,
+};
+
+use super::{
+    position_search_server::PositionSearch, GameMatch, SearchPositionsRequest,
+    SearchPositionsResponse,
+};
+use crate::auth::interceptor::{authenticated_user, log_request};
+use crate::chess::{engine::position::Position, material};
+use crate::paging::page_size;
+
+pub struct PositionSearchService {
+    pub db_connection: DatabaseConnection,
+}
+
+fn filter(r: &SearchPositionsRequest) -> Result<PositionFilter, Status> {
+    let set = [&r.fen, &r.moves, &r.material, &r.pattern]
+        .iter()
+        .filter(|field| !field.is_empty())
+        .count();
+    if set != 1 {
+        return Err(Status::invalid_argument(
+            "Exactly one of FEN, moves, material and pattern has to be set",
+        ));
+    }
+    if !r.fen.is_empty() {
+        let position =
+            Position::from_fen(&r.fen).map_err(|_| Status::invalid_argument("Invalid FEN"))?;
+        Ok(PositionFilter::Zobrist(position.key() as i64))
+    } else if !r.moves.is_empty() {
+        let (position, _) = Position::from_moves(&r.moves)
+            .map_err(|_| Status::invalid_argument("Invalid moves"))?;
+        Ok(PositionFilter::Zobrist(position.key() as i64))
+    } else if !r.material.is_empty() {
+        let signature = material::parse_signature(&r.material)
+            .ok_or_else(|| Status::invalid_argument("Invalid material"))?;
+        let mut signatures = vec![signature.clone()];
+        let mirrored = material::mirror(&signature);
+        if r.either_color && mirrored != signature {
+            signatures.push(mirrored);
+        }
+        Ok(PositionFilter::Material(signatures))
+    } else {
+        material::parse_pattern(&r.pattern)
+            .map(PositionFilter::Placement)
+            .ok_or_else(|| Status::invalid_argument("Invalid pattern"))
+    }
+}
+
+#[tonic::async_trait]
+impl PositionSearch for PositionSearchService {
+    async fn search_positions(
+        &self,
+        request: Request<SearchPositionsRequest>,
+    ) -> Result<Response<SearchPositionsResponse>, Status> {
+        log_request("SearchPositions", &request);
+        authenticated_user(&request)?;
+        let r = request.into_inner();
+        let filter = filter(&r)?;
+        let page_size = page_size(r.page_size)?;
+        if r.cursor < 0 {
+            return Err(Status::invalid_argument("Invalid cursor"));
+        }
+        let db = &self.db_connection;
+
+        // One more than asked for tells whether there is another page
+        let mut matches = query::Query::find_games_by_position(
+            db,
+            &filter,
+            (r.cursor > 0).then_some(r.cursor),
+            page_size as u64 + 1,
+        )
+        .await
+        .map_err(|_| Status::internal("Could not search games"))?;
+        let next_cursor = if matches.len() > page_size as usize {
+            matches.truncate(page_size as usize);
+            matches.last().map_or(0, |last| last.game_id)
+        } else {
+            0
+        };
+
+        let games: HashMap<i32, _> = game_query::Query::find_games_by_ids(
+            db,
+            matches.iter().map(|found| found.game_id).collect(),
+        )
+        .await
+        .map_err(|_| Status::internal("Could not load games"))?
+        .into_iter()
+        .map(|game_row| (game_row.id, game_row))
+        .collect();
+        let player_ids = games
+            .values()
+            .flat_map(|game_row| [game_row.player_white, game_row.player_black])
+            .collect();
+        let usernames: HashMap<i32, String> = user_query::Query::find_users_by_ids(db, player_ids)
+            .await
+            .map_err(|_| Status::internal("Could not load players"))?
+            .into_iter()
+            .map(|user| (user.id, user.username))
+            .collect();
+
+        let mut move_lists = query::Query::find_move_lists(db, games.keys().copied().collect())
+            .await
+            .map_err(|_| Status::internal("Could not load moves"))?;
+        let fens: HashMap<i32, String> = query::Query::find_matched_moves(db, &matches)
+            .await
+            .map_err(|_| Status::internal("Could not load moves"))?
+            .into_iter()
+            .filter_map(|mv| Some((mv.game_id, mv.fen?)))
+            .collect();
+
+        let mut response = SearchPositionsResponse {
+            games: Vec::with_capacity(matches.len()),
+            next_cursor,
+        };
+        for found in matches {
+            let game_row = match games.get(&found.game_id) {
+                Some(game_row) => game_row,
+                None => continue,
+            };
+            let username = |id| usernames.get(&id).cloned().unwrap_or_default();
+            response.games.push(GameMatch {
+                game_id: game_row.id,
+                ply: found.ply,
+                fen: fens.get(&game_row.id).cloned().unwrap_or_default(),
+                white: username(game_row.player_white),
+                black: username(game_row.player_black),
+                result: game_row.result.clone().unwrap_or_default(),
+                eco: game_row.eco.clone().unwrap_or_default(),
+                opening: game_row.opening.clone().unwrap_or_default(),
+                finished_at: game_row
+                    .updated_at
+                    .unwrap_or(game_row.created_at)
+                    .and_utc()
+                    .timestamp(),
+                moves: move_lists.remove(&game_row.id).unwrap_or_default(),
+            });
+        }
+        Ok(Response::new(response))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter() {
+        let request =
+            |fen: &str, moves: &str, material: &str, pattern: &str| SearchPositionsRequest {
+                fen: fen.to_string(),
+                moves: moves.to_string(),
+                material: material.to_string(),
+                pattern: pattern.to_string(),
+                ..Default::default()
+            };
+        let after_e4 = Position::from_moves("e4").unwrap().0;
+        assert_eq!(
+            filter(&request(&after_e4.to_fen(), "", "", "")).unwrap(),
+            PositionFilter::Zobrist(after_e4.key() as i64)
+        );
+        assert_eq!(
+            filter(&request("", "e4", "", "")).unwrap(),
+            PositionFilter::Zobrist(after_e4.key() as i64)
+        );
+        assert_eq!(
+            filter(&request("", "", "R+B vs R", "")).unwrap(),
+            PositionFilter::Material(vec!["KRBvKR".to_string()])
+        );
+        let mut either = request("", "", "R+B vs R", "");
+        either.either_color = true;
+        assert_eq!(
+            filter(&either).unwrap(),
+            PositionFilter::Material(vec!["KRBvKR".to_string(), "KRvKRB".to_string()])
+        );
+        // The same both ways is only searched once
+        let mut symmetric = request("", "", "R vs R", "");
+        symmetric.either_color = true;
+        assert_eq!(
+            filter(&symmetric).unwrap(),
+            PositionFilter::Material(vec!["KRvKR".to_string()])
+        );
+        assert!(matches!(
+            filter(&request("", "", "", "Kg1")).unwrap(),
+            PositionFilter::Placement(_)
+        ));
+
+        assert!(filter(&request("", "", "", "")).is_err());
+        assert!(filter(&request("", "e4", "R vs R", "")).is_err());
+        assert!(filter(&request("not a fen", "", "", "")).is_err());
+        assert!(filter(&request("", "", "", "Kz9")).is_err());
+    }
+}
@@ -0,0 +1,107 @@
+use sea_orm::DatabaseConnection;
+use tonic::{Request, Response, Status};
+
+use entity::entities::sessions;
+use service::sessions::{mutation, query};
+
+use super::{
+    sessions_server::Sessions, ListSessionsRequest, ListSessionsResponse, LogoutRequest,
+    RevokeAllSessionsRequest, RevokeResponse, RevokeSessionRequest, Session,
+};
+use crate::auth::{
+    interceptor::{authenticated_user, log_request},
+    session::RevokedSessions,
+};
+
+pub struct SessionService {
+    pub db_connection: DatabaseConnection,
+    pub revoked_sessions: RevokedSessions,
+}
+
+impl Session {
+    fn from_model(session: sessions::Model, current_session_id: i32) -> Session {
+        Session {
+            id: session.id,
+            device: session.device.unwrap_or_default(),
+            ip_address: session.ip_address.unwrap_or_default(),
+            created_at: session.created_at.and_utc().timestamp(),
+            last_seen_at: session.last_seen_at.and_utc().timestamp(),
+            expires_at: session.expires_at.and_utc().timestamp(),
+            current: session.id == current_session_id,
+        }
+    }
+}
+
+impl SessionService {
+    fn revoked(&self, ids: Vec<i32>) -> Response<RevokeResponse> {
+        self.revoked_sessions.revoke(&ids);
+        Response::new(RevokeResponse {
+            revoked: ids.len() as i32,
+        })
+    }
+}
+
+#[tonic::async_trait]
+impl Sessions for SessionService {
+    async fn list_sessions(
+        &self,
+        request: Request<ListSessionsRequest>,
+    ) -> Result<Response<ListSessionsResponse>, Status> {
+        log_request("ListSessions", &request);
+        let user = authenticated_user(&request)?;
+        let db_result = query::Query::find_active_sessions_by_user(&self.db_connection, user.id)
+            .await
+            .map_err(|_| Status::internal("Could not load sessions"))?;
+        let sessions = db_result
+            .into_iter()
+            .map(|session| Session::from_model(session, user.session_id))
+            .collect();
+        Ok(Response::new(ListSessionsResponse { sessions }))
+    }
+
+    async fn logout(
+        &self,
+        request: Request<LogoutRequest>,
+    ) -> Result<Response<RevokeResponse>, Status> {
+        log_request("Logout", &request);
+        let user = authenticated_user(&request)?;
+        let ids = mutation::Mutation::revoke_session(&self.db_connection, user.session_id, user.id)
+            .await
+            .map_err(|_| Status::internal("Could not revoke session"))?;
+        Ok(self.revoked(ids))
+    }
+
+    async fn revoke_session(
+        &self,
+        request: Request<RevokeSessionRequest>,
+    ) -> Result<Response<RevokeResponse>, Status> {
+        log_request("RevokeSession", &request);
+        let user = authenticated_user(&request)?;
+        let r = request.into_inner();
+        let ids = mutation::Mutation::revoke_session(&self.db_connection, r.session_id, user.id)
+            .await
+            .map_err(|_| Status::internal("Could not revoke session"))?;
+        if ids.is_empty() {
+            return Err(Status::not_found("Session not found"));
+        }
+        Ok(self.revoked(ids))
+    }
+
+    async fn revoke_all_sessions(
+        &self,
+        request: Request<RevokeAllSessionsRequest>,
+    ) -> Result<Response<RevokeResponse>, Status> {
+        log_request("RevokeAllSessions", &request);
+        let user = authenticated_user(&request)?;
+        let r = request.into_inner();
+        let except = if r.include_current {
+            None
+        } else {
+            Some(user.session_id)
+        };
+        let ids = mutation::Mutation::revoke_all_sessions(&self.db_connection, user.id, except)
+            .await
+            .map_err(|_| Status::internal("Could not revoke sessions"))?;
+        Ok(self.revoked(ids))
+    }
+}
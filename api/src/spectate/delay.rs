@@ -0,0 +1,184 @@
+use std::collections::VecDeque;
+use std::env;
+use std::time::{Duration, Instant};
+
+const DEFAULT_DELAY_PLIES: usize = 2;
+const DEFAULT_DELAY_SECONDS: u64 = 15;
+
+// How far spectators of rated games are kept behind the players. A move is shown once
+// `plies` more moves have been played after it and it is at least `delay` old.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DelayPolicy {
+    pub plies: usize,
+    pub delay: Duration,
+}
+
+impl DelayPolicy {
+    pub fn none() -> DelayPolicy {
+        DelayPolicy {
+            plies: 0,
+            delay: Duration::ZERO,
+        }
+    }
+
+    pub fn from_env() -> DelayPolicy {
+        let plies = env::var("SPECTATOR_DELAY_PLIES")
+            .ok()
+            .and_then(|s| s.parse::<usize>().ok())
+            .unwrap_or(DEFAULT_DELAY_PLIES);
+        let seconds = env::var("SPECTATOR_DELAY_SECONDS")
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok())
+            .unwrap_or(DEFAULT_DELAY_SECONDS);
+        DelayPolicy {
+            plies,
+            delay: Duration::from_secs(seconds),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DelayedMove {
+    pub san: String,
+    // Position after the move
+    pub fen: String,
+    pub played_at: Instant,
+}
+
+pub struct DelayBuffer {
+    policy: DelayPolicy,
+    pending: VecDeque<DelayedMove>,
+    // Plies played so far, shown or not
+    plies: usize,
+}
+
+impl DelayBuffer {
+    pub fn new(policy: DelayPolicy, plies: usize) -> DelayBuffer {
+        DelayBuffer {
+            policy,
+            pending: VecDeque::new(),
+            plies,
+        }
+    }
+
+    pub fn plies(&self) -> usize {
+        self.plies
+    }
+
+    // Moves the spectator has already seen (or that are already queued) are ignored
+    pub fn push(&mut self, ply: usize, delayed_move: DelayedMove) {
+        if ply <= self.plies {
+            return;
+        }
+        self.plies = ply;
+        self.pending.push_back(delayed_move);
+    }
+
+    // Every move after the front one is still pending, so the front has enough moves
+    // after it once more than `plies` are pending
+    fn front_ready(&self, now: Instant) -> bool {
+        match self.pending.front() {
+            Some(front) if self.pending.len() > self.policy.plies => {
+                now.saturating_duration_since(front.played_at) >= self.policy.delay
+            }
+            _ => false,
+        }
+    }
+
+    pub fn release(&mut self, now: Instant) -> Vec<DelayedMove> {
+        let mut released = Vec::new();
+        while self.front_ready(now) {
+            if let Some(front) = self.pending.pop_front() {
+                released.push(front);
+            }
+        }
+        released
+    }
+
+    // Nothing is withheld once the game is over
+    pub fn release_all(&mut self) -> Vec<DelayedMove> {
+        self.pending.drain(..).collect()
+    }
+
+    pub fn withheld(&self) -> usize {
+        self.pending.len()
+    }
+
+    // When the next move becomes visible without any new move being played
+    pub fn next_release(&self) -> Option<Instant> {
+        match self.pending.front() {
+            Some(front) if self.pending.len() > self.policy.plies => {
+                Some(front.played_at + self.policy.delay)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delayed_move(san: &str, played_at: Instant) -> DelayedMove {
+        DelayedMove {
+            san: san.to_string(),
+            fen: String::new(),
+            played_at,
+        }
+    }
+
+    fn sans(moves: Vec<DelayedMove>) -> Vec<String> {
+        moves.into_iter().map(|m| m.san).collect()
+    }
+
+    #[test]
+    fn test_no_delay() {
+        let now = Instant::now();
+        let mut buffer = DelayBuffer::new(DelayPolicy::none(), 0);
+        buffer.push(1, delayed_move("e4", now));
+        assert_eq!(sans(buffer.release(now)), vec!["e4"]);
+        assert_eq!(buffer.next_release(), None);
+    }
+
+    #[test]
+    fn test_ply_delay() {
+        let now = Instant::now();
+        let policy = DelayPolicy {
+            plies: 2,
+            delay: Duration::ZERO,
+        };
+        let mut buffer = DelayBuffer::new(policy, 0);
+        buffer.push(1, delayed_move("e4", now));
+        buffer.push(2, delayed_move("e5", now));
+        assert!(buffer.release(now).is_empty());
+        assert_eq!(buffer.withheld(), 2);
+        buffer.push(3, delayed_move("Nf3", now));
+        assert_eq!(sans(buffer.release(now)), vec!["e4"]);
+        // Already queued
+        buffer.push(3, delayed_move("Nf3", now));
+        assert_eq!(buffer.plies(), 3);
+        assert_eq!(sans(buffer.release_all()), vec!["e5", "Nf3"]);
+    }
+
+    #[test]
+    fn test_time_delay() {
+        let start = Instant::now();
+        let policy = DelayPolicy {
+            plies: 0,
+            delay: Duration::from_secs(10),
+        };
+        let mut buffer = DelayBuffer::new(policy, 4);
+        buffer.push(5, delayed_move("Bb5", start));
+        buffer.push(6, delayed_move("a6", start + Duration::from_secs(5)));
+        assert!(buffer.release(start + Duration::from_secs(9)).is_empty());
+        assert_eq!(buffer.next_release(), Some(start + Duration::from_secs(10)));
+        assert_eq!(
+            sans(buffer.release(start + Duration::from_secs(12))),
+            vec!["Bb5"]
+        );
+        assert_eq!(
+            sans(buffer.release(start + Duration::from_secs(15))),
+            vec!["a6"]
+        );
+    }
+}
@@ -0,0 +1,3 @@
+pub mod delay;
+pub mod service;
+tonic::include_proto!("spectate"); // The string specified here must match the proto package name
@@ -0,0 +1,216 @@
+use std::pin::Pin;
+use std::time::{Duration, Instant};
+
+use chrono::{NaiveDateTime, Utc};
+use sea_orm::DatabaseConnection;
+use tokio::sync::{broadcast::error::RecvError, mpsc};
+use tokio_stream::{wrappers::ReceiverStream, Stream};
+use tonic::{Request, Response, Status};
+
+use entity::entities::game;
+use service::{
+    game::query, game_move::query as game_move_query, tournaments::query as tournament_query,
+};
+
+use super::{
+    delay::{DelayBuffer, DelayPolicy, DelayedMove},
+    spectate_server::Spectate,
+    GameUpdate, WatchGameRequest,
+};
+use crate::auth::interceptor::{authenticated_user, log_request};
+use crate::chess::{
+    events::{GameEvent, GameEvents},
+    game::Game,
+};
+
+type UpdateSender = mpsc::Sender<Result<GameUpdate, Status>>;
+
+pub struct SpectateService {
+    pub db_connection: DatabaseConnection,
+    pub events: GameEvents,
+    pub delay: DelayPolicy,
+}
+
+impl SpectateService {
+    // Each move with the position after it, up to the first one whose position is not known
+    async fn find_positions(
+        &self,
+        game_id: i32,
+    ) -> Result<Vec<(String, String, NaiveDateTime)>, Status> {
+        let moves = game_move_query::Query::find_moves(&self.db_connection, game_id, 1, i32::MAX)
+            .await
+            .map_err(|_| Status::internal("Could not load moves"))?;
+        Ok(moves
+            .into_iter()
+            .map_while(|mv| Some((mv.san, mv.fen?, mv.created_at)))
+            .collect())
+    }
+
+    async fn find_game(&self, game_id: i32) -> Result<game::Model, Status> {
+        let db_result = query::Query::find_game_by_id(&self.db_connection, game_id)
+            .await
+            .map_err(|_| Status::internal("Could not load game"))?;
+        match db_result {
+            Some(game_row) => Ok(game_row),
+            None => Err(Status::not_found("Game not found")),
+        }
+    }
+}
+
+async fn forward_events(
+    match_id: String,
+    mut buffer: DelayBuffer,
+    mut board_state: String,
+    mut events: tokio::sync::broadcast::Receiver<GameEvent>,
+    sender: UpdateSender,
+) {
+    loop {
+        let next_release = buffer.next_release();
+        let mut result = None;
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(GameEvent::Move { ply, san, fen }) => buffer.push(
+                    ply,
+                    DelayedMove {
+                        san,
+                        fen,
+                        played_at: Instant::now(),
+                    },
+                ),
+                Ok(GameEvent::Finished { result: game_result }) => result = Some(game_result),
+                Err(RecvError::Lagged(_)) => {
+                    let _ = sender
+                        .send(Err(Status::data_loss("Fell behind the game, watch it again")))
+                        .await;
+                    return;
+                }
+                Err(RecvError::Closed) => return,
+            },
+            _ = tokio::time::sleep_until(next_release.unwrap_or_else(Instant::now).into()),
+                if next_release.is_some() => {}
+            _ = sender.closed() => return,
+        }
+        let released = match result {
+            Some(_) => buffer.release_all(),
+            None => buffer.release(Instant::now()),
+        };
+        if released.is_empty() && result.is_none() {
+            continue;
+        }
+        if let Some(last) = released.last() {
+            board_state = last.fen.clone();
+        }
+        let update = GameUpdate {
+            match_id: match_id.clone(),
+            board_state: board_state.clone(),
+            moves: released.into_iter().map(|m| m.san).collect(),
+            ply: (buffer.plies() - buffer.withheld()) as u32,
+            withheld: buffer.withheld() as u32,
+            state: match result {
+                Some(_) => "finished".to_string(),
+                None => "active".to_string(),
+            },
+            result: result.clone().unwrap_or_default(),
+        };
+        if sender.send(Ok(update)).await.is_err() || result.is_some() {
+            return;
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl Spectate for SpectateService {
+    type WatchGameStream = Pin<Box<dyn Stream<Item = Result<GameUpdate, Status>> + Send>>;
+
+    async fn watch_game(
+        &self,
+        request: Request<WatchGameRequest>,
+    ) -> Result<Response<Self::WatchGameStream>, Status> {
+        log_request("WatchGame", &request);
+        let user = authenticated_user(&request)?;
+        let r = request.into_inner();
+        let game_id = match r.match_id.parse::<i32>() {
+            Ok(id) => id,
+            Err(_) => return Err(Status::invalid_argument("Invalid match id")),
+        };
+        // Subscribed before the snapshot is read so no move falls in between
+        let events = match self.find_game(game_id).await?.state.as_str() {
+            "active" => self.events.subscribe(game_id),
+            _ => None,
+        };
+        let game_row = self.find_game(game_id).await?;
+        let active = game_row.state == "active";
+        let player = user.id == game_row.player_white || user.id == game_row.player_black;
+        // Tournament games, knockout matches included, are delayed like rated ones even when casual
+        let tournament = active
+            && tournament_query::Query::find_pairing_by_game(&self.db_connection, game_id)
+                .await
+                .map_err(|_| Status::internal("Could not load pairing"))?
+                .is_some();
+        let policy = if active && (game_row.rated || tournament) && !player {
+            self.delay.clone()
+        } else {
+            DelayPolicy::none()
+        };
+
+        let positions = self.find_positions(game_id).await?;
+        // How long ago each move was played, from the time saved with it
+        let now = Utc::now().naive_utc();
+        let ages: Vec<Duration> = positions
+            .iter()
+            .map(|(_, _, created_at)| (now - *created_at).to_std().unwrap_or(Duration::ZERO))
+            .collect();
+        // Shown are the moves the delay would already have released
+        let shown = ages
+            .iter()
+            .enumerate()
+            .take_while(|(i, age)| positions.len() - i > policy.plies && **age >= policy.delay)
+            .count();
+        let withheld = positions.len() - shown;
+        let mut buffer = DelayBuffer::new(policy, shown);
+        for (i, (san, fen, _)) in positions[shown..].iter().enumerate() {
+            buffer.push(
+                shown + i + 1,
+                DelayedMove {
+                    san: san.clone(),
+                    fen: fen.clone(),
+                    played_at: Instant::now()
+                        .checked_sub(ages[shown + i])
+                        .unwrap_or_else(Instant::now),
+                },
+            );
+        }
+        let board_state = if withheld == 0 {
+            game_row.board.clone()
+        } else if shown == 0 {
+            Game::new().to_fen()
+        } else {
+            positions[shown - 1].1.clone()
+        };
+
+        let (sender, receiver) = mpsc::channel(16);
+        let snapshot = GameUpdate {
+            match_id: r.match_id.clone(),
+            board_state: board_state.clone(),
+            moves: positions[..shown]
+                .iter()
+                .map(|(san, _, _)| san.clone())
+                .collect(),
+            ply: shown as u32,
+            withheld: withheld as u32,
+            state: game_row.state.clone(),
+            result: game_row.result.clone().unwrap_or_default(),
+        };
+        let _ = sender.try_send(Ok(snapshot));
+        if let (true, Some(events)) = (active, events) {
+            tokio::spawn(forward_events(
+                r.match_id,
+                buffer,
+                board_state,
+                events,
+                sender,
+            ));
+        }
+        Ok(Response::new(Box::pin(ReceiverStream::new(receiver))))
+    }
+}
@@ -0,0 +1,195 @@
+// Keeps the per-player counts behind profile pages up to date: every finished game adds to its
+// players' totals by pool and color, their openings and their win streaks, so nothing has to be
+// counted over `game` when a profile is shown.
+use sea_orm::{ConnectionTrait, DatabaseConnection, DbErr, TransactionTrait};
+
+use entity::entities::{game, player_opening_stats, player_stats};
+use service::{
+    game::query as game_query,
+    game_move::query as game_move_query,
+    player_stats::{mutation, query},
+    ratings::{mutation::white_score, pool::Pool},
+    time_control::query as time_control_query,
+};
+
+use crate::chess::lifecycle::TIMEOUT;
+
+const BACKFILL_PAGE: u64 = 100;
+
+// What one game adds for its players
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameStats {
+    pub stats: Vec<player_stats::Model>,
+    pub openings: Vec<player_opening_stats::Model>,
+    // Each player with whether they won
+    pub won: Vec<(i32, bool)>,
+}
+
+// Nothing when the game has no result. `plies` is the number of moves of the game.
+pub fn game_stats(game_row: &game::Model, plies: i64, pool: Pool) -> Option<GameStats> {
+    let white = white_score(game_row.result.as_deref()?)?;
+    let timeout = game_row.termination.as_deref() == Some(TIMEOUT);
+    let mut game_stats = GameStats {
+        stats: Vec::new(),
+        openings: Vec::new(),
+        won: Vec::new(),
+    };
+    for (user_id, color, score) in [
+        (game_row.player_white, "white", white),
+        (game_row.player_black, "black", 1.0 - white),
+    ] {
+        let (wins, draws, losses) = (
+            (score == 1.0) as i32,
+            (score == 0.5) as i32,
+            (score == 0.0) as i32,
+        );
+        game_stats.stats.push(player_stats::Model {
+            id: 0,
+            user_id,
+            pool: pool.as_str().to_string(),
+            color: color.to_string(),
+            games: 1,
+            wins,
+            draws,
+            losses,
+            timeouts: (timeout && losses == 1) as i32,
+            plies,
+        });
+        if let (Some(eco), Some(opening)) = (&game_row.eco, &game_row.opening) {
+            game_stats.openings.push(player_opening_stats::Model {
+                id: 0,
+                user_id,
+                color: color.to_string(),
+                eco: eco.clone(),
+                opening: opening.clone(),
+                games: 1,
+                wins,
+                draws,
+                losses,
+            });
+        }
+        game_stats.won.push((user_id, wins == 1));
+    }
+    Some(game_stats)
+}
+
+// What the stats of a game need besides its row: its number of moves and its pool
+pub async fn plies_and_pool(
+    db: &DatabaseConnection,
+    game_row: &game::Model,
+) -> Result<(i64, Pool), DbErr> {
+    let time_control =
+        time_control_query::Query::find_time_control_by_id(db, game_row.time_control)
+            .await?
+            .ok_or(DbErr::Custom("Cannot find time control.".to_owned()))?;
+    let plies = game_move_query::Query::count_moves(db, vec![game_row.id])
+        .await?
+        .remove(&game_row.id)
+        .unwrap_or(0);
+    Ok((plies, Pool::from_time(time_control.time)))
+}
+
+// Called once for every finished game, `db` is the transaction that stores its result so the
+// counts cannot miss a game
+pub async fn add_game<C: ConnectionTrait + TransactionTrait>(
+    db: &C,
+    game_row: &game::Model,
+    plies: i64,
+    pool: Pool,
+) -> Result<(), DbErr> {
+    match game_stats(game_row, plies, pool) {
+        Some(game_stats) => {
+            mutation::Mutation::add_game(db, game_stats.stats, game_stats.openings, game_stats.won)
+                .await
+        }
+        None => Ok(()),
+    }
+}
+
+async fn record_game(db: &DatabaseConnection, game_row: &game::Model) -> Result<(), DbErr> {
+    let (plies, pool) = plies_and_pool(db, game_row).await?;
+    add_game(db, game_row, plies, pool).await
+}
+
+// Counts the games finished before player stats existed, oldest first so the streaks come out
+// right. Only runs while nothing is counted, so a backfill that was interrupted is started over
+// by emptying the player stats tables.
+pub async fn backfill(db: &DatabaseConnection) -> Result<(), DbErr> {
+    if query::Query::has_stats(db).await? {
+        return Ok(());
+    }
+    let mut after_id = 0;
+    loop {
+        let games = game_query::Query::find_finished_games(db, after_id, BACKFILL_PAGE).await?;
+        let last = match games.last() {
+            Some(last) => last.id,
+            None => return Ok(()),
+        };
+        for game_row in &games {
+            record_game(db, game_row).await?;
+        }
+        after_id = last;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn game(result: Option<&str>, termination: Option<&str>) -> game::Model {
+        game::Model {
+            id: 1,
+            player_white: 7,
+            player_black: 9,
+            time_control: 1,
+            board: String::new(),
+            turn: "white".to_string(),
+            black_time: 0,
+            white_time: 0,
+            state: "finished".to_string(),
+            created_at: Default::default(),
+            updated_at: None,
+            rated: true,
+            result: result.map(str::to_string),
+            white_clock: None,
+            black_clock: None,
+            eco: Some("C60".to_string()),
+            opening: Some("Ruy Lopez".to_string()),
+            termination: termination.map(str::to_string),
+            replay_failed: false,
+        }
+    }
+
+    #[test]
+    fn test_game_stats() {
+        let counted = game_stats(&game(Some("0-1"), Some(TIMEOUT)), 5, Pool::Blitz).unwrap();
+        let [white, black] = [&counted.stats[0], &counted.stats[1]];
+        assert_eq!((white.user_id, white.color.as_str()), (7, "white"));
+        assert_eq!((white.wins, white.draws, white.losses), (0, 0, 1));
+        assert_eq!(white.timeouts, 1);
+        assert_eq!((black.user_id, black.color.as_str()), (9, "black"));
+        assert_eq!((black.wins, black.draws, black.losses), (1, 0, 0));
+        assert_eq!(black.timeouts, 0);
+        assert_eq!(white.pool, "blitz");
+        assert_eq!(white.plies, 5);
+        assert_eq!(counted.openings.len(), 2);
+        assert_eq!(counted.openings[1].eco, "C60");
+        assert_eq!(counted.openings[1].wins, 1);
+        assert_eq!(counted.won, vec![(7, false), (9, true)]);
+
+        let drawn = game_stats(&game(Some("1/2-1/2"), None), 5, Pool::Rapid).unwrap();
+        assert!(drawn
+            .stats
+            .iter()
+            .all(|row| row.draws == 1 && row.timeouts == 0));
+        assert_eq!(drawn.won, vec![(7, false), (9, false)]);
+
+        let mut unnamed = game(Some("1-0"), None);
+        unnamed.eco = None;
+        assert!(game_stats(&unnamed, 5, Pool::Rapid)
+            .unwrap()
+            .openings
+            .is_empty());
+        assert!(game_stats(&game(None, None), 5, Pool::Rapid).is_none());
+    }
+}
@@ -0,0 +1,236 @@
+use sea_orm::DatabaseConnection;
+use tonic::{Request, Response, Status};
+
+use entity::entities::{player_opening_stats, player_stats};
+use service::{
+    player_stats::query,
+    ratings::{glicko2::Glicko2, pool::Pool, query as rating_query},
+    users::query as user_query,
+};
+
+use super::{
+    player_stats_server::PlayerStats, GetPlayerStatsRequest, GetPlayerStatsResponse, OpeningStats,
+    PoolStats, Record,
+};
+use crate::auth::interceptor::{authenticated_user, log_request};
+
+const TOP_OPENINGS: u64 = 10;
+
+pub struct PlayerStatsService {
+    pub db_connection: DatabaseConnection,
+}
+
+impl Record {
+    fn add(&mut self, games: i32, wins: i32, draws: i32, losses: i32) {
+        self.games += games;
+        self.wins += wins;
+        self.draws += draws;
+        self.losses += losses;
+    }
+
+    // Points in percent, a draw counting half
+    fn score(&self) -> f64 {
+        if self.games == 0 {
+            return 0.0;
+        }
+        (self.wins as f64 + self.draws as f64 / 2.0) * 100.0 / self.games as f64
+    }
+}
+
+fn record(rows: &[&player_stats::Model]) -> Record {
+    let mut record = Record::default();
+    for row in rows {
+        record.add(row.games, row.wins, row.draws, row.losses);
+    }
+    record
+}
+
+// Totals, by color and by pool, the ratings are filled in by the caller
+fn summarize(rows: &[player_stats::Model]) -> GetPlayerStatsResponse {
+    let all: Vec<_> = rows.iter().collect();
+    let by_color = |color: &str| {
+        record(
+            &all.iter()
+                .copied()
+                .filter(|row| row.color == color)
+                .collect::<Vec<_>>(),
+        )
+    };
+    let total = record(&all);
+    let plies: i64 = rows.iter().map(|row| row.plies).sum();
+    let timeouts: i32 = rows.iter().map(|row| row.timeouts).sum();
+    let (average_game_length, timeout_rate) = match total.games {
+        0 => (0.0, 0.0),
+        games => (
+            plies as f64 / 2.0 / games as f64,
+            timeouts as f64 / games as f64,
+        ),
+    };
+    let pools = Pool::ALL
+        .into_iter()
+        .map(|pool| {
+            let in_pool = |color: &str| {
+                record(
+                    &all.iter()
+                        .copied()
+                        .filter(|row| row.pool == pool.as_str() && row.color == color)
+                        .collect::<Vec<_>>(),
+                )
+            };
+            PoolStats {
+                pool: pool.as_str().to_string(),
+                white: Some(in_pool("white")),
+                black: Some(in_pool("black")),
+                ..Default::default()
+            }
+        })
+        .collect();
+    GetPlayerStatsResponse {
+        total: Some(total),
+        white: Some(by_color("white")),
+        black: Some(by_color("black")),
+        pools,
+        average_game_length,
+        timeout_rate,
+        ..Default::default()
+    }
+}
+
+fn to_opening(row: &player_opening_stats::Model) -> OpeningStats {
+    let mut record = Record::default();
+    record.add(row.games, row.wins, row.draws, row.losses);
+    OpeningStats {
+        eco: row.eco.clone(),
+        opening: row.opening.clone(),
+        color: row.color.clone(),
+        score: record.score(),
+        record: Some(record),
+    }
+}
+
+#[tonic::async_trait]
+impl PlayerStats for PlayerStatsService {
+    async fn get_player_stats(
+        &self,
+        request: Request<GetPlayerStatsRequest>,
+    ) -> Result<Response<GetPlayerStatsResponse>, Status> {
+        log_request("GetPlayerStats", &request);
+        let caller = authenticated_user(&request)?;
+        let r = request.into_inner();
+        let db = &self.db_connection;
+        let user = match r.username.as_str() {
+            "" => user_query::Query::find_user_by_id(db, caller.id).await,
+            username => user_query::Query::find_user_by_username(db, username).await,
+        }
+        .map_err(|_| Status::internal("Could not load player"))?
+        .filter(|user| user.deleted_at.is_none())
+        .ok_or_else(|| Status::not_found("Player not found"))?;
+
+        let rows = query::Query::find_stats(db, user.id)
+            .await
+            .map_err(|_| Status::internal("Could not load stats"))?;
+        let mut response = summarize(&rows);
+        response.username = user.username;
+
+        let ratings = rating_query::Query::find_ratings_by_user(db, user.id)
+            .await
+            .map_err(|_| Status::internal("Could not load ratings"))?;
+        let peaks = rating_query::Query::find_peak_ratings(db, user.id)
+            .await
+            .map_err(|_| Status::internal("Could not load ratings"))?;
+        for pool_stats in &mut response.pools {
+            let rating = ratings.iter().find(|rating| rating.pool == pool_stats.pool);
+            let glicko2 = rating.map(Glicko2::from).unwrap_or_default();
+            pool_stats.rating = glicko2.rating;
+            pool_stats.provisional = glicko2.is_provisional();
+            pool_stats.peak_rating = peaks
+                .iter()
+                .find(|(pool, _)| *pool == pool_stats.pool)
+                .map_or(glicko2.rating, |&(_, peak)| peak.max(glicko2.rating));
+        }
+
+        if let Some(streak) = query::Query::find_streak(db, user.id)
+            .await
+            .map_err(|_| Status::internal("Could not load streak"))?
+        {
+            response.current_win_streak = streak.current;
+            response.longest_win_streak = streak.longest;
+        }
+        response.openings = query::Query::find_openings(db, user.id, TOP_OPENINGS)
+            .await
+            .map_err(|_| Status::internal("Could not load openings"))?
+            .iter()
+            .map(to_opening)
+            .collect();
+        Ok(Response::new(response))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(pool: &str, color: &str, counts: [i32; 5], plies: i64) -> player_stats::Model {
+        let [games, wins, draws, losses, timeouts] = counts;
+        player_stats::Model {
+            id: 0,
+            user_id: 7,
+            pool: pool.to_string(),
+            color: color.to_string(),
+            games,
+            wins,
+            draws,
+            losses,
+            timeouts,
+            plies,
+        }
+    }
+
+    #[test]
+    fn test_summarize() {
+        let rows = [
+            row("blitz", "white", [4, 2, 1, 1, 1], 320),
+            row("blitz", "black", [2, 0, 0, 2, 0], 100),
+            row("rapid", "white", [2, 1, 1, 0, 0], 180),
+        ];
+        let response = summarize(&rows);
+        let total = response.total.unwrap();
+        assert_eq!(
+            (total.games, total.wins, total.draws, total.losses),
+            (8, 3, 2, 3)
+        );
+        assert_eq!(response.white.unwrap().games, 6);
+        assert_eq!(response.black.unwrap().losses, 2);
+        // 600 plies over 8 games
+        assert_eq!(response.average_game_length, 37.5);
+        assert_eq!(response.timeout_rate, 0.125);
+
+        assert_eq!(response.pools.len(), Pool::ALL.len());
+        let blitz = &response.pools[1];
+        assert_eq!(blitz.pool, "blitz");
+        assert_eq!(blitz.white.as_ref().unwrap().wins, 2);
+        assert_eq!(blitz.black.as_ref().unwrap().games, 2);
+        assert_eq!(response.pools[0].white.as_ref().unwrap().games, 0);
+
+        let empty = summarize(&[]);
+        assert_eq!(empty.total.unwrap().games, 0);
+        assert_eq!(empty.average_game_length, 0.0);
+    }
+
+    #[test]
+    fn test_opening_score() {
+        let opening = to_opening(&player_opening_stats::Model {
+            id: 0,
+            user_id: 7,
+            color: "black".to_string(),
+            eco: "B90".to_string(),
+            opening: "Sicilian Defense: Najdorf Variation".to_string(),
+            games: 4,
+            wins: 2,
+            draws: 1,
+            losses: 1,
+        });
+        assert_eq!(opening.score, 62.5);
+        assert_eq!(opening.record.unwrap().games, 4);
+    }
+}
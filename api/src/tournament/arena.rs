@@ -0,0 +1,256 @@
+// Arena tournaments: players are paired again as soon as their game is over until the time
+// runs out. A win is worth 2 points and a draw 1, after two wins in a row a player is on fire
+// and scores double until they fail to win. Going berserk halves the clock for an extra point
+// on a win.
+use std::collections::HashMap;
+
+use super::standings::{PairingRecord, TournamentPlayer};
+use crate::chess::pieces::Color;
+
+const WIN: i32 = 2;
+const DRAW: i32 = 1;
+const BERSERK_BONUS: i32 = 1;
+// Wins in a row needed to be on fire
+const STREAK: usize = 2;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ArenaStanding {
+    pub rank: usize,
+    pub user_id: i32,
+    pub score: i32,
+    pub rating: f64,
+    pub on_fire: bool,
+    // Points scored in each finished game, in order
+    pub sheet: Vec<i32>,
+    pub withdrawn: bool,
+}
+
+// Points of every finished game of `user_id` in the order they were played, and whether the
+// player is currently on fire
+fn sheet(user_id: i32, pairings: &[PairingRecord]) -> (Vec<i32>, bool) {
+    let mut sheet = Vec::new();
+    let mut wins = 0;
+    for pairing in pairings.iter().filter(|p| p.involves(user_id)) {
+        let points = match pairing.points(user_id) {
+            Some(points) => points,
+            None => continue,
+        };
+        let on_fire = wins >= STREAK;
+        let berserk = if pairing.white == user_id {
+            pairing.white_berserk
+        } else {
+            pairing.black_berserk
+        };
+        let mut score = if points > 0.5 {
+            WIN
+        } else if points == 0.5 {
+            DRAW
+        } else {
+            0
+        };
+        if on_fire {
+            score *= 2;
+        }
+        if points > 0.5 {
+            wins += 1;
+            if berserk {
+                score += BERSERK_BONUS;
+            }
+        } else {
+            wins = 0;
+        }
+        sheet.push(score);
+    }
+    (sheet, wins >= STREAK)
+}
+
+// Ranked by score, then rating at registration
+pub fn standings(players: &[TournamentPlayer], pairings: &[PairingRecord]) -> Vec<ArenaStanding> {
+    let mut standings: Vec<ArenaStanding> = players
+        .iter()
+        .map(|player| {
+            let (sheet, on_fire) = sheet(player.id, pairings);
+            ArenaStanding {
+                rank: 0,
+                user_id: player.id,
+                score: sheet.iter().sum(),
+                rating: player.rating,
+                on_fire,
+                sheet,
+                withdrawn: player.withdrawn,
+            }
+        })
+        .collect();
+    standings.sort_by(|a, b| {
+        b.score
+            .cmp(&a.score)
+            .then(b.rating.total_cmp(&a.rating))
+            .then(a.user_id.cmp(&b.user_id))
+    });
+    for (i, standing) in standings.iter_mut().enumerate() {
+        standing.rank = i + 1;
+    }
+    standings
+}
+
+// Whites minus blacks so far
+fn white_balance(player: &TournamentPlayer) -> i32 {
+    let whites = player.colors.iter().filter(|c| **c == Color::White).count() as i32;
+    2 * whites - player.colors.len() as i32
+}
+
+// Pairs the players who are not in a game, closest in the standings first. A player does not
+// meet the opponent of their last game again right away unless there is no one else, and the
+// one who had white more often gets black.
+pub fn pair_waiting(
+    players: &[TournamentPlayer],
+    pairings: &[PairingRecord],
+    scores: &HashMap<i32, i32>,
+) -> Vec<(i32, i32)> {
+    let mut waiting: Vec<&TournamentPlayer> = players
+        .iter()
+        .filter(|p| !p.withdrawn)
+        .filter(|p| {
+            !pairings
+                .iter()
+                .any(|pairing| pairing.involves(p.id) && pairing.result.is_none())
+        })
+        .collect();
+    let score = |id: i32| scores.get(&id).copied().unwrap_or(0);
+    waiting.sort_by(|a, b| {
+        score(b.id)
+            .cmp(&score(a.id))
+            .then(b.rating.total_cmp(&a.rating))
+            .then(a.id.cmp(&b.id))
+    });
+
+    let mut games = Vec::new();
+    while waiting.len() >= 2 {
+        let first = waiting.remove(0);
+        let last_opponent = first.opponents.last().copied();
+        let index = waiting
+            .iter()
+            .position(|p| Some(p.id) != last_opponent && p.opponents.last() != Some(&first.id))
+            .unwrap_or(0);
+        let second = waiting.remove(index);
+        if white_balance(first) > white_balance(second) {
+            games.push((second.id, first.id));
+        } else {
+            games.push((first.id, second.id));
+        }
+    }
+    games
+}
+
+pub fn scores(standings: &[ArenaStanding]) -> HashMap<i32, i32> {
+    standings.iter().map(|s| (s.user_id, s.score)).collect()
+}
+
+// Crosstable as CSV, the sheet lists the points of each game in order
+pub fn export_csv(standings: &[ArenaStanding], names: &HashMap<i32, String>) -> String {
+    let mut csv = String::from("Rank,Player,Rating,Score,Games,Sheet\n");
+    for standing in standings {
+        let name = names.get(&standing.user_id).cloned().unwrap_or_default();
+        let sheet: Vec<String> = standing.sheet.iter().map(|p| p.to_string()).collect();
+        csv.push_str(&format!(
+            "{},{},{:.0},{},{},{}\n",
+            standing.rank,
+            name.replace(',', " "),
+            standing.rating,
+            standing.score,
+            standing.sheet.len(),
+            sheet.join(" "),
+        ));
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tournament::standings::build_players;
+
+    fn record(round: i32, white: i32, black: i32, result: Option<&str>) -> PairingRecord {
+        PairingRecord {
+            round,
+            white,
+            black: Some(black),
+            result: result.map(|r| r.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_streak_doubles_points() {
+        let pairings = vec![
+            record(1, 1, 2, Some("1-0")),
+            record(2, 3, 1, Some("0-1")),
+            record(3, 1, 2, Some("1-0")),
+            record(4, 1, 3, Some("1/2-1/2")),
+            record(5, 1, 2, Some("1-0")),
+        ];
+        let (points, on_fire) = sheet(1, &pairings);
+        assert_eq!(points, vec![2, 2, 4, 2, 2]);
+        assert!(!on_fire);
+        assert_eq!(sheet(3, &pairings).0, vec![0, 1]);
+    }
+
+    #[test]
+    fn test_berserk_bonus_on_win_only() {
+        let mut win = record(1, 1, 2, Some("1-0"));
+        win.white_berserk = true;
+        win.black_berserk = true;
+        let mut loss = record(2, 1, 2, Some("0-1"));
+        loss.white_berserk = true;
+        let pairings = vec![win, loss];
+        assert_eq!(sheet(1, &pairings).0, vec![3, 0]);
+        assert_eq!(sheet(2, &pairings).0, vec![0, 2]);
+    }
+
+    #[test]
+    fn test_standings_order() {
+        let registrations = vec![(1, 1500.0, false), (2, 1600.0, false), (3, 1700.0, false)];
+        let pairings = vec![
+            record(1, 1, 2, Some("1-0")),
+            record(2, 3, 2, Some("1/2-1/2")),
+        ];
+        let players = build_players(&registrations, &pairings);
+        let standings = standings(&players, &pairings);
+        assert_eq!(
+            standings.iter().map(|s| s.user_id).collect::<Vec<_>>(),
+            vec![1, 3, 2]
+        );
+        assert_eq!(standings[0].score, 2);
+        assert_eq!(standings[1].score, 1);
+    }
+
+    #[test]
+    fn test_pair_waiting_skips_playing_and_last_opponent() {
+        let registrations = vec![
+            (1, 1500.0, false),
+            (2, 1500.0, false),
+            (3, 1500.0, false),
+            (4, 1500.0, false),
+            (5, 1500.0, false),
+        ];
+        let pairings = vec![
+            record(1, 1, 2, Some("1-0")),
+            record(1, 3, 4, Some("1-0")),
+            record(1, 5, 6, None),
+        ];
+        let players = build_players(&registrations, &pairings);
+        let scores = scores(&standings(&players, &pairings));
+        let games = pair_waiting(&players, &pairings, &scores);
+        // 5 is still playing, the leaders meet and nobody faces their last opponent
+        assert_eq!(games, vec![(1, 3), (2, 4)]);
+    }
+
+    #[test]
+    fn test_pair_waiting_allows_rematch_when_alone() {
+        let registrations = vec![(1, 1500.0, false), (2, 1500.0, false)];
+        let pairings = vec![record(1, 1, 2, Some("1-0"))];
+        let players = build_players(&registrations, &pairings);
+        let games = pair_waiting(&players, &pairings, &HashMap::new());
+        assert_eq!(games, vec![(2, 1)]);
+    }
+}
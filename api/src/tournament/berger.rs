@@ -0,0 +1,115 @@
+// Round-robin schedules from the Berger tables (FIDE C.05 annex 1). Players are numbered by
+// seed from 0, with an odd number of players the extra seat means a bye. A double round-robin
+// plays the same cycle twice with colors reversed.
+
+// Games of every round of one cycle as (white, black) seeds, `None` is the bye seat
+pub fn cycle(players: usize) -> Vec<Vec<(Option<usize>, Option<usize>)>> {
+    if players < 2 {
+        return Vec::new();
+    }
+    let seats = players + players % 2;
+    let fixed = seats - 1;
+    let seat = |i: usize| if i < players { Some(i) } else { None };
+    (0..fixed)
+        .map(|round| {
+            // The player facing the last seat moves by half the table each round
+            let pivot = round * seats / 2 % fixed;
+            let mut games = Vec::with_capacity(seats / 2);
+            if round % 2 == 0 {
+                games.push((seat(pivot), seat(fixed)));
+            } else {
+                games.push((seat(fixed), seat(pivot)));
+            }
+            for board in 1..seats / 2 {
+                let white = (pivot + board) % fixed;
+                let black = (pivot + fixed - board) % fixed;
+                games.push((seat(white), seat(black)));
+            }
+            games
+        })
+        .collect()
+}
+
+// Games of `round` (from 1) out of `cycles` cycles, None once the schedule is over
+pub fn round(
+    players: usize,
+    cycles: usize,
+    round: usize,
+) -> Option<Vec<(Option<usize>, Option<usize>)>> {
+    let cycle = cycle(players);
+    if round == 0 || cycle.is_empty() || round > cycle.len() * cycles {
+        return None;
+    }
+    let index = round - 1;
+    let games = cycle[index % cycle.len()].clone();
+    if (index / cycle.len()) % 2 == 0 {
+        Some(games)
+    } else {
+        Some(games.into_iter().map(|(w, b)| (b, w)).collect())
+    }
+}
+
+pub fn rounds(players: usize, cycles: usize) -> usize {
+    cycle(players).len() * cycles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn seeds(games: &[(Option<usize>, Option<usize>)]) -> Vec<(usize, usize)> {
+        games.iter().filter_map(|&(w, b)| Some((w?, b?))).collect()
+    }
+
+    #[test]
+    fn test_four_players_matches_table() {
+        let cycle = cycle(4);
+        // 1-4 2-3 / 4-3 1-2 / 2-4 3-1
+        assert_eq!(seeds(&cycle[0]), vec![(0, 3), (1, 2)]);
+        assert_eq!(seeds(&cycle[1]), vec![(3, 2), (0, 1)]);
+        assert_eq!(seeds(&cycle[2]), vec![(1, 3), (2, 0)]);
+    }
+
+    #[test]
+    fn test_six_players_matches_table() {
+        let cycle = cycle(6);
+        // 1-6 2-5 3-4 / 6-4 5-3 1-2 / 2-6 3-1 4-5
+        assert_eq!(seeds(&cycle[0]), vec![(0, 5), (1, 4), (2, 3)]);
+        assert_eq!(seeds(&cycle[1]), vec![(5, 3), (4, 2), (0, 1)]);
+        assert_eq!(seeds(&cycle[2]), vec![(1, 5), (2, 0), (3, 4)]);
+    }
+
+    #[test]
+    fn test_everyone_meets_once_with_byes() {
+        for players in 2..=9 {
+            let cycle = cycle(players);
+            let mut met = HashSet::new();
+            let mut byes = vec![0; players];
+            for games in &cycle {
+                for &(white, black) in games {
+                    match (white, black) {
+                        (Some(w), Some(b)) => assert!(met.insert((w.min(b), w.max(b)))),
+                        (Some(p), None) | (None, Some(p)) => byes[p] += 1,
+                        (None, None) => unreachable!(),
+                    }
+                }
+            }
+            assert_eq!(met.len(), players * (players - 1) / 2);
+            let expected = if players % 2 == 1 { 1 } else { 0 };
+            assert!(byes.iter().all(|&b| b == expected));
+        }
+    }
+
+    #[test]
+    fn test_double_round_robin_reverses_colors() {
+        assert_eq!(rounds(4, 2), 6);
+        let first = round(4, 2, 1).unwrap();
+        let fourth = round(4, 2, 4).unwrap();
+        assert_eq!(
+            fourth,
+            first.iter().map(|&(w, b)| (b, w)).collect::<Vec<_>>()
+        );
+        assert_eq!(round(4, 2, 7), None);
+    }
+}
@@ -0,0 +1,602 @@
+use std::{collections::HashMap, time::Duration};
+
+use chrono::Utc;
+use sea_orm::DatabaseConnection;
+use tonic::Status;
+
+use entity::entities::{game, tournament, tournament_match, tournament_pairing};
+use service::tournaments::{mutation, query};
+
+use super::{
+    arena, berger,
+    knockout::{
+        self, Bracket, BracketMatch, MatchKey, Outcome, SeriesGame, SeriesState, Slot, Stage,
+    },
+    standings::{build_players, PairingRecord, TournamentPlayer, BYE},
+    swiss,
+};
+use crate::chess::lifecycle;
+
+// How often waiting arena players are paired and finished arenas closed
+const ARENA_INTERVAL: Duration = Duration::from_secs(3);
+
+impl From<&tournament_pairing::Model> for PairingRecord {
+    fn from(pairing: &tournament_pairing::Model) -> Self {
+        PairingRecord {
+            round: pairing.round,
+            white: pairing.player_white,
+            black: pairing.player_black,
+            result: pairing.result.clone(),
+            white_berserk: pairing.white_berserk,
+            black_berserk: pairing.black_berserk,
+        }
+    }
+}
+
+// A round before it is saved
+#[derive(Default)]
+struct Round {
+    // (white, black) of the games to start
+    games: Vec<(i32, i32)>,
+    // (white, black, result) of games decided without playing, when an opponent withdrew
+    forfeits: Vec<(i32, i32, &'static str)>,
+    bye: Option<i32>,
+}
+
+fn cycles(tournament: &tournament::Model) -> usize {
+    match tournament.format.as_str() {
+        "double_round_robin" => 2,
+        _ => 1,
+    }
+}
+
+fn is_round_robin(tournament: &tournament::Model) -> bool {
+    matches!(
+        tournament.format.as_str(),
+        "round_robin" | "double_round_robin"
+    )
+}
+
+fn is_knockout(tournament: &tournament::Model) -> bool {
+    matches!(
+        tournament.format.as_str(),
+        "knockout" | "double_elimination"
+    )
+}
+
+// Seeds by rating at registration, so the schedule stays the same whoever withdraws
+fn seeded(players: &[TournamentPlayer]) -> Vec<&TournamentPlayer> {
+    let mut seeded: Vec<&TournamentPlayer> = players.iter().collect();
+    seeded.sort_by(|a, b| b.rating.total_cmp(&a.rating).then(a.id.cmp(&b.id)));
+    seeded
+}
+
+fn round_robin_round(players: &[TournamentPlayer], cycles: usize, round: usize) -> Option<Round> {
+    let seeded = seeded(players);
+    let mut pairing = Round::default();
+    for (white, black) in berger::round(seeded.len(), cycles, round)? {
+        // A bye is worth nothing in a round-robin, so it is not recorded
+        let (white, black) = match (white, black) {
+            (Some(white), Some(black)) => (seeded[white], seeded[black]),
+            _ => continue,
+        };
+        match (white.withdrawn, black.withdrawn) {
+            (false, false) => pairing.games.push((white.id, black.id)),
+            (false, true) => pairing.forfeits.push((white.id, black.id, "1-0")),
+            (true, false) => pairing.forfeits.push((white.id, black.id, "0-1")),
+            (true, true) => {}
+        }
+    }
+    Some(pairing)
+}
+
+pub async fn load_players(
+    db: &DatabaseConnection,
+    tournament_id: i32,
+) -> Result<(Vec<TournamentPlayer>, Vec<PairingRecord>), Status> {
+    let players = query::Query::find_players(db, tournament_id)
+        .await
+        .map_err(|_| Status::internal("Could not load players"))?;
+    let pairings = query::Query::find_pairings(db, tournament_id)
+        .await
+        .map_err(|_| Status::internal("Could not load pairings"))?;
+    let registrations: Vec<(i32, f64, bool)> = players
+        .iter()
+        .map(|p| (p.user_id, p.rating, p.withdrawn))
+        .collect();
+    let records: Vec<PairingRecord> = pairings.iter().map(PairingRecord::from).collect();
+    Ok((build_players(&registrations, &records), records))
+}
+
+async fn finish(db: &DatabaseConnection, tournament: &tournament::Model) -> Result<(), Status> {
+    mutation::Mutation::finish_tournament(db, tournament.id)
+        .await
+        .map_err(|_| Status::internal("Could not finish tournament"))?;
+    Ok(())
+}
+
+// Closes registration: arenas start their clock, knockouts draw their bracket and the other
+// formats pair the first round
+pub async fn start(db: &DatabaseConnection, tournament: &tournament::Model) -> Result<(), Status> {
+    if tournament.format == "arena" {
+        let minutes = tournament.duration.unwrap_or_default() as i64;
+        let ends_at = Utc::now().naive_utc() + chrono::Duration::minutes(minutes);
+        // Players are paired by `run`
+        mutation::Mutation::start_arena(db, tournament.id, ends_at)
+            .await
+            .map_err(|_| Status::internal("Could not start tournament"))?;
+        return Ok(());
+    }
+    if is_knockout(tournament) {
+        return start_bracket(db, tournament).await;
+    }
+    if is_round_robin(tournament) {
+        let players = query::Query::find_players(db, tournament.id)
+            .await
+            .map_err(|_| Status::internal("Could not load players"))?;
+        let rounds = berger::rounds(players.len(), cycles(tournament)) as i32;
+        let tournament = mutation::Mutation::set_rounds(db, tournament.id, rounds)
+            .await
+            .map_err(|_| Status::internal("Could not start tournament"))?;
+        return start_next_round(db, &tournament).await;
+    }
+    start_next_round(db, tournament).await
+}
+
+// Pairs the next round and starts its games, or finishes the tournament after the last one
+pub async fn start_next_round(
+    db: &DatabaseConnection,
+    tournament: &tournament::Model,
+) -> Result<(), Status> {
+    let mut tournament = tournament.clone();
+    loop {
+        if let Some(rounds) = tournament.rounds {
+            if tournament.current_round >= rounds {
+                return finish(db, &tournament).await;
+            }
+        }
+        let (players, _) = load_players(db, tournament.id).await?;
+        let round = tournament.current_round + 1;
+        let pairing = if is_round_robin(&tournament) {
+            round_robin_round(&players, cycles(&tournament), round as usize)
+        } else {
+            swiss::pair_round(&players).map(|pairing| Round {
+                games: pairing.games,
+                forfeits: Vec::new(),
+                bye: pairing.bye,
+            })
+        };
+        let pairing = match pairing {
+            Some(pairing) => pairing,
+            // Everyone has met everyone they can
+            None => return finish(db, &tournament).await,
+        };
+        let advanced =
+            mutation::Mutation::advance_round(db, tournament.id, tournament.current_round)
+                .await
+                .map_err(|_| Status::internal("Could not start round"))?;
+        if !advanced {
+            return Ok(());
+        }
+
+        for &(white, black) in &pairing.games {
+            let game_row = lifecycle::start_game(
+                db,
+                white,
+                black,
+                tournament.time_control,
+                tournament.rated,
+                None,
+            )
+            .await?;
+            let pairing = tournament_pairing::Model {
+                game_id: Some(game_row.id),
+                ..new_pairing(tournament.id, round, white, Some(black))
+            };
+            save_pairing(db, pairing).await?;
+        }
+        for &(white, black, result) in &pairing.forfeits {
+            let pairing = tournament_pairing::Model {
+                result: Some(result.to_string()),
+                ..new_pairing(tournament.id, round, white, Some(black))
+            };
+            save_pairing(db, pairing).await?;
+        }
+        if let Some(bye) = pairing.bye {
+            let pairing = tournament_pairing::Model {
+                result: Some(BYE.to_string()),
+                ..new_pairing(tournament.id, round, bye, None)
+            };
+            save_pairing(db, pairing).await?;
+        }
+        if !pairing.games.is_empty() {
+            return Ok(());
+        }
+        // Nothing to play this round, move on to the next one
+        tournament = match query::Query::find_tournament_by_id(db, tournament.id)
+            .await
+            .map_err(|_| Status::internal("Could not load tournament"))?
+        {
+            Some(tournament) => tournament,
+            None => return Ok(()),
+        };
+    }
+}
+
+// Pairs every arena player who is not in a game. Each batch of pairings is a round, so two
+// callers cannot pair the same players.
+async fn pair_arena(db: &DatabaseConnection, tournament: &tournament::Model) -> Result<(), Status> {
+    let (players, records) = load_players(db, tournament.id).await?;
+    let standings = arena::standings(&players, &records);
+    let games = arena::pair_waiting(&players, &records, &arena::scores(&standings));
+    if games.is_empty() {
+        return Ok(());
+    }
+    let advanced = mutation::Mutation::advance_round(db, tournament.id, tournament.current_round)
+        .await
+        .map_err(|_| Status::internal("Could not pair players"))?;
+    if !advanced {
+        return Ok(());
+    }
+    let round = tournament.current_round + 1;
+    for (white, black) in games {
+        let game_row = lifecycle::start_game(
+            db,
+            white,
+            black,
+            tournament.time_control,
+            tournament.rated,
+            None,
+        )
+        .await?;
+        let pairing = tournament_pairing::Model {
+            game_id: Some(game_row.id),
+            ..new_pairing(tournament.id, round, white, Some(black))
+        };
+        save_pairing(db, pairing).await?;
+    }
+    Ok(())
+}
+
+fn series_games(tournament: &tournament::Model) -> usize {
+    tournament.series_games.unwrap_or(1).max(1) as usize
+}
+
+fn match_key(match_row: &tournament_match::Model) -> Option<MatchKey> {
+    Some(MatchKey {
+        bracket: Bracket::parse(&match_row.bracket)?,
+        round: match_row.round,
+        position: match_row.position,
+    })
+}
+
+fn series_game(pairing: &tournament_pairing::Model) -> Option<SeriesGame> {
+    Some(SeriesGame {
+        stage: Stage::parse(pairing.stage.as_deref()?)?,
+        white: pairing.player_white,
+        black: pairing.player_black?,
+        result: pairing.result.clone(),
+    })
+}
+
+pub async fn load_bracket(
+    db: &DatabaseConnection,
+    tournament_id: i32,
+) -> Result<Vec<BracketMatch>, Status> {
+    let matches = query::Query::find_matches(db, tournament_id)
+        .await
+        .map_err(|_| Status::internal("Could not load matches"))?;
+    let pairings = query::Query::find_pairings(db, tournament_id)
+        .await
+        .map_err(|_| Status::internal("Could not load pairings"))?;
+    Ok(matches
+        .iter()
+        .filter_map(|m| {
+            Some(BracketMatch {
+                key: match_key(m)?,
+                one: m.player_one,
+                two: m.player_two,
+                winner: m.winner,
+                loser: m.loser,
+                finished: m.state == "finished",
+                games: pairings
+                    .iter()
+                    .filter(|p| p.match_id == Some(m.id))
+                    .filter_map(series_game)
+                    .collect(),
+            })
+        })
+        .collect())
+}
+
+// Draws the bracket, only once even if the tournament is started twice
+async fn start_bracket(
+    db: &DatabaseConnection,
+    tournament: &tournament::Model,
+) -> Result<(), Status> {
+    let advanced = mutation::Mutation::advance_round(db, tournament.id, 0)
+        .await
+        .map_err(|_| Status::internal("Could not start tournament"))?;
+    if !advanced {
+        return Ok(());
+    }
+    let players = query::Query::find_players(db, tournament.id)
+        .await
+        .map_err(|_| Status::internal("Could not load players"))?;
+    let double = tournament.format == "double_elimination";
+    for plan in knockout::plan(players.len(), double) {
+        mutation::Mutation::create_match(
+            db,
+            tournament_match::Model {
+                id: 0,
+                tournament_id: tournament.id,
+                bracket: plan.key.bracket.as_str().to_string(),
+                round: plan.key.round,
+                position: plan.key.position,
+                player_one: None,
+                player_two: None,
+                winner: None,
+                loser: None,
+                state: "pending".to_string(),
+                created_at: Default::default(),
+                updated_at: None,
+            },
+        )
+        .await
+        .map_err(|_| Status::internal("Could not create match"))?;
+    }
+    advance_bracket(db, tournament).await
+}
+
+// Starts every match whose players are known, settling byes and withdrawals as walkovers, and
+// finishes the tournament once the last match is over
+async fn advance_bracket(
+    db: &DatabaseConnection,
+    tournament: &tournament::Model,
+) -> Result<(), Status> {
+    let double = tournament.format == "double_elimination";
+    loop {
+        let (players, _) = load_players(db, tournament.id).await?;
+        let seeds: Vec<i32> = seeded(&players).iter().map(|p| p.id).collect();
+        let withdrawn = |id: i32| players.iter().any(|p| p.id == id && p.withdrawn);
+        let plan = knockout::plan(seeds.len(), double);
+        let matches = query::Query::find_matches(db, tournament.id)
+            .await
+            .map_err(|_| Status::internal("Could not load matches"))?;
+        if matches.iter().all(|m| m.state == "finished") {
+            return finish(db, tournament).await;
+        }
+        let outcomes: HashMap<MatchKey, Outcome> = matches
+            .iter()
+            .filter(|m| m.state == "finished")
+            .filter_map(|m| {
+                let outcome = Outcome {
+                    winner: m.winner,
+                    loser: m.loser,
+                };
+                Some((match_key(m)?, outcome))
+            })
+            .collect();
+
+        let mut walkovers = false;
+        for match_row in matches.iter().filter(|m| m.state == "pending") {
+            let sources = match plan.iter().find(|p| Some(p.key) == match_key(match_row)) {
+                Some(plan) => plan.sources,
+                None => continue,
+            };
+            let slots = sources.map(|s| knockout::slot(&s, &seeds, &outcomes));
+            let (one, two) = match slots {
+                [Slot::Pending, _] | [_, Slot::Pending] => continue,
+                [Slot::Player(one), Slot::Player(two)] if !withdrawn(one) && !withdrawn(two) => {
+                    let started = mutation::Mutation::start_match(db, match_row.id, one, two)
+                        .await
+                        .map_err(|_| Status::internal("Could not start match"))?;
+                    if started {
+                        let match_row = tournament_match::Model {
+                            player_one: Some(one),
+                            player_two: Some(two),
+                            state: "playing".to_string(),
+                            ..match_row.clone()
+                        };
+                        play_match(db, tournament, &match_row).await?;
+                    }
+                    continue;
+                }
+                [one, two] => (one, two),
+            };
+            let player = |slot: Slot| match slot {
+                Slot::Player(id) => Some(id),
+                _ => None,
+            };
+            let (one, two) = (player(one), player(two));
+            // Whoever is still in goes through
+            let winner = [one, two].into_iter().flatten().find(|&id| !withdrawn(id));
+            let loser = [one, two]
+                .into_iter()
+                .flatten()
+                .find(|&id| Some(id) != winner);
+            mutation::Mutation::finish_match(
+                db,
+                tournament_match::Model {
+                    player_one: one,
+                    player_two: two,
+                    winner,
+                    loser,
+                    ..match_row.clone()
+                },
+            )
+            .await
+            .map_err(|_| Status::internal("Could not finish match"))?;
+            walkovers = true;
+        }
+        if !walkovers {
+            return Ok(());
+        }
+    }
+}
+
+// Starts the next game of a match, or records its winner. Returns true once the match is over.
+async fn play_match(
+    db: &DatabaseConnection,
+    tournament: &tournament::Model,
+    match_row: &tournament_match::Model,
+) -> Result<bool, Status> {
+    let (one, two) = match (match_row.player_one, match_row.player_two) {
+        (Some(one), Some(two)) if match_row.state == "playing" => (one, two),
+        _ => return Ok(false),
+    };
+    let games: Vec<SeriesGame> = query::Query::find_pairings_by_match(db, match_row.id)
+        .await
+        .map_err(|_| Status::internal("Could not load pairings"))?
+        .iter()
+        .filter_map(series_game)
+        .collect();
+    match knockout::series_state(one, two, series_games(tournament), &games) {
+        SeriesState::Playing => Ok(false),
+        SeriesState::Next {
+            stage,
+            white,
+            black,
+        } => {
+            // Tiebreak games are played with other clocks, so they are not rated
+            let rated = tournament.rated && stage == Stage::Series;
+            let game_row = lifecycle::start_game(
+                db,
+                white,
+                black,
+                tournament.time_control,
+                rated,
+                stage.clock(),
+            )
+            .await?;
+            let pairing = tournament_pairing::Model {
+                game_id: Some(game_row.id),
+                match_id: Some(match_row.id),
+                stage: Some(stage.as_str().to_string()),
+                ..new_pairing(tournament.id, match_row.round, white, Some(black))
+            };
+            save_pairing(db, pairing).await?;
+            Ok(false)
+        }
+        SeriesState::Decided { winner, loser } => mutation::Mutation::finish_match(
+            db,
+            tournament_match::Model {
+                winner: Some(winner),
+                loser: Some(loser),
+                ..match_row.clone()
+            },
+        )
+        .await
+        .map_err(|_| Status::internal("Could not finish match")),
+    }
+}
+
+pub async fn run(db: DatabaseConnection) {
+    let mut interval = tokio::time::interval(ARENA_INTERVAL);
+    loop {
+        interval.tick().await;
+        let arenas = match query::Query::find_running_tournaments(&db, "arena").await {
+            Ok(arenas) => arenas,
+            Err(err) => {
+                println!("Error: could not load arenas: {err}");
+                continue;
+            }
+        };
+        let now = Utc::now().naive_utc();
+        for tournament in arenas {
+            // Games still being played when time is up count, but no new ones start
+            let result = match tournament.ends_at {
+                Some(ends_at) if ends_at <= now => finish(&db, &tournament).await,
+                _ => pair_arena(&db, &tournament).await,
+            };
+            if let Err(err) = result {
+                println!("Error: could not run arena {}: {err}", tournament.id);
+            }
+        }
+    }
+}
+
+fn new_pairing(
+    tournament_id: i32,
+    round: i32,
+    player_white: i32,
+    player_black: Option<i32>,
+) -> tournament_pairing::Model {
+    tournament_pairing::Model {
+        id: 0,
+        tournament_id,
+        round,
+        player_white,
+        player_black,
+        game_id: None,
+        result: None,
+        created_at: Default::default(),
+        white_berserk: false,
+        black_berserk: false,
+        match_id: None,
+        stage: None,
+    }
+}
+
+async fn save_pairing(
+    db: &DatabaseConnection,
+    pairing: tournament_pairing::Model,
+) -> Result<tournament_pairing::Model, Status> {
+    mutation::Mutation::create_pairing(db, pairing)
+        .await
+        .map_err(|_| Status::internal("Could not save pairing"))
+}
+
+// Records the result of a tournament game, and starts the next round once every game of the
+// current one is over
+pub async fn on_game_finished(
+    db: &DatabaseConnection,
+    game_row: &game::Model,
+) -> Result<(), Status> {
+    let pairing = match query::Query::find_pairing_by_game(db, game_row.id)
+        .await
+        .map_err(|_| Status::internal("Could not load pairing"))?
+    {
+        Some(pairing) => pairing,
+        None => return Ok(()),
+    };
+    let result = match &game_row.result {
+        Some(result) => result.clone(),
+        None => return Ok(()),
+    };
+    mutation::Mutation::set_pairing_result(db, pairing.id, result)
+        .await
+        .map_err(|_| Status::internal("Could not save pairing result"))?;
+
+    let tournament = match query::Query::find_tournament_by_id(db, pairing.tournament_id)
+        .await
+        .map_err(|_| Status::internal("Could not load tournament"))?
+    {
+        // Arena players are paired again by `run`
+        Some(tournament) if tournament.state == "running" && tournament.format != "arena" => {
+            tournament
+        }
+        _ => return Ok(()),
+    };
+    if let Some(match_id) = pairing.match_id {
+        let match_row = query::Query::find_match_by_id(db, match_id)
+            .await
+            .map_err(|_| Status::internal("Could not load match"))?
+            .ok_or_else(|| Status::internal("Could not load match"))?;
+        if play_match(db, &tournament, &match_row).await? {
+            advance_bracket(db, &tournament).await?;
+        }
+        return Ok(());
+    }
+    let pairings = query::Query::find_pairings(db, tournament.id)
+        .await
+        .map_err(|_| Status::internal("Could not load pairings"))?;
+    let round_over = pairings
+        .iter()
+        .filter(|p| p.round == tournament.current_round)
+        .all(|p| p.result.is_some());
+    if round_over {
+        start_next_round(db, &tournament).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,621 @@
+// Knockout tournaments. Each match is a mini-series of games with the tournament's time
+// control. A tied series goes on with two rapid games, then two blitz games, and finally an
+// Armageddon game where Black has less time but a draw counts as a win for Black. In double
+// elimination a first loss sends a player to the losers bracket, and the winners of both
+// brackets meet in a final match.
+use std::collections::HashMap;
+
+use super::standings::TournamentPlayer;
+use crate::chess::lifecycle::Clock;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Bracket {
+    Winners,
+    Losers,
+    Final,
+}
+
+impl Bracket {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Bracket::Winners => "winners",
+            Bracket::Losers => "losers",
+            Bracket::Final => "final",
+        }
+    }
+
+    pub fn parse(bracket: &str) -> Option<Bracket> {
+        match bracket {
+            "winners" => Some(Bracket::Winners),
+            "losers" => Some(Bracket::Losers),
+            "final" => Some(Bracket::Final),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct MatchKey {
+    pub bracket: Bracket,
+    pub round: i32,
+    pub position: i32,
+}
+
+fn key(bracket: Bracket, round: i32, position: usize) -> MatchKey {
+    MatchKey {
+        bracket,
+        round,
+        position: position as i32,
+    }
+}
+
+// Where a player of a match comes from
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Source {
+    // Seed number from 0, seeds past the number of players are byes
+    Seed(usize),
+    Winner(MatchKey),
+    Loser(MatchKey),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct MatchPlan {
+    pub key: MatchKey,
+    pub sources: [Source; 2],
+}
+
+// Seeds in bracket order, so that the best seeds only meet late: 1-8, 4-5, 2-7, 3-6
+fn seed_order(size: usize) -> Vec<usize> {
+    let mut order = vec![0];
+    while order.len() < size {
+        let n = order.len() * 2;
+        order = order.iter().flat_map(|&s| [s, n - 1 - s]).collect();
+    }
+    order
+}
+
+// Every match of the bracket. The losers bracket alternates between rounds where its players
+// meet each other and rounds where they meet the losers of the next winners round, the latter
+// in reverse order to avoid early rematches.
+pub fn plan(players: usize, double: bool) -> Vec<MatchPlan> {
+    if players < 2 {
+        return Vec::new();
+    }
+    let size = players.next_power_of_two();
+    let rounds = size.trailing_zeros() as i32;
+    let order = seed_order(size);
+    let mut plan: Vec<MatchPlan> = (0..size / 2)
+        .map(|p| MatchPlan {
+            key: key(Bracket::Winners, 1, p),
+            sources: [Source::Seed(order[2 * p]), Source::Seed(order[2 * p + 1])],
+        })
+        .collect();
+    for round in 2..=rounds {
+        for p in 0..size >> round {
+            plan.push(MatchPlan {
+                key: key(Bracket::Winners, round, p),
+                sources: [
+                    Source::Winner(key(Bracket::Winners, round - 1, 2 * p)),
+                    Source::Winner(key(Bracket::Winners, round - 1, 2 * p + 1)),
+                ],
+            });
+        }
+    }
+    if !double {
+        return plan;
+    }
+
+    let losers_rounds = 2 * (rounds - 1);
+    for round in 1..=losers_rounds {
+        let count = size >> (round / 2 + 1 + round % 2);
+        for p in 0..count {
+            let sources = if round == 1 {
+                [
+                    Source::Loser(key(Bracket::Winners, 1, 2 * p)),
+                    Source::Loser(key(Bracket::Winners, 1, 2 * p + 1)),
+                ]
+            } else if round % 2 == 0 {
+                [
+                    Source::Winner(key(Bracket::Losers, round - 1, p)),
+                    Source::Loser(key(Bracket::Winners, round / 2 + 1, count - 1 - p)),
+                ]
+            } else {
+                [
+                    Source::Winner(key(Bracket::Losers, round - 1, 2 * p)),
+                    Source::Winner(key(Bracket::Losers, round - 1, 2 * p + 1)),
+                ]
+            };
+            plan.push(MatchPlan {
+                key: key(Bracket::Losers, round, p),
+                sources,
+            });
+        }
+    }
+    let challenger = if losers_rounds > 0 {
+        Source::Winner(key(Bracket::Losers, losers_rounds, 0))
+    } else {
+        Source::Loser(key(Bracket::Winners, rounds, 0))
+    };
+    plan.push(MatchPlan {
+        key: key(Bracket::Final, 1, 0),
+        sources: [Source::Winner(key(Bracket::Winners, rounds, 0)), challenger],
+    });
+    plan
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Slot {
+    // The match it comes from is not over yet
+    Pending,
+    // A bye
+    Empty,
+    Player(i32),
+}
+
+// Winner and loser of a finished match, None on the side of a bye
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct Outcome {
+    pub winner: Option<i32>,
+    pub loser: Option<i32>,
+}
+
+// `seeds` are user ids by seed
+pub fn slot(source: &Source, seeds: &[i32], outcomes: &HashMap<MatchKey, Outcome>) -> Slot {
+    let player = |p: Option<i32>| p.map_or(Slot::Empty, Slot::Player);
+    match source {
+        Source::Seed(seed) => player(seeds.get(*seed).copied()),
+        Source::Winner(key) => outcomes
+            .get(key)
+            .map_or(Slot::Pending, |o| player(o.winner)),
+        Source::Loser(key) => outcomes.get(key).map_or(Slot::Pending, |o| player(o.loser)),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Stage {
+    Series,
+    Rapid,
+    Blitz,
+    Armageddon,
+}
+
+impl Stage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Stage::Series => "series",
+            Stage::Rapid => "rapid",
+            Stage::Blitz => "blitz",
+            Stage::Armageddon => "armageddon",
+        }
+    }
+
+    pub fn parse(stage: &str) -> Option<Stage> {
+        match stage {
+            "series" => Some(Stage::Series),
+            "rapid" => Some(Stage::Rapid),
+            "blitz" => Some(Stage::Blitz),
+            "armageddon" => Some(Stage::Armageddon),
+            _ => None,
+        }
+    }
+
+    fn games(&self, series_games: usize) -> usize {
+        match self {
+            Stage::Series => series_games,
+            Stage::Rapid | Stage::Blitz => 2,
+            Stage::Armageddon => 1,
+        }
+    }
+
+    // Tiebreaks replace the tournament's time control
+    pub fn clock(&self) -> Option<Clock> {
+        match self {
+            Stage::Series => None,
+            Stage::Rapid => Some(Clock {
+                white: 10 * 60,
+                black: 10 * 60,
+            }),
+            Stage::Blitz => Some(Clock {
+                white: 3 * 60,
+                black: 3 * 60,
+            }),
+            Stage::Armageddon => Some(Clock {
+                white: 5 * 60,
+                black: 4 * 60,
+            }),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SeriesGame {
+    pub stage: Stage,
+    pub white: i32,
+    pub black: i32,
+    // None while the game is being played
+    pub result: Option<String>,
+}
+
+impl SeriesGame {
+    fn points(&self, user_id: i32) -> f64 {
+        let white_points = match self.result.as_deref() {
+            Some("1-0") => 1.0,
+            Some("0-1") => 0.0,
+            _ => 0.5,
+        };
+        if user_id == self.white {
+            white_points
+        } else {
+            1.0 - white_points
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SeriesState {
+    // A game is being played
+    Playing,
+    Next {
+        stage: Stage,
+        white: i32,
+        black: i32,
+    },
+    Decided {
+        winner: i32,
+        loser: i32,
+    },
+}
+
+// Points of `one` and `two` over every finished game of the match
+pub fn score(one: i32, two: i32, games: &[SeriesGame]) -> (f64, f64) {
+    games
+        .iter()
+        .filter(|g| g.result.is_some())
+        .fold((0.0, 0.0), |(a, b), g| {
+            (a + g.points(one), b + g.points(two))
+        })
+}
+
+// What happens next in the match between `one`, the better seed, and `two`. A stage stops as
+// soon as one player cannot be caught up.
+pub fn series_state(one: i32, two: i32, series_games: usize, games: &[SeriesGame]) -> SeriesState {
+    if games.iter().any(|g| g.result.is_none()) {
+        return SeriesState::Playing;
+    }
+    let decided = |winner: i32| SeriesState::Decided {
+        winner,
+        loser: if winner == one { two } else { one },
+    };
+    for stage in [Stage::Series, Stage::Rapid, Stage::Blitz] {
+        let played: Vec<SeriesGame> = games.iter().filter(|g| g.stage == stage).cloned().collect();
+        let (a, b) = score(one, two, &played);
+        let remaining = stage.games(series_games).saturating_sub(played.len()) as f64;
+        if a > b + remaining {
+            return decided(one);
+        }
+        if b > a + remaining {
+            return decided(two);
+        }
+        if remaining > 0.0 {
+            // Colors alternate, the first player starts with white
+            let (white, black) = if played.len() % 2 == 0 {
+                (one, two)
+            } else {
+                (two, one)
+            };
+            return SeriesState::Next {
+                stage,
+                white,
+                black,
+            };
+        }
+    }
+    match games.iter().find(|g| g.stage == Stage::Armageddon) {
+        // Black has draw odds
+        Some(game) if game.result.as_deref() == Some("1-0") => decided(game.white),
+        Some(game) => decided(game.black),
+        // The better seed gets the draw odds
+        None => SeriesState::Next {
+            stage: Stage::Armageddon,
+            white: two,
+            black: one,
+        },
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BracketMatch {
+    pub key: MatchKey,
+    pub one: Option<i32>,
+    pub two: Option<i32>,
+    pub winner: Option<i32>,
+    pub loser: Option<i32>,
+    pub finished: bool,
+    pub games: Vec<SeriesGame>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct KnockoutStanding {
+    pub rank: usize,
+    pub user_id: i32,
+    // Matches won, walkovers included
+    pub wins: usize,
+    pub eliminated: bool,
+    pub rating: f64,
+    pub withdrawn: bool,
+}
+
+// Players still in are ranked first, then by how far they went
+pub fn standings(
+    players: &[TournamentPlayer],
+    matches: &[BracketMatch],
+    double: bool,
+) -> Vec<KnockoutStanding> {
+    let mut ranked: Vec<(KnockoutStanding, i32)> = players
+        .iter()
+        .map(|player| {
+            let finished = matches.iter().filter(|m| m.finished);
+            let wins = finished
+                .clone()
+                .filter(|m| m.winner == Some(player.id))
+                .count();
+            // In double elimination only a loss in the losers bracket or the final counts
+            let elimination = finished
+                .filter(|m| m.loser == Some(player.id))
+                .filter(|m| !double || m.key.bracket != Bracket::Winners)
+                .map(|m| match m.key.bracket {
+                    Bracket::Final => i32::MAX,
+                    _ => m.key.round,
+                })
+                .max();
+            let standing = KnockoutStanding {
+                rank: 0,
+                user_id: player.id,
+                wins,
+                eliminated: elimination.is_some(),
+                rating: player.rating,
+                withdrawn: player.withdrawn,
+            };
+            (standing, elimination.unwrap_or(0))
+        })
+        .collect();
+    ranked.sort_by(|(a, a_depth), (b, b_depth)| {
+        a.eliminated
+            .cmp(&b.eliminated)
+            .then(b_depth.cmp(a_depth))
+            .then(b.wins.cmp(&a.wins))
+            .then(b.rating.total_cmp(&a.rating))
+            .then(a.user_id.cmp(&b.user_id))
+    });
+    ranked
+        .into_iter()
+        .enumerate()
+        .map(|(i, (standing, _))| KnockoutStanding {
+            rank: i + 1,
+            ..standing
+        })
+        .collect()
+}
+
+fn format_points(points: f64) -> String {
+    if points.fract() == 0.0 {
+        format!("{}", points as i64)
+    } else {
+        format!("{:.1}", points)
+    }
+}
+
+// Every match with its score, in bracket order
+pub fn export_csv(matches: &[BracketMatch], names: &HashMap<i32, String>) -> String {
+    let name = |id: Option<i32>| {
+        id.and_then(|id| names.get(&id))
+            .map(|name| name.replace(',', " "))
+            .unwrap_or_default()
+    };
+    let mut csv = String::from("Bracket,Round,Match,Player 1,Player 2,Score,Winner\n");
+    for m in matches {
+        let score = match (m.one, m.two) {
+            (Some(one), Some(two)) if !m.games.is_empty() => {
+                let (a, b) = score(one, two, &m.games);
+                format!("{}-{}", format_points(a), format_points(b))
+            }
+            _ => String::new(),
+        };
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{}\n",
+            m.key.bracket.as_str(),
+            m.key.round,
+            m.key.position + 1,
+            name(m.one),
+            name(m.two),
+            score,
+            name(m.winner),
+        ));
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn game(stage: Stage, white: i32, black: i32, result: &str) -> SeriesGame {
+        SeriesGame {
+            stage,
+            white,
+            black,
+            result: Some(result.to_string()),
+        }
+    }
+
+    // Plays the whole bracket, the lower user id always wins
+    fn play_out(players: usize, double: bool) -> HashMap<MatchKey, Outcome> {
+        let plan = plan(players, double);
+        let seeds: Vec<i32> = (1..=players as i32).collect();
+        let mut outcomes = HashMap::new();
+        while outcomes.len() < plan.len() {
+            let before = outcomes.len();
+            for m in &plan {
+                if outcomes.contains_key(&m.key) {
+                    continue;
+                }
+                let slots = m.sources.map(|s| slot(&s, &seeds, &outcomes));
+                let outcome = match slots {
+                    [Slot::Pending, _] | [_, Slot::Pending] => continue,
+                    [Slot::Player(a), Slot::Player(b)] => Outcome {
+                        winner: Some(a.min(b)),
+                        loser: Some(a.max(b)),
+                    },
+                    [Slot::Player(a), Slot::Empty] | [Slot::Empty, Slot::Player(a)] => Outcome {
+                        winner: Some(a),
+                        loser: None,
+                    },
+                    [Slot::Empty, Slot::Empty] => Outcome::default(),
+                };
+                outcomes.insert(m.key, outcome);
+            }
+            assert!(outcomes.len() > before, "bracket is stuck");
+        }
+        outcomes
+    }
+
+    #[test]
+    fn test_seed_order() {
+        assert_eq!(seed_order(8), vec![0, 7, 3, 4, 1, 6, 2, 5]);
+    }
+
+    #[test]
+    fn test_single_elimination_with_byes() {
+        let plan = plan(6, false);
+        assert_eq!(plan.len(), 4 + 2 + 1);
+        // Seeds 1 and 2 get byes in the first round
+        assert_eq!(plan[0].sources, [Source::Seed(0), Source::Seed(7)]);
+        let outcomes = play_out(6, false);
+        let last = key(Bracket::Winners, 3, 0);
+        assert_eq!(outcomes[&last].winner, Some(1));
+        assert_eq!(outcomes[&last].loser, Some(2));
+    }
+
+    #[test]
+    fn test_double_elimination_everyone_loses_twice() {
+        for players in 2..=16 {
+            let outcomes = play_out(players, true);
+            let mut losses: HashMap<i32, usize> = HashMap::new();
+            for outcome in outcomes.values() {
+                if let Some(loser) = outcome.loser {
+                    *losses.entry(loser).or_default() += 1;
+                }
+            }
+            let final_match = outcomes[&key(Bracket::Final, 1, 0)];
+            assert_eq!(final_match.winner, Some(1));
+            assert_eq!(final_match.loser, Some(2));
+            for player in 2..=players as i32 {
+                assert_eq!(losses[&player], 2, "{players} players, player {player}");
+            }
+            assert!(!losses.contains_key(&1));
+        }
+    }
+
+    #[test]
+    fn test_series_decided_early() {
+        let games = vec![
+            game(Stage::Series, 1, 2, "1-0"),
+            game(Stage::Series, 2, 1, "0-1"),
+        ];
+        assert_eq!(
+            series_state(1, 2, 4, &games),
+            SeriesState::Next {
+                stage: Stage::Series,
+                white: 1,
+                black: 2
+            }
+        );
+        let mut games = games;
+        games.push(game(Stage::Series, 1, 2, "1-0"));
+        assert_eq!(
+            series_state(1, 2, 4, &games),
+            SeriesState::Decided {
+                winner: 1,
+                loser: 2
+            }
+        );
+    }
+
+    #[test]
+    fn test_tiebreaks_then_armageddon() {
+        let mut games = vec![
+            game(Stage::Series, 1, 2, "1-0"),
+            game(Stage::Series, 2, 1, "1-0"),
+        ];
+        assert_eq!(
+            series_state(1, 2, 2, &games),
+            SeriesState::Next {
+                stage: Stage::Rapid,
+                white: 1,
+                black: 2
+            }
+        );
+        games.push(game(Stage::Rapid, 1, 2, "1/2-1/2"));
+        games.push(game(Stage::Rapid, 2, 1, "1/2-1/2"));
+        games.push(game(Stage::Blitz, 1, 2, "0-1"));
+        games.push(game(Stage::Blitz, 2, 1, "0-1"));
+        let next = series_state(1, 2, 2, &games);
+        assert_eq!(
+            next,
+            SeriesState::Next {
+                stage: Stage::Armageddon,
+                white: 2,
+                black: 1
+            }
+        );
+        // A draw sends Black through
+        games.push(game(Stage::Armageddon, 2, 1, "1/2-1/2"));
+        assert_eq!(
+            series_state(1, 2, 2, &games),
+            SeriesState::Decided {
+                winner: 1,
+                loser: 2
+            }
+        );
+        assert_eq!(score(1, 2, &games), (3.5, 3.5));
+    }
+
+    #[test]
+    fn test_armageddon_clock_favours_white() {
+        let clock = Stage::Armageddon.clock().unwrap();
+        assert!(clock.white > clock.black);
+        assert_eq!(Stage::Series.clock(), None);
+    }
+
+    #[test]
+    fn test_standings_by_elimination_round() {
+        let players: Vec<TournamentPlayer> = (1..=4)
+            .map(|id| TournamentPlayer {
+                id,
+                rating: 1500.0,
+                ..Default::default()
+            })
+            .collect();
+        let finished = |bracket, round, position, winner, loser| BracketMatch {
+            key: key(bracket, round, position),
+            one: Some(winner),
+            two: Some(loser),
+            winner: Some(winner),
+            loser: Some(loser),
+            finished: true,
+            games: Vec::new(),
+        };
+        let matches = vec![
+            finished(Bracket::Winners, 1, 0, 1, 4),
+            finished(Bracket::Winners, 1, 1, 3, 2),
+            finished(Bracket::Winners, 2, 0, 3, 1),
+        ];
+        let standings = standings(&players, &matches, false);
+        assert_eq!(
+            standings.iter().map(|s| s.user_id).collect::<Vec<_>>(),
+            vec![3, 1, 2, 4]
+        );
+        assert!(!standings[0].eliminated);
+        assert_eq!(standings[0].wins, 2);
+    }
+}
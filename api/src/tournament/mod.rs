@@ -0,0 +1,8 @@
+pub mod arena;
+pub mod berger;
+pub mod director;
+pub mod knockout;
+pub mod service;
+pub mod standings;
+pub mod swiss;
+tonic::include_proto!("tournament"); // The string specified here must match the proto package name
@@ -0,0 +1,514 @@
+use std::collections::HashMap;
+
+use sea_orm::{DatabaseConnection, DbErr, SqlErr};
+use tonic::{Request, Response, Status};
+
+use entity::entities::{tournament, tournament_player};
+use service::{
+    game::{mutation as game_mutation, query as game_query},
+    game_move::query as game_move_query,
+    ratings::{glicko2::Glicko2, pool::Pool, query as rating_query},
+    time_control::query as time_control_query,
+    tournaments::{mutation, query},
+    users::query as user_query,
+};
+
+use super::{
+    arena, director, knockout, standings, tournaments_server::Tournaments, BerserkRequest,
+    BerserkResponse, BracketMatch, CreateTournamentRequest, ExportResultsResponse, Format,
+    GetBracketResponse, GetPairingsRequest, GetPairingsResponse, GetStandingsResponse, Pairing,
+    Standing, Tournament, TournamentRequest,
+};
+use crate::auth::interceptor::{authenticated_user, log_request};
+
+const MAX_NAME_LENGTH: usize = 100;
+const MAX_ROUNDS: i32 = 50;
+// Arena length in minutes
+const MAX_DURATION: i32 = 24 * 60;
+const DEFAULT_SERIES_GAMES: i32 = 2;
+const MAX_SERIES_GAMES: i32 = 12;
+
+fn format_to_str(format: Format) -> &'static str {
+    match format {
+        Format::Swiss => "swiss",
+        Format::Arena => "arena",
+        Format::RoundRobin => "round_robin",
+        Format::DoubleRoundRobin => "double_round_robin",
+        Format::Knockout => "knockout",
+        Format::DoubleElimination => "double_elimination",
+    }
+}
+
+fn format_from_str(format: &str) -> Format {
+    match format {
+        "arena" => Format::Arena,
+        "round_robin" => Format::RoundRobin,
+        "double_round_robin" => Format::DoubleRoundRobin,
+        "knockout" => Format::Knockout,
+        "double_elimination" => Format::DoubleElimination,
+        _ => Format::Swiss,
+    }
+}
+
+pub struct TournamentService {
+    pub db_connection: DatabaseConnection,
+}
+
+impl TournamentService {
+    async fn find_tournament(&self, id: i32) -> Result<tournament::Model, Status> {
+        let db_result = query::Query::find_tournament_by_id(&self.db_connection, id)
+            .await
+            .map_err(|_| Status::internal("Could not load tournament"))?;
+        match db_result {
+            Some(tournament) => Ok(tournament),
+            None => Err(Status::not_found("Tournament not found")),
+        }
+    }
+
+    async fn usernames(&self, ids: Vec<i32>) -> Result<HashMap<i32, String>, Status> {
+        let users = user_query::Query::find_users_by_ids(&self.db_connection, ids)
+            .await
+            .map_err(|_| Status::internal("Could not load users"))?;
+        Ok(users.into_iter().map(|u| (u.id, u.username)).collect())
+    }
+
+    async fn to_message(&self, tournament: &tournament::Model) -> Result<Tournament, Status> {
+        let players = query::Query::find_players(&self.db_connection, tournament.id)
+            .await
+            .map_err(|_| Status::internal("Could not load players"))?;
+        let created_by =
+            user_query::Query::find_user_by_id(&self.db_connection, tournament.created_by)
+                .await
+                .map_err(|_| Status::internal("Could not load user"))?
+                .map(|u| u.username)
+                .unwrap_or_default();
+        Ok(Tournament {
+            id: tournament.id,
+            name: tournament.name.clone(),
+            format: format_from_str(&tournament.format) as i32,
+            time_control_id: tournament.time_control,
+            rated: tournament.rated,
+            rounds: tournament.rounds.unwrap_or_default(),
+            current_round: tournament.current_round,
+            state: tournament.state.clone(),
+            created_by,
+            players: players.iter().filter(|p| !p.withdrawn).count() as i32,
+            duration: tournament.duration.unwrap_or_default(),
+            ends_at: tournament
+                .ends_at
+                .map(|ends_at| ends_at.and_utc().timestamp())
+                .unwrap_or_default(),
+            series_games: tournament.series_games.unwrap_or_default(),
+        })
+    }
+}
+
+#[tonic::async_trait]
+impl Tournaments for TournamentService {
+    async fn create_tournament(
+        &self,
+        request: Request<CreateTournamentRequest>,
+    ) -> Result<Response<Tournament>, Status> {
+        log_request("CreateTournament", &request);
+        let user = authenticated_user(&request)?;
+        let r = request.into_inner();
+        let name = r.name.trim();
+        if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+            return Err(Status::invalid_argument("Invalid tournament name"));
+        }
+        let format =
+            Format::try_from(r.format).map_err(|_| Status::invalid_argument("Invalid format"))?;
+        let (rounds, duration, series_games) = match format {
+            Format::Swiss if r.rounds < 1 || r.rounds > MAX_ROUNDS => {
+                return Err(Status::invalid_argument("Invalid number of rounds"));
+            }
+            Format::Swiss => (Some(r.rounds), None, None),
+            Format::Arena if r.duration < 1 || r.duration > MAX_DURATION => {
+                return Err(Status::invalid_argument("Invalid duration"));
+            }
+            Format::Arena => (None, Some(r.duration), None),
+            // Set from the number of players at the start
+            Format::RoundRobin | Format::DoubleRoundRobin => (None, None, None),
+            Format::Knockout | Format::DoubleElimination => match r.series_games {
+                0 => (None, None, Some(DEFAULT_SERIES_GAMES)),
+                1..=MAX_SERIES_GAMES => (None, None, Some(r.series_games)),
+                _ => {
+                    return Err(Status::invalid_argument(
+                        "Invalid number of games per match",
+                    ))
+                }
+            },
+        };
+        let time_control = time_control_query::Query::find_time_control_by_id(
+            &self.db_connection,
+            r.time_control_id,
+        )
+        .await
+        .map_err(|_| Status::internal("Could not load time control"))?;
+        if time_control.is_none() {
+            return Err(Status::invalid_argument("Unknown time control"));
+        }
+        let tournament = mutation::Mutation::create_tournament(
+            &self.db_connection,
+            tournament::Model {
+                id: 0,
+                name: name.to_string(),
+                format: format_to_str(format).to_string(),
+                time_control: r.time_control_id,
+                rated: r.rated,
+                rounds,
+                current_round: 0,
+                state: "registration".to_string(),
+                created_by: user.id,
+                created_at: Default::default(),
+                started_at: None,
+                finished_at: None,
+                updated_at: None,
+                duration,
+                ends_at: None,
+                series_games,
+            },
+        )
+        .await
+        .map_err(|_| Status::internal("Could not create tournament"))?;
+        Ok(Response::new(self.to_message(&tournament).await?))
+    }
+
+    async fn get_tournament(
+        &self,
+        request: Request<TournamentRequest>,
+    ) -> Result<Response<Tournament>, Status> {
+        log_request("GetTournament", &request);
+        let r = request.into_inner();
+        let tournament = self.find_tournament(r.tournament_id).await?;
+        Ok(Response::new(self.to_message(&tournament).await?))
+    }
+
+    async fn join_tournament(
+        &self,
+        request: Request<TournamentRequest>,
+    ) -> Result<Response<Tournament>, Status> {
+        log_request("JoinTournament", &request);
+        let user = authenticated_user(&request)?;
+        let r = request.into_inner();
+        let tournament = self.find_tournament(r.tournament_id).await?;
+        // Arenas can be joined until they are over
+        let open = match tournament.state.as_str() {
+            "registration" => true,
+            "running" => tournament.format == "arena",
+            _ => false,
+        };
+        if !open {
+            return Err(Status::failed_precondition("Registration is closed"));
+        }
+        let time_control = time_control_query::Query::find_time_control_by_id(
+            &self.db_connection,
+            tournament.time_control,
+        )
+        .await
+        .map_err(|_| Status::internal("Could not load time control"))?
+        .ok_or_else(|| Status::internal("Could not load time control"))?;
+        let rating = rating_query::Query::find_rating(
+            &self.db_connection,
+            user.id,
+            Pool::from_time(time_control.time),
+        )
+        .await
+        .map_err(|_| Status::internal("Could not load rating"))?
+        .map(|rating| Glicko2::from(&rating))
+        .unwrap_or_default();
+        let db_result = mutation::Mutation::add_player(
+            &self.db_connection,
+            tournament_player::Model {
+                id: 0,
+                tournament_id: tournament.id,
+                user_id: user.id,
+                rating: rating.rating,
+                withdrawn: false,
+                created_at: Default::default(),
+            },
+        )
+        .await;
+        if let Err(err) = db_result {
+            if let Some(SqlErr::UniqueConstraintViolation(_)) = err.sql_err() {
+                return Err(Status::already_exists("Already registered"));
+            }
+            return Err(Status::internal("Could not register"));
+        }
+        Ok(Response::new(self.to_message(&tournament).await?))
+    }
+
+    async fn leave_tournament(
+        &self,
+        request: Request<TournamentRequest>,
+    ) -> Result<Response<Tournament>, Status> {
+        log_request("LeaveTournament", &request);
+        let user = authenticated_user(&request)?;
+        let r = request.into_inner();
+        let tournament = self.find_tournament(r.tournament_id).await?;
+        let player = query::Query::find_player(&self.db_connection, tournament.id, user.id)
+            .await
+            .map_err(|_| Status::internal("Could not load player"))?
+            .ok_or_else(|| Status::not_found("Not registered"))?;
+        let db_result: Result<(), DbErr> = match tournament.state.as_str() {
+            "registration" => mutation::Mutation::remove_player(&self.db_connection, player.id)
+                .await
+                .map(|_| ()),
+            "running" => mutation::Mutation::withdraw_player(&self.db_connection, player.id)
+                .await
+                .map(|_| ()),
+            _ => return Err(Status::failed_precondition("Tournament is over")),
+        };
+        db_result.map_err(|_| Status::internal("Could not leave tournament"))?;
+        Ok(Response::new(self.to_message(&tournament).await?))
+    }
+
+    async fn start_tournament(
+        &self,
+        request: Request<TournamentRequest>,
+    ) -> Result<Response<Tournament>, Status> {
+        log_request("StartTournament", &request);
+        let user = authenticated_user(&request)?;
+        let r = request.into_inner();
+        let tournament = self.find_tournament(r.tournament_id).await?;
+        if tournament.created_by != user.id {
+            return Err(Status::permission_denied("Only the creator can start it"));
+        }
+        if tournament.state != "registration" {
+            return Err(Status::failed_precondition("Tournament already started"));
+        }
+        let players = query::Query::find_players(&self.db_connection, tournament.id)
+            .await
+            .map_err(|_| Status::internal("Could not load players"))?;
+        if players.len() < 2 {
+            return Err(Status::failed_precondition("Not enough players"));
+        }
+        director::start(&self.db_connection, &tournament).await?;
+        let tournament = self.find_tournament(tournament.id).await?;
+        Ok(Response::new(self.to_message(&tournament).await?))
+    }
+
+    async fn berserk(
+        &self,
+        request: Request<BerserkRequest>,
+    ) -> Result<Response<BerserkResponse>, Status> {
+        log_request("Berserk", &request);
+        let user = authenticated_user(&request)?;
+        let r = request.into_inner();
+        let game_id = match r.match_id.parse::<i32>() {
+            Ok(id) => id,
+            Err(_) => return Err(Status::invalid_argument("Invalid match id")),
+        };
+        let game_row = game_query::Query::find_game_by_id(&self.db_connection, game_id)
+            .await
+            .map_err(|_| Status::internal("Could not load game"))?
+            .ok_or_else(|| Status::not_found("Game not found"))?;
+        let pairing = query::Query::find_pairing_by_game(&self.db_connection, game_id)
+            .await
+            .map_err(|_| Status::internal("Could not load pairing"))?
+            .ok_or_else(|| Status::failed_precondition("Not a tournament game"))?;
+        let tournament = self.find_tournament(pairing.tournament_id).await?;
+        if tournament.format != "arena" {
+            return Err(Status::failed_precondition(
+                "Berserk is only allowed in arenas",
+            ));
+        }
+        let white = if user.id == game_row.player_white {
+            true
+        } else if user.id == game_row.player_black {
+            false
+        } else {
+            return Err(Status::permission_denied("Not a player in this game"));
+        };
+        let moved = !game_move_query::Query::find_last_moves(&self.db_connection, game_row.id, 1)
+            .await
+            .map_err(|_| Status::internal("Could not load moves"))?
+            .is_empty();
+        if game_row.state != "active" || moved {
+            return Err(Status::failed_precondition("Too late to go berserk"));
+        }
+        let marked = mutation::Mutation::set_berserk(&self.db_connection, pairing.id, white)
+            .await
+            .map_err(|_| Status::internal("Could not go berserk"))?;
+        if !marked {
+            return Err(Status::already_exists("Already berserk"));
+        }
+        let halved = game_mutation::Mutation::halve_clock(&self.db_connection, game_id, white)
+            .await
+            .map_err(|_| Status::internal("Could not go berserk"))?;
+        if !halved {
+            return Err(Status::failed_precondition("Too late to go berserk"));
+        }
+        let time = if white {
+            game_row.white_time
+        } else {
+            game_row.black_time
+        };
+        Ok(Response::new(BerserkResponse {
+            match_id: r.match_id,
+            time: time / 2,
+        }))
+    }
+
+    async fn get_pairings(
+        &self,
+        request: Request<GetPairingsRequest>,
+    ) -> Result<Response<GetPairingsResponse>, Status> {
+        log_request("GetPairings", &request);
+        let r = request.into_inner();
+        let tournament = self.find_tournament(r.tournament_id).await?;
+        let pairings: Vec<_> = query::Query::find_pairings(&self.db_connection, tournament.id)
+            .await
+            .map_err(|_| Status::internal("Could not load pairings"))?
+            .into_iter()
+            .filter(|p| match (r.round, format_from_str(&tournament.format)) {
+                // Games in progress, there are no rounds played in step
+                (0, Format::Arena | Format::Knockout | Format::DoubleElimination) => {
+                    p.result.is_none()
+                }
+                (0, _) => p.round == tournament.current_round,
+                (round, _) => p.round == round,
+            })
+            .collect();
+        let mut ids: Vec<i32> = pairings.iter().map(|p| p.player_white).collect();
+        ids.extend(pairings.iter().filter_map(|p| p.player_black));
+        let names = self.usernames(ids).await?;
+        let name = |id: i32| names.get(&id).cloned().unwrap_or_default();
+        let pairings = pairings
+            .iter()
+            .map(|p| Pairing {
+                round: p.round,
+                white: name(p.player_white),
+                black: p.player_black.map(name).unwrap_or_default(),
+                match_id: p.game_id.map(|id| id.to_string()).unwrap_or_default(),
+                result: p.result.clone().unwrap_or_default(),
+                stage: p.stage.clone().unwrap_or_default(),
+            })
+            .collect();
+        Ok(Response::new(GetPairingsResponse { pairings }))
+    }
+
+    async fn get_bracket(
+        &self,
+        request: Request<TournamentRequest>,
+    ) -> Result<Response<GetBracketResponse>, Status> {
+        log_request("GetBracket", &request);
+        let r = request.into_inner();
+        let tournament = self.find_tournament(r.tournament_id).await?;
+        let matches = director::load_bracket(&self.db_connection, tournament.id).await?;
+        let mut ids: Vec<i32> = matches.iter().filter_map(|m| m.one).collect();
+        ids.extend(matches.iter().filter_map(|m| m.two));
+        let names = self.usernames(ids).await?;
+        let name = |id: Option<i32>| {
+            id.and_then(|id| names.get(&id).cloned())
+                .unwrap_or_default()
+        };
+        let matches = matches
+            .iter()
+            .map(|m| {
+                let (score_one, score_two) = match (m.one, m.two) {
+                    (Some(one), Some(two)) => knockout::score(one, two, &m.games),
+                    _ => (0.0, 0.0),
+                };
+                let state = match (m.finished, m.one.zip(m.two)) {
+                    (true, _) => "finished",
+                    (false, Some(_)) => "playing",
+                    (false, None) => "pending",
+                };
+                BracketMatch {
+                    bracket: m.key.bracket.as_str().to_string(),
+                    round: m.key.round,
+                    position: m.key.position,
+                    player_one: name(m.one),
+                    player_two: name(m.two),
+                    winner: name(m.winner),
+                    score_one,
+                    score_two,
+                    state: state.to_string(),
+                }
+            })
+            .collect();
+        Ok(Response::new(GetBracketResponse { matches }))
+    }
+
+    async fn get_standings(
+        &self,
+        request: Request<TournamentRequest>,
+    ) -> Result<Response<GetStandingsResponse>, Status> {
+        log_request("GetStandings", &request);
+        let r = request.into_inner();
+        let tournament = self.find_tournament(r.tournament_id).await?;
+        let (players, records) = director::load_players(&self.db_connection, tournament.id).await?;
+        let names = self
+            .usernames(players.iter().map(|p| p.id).collect())
+            .await?;
+        let username = |id: i32| names.get(&id).cloned().unwrap_or_default();
+        let withdrawn = |id: i32| players.iter().any(|p| p.id == id && p.withdrawn);
+        let standings = match format_from_str(&tournament.format) {
+            Format::Arena => arena::standings(&players, &records)
+                .into_iter()
+                .map(|s| Standing {
+                    rank: s.rank as i32,
+                    username: username(s.user_id),
+                    score: s.score as f64,
+                    rating: s.rating,
+                    withdrawn: s.withdrawn,
+                    on_fire: s.on_fire,
+                    ..Default::default()
+                })
+                .collect(),
+            format @ (Format::Knockout | Format::DoubleElimination) => {
+                let matches = director::load_bracket(&self.db_connection, tournament.id).await?;
+                let double = format == Format::DoubleElimination;
+                knockout::standings(&players, &matches, double)
+                    .into_iter()
+                    .map(|s| Standing {
+                        rank: s.rank as i32,
+                        username: username(s.user_id),
+                        score: s.wins as f64,
+                        rating: s.rating,
+                        withdrawn: s.withdrawn,
+                        ..Default::default()
+                    })
+                    .collect()
+            }
+            _ => standings::standings(&players, &records)
+                .into_iter()
+                .map(|s| Standing {
+                    rank: s.rank as i32,
+                    username: username(s.user_id),
+                    score: s.score,
+                    buchholz: s.buchholz,
+                    sonneborn_berger: s.sonneborn_berger,
+                    progressive: s.progressive,
+                    rating: s.rating,
+                    withdrawn: withdrawn(s.user_id),
+                    on_fire: false,
+                })
+                .collect(),
+        };
+        Ok(Response::new(GetStandingsResponse { standings }))
+    }
+
+    async fn export_results(
+        &self,
+        request: Request<TournamentRequest>,
+    ) -> Result<Response<ExportResultsResponse>, Status> {
+        log_request("ExportResults", &request);
+        let r = request.into_inner();
+        let tournament = self.find_tournament(r.tournament_id).await?;
+        let (players, records) = director::load_players(&self.db_connection, tournament.id).await?;
+        let names = self
+            .usernames(players.iter().map(|p| p.id).collect())
+            .await?;
+        let csv = match format_from_str(&tournament.format) {
+            Format::Arena => arena::export_csv(&arena::standings(&players, &records), &names),
+            Format::Knockout | Format::DoubleElimination => {
+                let matches = director::load_bracket(&self.db_connection, tournament.id).await?;
+                knockout::export_csv(&matches, &names)
+            }
+            _ => standings::export_csv(&standings::standings(&players, &records), &records, &names),
+        };
+        Ok(Response::new(ExportResultsResponse { csv }))
+    }
+}
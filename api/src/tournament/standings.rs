@@ -0,0 +1,329 @@
+use std::collections::HashMap;
+
+use crate::chess::pieces::Color;
+
+pub const BYE: &str = "bye";
+
+// A registered player and what they did so far, as needed for pairings and standings
+#[derive(Clone, Default, Debug)]
+pub struct TournamentPlayer {
+    pub id: i32,
+    // Rating at registration, also the pairing number order
+    pub rating: f64,
+    pub score: f64,
+    // Colors of the games played, in round order
+    pub colors: Vec<Color>,
+    pub opponents: Vec<i32>,
+    pub had_bye: bool,
+    pub withdrawn: bool,
+}
+
+#[derive(Clone, Default, Debug, PartialEq)]
+pub struct PairingRecord {
+    pub round: i32,
+    pub white: i32,
+    // None for a bye
+    pub black: Option<i32>,
+    // None while the game is being played
+    pub result: Option<String>,
+    // Arena only, the side gave up half of its clock
+    pub white_berserk: bool,
+    pub black_berserk: bool,
+}
+
+impl PairingRecord {
+    // Points scored by `user_id` in this pairing, None if not decided yet
+    pub fn points(&self, user_id: i32) -> Option<f64> {
+        let white_points = match self.result.as_deref()? {
+            BYE => 1.0,
+            "1-0" => 1.0,
+            "0-1" => 0.0,
+            "1/2-1/2" => 0.5,
+            _ => return None,
+        };
+        if user_id == self.white {
+            Some(white_points)
+        } else if Some(user_id) == self.black {
+            Some(1.0 - white_points)
+        } else {
+            None
+        }
+    }
+
+    pub fn opponent(&self, user_id: i32) -> Option<i32> {
+        if user_id == self.white {
+            self.black
+        } else if Some(user_id) == self.black {
+            Some(self.white)
+        } else {
+            None
+        }
+    }
+
+    pub fn involves(&self, user_id: i32) -> bool {
+        self.white == user_id || self.black == Some(user_id)
+    }
+}
+
+// (user id, rating, withdrawn) for every registration
+pub fn build_players(
+    registrations: &[(i32, f64, bool)],
+    pairings: &[PairingRecord],
+) -> Vec<TournamentPlayer> {
+    let mut pairings: Vec<&PairingRecord> = pairings.iter().collect();
+    pairings.sort_by_key(|p| p.round);
+    registrations
+        .iter()
+        .map(|&(id, rating, withdrawn)| {
+            let mut player = TournamentPlayer {
+                id,
+                rating,
+                withdrawn,
+                ..Default::default()
+            };
+            for pairing in pairings.iter().filter(|p| p.involves(id)) {
+                player.score += pairing.points(id).unwrap_or(0.0);
+                match pairing.black {
+                    None => player.had_bye = true,
+                    Some(black) if black == id => {
+                        player.colors.push(Color::Black);
+                        player.opponents.push(pairing.white);
+                    }
+                    Some(black) => {
+                        player.colors.push(Color::White);
+                        player.opponents.push(black);
+                    }
+                }
+            }
+            player
+        })
+        .collect()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Standing {
+    pub rank: usize,
+    pub user_id: i32,
+    pub score: f64,
+    // Sum of the opponents' scores
+    pub buchholz: f64,
+    // Sum of the scores of beaten opponents and half of those of drawn opponents
+    pub sonneborn_berger: f64,
+    // Sum of the running score after each round
+    pub progressive: f64,
+    pub rating: f64,
+}
+
+// Ranked by score, then Buchholz, Sonneborn-Berger, progressive score and rating
+pub fn standings(players: &[TournamentPlayer], pairings: &[PairingRecord]) -> Vec<Standing> {
+    let scores: HashMap<i32, f64> = players.iter().map(|p| (p.id, p.score)).collect();
+    let last_round = pairings.iter().map(|p| p.round).max().unwrap_or(0);
+    let mut standings: Vec<Standing> = players
+        .iter()
+        .map(|player| {
+            let mut buchholz = 0.0;
+            let mut sonneborn_berger = 0.0;
+            for pairing in pairings.iter().filter(|p| p.involves(player.id)) {
+                let opponent_score = match pairing.opponent(player.id) {
+                    Some(opponent) => scores.get(&opponent).copied().unwrap_or(0.0),
+                    None => continue,
+                };
+                buchholz += opponent_score;
+                sonneborn_berger += pairing.points(player.id).unwrap_or(0.0) * opponent_score;
+            }
+            let mut running = 0.0;
+            let mut progressive = 0.0;
+            for round in 1..=last_round {
+                running += pairings
+                    .iter()
+                    .filter(|p| p.round == round)
+                    .filter_map(|p| p.points(player.id))
+                    .sum::<f64>();
+                progressive += running;
+            }
+            Standing {
+                rank: 0,
+                user_id: player.id,
+                score: player.score,
+                buchholz,
+                sonneborn_berger,
+                progressive,
+                rating: player.rating,
+            }
+        })
+        .collect();
+    standings.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then(b.buchholz.total_cmp(&a.buchholz))
+            .then(b.sonneborn_berger.total_cmp(&a.sonneborn_berger))
+            .then(b.progressive.total_cmp(&a.progressive))
+            .then(b.rating.total_cmp(&a.rating))
+            .then(a.user_id.cmp(&b.user_id))
+    });
+    for (i, standing) in standings.iter_mut().enumerate() {
+        standing.rank = i + 1;
+    }
+    standings
+}
+
+fn format_points(points: f64) -> String {
+    if points.fract() == 0.0 {
+        format!("{}", points as i64)
+    } else {
+        format!("{:.1}", points)
+    }
+}
+
+// Crosstable as CSV, each round cell is the result, opponent rank and color, e.g. "+3w", "=1b"
+pub fn export_csv(
+    standings: &[Standing],
+    pairings: &[PairingRecord],
+    names: &HashMap<i32, String>,
+) -> String {
+    let ranks: HashMap<i32, usize> = standings.iter().map(|s| (s.user_id, s.rank)).collect();
+    let rounds = pairings.iter().map(|p| p.round).max().unwrap_or(0);
+    let mut csv = String::from("Rank,Player,Rating,Score,Buchholz,Sonneborn-Berger,Progressive");
+    for round in 1..=rounds {
+        csv.push_str(&format!(",R{}", round));
+    }
+    csv.push('\n');
+    for standing in standings {
+        let name = names.get(&standing.user_id).cloned().unwrap_or_default();
+        csv.push_str(&format!(
+            "{},{},{:.0},{},{},{},{}",
+            standing.rank,
+            name.replace(',', " "),
+            standing.rating,
+            format_points(standing.score),
+            format_points(standing.buchholz),
+            format_points(standing.sonneborn_berger),
+            format_points(standing.progressive),
+        ));
+        for round in 1..=rounds {
+            let pairing = pairings
+                .iter()
+                .find(|p| p.round == round && p.involves(standing.user_id));
+            let cell = match pairing {
+                None => String::new(),
+                Some(pairing) if pairing.black.is_none() => "+bye".to_string(),
+                Some(pairing) => {
+                    let result = match pairing.points(standing.user_id) {
+                        Some(points) if points > 0.5 => "+",
+                        Some(points) if points < 0.5 => "-",
+                        Some(_) => "=",
+                        None => "*",
+                    };
+                    let opponent = pairing
+                        .opponent(standing.user_id)
+                        .and_then(|o| ranks.get(&o))
+                        .map(|r| r.to_string())
+                        .unwrap_or_default();
+                    let color = if pairing.white == standing.user_id {
+                        "w"
+                    } else {
+                        "b"
+                    };
+                    format!("{}{}{}", result, opponent, color)
+                }
+            };
+            csv.push(',');
+            csv.push_str(&cell);
+        }
+        csv.push('\n');
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(round: i32, white: i32, black: Option<i32>, result: &str) -> PairingRecord {
+        PairingRecord {
+            round,
+            white,
+            black,
+            result: Some(result.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn example() -> (Vec<TournamentPlayer>, Vec<PairingRecord>) {
+        let registrations = vec![
+            (1, 2000.0, false),
+            (2, 1900.0, false),
+            (3, 1800.0, false),
+            (4, 1700.0, false),
+        ];
+        let pairings = vec![
+            record(1, 1, Some(3), "1-0"),
+            record(1, 4, Some(2), "1/2-1/2"),
+            record(2, 2, Some(1), "0-1"),
+            record(2, 3, Some(4), "1-0"),
+        ];
+        (build_players(&registrations, &pairings), pairings)
+    }
+
+    #[test]
+    fn test_build_players() {
+        let (players, _) = example();
+        assert_eq!(players[0].score, 2.0);
+        assert_eq!(players[0].colors, vec![Color::White, Color::Black]);
+        assert_eq!(players[0].opponents, vec![3, 2]);
+        assert_eq!(players[1].score, 0.5);
+        assert_eq!(players[3].score, 0.5);
+    }
+
+    #[test]
+    fn test_standings_tie_breaks() {
+        let (players, pairings) = example();
+        let standings = standings(&players, &pairings);
+        assert_eq!(
+            standings.iter().map(|s| s.user_id).collect::<Vec<_>>(),
+            vec![1, 3, 2, 4]
+        );
+        let first = &standings[0];
+        assert_eq!(first.buchholz, 1.5);
+        assert_eq!(first.sonneborn_berger, 1.5);
+        assert_eq!(first.progressive, 3.0);
+        // Players 2 and 4 both have half a point, 2 met stronger opponents
+        assert_eq!(standings[2].buchholz, 2.5);
+        assert_eq!(standings[3].buchholz, 1.5);
+    }
+
+    #[test]
+    fn test_bye_points() {
+        let bye = PairingRecord {
+            round: 1,
+            white: 5,
+            black: None,
+            result: Some(BYE.to_string()),
+            ..Default::default()
+        };
+        assert_eq!(bye.points(5), Some(1.0));
+        assert_eq!(bye.opponent(5), None);
+        let players = build_players(&[(5, 1500.0, false)], &[bye]);
+        assert!(players[0].had_bye);
+        assert!(players[0].colors.is_empty());
+    }
+
+    #[test]
+    fn test_export_csv() {
+        let (players, pairings) = example();
+        let standings = standings(&players, &pairings);
+        let names = HashMap::from([
+            (1, "alice".to_string()),
+            (2, "bob".to_string()),
+            (3, "carol".to_string()),
+            (4, "dave".to_string()),
+        ]);
+        let csv = export_csv(&standings, &pairings, &names);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(
+            lines[0],
+            "Rank,Player,Rating,Score,Buchholz,Sonneborn-Berger,Progressive,R1,R2"
+        );
+        assert_eq!(lines[1], "1,alice,2000,2,1.5,1.5,3,+2w,+3b");
+    }
+}
@@ -0,0 +1,345 @@
+// Swiss pairings following the Dutch system (FIDE C.04.3): players are sorted by score and
+// pairing number, each score bracket is split in a top half S1 and a bottom half S2 and S1[i]
+// meets S2[i]. When that is not possible the transpositions and exchanges of C.04.3 are
+// approximated by backtracking in the same order, floating players down to the next bracket
+// as a last resort. Players never meet twice, get at most one bye and the colour rules of
+// C.04.1 are applied.
+use crate::chess::pieces::Color;
+
+use super::standings::TournamentPlayer;
+
+// Pairing attempts before giving up on a round
+const SEARCH_BUDGET: usize = 200_000;
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+enum Preference {
+    None,
+    Mild(Color),
+    Strong(Color),
+    Absolute(Color),
+}
+
+impl Preference {
+    fn color(&self) -> Option<Color> {
+        match self {
+            Preference::None => None,
+            Preference::Mild(c) | Preference::Strong(c) | Preference::Absolute(c) => Some(*c),
+        }
+    }
+
+    fn strength(&self) -> u8 {
+        match self {
+            Preference::None => 0,
+            Preference::Mild(_) => 1,
+            Preference::Strong(_) => 2,
+            Preference::Absolute(_) => 3,
+        }
+    }
+}
+
+fn preference(player: &TournamentPlayer) -> Preference {
+    let last = match player.colors.last() {
+        Some(last) => *last,
+        None => return Preference::None,
+    };
+    let whites = player.colors.iter().filter(|c| **c == Color::White).count() as i32;
+    let difference = 2 * whites - player.colors.len() as i32;
+    let n = player.colors.len();
+    if n >= 2 && player.colors[n - 2] == last {
+        return Preference::Absolute(last.opponent());
+    }
+    match difference {
+        d if d > 1 => Preference::Absolute(Color::Black),
+        d if d < -1 => Preference::Absolute(Color::White),
+        1 => Preference::Strong(Color::Black),
+        -1 => Preference::Strong(Color::White),
+        _ => Preference::Mild(last.opponent()),
+    }
+}
+
+fn compatible(a: &TournamentPlayer, b: &TournamentPlayer) -> bool {
+    if a.opponents.contains(&b.id) {
+        return false;
+    }
+    match (preference(a), preference(b)) {
+        (Preference::Absolute(x), Preference::Absolute(y)) => x != y,
+        _ => true,
+    }
+}
+
+fn conflicting(a: &TournamentPlayer, b: &TournamentPlayer) -> bool {
+    match (preference(a).color(), preference(b).color()) {
+        (Some(x), Some(y)) => x == y,
+        _ => false,
+    }
+}
+
+// The color `higher` (the better ranked player) gets, C.04.3 A.6 / E
+fn allocate(higher: &TournamentPlayer, lower: &TournamentPlayer, board: usize) -> Color {
+    let ph = preference(higher);
+    let pl = preference(lower);
+    match (ph.color(), pl.color()) {
+        (None, None) => {
+            // First round, the top seed gets white and colors alternate by board
+            if board % 2 == 0 {
+                Color::White
+            } else {
+                Color::Black
+            }
+        }
+        (Some(c), None) => c,
+        (None, Some(c)) => c.opponent(),
+        (Some(x), Some(y)) if x != y => x,
+        (Some(x), Some(_)) => {
+            if ph.strength() > pl.strength() {
+                return x;
+            }
+            if pl.strength() > ph.strength() {
+                return x.opponent();
+            }
+            // Alternate from the most recent round they had different colors
+            let recent = higher
+                .colors
+                .iter()
+                .rev()
+                .zip(lower.colors.iter().rev())
+                .find(|(h, l)| h != l);
+            match recent {
+                Some((h, _)) => h.opponent(),
+                None => x,
+            }
+        }
+    }
+}
+
+#[derive(PartialEq, Debug)]
+pub struct RoundPairing {
+    // (white, black) user ids, board order
+    pub games: Vec<(i32, i32)>,
+    pub bye: Option<i32>,
+}
+
+// Players are ranked by score, then by pairing number (rating at registration)
+fn ranked(players: &[TournamentPlayer]) -> Vec<&TournamentPlayer> {
+    let mut ranked: Vec<&TournamentPlayer> = players.iter().filter(|p| !p.withdrawn).collect();
+    ranked.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then(b.rating.total_cmp(&a.rating))
+            .then(a.id.cmp(&b.id))
+    });
+    ranked
+}
+
+fn pair_bracket(
+    ranked: &[&TournamentPlayer],
+    unpaired: &[usize],
+    budget: &mut usize,
+) -> Option<Vec<(usize, usize)>> {
+    if unpaired.is_empty() {
+        return Some(Vec::new());
+    }
+    if *budget == 0 {
+        return None;
+    }
+    *budget -= 1;
+    let top = unpaired[0];
+    let bracket: Vec<usize> = unpaired
+        .iter()
+        .copied()
+        .filter(|&i| ranked[i].score == ranked[top].score)
+        .collect();
+    let half = bracket.len() / 2;
+    // S2 in order, then the rest of S1 (exchanges), then lower brackets (floating down)
+    let mut candidates: Vec<usize> = bracket.iter().skip(half.max(1)).copied().collect();
+    candidates.extend(bracket.iter().take(half).skip(1).copied());
+    candidates.extend(
+        unpaired
+            .iter()
+            .copied()
+            .filter(|&i| ranked[i].score != ranked[top].score),
+    );
+    candidates.retain(|&i| compatible(ranked[top], ranked[i]));
+    // Within the bracket, opponents whose color preference can be granted come first, but
+    // never ahead of the bracket itself
+    candidates.sort_by_key(|&i| {
+        (
+            ranked[i].score != ranked[top].score,
+            conflicting(ranked[top], ranked[i]),
+        )
+    });
+
+    for candidate in candidates {
+        let rest: Vec<usize> = unpaired
+            .iter()
+            .copied()
+            .filter(|&i| i != top && i != candidate)
+            .collect();
+        if let Some(mut pairs) = pair_bracket(ranked, &rest, budget) {
+            pairs.insert(0, (top, candidate));
+            return Some(pairs);
+        }
+    }
+    None
+}
+
+pub fn pair_round(players: &[TournamentPlayer]) -> Option<RoundPairing> {
+    let ranked = ranked(players);
+    let all: Vec<usize> = (0..ranked.len()).collect();
+    let mut budget = SEARCH_BUDGET;
+
+    let (pairs, bye) = if ranked.len() % 2 == 0 {
+        (pair_bracket(&ranked, &all, &mut budget)?, None)
+    } else {
+        // The lowest ranked player without a bye yet sits out
+        let mut found = None;
+        for &candidate in all.iter().rev() {
+            if ranked[candidate].had_bye {
+                continue;
+            }
+            let rest: Vec<usize> = all.iter().copied().filter(|&i| i != candidate).collect();
+            if let Some(pairs) = pair_bracket(&ranked, &rest, &mut budget) {
+                found = Some((pairs, Some(ranked[candidate].id)));
+                break;
+            }
+        }
+        found?
+    };
+
+    let games = pairs
+        .into_iter()
+        .enumerate()
+        .map(|(board, (a, b))| {
+            let (higher, lower) = if a < b { (a, b) } else { (b, a) };
+            match allocate(ranked[higher], ranked[lower], board) {
+                Color::White => (ranked[higher].id, ranked[lower].id),
+                Color::Black => (ranked[lower].id, ranked[higher].id),
+            }
+        })
+        .collect();
+    Some(RoundPairing { games, bye })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player(id: i32, rating: f64) -> TournamentPlayer {
+        TournamentPlayer {
+            id,
+            rating,
+            ..Default::default()
+        }
+    }
+
+    fn play(players: &mut [TournamentPlayer], round: &RoundPairing, winner_white: bool) {
+        for (white, black) in &round.games {
+            for p in players.iter_mut() {
+                if p.id == *white {
+                    p.colors.push(Color::White);
+                    p.opponents.push(*black);
+                    if winner_white {
+                        p.score += 1.0;
+                    }
+                } else if p.id == *black {
+                    p.colors.push(Color::Black);
+                    p.opponents.push(*white);
+                    if !winner_white {
+                        p.score += 1.0;
+                    }
+                }
+            }
+        }
+        if let Some(bye) = round.bye {
+            for p in players.iter_mut().filter(|p| p.id == bye) {
+                p.had_bye = true;
+                p.score += 1.0;
+            }
+        }
+    }
+
+    #[test]
+    fn test_first_round_top_half_meets_bottom_half() {
+        let players: Vec<TournamentPlayer> = (1..=8)
+            .map(|id| player(id, 2000.0 - id as f64 * 10.0))
+            .collect();
+        let round = pair_round(&players).unwrap();
+        assert_eq!(round.bye, None);
+        assert_eq!(round.games, vec![(1, 5), (6, 2), (3, 7), (8, 4)]);
+    }
+
+    #[test]
+    fn test_bye_goes_to_lowest_ranked() {
+        let mut players: Vec<TournamentPlayer> = (1..=5)
+            .map(|id| player(id, 2000.0 - id as f64 * 10.0))
+            .collect();
+        let round = pair_round(&players).unwrap();
+        assert_eq!(round.bye, Some(5));
+        play(&mut players, &round, true);
+        let round = pair_round(&players).unwrap();
+        assert!(round.bye.is_some());
+        assert_ne!(round.bye, Some(5));
+    }
+
+    #[test]
+    fn test_no_rematches_and_color_limits() {
+        let mut players: Vec<TournamentPlayer> = (1..=8)
+            .map(|id| player(id, 2000.0 - id as f64 * 10.0))
+            .collect();
+        let mut met = std::collections::HashSet::new();
+        for round_number in 0..5 {
+            let round = pair_round(&players).unwrap();
+            assert_eq!(round.games.len(), 4);
+            for (white, black) in &round.games {
+                assert!(met.insert((*white.min(black), *white.max(black))));
+            }
+            play(&mut players, &round, round_number % 2 == 0);
+        }
+        for p in &players {
+            let whites = p.colors.iter().filter(|c| **c == Color::White).count() as i32;
+            assert!((2 * whites - p.colors.len() as i32).abs() <= 2);
+            for window in p.colors.windows(3) {
+                assert!(!(window[0] == window[1] && window[1] == window[2]));
+            }
+        }
+    }
+
+    #[test]
+    fn test_winners_meet_winners() {
+        let mut players: Vec<TournamentPlayer> = (1..=8)
+            .map(|id| player(id, 2000.0 - id as f64 * 10.0))
+            .collect();
+        let round = pair_round(&players).unwrap();
+        play(&mut players, &round, true);
+        let round = pair_round(&players).unwrap();
+        for (white, black) in &round.games {
+            let score = |id: i32| players.iter().find(|p| p.id == id).unwrap().score;
+            assert_eq!(score(*white), score(*black));
+        }
+    }
+
+    #[test]
+    fn test_color_preferences() {
+        let mut a = player(1, 2000.0);
+        a.colors = vec![Color::White, Color::White];
+        assert_eq!(preference(&a), Preference::Absolute(Color::Black));
+        a.colors = vec![Color::White, Color::Black, Color::White];
+        assert_eq!(preference(&a), Preference::Strong(Color::Black));
+        a.colors = vec![Color::White, Color::Black];
+        assert_eq!(preference(&a), Preference::Mild(Color::White));
+
+        let mut b = player(2, 1900.0);
+        b.colors = vec![Color::Black, Color::Black];
+        assert!(compatible(&a, &b));
+        a.colors = vec![Color::Black, Color::Black];
+        assert!(!compatible(&a, &b));
+    }
+
+    #[test]
+    fn test_unpairable_round() {
+        let mut players = vec![player(1, 2000.0), player(2, 1900.0)];
+        let round = pair_round(&players).unwrap();
+        play(&mut players, &round, true);
+        assert_eq!(pair_round(&players), None);
+    }
+}
@@ -15,13 +15,24 @@ pub struct Model {
     pub black_time: i32,
     pub white_time: i32,
     pub state: String,
-    pub moves: String,
     pub created_at: DateTime,
     pub updated_at: Option<DateTime>,
+    pub rated: bool,
+    pub result: Option<String>,
+    pub white_clock: Option<i32>,
+    pub black_clock: Option<i32>,
+    pub eco: Option<String>,
+    pub opening: Option<String>,
+    pub termination: Option<String>,
+    pub replay_failed: bool,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_one = "super::game_analysis::Entity")]
+    GameAnalysis,
+    #[sea_orm(has_many = "super::game_move::Entity")]
+    GameMove,
     #[sea_orm(
         belongs_to = "super::time_control::Entity",
         from = "Column::TimeControl",
@@ -48,6 +59,18 @@ pub enum Relation {
     Users1,
 }
 
+impl Related<super::game_analysis::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::GameAnalysis.def()
+    }
+}
+
+impl Related<super::game_move::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::GameMove.def()
+    }
+}
+
 impl Related<super::time_control::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::TimeControl.def()
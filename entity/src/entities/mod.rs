@@ -1,7 +1,23 @@
-
-
 pub mod prelude;
 
+pub mod challenge;
 pub mod game;
+pub mod game_analysis;
+pub mod game_move;
+pub mod login_attempts;
+pub mod opening_move;
+pub mod player_opening_stats;
+pub mod player_stats;
+pub mod player_streak;
+pub mod puzzle;
+pub mod puzzle_attempt;
+pub mod puzzle_rating;
+pub mod rating;
+pub mod rating_history;
+pub mod sessions;
 pub mod time_control;
+pub mod tournament;
+pub mod tournament_match;
+pub mod tournament_pairing;
+pub mod tournament_player;
 pub mod users;
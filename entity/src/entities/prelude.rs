@@ -1,5 +1,23 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.15
 
+pub use super::challenge::Entity as Challenge;
 pub use super::game::Entity as Game;
+pub use super::game_analysis::Entity as GameAnalysis;
+pub use super::game_move::Entity as GameMove;
+pub use super::login_attempts::Entity as LoginAttempts;
+pub use super::opening_move::Entity as OpeningMove;
+pub use super::player_opening_stats::Entity as PlayerOpeningStats;
+pub use super::player_stats::Entity as PlayerStats;
+pub use super::player_streak::Entity as PlayerStreak;
+pub use super::puzzle::Entity as Puzzle;
+pub use super::puzzle_attempt::Entity as PuzzleAttempt;
+pub use super::puzzle_rating::Entity as PuzzleRating;
+pub use super::rating::Entity as Rating;
+pub use super::rating_history::Entity as RatingHistory;
+pub use super::sessions::Entity as Sessions;
 pub use super::time_control::Entity as TimeControl;
+pub use super::tournament::Entity as Tournament;
+pub use super::tournament_match::Entity as TournamentMatch;
+pub use super::tournament_pairing::Entity as TournamentPairing;
+pub use super::tournament_player::Entity as TournamentPlayer;
 pub use super::users::Entity as Users;
@@ -11,9 +11,25 @@ pub struct Model {
     pub password: String,
     pub created_at: DateTime,
     pub updated_at: Option<DateTime>,
+    pub display_name: Option<String>,
+    pub country: Option<String>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub bio: Option<String>,
+    pub avatar_url: Option<String>,
+    pub deleted_at: Option<DateTime>,
+    pub bot_level: Option<i32>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
-pub enum Relation {}
+pub enum Relation {
+    #[sea_orm(has_many = "super::sessions::Entity")]
+    Sessions,
+}
+
+impl Related<super::sessions::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Sessions.def()
+    }
+}
 
 impl ActiveModelBehavior for ActiveModel {}
@@ -11,6 +11,29 @@ impl MigratorTrait for Migrator {
             Box::new(m20240414_000001_create_users_table::Migration),
             Box::new(m20240414_000002_create_time_control_table::Migration),
             Box::new(m20240414_000003_create_game_table::Migration),
+            Box::new(m20240420_000004_add_username_unique_index::Migration),
+            Box::new(m20240427_000005_add_user_profile_columns::Migration),
+            Box::new(m20240504_000006_create_sessions_table::Migration),
+            Box::new(m20240511_000007_create_login_attempts_table::Migration),
+            Box::new(m20240518_000008_add_game_result_columns::Migration),
+            Box::new(m20240518_000009_create_ratings_tables::Migration),
+            Box::new(m20240525_000010_create_challenge_table::Migration),
+            Box::new(m20240601_000011_create_tournament_tables::Migration),
+            Box::new(m20240608_000012_add_tournament_format_columns::Migration),
+            Box::new(m20240615_000013_create_tournament_match_table::Migration),
+            Box::new(m20240622_000014_add_user_bot_level::Migration),
+            Box::new(m20240629_000015_create_game_analysis_table::Migration),
+            Box::new(m20240706_000016_create_game_move_table::Migration),
+            Box::new(m20240713_000017_create_opening_move_table::Migration),
+            Box::new(m20240720_000018_add_game_opening_columns::Migration),
+            Box::new(m20240727_000019_create_puzzle_tables::Migration),
+            Box::new(m20240803_000020_add_game_move_material_columns::Migration),
+            Box::new(m20240810_000021_add_game_player_indexes::Migration),
+            Box::new(m20240817_000022_create_player_stats_tables::Migration),
+            Box::new(m20240824_000023_add_session_refresh_generation::Migration),
+            Box::new(m20240831_000024_add_game_analysis_retries::Migration),
+            Box::new(m20240907_000025_replace_game_moves::Migration),
+            Box::new(m20240914_000026_hash_plaintext_passwords::Migration),
         ]
     }
 }
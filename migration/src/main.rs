@@ -19,6 +19,24 @@ async fn run(db: &DatabaseConnection) -> Result<(), DbErr> {
     assert!(schema_manager.has_table("users").await?);
     assert!(schema_manager.has_table("time_control").await?);
     assert!(schema_manager.has_table("game").await?);
+    assert!(schema_manager.has_table("sessions").await?);
+    assert!(schema_manager.has_table("login_attempts").await?);
+    assert!(schema_manager.has_table("rating").await?);
+    assert!(schema_manager.has_table("rating_history").await?);
+    assert!(schema_manager.has_table("challenge").await?);
+    assert!(schema_manager.has_table("tournament").await?);
+    assert!(schema_manager.has_table("tournament_player").await?);
+    assert!(schema_manager.has_table("tournament_pairing").await?);
+    assert!(schema_manager.has_table("tournament_match").await?);
+    assert!(schema_manager.has_table("game_analysis").await?);
+    assert!(schema_manager.has_table("game_move").await?);
+    assert!(schema_manager.has_table("opening_move").await?);
+    assert!(schema_manager.has_table("puzzle").await?);
+    assert!(schema_manager.has_table("puzzle_rating").await?);
+    assert!(schema_manager.has_table("puzzle_attempt").await?);
+    assert!(schema_manager.has_table("player_stats").await?);
+    assert!(schema_manager.has_table("player_opening_stats").await?);
+    assert!(schema_manager.has_table("player_streak").await?);
 
     Ok(())
 }
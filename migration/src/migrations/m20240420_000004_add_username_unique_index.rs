@@ -0,0 +1,69 @@
+use sea_orm_migration::prelude::*;
+
+use super::m20240414_000001_create_users_table::Users;
+
+pub struct Migration;
+
+static INDEX_NAME: &str = "idx_users_username_lower";
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240420_000004_add_username_unique_index"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        // Rename accounts that clash case-insensitively with an older one, otherwise the index can't be built.
+        // The new name gets a further suffix until it clashes with nothing either.
+        db.execute_unprepared(
+            "DO $$ \
+             DECLARE \
+                dup RECORD; \
+                candidate TEXT; \
+                suffix INT; \
+             BEGIN \
+                FOR dup IN \
+                    SELECT u.id, u.username FROM users AS u \
+                    WHERE EXISTS ( \
+                        SELECT 1 FROM users AS o \
+                        WHERE LOWER(o.username) = LOWER(u.username) AND o.id < u.id \
+                    ) \
+                    ORDER BY u.id \
+                LOOP \
+                    candidate := dup.username || '_' || dup.id; \
+                    suffix := 1; \
+                    WHILE EXISTS ( \
+                        SELECT 1 FROM users WHERE LOWER(username) = LOWER(candidate) \
+                    ) LOOP \
+                        candidate := dup.username || '_' || dup.id || '_' || suffix; \
+                        suffix := suffix + 1; \
+                    END LOOP; \
+                    UPDATE users SET username = candidate WHERE id = dup.id; \
+                END LOOP; \
+             END $$",
+        )
+        .await?;
+        db.execute_unprepared(&format!(
+            "CREATE UNIQUE INDEX IF NOT EXISTS {} ON {} (LOWER({}))",
+            INDEX_NAME,
+            Users::Table.to_string(),
+            Users::Username.to_string(),
+        ))
+        .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name(INDEX_NAME)
+                    .table(Users::Table)
+                    .to_owned(),
+            )
+            .await
+    }
+}
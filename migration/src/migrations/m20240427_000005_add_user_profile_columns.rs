@@ -0,0 +1,53 @@
+use sea_orm_migration::prelude::*;
+
+use super::m20240414_000001_create_users_table::Users;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240427_000005_add_user_profile_columns"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .add_column_if_not_exists(ColumnDef::new(UserProfile::DisplayName).string())
+                    .add_column_if_not_exists(ColumnDef::new(UserProfile::Country).string())
+                    .add_column_if_not_exists(ColumnDef::new(UserProfile::Bio).text())
+                    .add_column_if_not_exists(ColumnDef::new(UserProfile::AvatarUrl).string())
+                    .add_column_if_not_exists(ColumnDef::new(UserProfile::DeletedAt).timestamp())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .drop_column(UserProfile::DisplayName)
+                    .drop_column(UserProfile::Country)
+                    .drop_column(UserProfile::Bio)
+                    .drop_column(UserProfile::AvatarUrl)
+                    .drop_column(UserProfile::DeletedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum UserProfile {
+    DisplayName,
+    Country,
+    Bio,
+    AvatarUrl,
+    DeletedAt,
+}
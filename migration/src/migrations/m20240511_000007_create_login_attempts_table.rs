@@ -0,0 +1,52 @@
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240511_000007_create_login_attempts_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(LoginAttempts::Table)
+                    .if_not_exists()
+                    // "user:<username>" or "ip:<address>"
+                    .col(
+                        ColumnDef::new(LoginAttempts::Key)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(LoginAttempts::Failures).integer().not_null())
+                    .col(
+                        ColumnDef::new(LoginAttempts::LastFailureAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(LoginAttempts::LockedUntil).timestamp())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(LoginAttempts::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum LoginAttempts {
+    Table,
+    Key,
+    Failures,
+    LastFailureAt,
+    LockedUntil,
+}
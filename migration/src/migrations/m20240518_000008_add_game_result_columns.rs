@@ -0,0 +1,50 @@
+use sea_orm_migration::prelude::*;
+
+use super::m20240414_000003_create_game_table::Game;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240518_000008_add_game_result_columns"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Game::Table)
+                    .add_column_if_not_exists(
+                        ColumnDef::new(GameResult::Rated)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    // "1-0", "0-1" or "1/2-1/2" once the game is over
+                    .add_column_if_not_exists(ColumnDef::new(GameResult::Result).string())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Game::Table)
+                    .drop_column(GameResult::Rated)
+                    .drop_column(GameResult::Result)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum GameResult {
+    Rated,
+    Result,
+}
@@ -0,0 +1,160 @@
+use sea_orm_migration::prelude::*;
+
+use super::{m20240414_000001_create_users_table::Users, m20240414_000003_create_game_table::Game};
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240518_000009_create_ratings_tables"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Rating::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Rating::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Rating::UserId).integer().not_null())
+                    // bullet, blitz, rapid, classical or correspondence
+                    .col(ColumnDef::new(Rating::Pool).string().not_null())
+                    .col(ColumnDef::new(Rating::Value).double().not_null())
+                    .col(ColumnDef::new(Rating::Deviation).double().not_null())
+                    .col(ColumnDef::new(Rating::Volatility).double().not_null())
+                    .col(
+                        ColumnDef::new(Rating::Games)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(
+                        ColumnDef::new(Rating::CreatedAt)
+                            .timestamp()
+                            .not_null()
+                            .default(SimpleExpr::Keyword(Keyword::CurrentTimestamp)),
+                    )
+                    .col(ColumnDef::new(Rating::UpdatedAt).timestamp())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_rating_user")
+                            .from(Rating::Table, Rating::UserId)
+                            .to(Users::Table, Users::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_rating_user_pool")
+                    .table(Rating::Table)
+                    .col(Rating::UserId)
+                    .col(Rating::Pool)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_table(
+                Table::create()
+                    .table(RatingHistory::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(RatingHistory::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(RatingHistory::UserId).integer().not_null())
+                    .col(ColumnDef::new(RatingHistory::Pool).string().not_null())
+                    .col(ColumnDef::new(RatingHistory::GameId).integer())
+                    .col(ColumnDef::new(RatingHistory::Rating).double().not_null())
+                    .col(ColumnDef::new(RatingHistory::Deviation).double().not_null())
+                    .col(
+                        ColumnDef::new(RatingHistory::Volatility)
+                            .double()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(RatingHistory::CreatedAt)
+                            .timestamp()
+                            .not_null()
+                            .default(SimpleExpr::Keyword(Keyword::CurrentTimestamp)),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_rating_history_user")
+                            .from(RatingHistory::Table, RatingHistory::UserId)
+                            .to(Users::Table, Users::Id),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_rating_history_game")
+                            .from(RatingHistory::Table, RatingHistory::GameId)
+                            .to(Game::Table, Game::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_rating_history_user_pool")
+                    .table(RatingHistory::Table)
+                    .col(RatingHistory::UserId)
+                    .col(RatingHistory::Pool)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(RatingHistory::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Rating::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum Rating {
+    Table,
+    Id,
+    UserId,
+    Pool,
+    #[iden = "rating"]
+    Value,
+    Deviation,
+    Volatility,
+    Games,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(Iden)]
+pub enum RatingHistory {
+    Table,
+    Id,
+    UserId,
+    Pool,
+    GameId,
+    Rating,
+    Deviation,
+    Volatility,
+    CreatedAt,
+}
@@ -0,0 +1,132 @@
+use sea_orm_migration::prelude::*;
+
+use super::{
+    m20240414_000001_create_users_table::Users,
+    m20240414_000002_create_time_control_table::TimeControl,
+    m20240414_000003_create_game_table::Game,
+};
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240525_000010_create_challenge_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Challenge::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Challenge::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Challenge::Challenger).integer().not_null())
+                    .col(ColumnDef::new(Challenge::Challenged).integer().not_null())
+                    .col(ColumnDef::new(Challenge::TimeControl).integer().not_null())
+                    // "random", "w" or "b", the color asked for by the challenger
+                    .col(
+                        ColumnDef::new(Challenge::Color)
+                            .string()
+                            .not_null()
+                            .default("random"),
+                    )
+                    .col(
+                        ColumnDef::new(Challenge::Rated)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .col(
+                        ColumnDef::new(Challenge::Variant)
+                            .string()
+                            .not_null()
+                            .default("standard"),
+                    )
+                    // "pending", "accepted", "declined", "cancelled" or "expired"
+                    .col(
+                        ColumnDef::new(Challenge::State)
+                            .string()
+                            .not_null()
+                            .default("pending"),
+                    )
+                    .col(ColumnDef::new(Challenge::GameId).integer())
+                    .col(
+                        ColumnDef::new(Challenge::CreatedAt)
+                            .timestamp()
+                            .not_null()
+                            .default(SimpleExpr::Keyword(Keyword::CurrentTimestamp)),
+                    )
+                    .col(ColumnDef::new(Challenge::ExpiresAt).timestamp().not_null())
+                    .col(ColumnDef::new(Challenge::UpdatedAt).timestamp())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_challenge_challenger")
+                            .from(Challenge::Table, Challenge::Challenger)
+                            .to(Users::Table, Users::Id),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_challenge_challenged")
+                            .from(Challenge::Table, Challenge::Challenged)
+                            .to(Users::Table, Users::Id),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_challenge_time_control")
+                            .from(Challenge::Table, Challenge::TimeControl)
+                            .to(TimeControl::Table, TimeControl::Id),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_challenge_game")
+                            .from(Challenge::Table, Challenge::GameId)
+                            .to(Game::Table, Game::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_challenge_challenged_state")
+                    .table(Challenge::Table)
+                    .col(Challenge::Challenged)
+                    .col(Challenge::State)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Challenge::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum Challenge {
+    Table,
+    Id,
+    Challenger,
+    Challenged,
+    TimeControl,
+    Color,
+    Rated,
+    Variant,
+    State,
+    GameId,
+    CreatedAt,
+    ExpiresAt,
+    UpdatedAt,
+}
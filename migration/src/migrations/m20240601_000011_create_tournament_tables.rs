@@ -0,0 +1,285 @@
+use sea_orm_migration::prelude::*;
+
+use super::{
+    m20240414_000001_create_users_table::Users,
+    m20240414_000002_create_time_control_table::TimeControl,
+    m20240414_000003_create_game_table::Game,
+};
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240601_000011_create_tournament_tables"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Tournament::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Tournament::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Tournament::Name).string().not_null())
+                    // "swiss"
+                    .col(ColumnDef::new(Tournament::Format).string().not_null())
+                    .col(ColumnDef::new(Tournament::TimeControl).integer().not_null())
+                    .col(
+                        ColumnDef::new(Tournament::Rated)
+                            .boolean()
+                            .not_null()
+                            .default(true),
+                    )
+                    .col(ColumnDef::new(Tournament::Rounds).integer())
+                    .col(
+                        ColumnDef::new(Tournament::CurrentRound)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    // "registration", "running" or "finished"
+                    .col(
+                        ColumnDef::new(Tournament::State)
+                            .string()
+                            .not_null()
+                            .default("registration"),
+                    )
+                    .col(ColumnDef::new(Tournament::CreatedBy).integer().not_null())
+                    .col(
+                        ColumnDef::new(Tournament::CreatedAt)
+                            .timestamp()
+                            .not_null()
+                            .default(SimpleExpr::Keyword(Keyword::CurrentTimestamp)),
+                    )
+                    .col(ColumnDef::new(Tournament::StartedAt).timestamp())
+                    .col(ColumnDef::new(Tournament::FinishedAt).timestamp())
+                    .col(ColumnDef::new(Tournament::UpdatedAt).timestamp())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_tournament_time_control")
+                            .from(Tournament::Table, Tournament::TimeControl)
+                            .to(TimeControl::Table, TimeControl::Id),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_tournament_created_by")
+                            .from(Tournament::Table, Tournament::CreatedBy)
+                            .to(Users::Table, Users::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_table(
+                Table::create()
+                    .table(TournamentPlayer::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(TournamentPlayer::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(TournamentPlayer::TournamentId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TournamentPlayer::UserId)
+                            .integer()
+                            .not_null(),
+                    )
+                    // Rating at registration, used for seeding
+                    .col(ColumnDef::new(TournamentPlayer::Rating).double().not_null())
+                    .col(
+                        ColumnDef::new(TournamentPlayer::Withdrawn)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .col(
+                        ColumnDef::new(TournamentPlayer::CreatedAt)
+                            .timestamp()
+                            .not_null()
+                            .default(SimpleExpr::Keyword(Keyword::CurrentTimestamp)),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_tournament_player_tournament")
+                            .from(TournamentPlayer::Table, TournamentPlayer::TournamentId)
+                            .to(Tournament::Table, Tournament::Id),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_tournament_player_user")
+                            .from(TournamentPlayer::Table, TournamentPlayer::UserId)
+                            .to(Users::Table, Users::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_tournament_player_tournament_user")
+                    .table(TournamentPlayer::Table)
+                    .col(TournamentPlayer::TournamentId)
+                    .col(TournamentPlayer::UserId)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_table(
+                Table::create()
+                    .table(TournamentPairing::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(TournamentPairing::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(TournamentPairing::TournamentId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TournamentPairing::Round)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TournamentPairing::PlayerWhite)
+                            .integer()
+                            .not_null(),
+                    )
+                    // Null for a bye
+                    .col(ColumnDef::new(TournamentPairing::PlayerBlack).integer())
+                    .col(ColumnDef::new(TournamentPairing::GameId).integer())
+                    // "1-0", "0-1", "1/2-1/2" or "bye"
+                    .col(ColumnDef::new(TournamentPairing::Result).string())
+                    .col(
+                        ColumnDef::new(TournamentPairing::CreatedAt)
+                            .timestamp()
+                            .not_null()
+                            .default(SimpleExpr::Keyword(Keyword::CurrentTimestamp)),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_tournament_pairing_tournament")
+                            .from(TournamentPairing::Table, TournamentPairing::TournamentId)
+                            .to(Tournament::Table, Tournament::Id),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_tournament_pairing_white")
+                            .from(TournamentPairing::Table, TournamentPairing::PlayerWhite)
+                            .to(Users::Table, Users::Id),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_tournament_pairing_black")
+                            .from(TournamentPairing::Table, TournamentPairing::PlayerBlack)
+                            .to(Users::Table, Users::Id),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_tournament_pairing_game")
+                            .from(TournamentPairing::Table, TournamentPairing::GameId)
+                            .to(Game::Table, Game::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_tournament_pairing_tournament_round")
+                    .table(TournamentPairing::Table)
+                    .col(TournamentPairing::TournamentId)
+                    .col(TournamentPairing::Round)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_tournament_pairing_game_id")
+                    .table(TournamentPairing::Table)
+                    .col(TournamentPairing::GameId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(TournamentPairing::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(TournamentPlayer::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Tournament::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum Tournament {
+    Table,
+    Id,
+    Name,
+    Format,
+    TimeControl,
+    Rated,
+    Rounds,
+    CurrentRound,
+    State,
+    CreatedBy,
+    CreatedAt,
+    StartedAt,
+    FinishedAt,
+    UpdatedAt,
+}
+
+#[derive(Iden)]
+pub enum TournamentPlayer {
+    Table,
+    Id,
+    TournamentId,
+    UserId,
+    Rating,
+    Withdrawn,
+    CreatedAt,
+}
+
+#[derive(Iden)]
+pub enum TournamentPairing {
+    Table,
+    Id,
+    TournamentId,
+    Round,
+    PlayerWhite,
+    PlayerBlack,
+    GameId,
+    Result,
+    CreatedAt,
+}
@@ -0,0 +1,79 @@
+use sea_orm_migration::prelude::*;
+
+use super::m20240601_000011_create_tournament_tables::{Tournament, TournamentPairing};
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240608_000012_add_tournament_format_columns"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Tournament::Table)
+                    // Arena length in minutes
+                    .add_column_if_not_exists(ColumnDef::new(TournamentArena::Duration).integer())
+                    .add_column_if_not_exists(ColumnDef::new(TournamentArena::EndsAt).timestamp())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TournamentPairing::Table)
+                    .add_column_if_not_exists(
+                        ColumnDef::new(PairingBerserk::WhiteBerserk)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .add_column_if_not_exists(
+                        ColumnDef::new(PairingBerserk::BlackBerserk)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TournamentPairing::Table)
+                    .drop_column(PairingBerserk::WhiteBerserk)
+                    .drop_column(PairingBerserk::BlackBerserk)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Tournament::Table)
+                    .drop_column(TournamentArena::Duration)
+                    .drop_column(TournamentArena::EndsAt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum TournamentArena {
+    Duration,
+    EndsAt,
+}
+
+#[derive(Iden)]
+pub enum PairingBerserk {
+    WhiteBerserk,
+    BlackBerserk,
+}
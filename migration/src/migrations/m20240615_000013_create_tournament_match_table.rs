@@ -0,0 +1,203 @@
+use sea_orm_migration::prelude::*;
+
+use super::{
+    m20240414_000001_create_users_table::Users,
+    m20240414_000003_create_game_table::Game,
+    m20240601_000011_create_tournament_tables::{Tournament, TournamentPairing},
+};
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240615_000013_create_tournament_match_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(TournamentMatch::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(TournamentMatch::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(TournamentMatch::TournamentId)
+                            .integer()
+                            .not_null(),
+                    )
+                    // "winners", "losers" or "final"
+                    .col(ColumnDef::new(TournamentMatch::Bracket).string().not_null())
+                    .col(ColumnDef::new(TournamentMatch::Round).integer().not_null())
+                    .col(
+                        ColumnDef::new(TournamentMatch::Position)
+                            .integer()
+                            .not_null(),
+                    )
+                    // Null until known
+                    .col(ColumnDef::new(TournamentMatch::PlayerOne).integer())
+                    .col(ColumnDef::new(TournamentMatch::PlayerTwo).integer())
+                    // Null after a walkover
+                    .col(ColumnDef::new(TournamentMatch::Winner).integer())
+                    .col(ColumnDef::new(TournamentMatch::Loser).integer())
+                    // "pending", "playing" or "finished"
+                    .col(
+                        ColumnDef::new(TournamentMatch::State)
+                            .string()
+                            .not_null()
+                            .default("pending"),
+                    )
+                    .col(
+                        ColumnDef::new(TournamentMatch::CreatedAt)
+                            .timestamp()
+                            .not_null()
+                            .default(SimpleExpr::Keyword(Keyword::CurrentTimestamp)),
+                    )
+                    .col(ColumnDef::new(TournamentMatch::UpdatedAt).timestamp())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_tournament_match_tournament")
+                            .from(TournamentMatch::Table, TournamentMatch::TournamentId)
+                            .to(Tournament::Table, Tournament::Id),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_tournament_match_player_one")
+                            .from(TournamentMatch::Table, TournamentMatch::PlayerOne)
+                            .to(Users::Table, Users::Id),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_tournament_match_player_two")
+                            .from(TournamentMatch::Table, TournamentMatch::PlayerTwo)
+                            .to(Users::Table, Users::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_tournament_match_tournament")
+                    .table(TournamentMatch::Table)
+                    .col(TournamentMatch::TournamentId)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TournamentPairing::Table)
+                    .add_column_if_not_exists(ColumnDef::new(PairingMatch::MatchId).integer())
+                    // "series", "rapid", "blitz" or "armageddon" within a knockout match
+                    .add_column_if_not_exists(ColumnDef::new(PairingMatch::Stage).string())
+                    .add_foreign_key(
+                        TableForeignKey::new()
+                            .name("fk_tournament_pairing_match")
+                            .from_tbl(TournamentPairing::Table)
+                            .from_col(PairingMatch::MatchId)
+                            .to_tbl(TournamentMatch::Table)
+                            .to_col(TournamentMatch::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Tournament::Table)
+                    // Games per knockout match before tiebreaks
+                    .add_column_if_not_exists(
+                        ColumnDef::new(TournamentSeries::SeriesGames).integer(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Game::Table)
+                    // Starting clocks in seconds when they differ from the time control
+                    .add_column_if_not_exists(ColumnDef::new(GameClock::WhiteClock).integer())
+                    .add_column_if_not_exists(ColumnDef::new(GameClock::BlackClock).integer())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Game::Table)
+                    .drop_column(GameClock::WhiteClock)
+                    .drop_column(GameClock::BlackClock)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Tournament::Table)
+                    .drop_column(TournamentSeries::SeriesGames)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TournamentPairing::Table)
+                    .drop_foreign_key(Alias::new("fk_tournament_pairing_match"))
+                    .drop_column(PairingMatch::MatchId)
+                    .drop_column(PairingMatch::Stage)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_table(Table::drop().table(TournamentMatch::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum TournamentMatch {
+    Table,
+    Id,
+    TournamentId,
+    Bracket,
+    Round,
+    Position,
+    PlayerOne,
+    PlayerTwo,
+    Winner,
+    Loser,
+    State,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(Iden)]
+pub enum PairingMatch {
+    MatchId,
+    Stage,
+}
+
+#[derive(Iden)]
+pub enum TournamentSeries {
+    SeriesGames,
+}
+
+#[derive(Iden)]
+pub enum GameClock {
+    WhiteClock,
+    BlackClock,
+}
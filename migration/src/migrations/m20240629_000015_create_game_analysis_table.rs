@@ -0,0 +1,94 @@
+use sea_orm_migration::prelude::*;
+
+use super::m20240414_000003_create_game_table::Game;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240629_000015_create_game_analysis_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(GameAnalysis::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(GameAnalysis::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(GameAnalysis::GameId)
+                            .integer()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    // "done", or "failed" when the moves could not be replayed
+                    .col(ColumnDef::new(GameAnalysis::State).string().not_null())
+                    // "built-in" or "uci"
+                    .col(ColumnDef::new(GameAnalysis::Engine).string().not_null())
+                    // One per position from the start, for white, e.g. "20 -35 #3"
+                    .col(
+                        ColumnDef::new(GameAnalysis::Evaluations)
+                            .text()
+                            .not_null()
+                            .default(""),
+                    )
+                    // The engine's move in each position in UCI notation, "-" when there is none
+                    .col(
+                        ColumnDef::new(GameAnalysis::BestMoves)
+                            .text()
+                            .not_null()
+                            .default(""),
+                    )
+                    .col(ColumnDef::new(GameAnalysis::WhiteAccuracy).double())
+                    .col(ColumnDef::new(GameAnalysis::BlackAccuracy).double())
+                    .col(ColumnDef::new(GameAnalysis::WhiteAcpl).double())
+                    .col(ColumnDef::new(GameAnalysis::BlackAcpl).double())
+                    .col(
+                        ColumnDef::new(GameAnalysis::CreatedAt)
+                            .timestamp()
+                            .not_null()
+                            .default(SimpleExpr::Keyword(Keyword::CurrentTimestamp)),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_game_analysis_game")
+                            .from(GameAnalysis::Table, GameAnalysis::GameId)
+                            .to(Game::Table, Game::Id),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(GameAnalysis::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum GameAnalysis {
+    Table,
+    Id,
+    GameId,
+    State,
+    Engine,
+    Evaluations,
+    BestMoves,
+    WhiteAccuracy,
+    BlackAccuracy,
+    WhiteAcpl,
+    BlackAcpl,
+    CreatedAt,
+}
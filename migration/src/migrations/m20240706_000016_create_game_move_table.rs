@@ -0,0 +1,116 @@
+use sea_orm_migration::prelude::*;
+
+use super::m20240414_000003_create_game_table::Game;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240706_000016_create_game_move_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(GameMove::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(GameMove::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(GameMove::GameId).integer().not_null())
+                    // From 1
+                    .col(ColumnDef::new(GameMove::Ply).integer().not_null())
+                    .col(ColumnDef::new(GameMove::San).string().not_null())
+                    // Null when the move could not be replayed
+                    .col(ColumnDef::new(GameMove::Uci).string())
+                    // Position after the move
+                    .col(ColumnDef::new(GameMove::Fen).string())
+                    .col(ColumnDef::new(GameMove::Zobrist).big_integer())
+                    // Milliseconds, null for moves played before this table existed
+                    .col(ColumnDef::new(GameMove::ClockRemaining).integer())
+                    .col(ColumnDef::new(GameMove::TimeSpent).integer())
+                    .col(
+                        ColumnDef::new(GameMove::CreatedAt)
+                            .timestamp()
+                            .not_null()
+                            .default(SimpleExpr::Keyword(Keyword::CurrentTimestamp)),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_game_move_game")
+                            .from(GameMove::Table, GameMove::GameId)
+                            .to(Game::Table, Game::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_game_move_game_ply")
+                    .table(GameMove::Table)
+                    .col(GameMove::GameId)
+                    .col(GameMove::Ply)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_game_move_zobrist")
+                    .table(GameMove::Table)
+                    .col(GameMove::Zobrist)
+                    .to_owned(),
+            )
+            .await?;
+
+        // One row per move of the existing games. Only the last position is known here, the
+        // server fills in UCI, FEN and keys of the others on startup by replaying the games.
+        let db = manager.get_connection();
+        db.execute_unprepared(
+            "INSERT INTO game_move (game_id, ply, san, fen, created_at) \
+             SELECT g.id, m.ply, m.san, \
+                CASE WHEN m.ply = array_length(regexp_split_to_array(g.moves, ' '), 1) \
+                    THEN g.board END, \
+                COALESCE(g.updated_at, g.created_at) \
+             FROM game AS g, \
+                regexp_split_to_table(g.moves, ' ') WITH ORDINALITY AS m(san, ply) \
+             WHERE g.moves <> '' \
+             ON CONFLICT DO NOTHING",
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(GameMove::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum GameMove {
+    Table,
+    Id,
+    GameId,
+    Ply,
+    San,
+    Uci,
+    Fen,
+    Zobrist,
+    ClockRemaining,
+    TimeSpent,
+    CreatedAt,
+}
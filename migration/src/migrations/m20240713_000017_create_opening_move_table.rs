@@ -0,0 +1,94 @@
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240713_000017_create_opening_move_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Games counted per move played from a position, split by what the explorer filters on.
+        // Filled by the server as games finish.
+        manager
+            .create_table(
+                Table::create()
+                    .table(OpeningMove::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(OpeningMove::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    // Zobrist key of the position the move was played in
+                    .col(
+                        ColumnDef::new(OpeningMove::Zobrist)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(OpeningMove::Uci).string().not_null())
+                    .col(ColumnDef::new(OpeningMove::San).string().not_null())
+                    // Rating pool of the time control
+                    .col(ColumnDef::new(OpeningMove::Pool).string().not_null())
+                    // Average rating of the players rounded down to 200
+                    .col(ColumnDef::new(OpeningMove::RatingBand).integer().not_null())
+                    // First day of the month the game finished in
+                    .col(ColumnDef::new(OpeningMove::Month).date().not_null())
+                    .col(ColumnDef::new(OpeningMove::Games).integer().not_null())
+                    .col(ColumnDef::new(OpeningMove::WhiteWins).integer().not_null())
+                    .col(ColumnDef::new(OpeningMove::Draws).integer().not_null())
+                    .col(ColumnDef::new(OpeningMove::BlackWins).integer().not_null())
+                    // Sum of the average ratings of the games
+                    .col(
+                        ColumnDef::new(OpeningMove::RatingSum)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_opening_move_key")
+                    .table(OpeningMove::Table)
+                    .col(OpeningMove::Zobrist)
+                    .col(OpeningMove::Pool)
+                    .col(OpeningMove::RatingBand)
+                    .col(OpeningMove::Month)
+                    .col(OpeningMove::Uci)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(OpeningMove::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum OpeningMove {
+    Table,
+    Id,
+    Zobrist,
+    Uci,
+    San,
+    Pool,
+    RatingBand,
+    Month,
+    Games,
+    WhiteWins,
+    Draws,
+    BlackWins,
+    RatingSum,
+}
@@ -0,0 +1,45 @@
+use sea_orm_migration::prelude::*;
+
+use super::m20240414_000003_create_game_table::Game;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240720_000018_add_game_opening_columns"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Game::Table)
+                    // ECO code and name of the opening, e.g. "C65" and "Ruy Lopez: Berlin Defense"
+                    .add_column_if_not_exists(ColumnDef::new(GameOpening::Eco).string())
+                    .add_column_if_not_exists(ColumnDef::new(GameOpening::Opening).string())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Game::Table)
+                    .drop_column(GameOpening::Eco)
+                    .drop_column(GameOpening::Opening)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum GameOpening {
+    Eco,
+    Opening,
+}
@@ -0,0 +1,240 @@
+use sea_orm_migration::prelude::*;
+
+use super::{m20240414_000001_create_users_table::Users, m20240414_000003_create_game_table::Game};
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240727_000019_create_puzzle_tables"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Puzzle::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Puzzle::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    // The game and ply of the blunder it was mined from, if any
+                    .col(ColumnDef::new(Puzzle::GameId).integer())
+                    .col(ColumnDef::new(Puzzle::Ply).integer())
+                    // The position before the opponent's move, the first of the solution
+                    .col(ColumnDef::new(Puzzle::Fen).string().not_null())
+                    // UCI moves separated by spaces, the solver's are every other one
+                    .col(ColumnDef::new(Puzzle::Solution).text().not_null())
+                    // Separated by spaces, e.g. "mate mateIn2 short middlegame"
+                    .col(ColumnDef::new(Puzzle::Themes).string().not_null())
+                    .col(ColumnDef::new(Puzzle::Rating).double().not_null())
+                    .col(ColumnDef::new(Puzzle::Deviation).double().not_null())
+                    .col(ColumnDef::new(Puzzle::Volatility).double().not_null())
+                    .col(
+                        ColumnDef::new(Puzzle::Plays)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(
+                        ColumnDef::new(Puzzle::CreatedAt)
+                            .timestamp()
+                            .not_null()
+                            .default(SimpleExpr::Keyword(Keyword::CurrentTimestamp)),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_puzzle_game")
+                            .from(Puzzle::Table, Puzzle::GameId)
+                            .to(Game::Table, Game::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_puzzle_game_ply")
+                    .table(Puzzle::Table)
+                    .col(Puzzle::GameId)
+                    .col(Puzzle::Ply)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_puzzle_rating")
+                    .table(Puzzle::Table)
+                    .col(Puzzle::Rating)
+                    .to_owned(),
+            )
+            .await?;
+
+        // One per player, puzzle ratings are kept apart from the game rating pools
+        manager
+            .create_table(
+                Table::create()
+                    .table(PuzzleRating::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(PuzzleRating::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(PuzzleRating::UserId)
+                            .integer()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(ColumnDef::new(PuzzleRating::Rating).double().not_null())
+                    .col(ColumnDef::new(PuzzleRating::Deviation).double().not_null())
+                    .col(ColumnDef::new(PuzzleRating::Volatility).double().not_null())
+                    .col(
+                        ColumnDef::new(PuzzleRating::Attempts)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(
+                        ColumnDef::new(PuzzleRating::Solved)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(
+                        ColumnDef::new(PuzzleRating::CreatedAt)
+                            .timestamp()
+                            .not_null()
+                            .default(SimpleExpr::Keyword(Keyword::CurrentTimestamp)),
+                    )
+                    .col(ColumnDef::new(PuzzleRating::UpdatedAt).timestamp())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_puzzle_rating_user")
+                            .from(PuzzleRating::Table, PuzzleRating::UserId)
+                            .to(Users::Table, Users::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Only the first attempt at a puzzle is rated
+        manager
+            .create_table(
+                Table::create()
+                    .table(PuzzleAttempt::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(PuzzleAttempt::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(PuzzleAttempt::PuzzleId).integer().not_null())
+                    .col(ColumnDef::new(PuzzleAttempt::UserId).integer().not_null())
+                    .col(ColumnDef::new(PuzzleAttempt::Solved).boolean().not_null())
+                    // The player's puzzle rating after the attempt
+                    .col(ColumnDef::new(PuzzleAttempt::Rating).double().not_null())
+                    .col(
+                        ColumnDef::new(PuzzleAttempt::CreatedAt)
+                            .timestamp()
+                            .not_null()
+                            .default(SimpleExpr::Keyword(Keyword::CurrentTimestamp)),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_puzzle_attempt_puzzle")
+                            .from(PuzzleAttempt::Table, PuzzleAttempt::PuzzleId)
+                            .to(Puzzle::Table, Puzzle::Id),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_puzzle_attempt_user")
+                            .from(PuzzleAttempt::Table, PuzzleAttempt::UserId)
+                            .to(Users::Table, Users::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_puzzle_attempt_user_puzzle")
+                    .table(PuzzleAttempt::Table)
+                    .col(PuzzleAttempt::UserId)
+                    .col(PuzzleAttempt::PuzzleId)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(PuzzleAttempt::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(PuzzleRating::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Puzzle::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum Puzzle {
+    Table,
+    Id,
+    GameId,
+    Ply,
+    Fen,
+    Solution,
+    Themes,
+    Rating,
+    Deviation,
+    Volatility,
+    Plays,
+    CreatedAt,
+}
+
+#[derive(Iden)]
+pub enum PuzzleRating {
+    Table,
+    Id,
+    UserId,
+    Rating,
+    Deviation,
+    Volatility,
+    Attempts,
+    Solved,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(Iden)]
+pub enum PuzzleAttempt {
+    Table,
+    Id,
+    PuzzleId,
+    UserId,
+    Solved,
+    Rating,
+    CreatedAt,
+}
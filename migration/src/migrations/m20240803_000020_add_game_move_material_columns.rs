@@ -0,0 +1,66 @@
+use sea_orm_migration::prelude::*;
+
+use super::m20240706_000016_create_game_move_table::GameMove;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240803_000020_add_game_move_material_columns"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    // Null for the moves already stored, the server fills them in on startup by replaying the
+    // games
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(GameMove::Table)
+                    // Pieces on the board after the move, e.g. "KRBvKR"
+                    .add_column_if_not_exists(ColumnDef::new(GameMoveMaterial::Material).string())
+                    // The 64 squares from a8 to h1, a piece letter as in FEN or '.' when empty
+                    .add_column_if_not_exists(ColumnDef::new(GameMoveMaterial::Placement).string())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_game_move_material")
+                    .table(GameMove::Table)
+                    .col(GameMoveMaterial::Material)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_game_move_material")
+                    .table(GameMove::Table)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(GameMove::Table)
+                    .drop_column(GameMoveMaterial::Material)
+                    .drop_column(GameMoveMaterial::Placement)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum GameMoveMaterial {
+    Material,
+    Placement,
+}
@@ -0,0 +1,59 @@
+use sea_orm_migration::prelude::*;
+
+use super::m20240414_000003_create_game_table::Game;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240810_000021_add_game_player_indexes"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    // A player's games in order of id, as the game archive pages through them
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_game_player_white")
+                    .table(Game::Table)
+                    .col(Game::PlayerWhite)
+                    .col(Game::Id)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_game_player_black")
+                    .table(Game::Table)
+                    .col(Game::PlayerBlack)
+                    .col(Game::Id)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_game_player_white")
+                    .table(Game::Table)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_game_player_black")
+                    .table(Game::Table)
+                    .to_owned(),
+            )
+            .await
+    }
+}
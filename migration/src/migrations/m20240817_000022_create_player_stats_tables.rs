@@ -0,0 +1,243 @@
+use sea_orm_migration::prelude::*;
+
+use super::{m20240414_000001_create_users_table::Users, m20240414_000003_create_game_table::Game};
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240817_000022_create_player_stats_tables"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Game::Table)
+                    // How the game ended, e.g. "checkmate", "resignation" or "timeout". Null for
+                    // games finished before it was recorded.
+                    .add_column_if_not_exists(ColumnDef::new(GameTermination::Termination).string())
+                    .to_owned(),
+            )
+            .await?;
+
+        // Finished games counted per player, rating pool and color. Filled by the server as
+        // games finish.
+        manager
+            .create_table(
+                Table::create()
+                    .table(PlayerStats::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(PlayerStats::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(PlayerStats::UserId).integer().not_null())
+                    .col(ColumnDef::new(PlayerStats::Pool).string().not_null())
+                    // "white" or "black"
+                    .col(ColumnDef::new(PlayerStats::Color).string().not_null())
+                    .col(ColumnDef::new(PlayerStats::Games).integer().not_null())
+                    .col(ColumnDef::new(PlayerStats::Wins).integer().not_null())
+                    .col(ColumnDef::new(PlayerStats::Draws).integer().not_null())
+                    .col(ColumnDef::new(PlayerStats::Losses).integer().not_null())
+                    // Games lost on time
+                    .col(ColumnDef::new(PlayerStats::Timeouts).integer().not_null())
+                    // Sum of the number of moves of the games, both sides' counted
+                    .col(ColumnDef::new(PlayerStats::Plies).big_integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_player_stats_user")
+                            .from(PlayerStats::Table, PlayerStats::UserId)
+                            .to(Users::Table, Users::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_player_stats_key")
+                    .table(PlayerStats::Table)
+                    .col(PlayerStats::UserId)
+                    .col(PlayerStats::Pool)
+                    .col(PlayerStats::Color)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        // Games counted per player and the opening the game was named after
+        manager
+            .create_table(
+                Table::create()
+                    .table(PlayerOpeningStats::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(PlayerOpeningStats::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(PlayerOpeningStats::UserId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(PlayerOpeningStats::Color)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(PlayerOpeningStats::Eco).string().not_null())
+                    .col(
+                        ColumnDef::new(PlayerOpeningStats::Opening)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(PlayerOpeningStats::Games)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(PlayerOpeningStats::Wins)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(PlayerOpeningStats::Draws)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(PlayerOpeningStats::Losses)
+                            .integer()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_player_opening_stats_user")
+                            .from(PlayerOpeningStats::Table, PlayerOpeningStats::UserId)
+                            .to(Users::Table, Users::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_player_opening_stats_key")
+                    .table(PlayerOpeningStats::Table)
+                    .col(PlayerOpeningStats::UserId)
+                    .col(PlayerOpeningStats::Color)
+                    .col(PlayerOpeningStats::Eco)
+                    .col(PlayerOpeningStats::Opening)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        // Consecutive wins over all pools, a draw or a loss ends a streak
+        manager
+            .create_table(
+                Table::create()
+                    .table(PlayerStreak::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(PlayerStreak::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(PlayerStreak::UserId)
+                            .integer()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(ColumnDef::new(PlayerStreak::Current).integer().not_null())
+                    .col(ColumnDef::new(PlayerStreak::Longest).integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_player_streak_user")
+                            .from(PlayerStreak::Table, PlayerStreak::UserId)
+                            .to(Users::Table, Users::Id),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(PlayerStreak::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(PlayerOpeningStats::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(PlayerStats::Table).to_owned())
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Game::Table)
+                    .drop_column(GameTermination::Termination)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum GameTermination {
+    Termination,
+}
+
+#[derive(Iden)]
+pub enum PlayerStats {
+    Table,
+    Id,
+    UserId,
+    Pool,
+    Color,
+    Games,
+    Wins,
+    Draws,
+    Losses,
+    Timeouts,
+    Plies,
+}
+
+#[derive(Iden)]
+pub enum PlayerOpeningStats {
+    Table,
+    Id,
+    UserId,
+    Color,
+    Eco,
+    Opening,
+    Games,
+    Wins,
+    Draws,
+    Losses,
+}
+
+#[derive(Iden)]
+pub enum PlayerStreak {
+    Table,
+    Id,
+    UserId,
+    Current,
+    Longest,
+}
@@ -0,0 +1,47 @@
+use sea_orm_migration::prelude::*;
+
+use super::m20240504_000006_create_sessions_table::Sessions;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240824_000023_add_session_refresh_generation"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Sessions::Table)
+                    // Goes up with every refresh, only the refresh token of the current one is valid
+                    .add_column_if_not_exists(
+                        ColumnDef::new(SessionRefresh::RefreshGeneration)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Sessions::Table)
+                    .drop_column(SessionRefresh::RefreshGeneration)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum SessionRefresh {
+    RefreshGeneration,
+}
@@ -0,0 +1,51 @@
+use sea_orm_migration::prelude::*;
+
+use super::m20240629_000015_create_game_analysis_table::GameAnalysis;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240831_000024_add_game_analysis_retries"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(GameAnalysis::Table)
+                    // Failed runs so far, the game is given up on after a few
+                    .add_column_if_not_exists(
+                        ColumnDef::new(AnalysisRetry::Attempts)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    // When a game whose analysis failed is tried again
+                    .add_column_if_not_exists(ColumnDef::new(AnalysisRetry::RetryAt).date_time())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(GameAnalysis::Table)
+                    .drop_column(AnalysisRetry::Attempts)
+                    .drop_column(AnalysisRetry::RetryAt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum AnalysisRetry {
+    Attempts,
+    RetryAt,
+}
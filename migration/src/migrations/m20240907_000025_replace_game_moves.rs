@@ -0,0 +1,70 @@
+use sea_orm_migration::prelude::*;
+
+use super::m20240414_000003_create_game_table::Game;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240907_000025_replace_game_moves"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Moves only `game.moves` has are copied over before it goes, `game_move` is the one list
+        // of moves from here on
+        let db = manager.get_connection();
+        db.execute_unprepared(
+            "INSERT INTO game_move (game_id, ply, san, created_at) \
+             SELECT g.id, m.ply, m.san, COALESCE(g.updated_at, g.created_at) \
+             FROM game AS g, \
+                regexp_split_to_table(g.moves, ' ') WITH ORDINALITY AS m(san, ply) \
+             WHERE g.moves <> '' \
+             ON CONFLICT DO NOTHING",
+        )
+        .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Game::Table)
+                    .drop_column(Game::Moves)
+                    // Set once the server found it cannot replay the moves, so it stops trying
+                    .add_column_if_not_exists(
+                        ColumnDef::new(GameReplay::ReplayFailed)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Game::Table)
+                    .drop_column(GameReplay::ReplayFailed)
+                    .add_column(ColumnDef::new(Game::Moves).string().not_null().default(""))
+                    .to_owned(),
+            )
+            .await?;
+        let db = manager.get_connection();
+        db.execute_unprepared(
+            "UPDATE game AS g SET moves = m.moves \
+             FROM (SELECT game_id, string_agg(san, ' ' ORDER BY ply) AS moves \
+                FROM game_move GROUP BY game_id) AS m \
+             WHERE m.game_id = g.id",
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+pub enum GameReplay {
+    ReplayFailed,
+}
@@ -0,0 +1,53 @@
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, SaltString},
+    Argon2,
+};
+use sea_orm_migration::{prelude::*, sea_orm::Statement};
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m20240914_000026_hash_plaintext_passwords"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    // Accounts from before hashing still have the password itself, it is replaced by its Argon2
+    // hash the same way the server stores new ones
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        let backend = manager.get_database_backend();
+        let rows = db
+            .query_all(Statement::from_string(
+                backend,
+                "SELECT id, password FROM users WHERE password <> ''",
+            ))
+            .await?;
+        for row in rows {
+            let id: i32 = row.try_get("", "id")?;
+            let password: String = row.try_get("", "password")?;
+            if PasswordHash::new(&password).is_ok() {
+                continue;
+            }
+            let salt = SaltString::generate(&mut OsRng);
+            let hashed = Argon2::default()
+                .hash_password(password.as_bytes(), &salt)
+                .map_err(|err| DbErr::Migration(format!("Could not hash password: {err}")))?
+                .to_string();
+            db.execute(Statement::from_sql_and_values(
+                backend,
+                "UPDATE users SET password = $1 WHERE id = $2",
+                [hashed.into(), id.into()],
+            ))
+            .await?;
+        }
+        Ok(())
+    }
+
+    // Hashes cannot be turned back into passwords, they stay
+    async fn down(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
+        Ok(())
+    }
+}
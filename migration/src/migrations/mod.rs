@@ -1,4 +1,26 @@
 pub mod m20240414_000001_create_users_table;
 pub mod m20240414_000002_create_time_control_table;
 pub mod m20240414_000003_create_game_table;
-
+pub mod m20240420_000004_add_username_unique_index;
+pub mod m20240427_000005_add_user_profile_columns;
+pub mod m20240504_000006_create_sessions_table;
+pub mod m20240511_000007_create_login_attempts_table;
+pub mod m20240518_000008_add_game_result_columns;
+pub mod m20240518_000009_create_ratings_tables;
+pub mod m20240525_000010_create_challenge_table;
+pub mod m20240601_000011_create_tournament_tables;
+pub mod m20240608_000012_add_tournament_format_columns;
+pub mod m20240615_000013_create_tournament_match_table;
+pub mod m20240622_000014_add_user_bot_level;
+pub mod m20240629_000015_create_game_analysis_table;
+pub mod m20240706_000016_create_game_move_table;
+pub mod m20240713_000017_create_opening_move_table;
+pub mod m20240720_000018_add_game_opening_columns;
+pub mod m20240727_000019_create_puzzle_tables;
+pub mod m20240803_000020_add_game_move_material_columns;
+pub mod m20240810_000021_add_game_player_indexes;
+pub mod m20240817_000022_create_player_stats_tables;
+pub mod m20240824_000023_add_session_refresh_generation;
+pub mod m20240831_000024_add_game_analysis_retries;
+pub mod m20240907_000025_replace_game_moves;
+pub mod m20240914_000026_hash_plaintext_passwords;
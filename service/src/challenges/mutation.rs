@@ -0,0 +1,89 @@
+use ::entity::entities::{challenge, challenge::Entity as Challenge};
+use chrono::{NaiveDateTime, Utc};
+use sea_orm::{sea_query::Expr, *};
+
+pub struct Mutation;
+
+impl Mutation {
+    pub async fn create_challenge(
+        db: &DbConn,
+        form_data: challenge::Model,
+    ) -> Result<challenge::Model, DbErr> {
+        challenge::ActiveModel {
+            challenger: Set(form_data.challenger),
+            challenged: Set(form_data.challenged),
+            time_control: Set(form_data.time_control),
+            color: Set(form_data.color.to_owned()),
+            rated: Set(form_data.rated),
+            variant: Set(form_data.variant.to_owned()),
+            expires_at: Set(form_data.expires_at),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+    }
+
+    // Answers a challenge that is still pending, None if it was answered or expired in the meantime
+    pub async fn answer_challenge<C: ConnectionTrait>(
+        db: &C,
+        id: i32,
+        state: &str,
+    ) -> Result<Option<challenge::Model>, DbErr> {
+        let now = Utc::now().naive_utc();
+        let update = Challenge::update_many()
+            .col_expr(challenge::Column::State, Expr::value(state))
+            .col_expr(challenge::Column::UpdatedAt, Expr::value(now))
+            .filter(challenge::Column::Id.eq(id))
+            .filter(challenge::Column::State.eq("pending"))
+            .filter(challenge::Column::ExpiresAt.gt(now))
+            .exec(db)
+            .await?;
+        if update.rows_affected == 0 {
+            return Ok(None);
+        }
+        Challenge::find_by_id(id).one(db).await
+    }
+
+    pub async fn set_challenge_game<C: ConnectionTrait>(
+        db: &C,
+        id: i32,
+        game_id: i32,
+    ) -> Result<challenge::Model, DbErr> {
+        let challenge: challenge::ActiveModel = Challenge::find_by_id(id)
+            .one(db)
+            .await?
+            .ok_or(DbErr::Custom("Cannot find challenge.".to_owned()))
+            .map(Into::into)?;
+
+        challenge::ActiveModel {
+            id: challenge.id,
+            game_id: Set(Some(game_id)),
+            updated_at: Set(Some(Utc::now().naive_utc())),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+    }
+
+    pub async fn expire_challenges(
+        db: &DbConn,
+        now: NaiveDateTime,
+    ) -> Result<Vec<challenge::Model>, DbErr> {
+        let expired = Challenge::find()
+            .filter(challenge::Column::State.eq("pending"))
+            .filter(challenge::Column::ExpiresAt.lte(now))
+            .all(db)
+            .await?;
+        if expired.is_empty() {
+            return Ok(expired);
+        }
+        Challenge::update_many()
+            .col_expr(challenge::Column::State, Expr::value("expired"))
+            .col_expr(challenge::Column::UpdatedAt, Expr::value(now))
+            .filter(challenge::Column::Id.is_in(expired.iter().map(|c| c.id)))
+            .filter(challenge::Column::State.eq("pending"))
+            .exec(db)
+            .await?;
+        Ok(expired)
+    }
+}
@@ -0,0 +1,33 @@
+use ::entity::entities::{challenge, challenge::Entity as Challenge};
+use chrono::NaiveDateTime;
+use sea_orm::*;
+
+pub struct Query;
+
+impl Query {
+    pub async fn find_challenge_by_id(
+        db: &DbConn,
+        id: i32,
+    ) -> Result<Option<challenge::Model>, DbErr> {
+        Challenge::find_by_id(id).one(db).await
+    }
+
+    // Sent and received challenges that can still be answered
+    pub async fn find_pending_challenges_by_user(
+        db: &DbConn,
+        user_id: i32,
+        now: NaiveDateTime,
+    ) -> Result<Vec<challenge::Model>, DbErr> {
+        Challenge::find()
+            .filter(
+                Condition::any()
+                    .add(challenge::Column::Challenger.eq(user_id))
+                    .add(challenge::Column::Challenged.eq(user_id)),
+            )
+            .filter(challenge::Column::State.eq("pending"))
+            .filter(challenge::Column::ExpiresAt.gt(now))
+            .order_by_asc(challenge::Column::CreatedAt)
+            .all(db)
+            .await
+    }
+}
@@ -0,0 +1,164 @@
+use ::entity::entities::{game, game::Entity as Game, game_move, game_move::Entity as GameMove};
+use chrono::Utc;
+use sea_orm::{sea_query::Expr, *};
+
+pub struct Mutation;
+
+impl Mutation {
+    pub async fn create_game<C: ConnectionTrait>(
+        db: &C,
+        form_data: game::Model,
+    ) -> Result<game::Model, DbErr> {
+        game::ActiveModel {
+            player_white: Set(form_data.player_white),
+            player_black: Set(form_data.player_black),
+            time_control: Set(form_data.time_control),
+            board: Set(form_data.board.to_owned()),
+            turn: Set(form_data.turn.to_owned()),
+            white_time: Set(form_data.white_time),
+            black_time: Set(form_data.black_time),
+            state: Set(form_data.state.to_owned()),
+            rated: Set(form_data.rated),
+            white_clock: Set(form_data.white_clock),
+            black_clock: Set(form_data.black_clock),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+    }
+
+    // The game row keeps the latest position, `game_move` one row per move, both are written
+    // together. The game row is locked first, so of two moves worked out from the same position
+    // only one is saved. None when the game is over or `game_move` no longer follows the last
+    // saved move.
+    pub async fn update_game_position(
+        db: &DbConn,
+        id: i32,
+        board: String,
+        turn: String,
+        game_move: game_move::Model,
+    ) -> Result<Option<game::Model>, DbErr> {
+        let txn = db.begin().await?;
+        let game = Game::find_by_id(id)
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+            .ok_or(DbErr::Custom("Cannot find game.".to_owned()))?;
+        if game.state != "active" {
+            return Ok(None);
+        }
+        let last_ply: Option<i32> = GameMove::find()
+            .select_only()
+            .column_as(game_move::Column::Ply.max(), "ply")
+            .filter(game_move::Column::GameId.eq(id))
+            .into_tuple()
+            .one(&txn)
+            .await?
+            .flatten();
+        if game_move.ply != last_ply.unwrap_or(0) + 1 {
+            return Ok(None);
+        }
+
+        let game = game::ActiveModel {
+            id: Set(game.id),
+            board: Set(board),
+            turn: Set(turn),
+            updated_at: Set(Some(Utc::now().naive_utc())),
+            ..Default::default()
+        }
+        .update(&txn)
+        .await?;
+        game_move::ActiveModel {
+            game_id: Set(id),
+            ply: Set(game_move.ply),
+            san: Set(game_move.san.to_owned()),
+            uci: Set(game_move.uci.to_owned()),
+            fen: Set(game_move.fen.to_owned()),
+            zobrist: Set(game_move.zobrist),
+            material: Set(game_move.material.to_owned()),
+            placement: Set(game_move.placement.to_owned()),
+            clock_remaining: Set(game_move.clock_remaining),
+            time_spent: Set(game_move.time_spent),
+            created_at: Set(game_move.created_at),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?;
+        txn.commit().await?;
+        Ok(Some(game))
+    }
+
+    // Only an active game can be finished, so the result (and rating changes) are applied once.
+    // `db` can be a transaction that also records what follows from the result.
+    pub async fn finish_game<C: ConnectionTrait>(
+        db: &C,
+        id: i32,
+        result: String,
+        termination: String,
+    ) -> Result<Option<game::Model>, DbErr> {
+        let update = Game::update_many()
+            .col_expr(game::Column::State, Expr::value("finished"))
+            .col_expr(game::Column::Result, Expr::value(result))
+            .col_expr(game::Column::Termination, Expr::value(termination))
+            .col_expr(game::Column::UpdatedAt, Expr::value(Utc::now().naive_utc()))
+            .filter(game::Column::Id.eq(id))
+            .filter(game::Column::State.eq("active"))
+            .exec(db)
+            .await?;
+        if update.rows_affected == 0 {
+            return Ok(None);
+        }
+        Game::find_by_id(id).one(db).await
+    }
+
+    pub async fn set_game_opening(
+        db: &DbConn,
+        id: i32,
+        eco: String,
+        opening: String,
+    ) -> Result<(), DbErr> {
+        Game::update_many()
+            .col_expr(game::Column::Eco, Expr::value(eco))
+            .col_expr(game::Column::Opening, Expr::value(opening))
+            .filter(game::Column::Id.eq(id))
+            .exec(db)
+            .await?;
+        Ok(())
+    }
+
+    // Halves one side's clock, only possible before the first move
+    pub async fn halve_clock(db: &DbConn, id: i32, white: bool) -> Result<bool, DbErr> {
+        let column = if white {
+            game::Column::WhiteTime
+        } else {
+            game::Column::BlackTime
+        };
+        let update = Game::update_many()
+            .col_expr(column, Expr::col(column).div(2))
+            .col_expr(game::Column::UpdatedAt, Expr::value(Utc::now().naive_utc()))
+            .filter(game::Column::Id.eq(id))
+            .filter(game::Column::State.eq("active"))
+            .filter(
+                game::Column::Id.not_in_subquery(
+                    sea_query::Query::select()
+                        .column(game_move::Column::GameId)
+                        .from(game_move::Entity)
+                        .and_where(game_move::Column::GameId.eq(id))
+                        .to_owned(),
+                ),
+            )
+            .exec(db)
+            .await?;
+        Ok(update.rows_affected > 0)
+    }
+
+    // The moves of the game cannot be replayed, so positions are not looked for again
+    pub async fn mark_replay_failed(db: &DbConn, id: i32) -> Result<(), DbErr> {
+        Game::update_many()
+            .col_expr(game::Column::ReplayFailed, Expr::value(true))
+            .filter(game::Column::Id.eq(id))
+            .exec(db)
+            .await?;
+        Ok(())
+    }
+}
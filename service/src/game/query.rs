@@ -0,0 +1,281 @@
+use ::entity::entities::{game, game::Entity as Game, game_move};
+use chrono::NaiveDateTime;
+use sea_orm::{
+    sea_query::{Expr, LikeExpr, SimpleExpr},
+    *,
+};
+
+pub struct Query;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Side {
+    White,
+    Black,
+}
+
+// The result for the player the archive is for
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Outcome {
+    Win,
+    Draw,
+    Loss,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ArchiveOrder {
+    #[default]
+    NewestFirst,
+    OldestFirst,
+}
+
+// Finished games of `player_id`, unset fields do not filter
+#[derive(Clone, Debug, Default)]
+pub struct GameFilter {
+    pub player_id: i32,
+    pub opponent_id: Option<i32>,
+    // The player's color
+    pub side: Option<Side>,
+    pub outcome: Option<Outcome>,
+    // Any of these time controls when not empty
+    pub time_controls: Vec<i32>,
+    // Codes starting with it, e.g. "B2" for B20 to B29
+    pub eco: Option<String>,
+    // Names starting with it, so a name includes its variations
+    pub opening: Option<String>,
+    // When the game started, from `since` up to but not including `until`
+    pub since: Option<NaiveDateTime>,
+    pub until: Option<NaiveDateTime>,
+    pub rated: Option<bool>,
+}
+
+fn played_as(side: Side, player_id: i32) -> Condition {
+    match side {
+        Side::White => Condition::all().add(game::Column::PlayerWhite.eq(player_id)),
+        Side::Black => Condition::all().add(game::Column::PlayerBlack.eq(player_id)),
+    }
+}
+
+// Values of `column` that start with `prefix`, LIKE's wildcards in it are matched as they are
+fn starts_with(column: game::Column, prefix: &str) -> SimpleExpr {
+    let escaped = prefix
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    Expr::col((Game, column)).like(LikeExpr::new(format!("{escaped}%")).escape('\\'))
+}
+
+fn archive_condition(filter: &GameFilter) -> Condition {
+    let player = filter.player_id;
+    let sides = match filter.side {
+        Some(side) => vec![side],
+        None => vec![Side::White, Side::Black],
+    };
+    let mut as_player = Condition::any();
+    for side in sides {
+        let mut condition = played_as(side, player);
+        if let Some(opponent) = filter.opponent_id {
+            let opponent_column = match side {
+                Side::White => game::Column::PlayerBlack,
+                Side::Black => game::Column::PlayerWhite,
+            };
+            condition = condition.add(opponent_column.eq(opponent));
+        }
+        if let Some(outcome) = filter.outcome {
+            let result = match (outcome, side) {
+                (Outcome::Draw, _) => "1/2-1/2",
+                (Outcome::Win, Side::White) | (Outcome::Loss, Side::Black) => "1-0",
+                (Outcome::Win, Side::Black) | (Outcome::Loss, Side::White) => "0-1",
+            };
+            condition = condition.add(game::Column::Result.eq(result));
+        }
+        as_player = as_player.add(condition);
+    }
+
+    Condition::all()
+        .add(game::Column::Result.is_not_null())
+        .add(as_player)
+        .add_option(
+            (!filter.time_controls.is_empty())
+                .then(|| game::Column::TimeControl.is_in(filter.time_controls.clone())),
+        )
+        .add_option(
+            filter
+                .eco
+                .as_ref()
+                .map(|eco| starts_with(game::Column::Eco, eco)),
+        )
+        .add_option(
+            filter
+                .opening
+                .as_ref()
+                .map(|opening| starts_with(game::Column::Opening, opening)),
+        )
+        .add_option(filter.since.map(|since| game::Column::CreatedAt.gte(since)))
+        .add_option(filter.until.map(|until| game::Column::CreatedAt.lt(until)))
+        .add_option(filter.rated.map(|rated| game::Column::Rated.eq(rated)))
+}
+
+impl Query {
+    pub async fn find_game_by_id(db: &DbConn, id: i32) -> Result<Option<game::Model>, DbErr> {
+        Game::find_by_id(id).one(db).await
+    }
+
+    pub async fn find_games_by_ids(db: &DbConn, ids: Vec<i32>) -> Result<Vec<game::Model>, DbErr> {
+        Game::find()
+            .filter(game::Column::Id.is_in(ids))
+            .all(db)
+            .await
+    }
+    pub async fn find_recent_games_by_user(
+        db: &DbConn,
+        user_id: i32,
+        limit: u64,
+    ) -> Result<Vec<game::Model>, DbErr> {
+        Game::find()
+            .filter(
+                Condition::any()
+                    .add(game::Column::PlayerWhite.eq(user_id))
+                    .add(game::Column::PlayerBlack.eq(user_id)),
+            )
+            .order_by_desc(game::Column::CreatedAt)
+            .limit(limit)
+            .all(db)
+            .await
+    }
+
+    // Games with a result in order of id, `limit` at a time starting after `after_id`
+    pub async fn find_finished_games(
+        db: &DbConn,
+        after_id: i32,
+        limit: u64,
+    ) -> Result<Vec<game::Model>, DbErr> {
+        Game::find()
+            .filter(game::Column::Id.gt(after_id))
+            .filter(game::Column::Result.is_not_null())
+            .order_by_asc(game::Column::Id)
+            .limit(limit)
+            .all(db)
+            .await
+    }
+
+    pub async fn find_active_games(db: &DbConn) -> Result<Vec<game::Model>, DbErr> {
+        Game::find()
+            .filter(game::Column::State.eq("active"))
+            .all(db)
+            .await
+    }
+
+    pub async fn find_active_games_by_user(
+        db: &DbConn,
+        user_id: i32,
+    ) -> Result<Vec<game::Model>, DbErr> {
+        Game::find()
+            .filter(game::Column::State.eq("active"))
+            .filter(
+                Condition::any()
+                    .add(game::Column::PlayerWhite.eq(user_id))
+                    .add(game::Column::PlayerBlack.eq(user_id)),
+            )
+            .all(db)
+            .await
+    }
+
+    // Games with moves but no opening name yet
+    pub async fn find_unclassified_games(db: &DbConn) -> Result<Vec<game::Model>, DbErr> {
+        Game::find()
+            .filter(game::Column::Eco.is_null())
+            .filter(
+                game::Column::Id.in_subquery(
+                    sea_query::Query::select()
+                        .column(game_move::Column::GameId)
+                        .from(game_move::Entity)
+                        .to_owned(),
+                ),
+            )
+            .all(db)
+            .await
+    }
+
+    // A page of the archive, `limit` games from the one after the game `cursor` in `order`
+    pub async fn find_archived_games(
+        db: &DbConn,
+        filter: &GameFilter,
+        order: ArchiveOrder,
+        cursor: Option<i32>,
+        limit: u64,
+    ) -> Result<Vec<game::Model>, DbErr> {
+        // Ids grow with the start of the games, so they keep pages stable while games finish
+        let (after_cursor, sort) = match order {
+            ArchiveOrder::NewestFirst => (cursor.map(|id| game::Column::Id.lt(id)), Order::Desc),
+            ArchiveOrder::OldestFirst => (cursor.map(|id| game::Column::Id.gt(id)), Order::Asc),
+        };
+        Game::find()
+            .filter(archive_condition(filter))
+            .apply_if(after_cursor, |query, condition| query.filter(condition))
+            .order_by(game::Column::Id, sort)
+            .limit(limit)
+            .all(db)
+            .await
+    }
+
+    // All games the filter matches, regardless of pages
+    pub async fn count_archived_games(db: &DbConn, filter: &GameFilter) -> Result<u64, DbErr> {
+        Game::find()
+            .filter(archive_condition(filter))
+            .count(db)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_sql(filter: &GameFilter) -> String {
+        Game::find()
+            .filter(archive_condition(filter))
+            .build(DbBackend::Postgres)
+            .to_string()
+    }
+
+    #[test]
+    fn test_archive_condition_is_relative_to_the_player() {
+        let filter = GameFilter {
+            player_id: 7,
+            opponent_id: Some(9),
+            outcome: Some(Outcome::Win),
+            ..Default::default()
+        };
+        let sql = to_sql(&filter);
+        assert!(sql.contains(
+            r#"("game"."player_white" = 7 AND "game"."player_black" = 9 AND "game"."result" = '1-0') OR ("game"."player_black" = 7 AND "game"."player_white" = 9 AND "game"."result" = '0-1')"#
+        ));
+        assert!(sql.contains(r#""game"."result" IS NOT NULL"#));
+
+        let filter = GameFilter {
+            player_id: 7,
+            side: Some(Side::Black),
+            time_controls: vec![1, 2],
+            eco: Some("B2".to_string()),
+            rated: Some(true),
+            ..Default::default()
+        };
+        let sql = to_sql(&filter);
+        assert!(sql.contains(r#""game"."player_black" = 7"#));
+        assert!(!sql.contains(r#""game"."player_white" = 7"#));
+        assert!(sql.contains(r#""game"."time_control" IN (1, 2)"#));
+        assert!(sql.contains(r#""game"."eco" LIKE 'B2%'"#));
+        assert!(sql.contains(r#""game"."rated" = TRUE"#));
+    }
+
+    #[test]
+    fn test_prefix_wildcards_are_escaped() {
+        let filter = GameFilter {
+            player_id: 7,
+            opening: Some("100%_".to_string()),
+            ..Default::default()
+        };
+        let sql = to_sql(&filter);
+        assert!(sql.contains(r#""game"."opening" LIKE E'100\\%\\_%' ESCAPE E'\\'"#));
+    }
+}
@@ -0,0 +1,53 @@
+use ::entity::entities::game_analysis;
+use sea_orm::*;
+
+pub struct Mutation;
+
+impl Mutation {
+    pub async fn create_analysis(
+        db: &DbConn,
+        form_data: game_analysis::Model,
+    ) -> Result<game_analysis::Model, DbErr> {
+        game_analysis::ActiveModel {
+            game_id: Set(form_data.game_id),
+            state: Set(form_data.state.to_owned()),
+            engine: Set(form_data.engine.to_owned()),
+            evaluations: Set(form_data.evaluations.to_owned()),
+            best_moves: Set(form_data.best_moves.to_owned()),
+            white_accuracy: Set(form_data.white_accuracy),
+            black_accuracy: Set(form_data.black_accuracy),
+            white_acpl: Set(form_data.white_acpl),
+            black_acpl: Set(form_data.black_acpl),
+            attempts: Set(form_data.attempts),
+            retry_at: Set(form_data.retry_at),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+    }
+
+    // Overwrites an analysis that failed before with the outcome of another run
+    pub async fn update_analysis(
+        db: &DbConn,
+        id: i32,
+        form_data: game_analysis::Model,
+    ) -> Result<game_analysis::Model, DbErr> {
+        game_analysis::ActiveModel {
+            id: Set(id),
+            game_id: Set(form_data.game_id),
+            state: Set(form_data.state.to_owned()),
+            engine: Set(form_data.engine.to_owned()),
+            evaluations: Set(form_data.evaluations.to_owned()),
+            best_moves: Set(form_data.best_moves.to_owned()),
+            white_accuracy: Set(form_data.white_accuracy),
+            black_accuracy: Set(form_data.black_accuracy),
+            white_acpl: Set(form_data.white_acpl),
+            black_acpl: Set(form_data.black_acpl),
+            attempts: Set(form_data.attempts),
+            retry_at: Set(form_data.retry_at),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+    }
+}
@@ -0,0 +1,44 @@
+use ::entity::entities::{
+    game, game::Entity as Game, game_analysis, game_analysis::Entity as GameAnalysis,
+};
+use chrono::NaiveDateTime;
+use sea_orm::*;
+
+pub struct Query;
+
+impl Query {
+    pub async fn find_analysis_by_game(
+        db: &DbConn,
+        game_id: i32,
+    ) -> Result<Option<game_analysis::Model>, DbErr> {
+        GameAnalysis::find()
+            .filter(game_analysis::Column::GameId.eq(game_id))
+            .one(db)
+            .await
+    }
+
+    // Finished games without an analysis yet or whose failed one is due for a retry, those that
+    // ended first first
+    pub async fn find_unanalysed_games(
+        db: &DbConn,
+        now: NaiveDateTime,
+        limit: u64,
+    ) -> Result<Vec<(game::Model, Option<game_analysis::Model>)>, DbErr> {
+        Game::find()
+            .find_also_related(GameAnalysis)
+            .filter(game::Column::State.eq("finished"))
+            .filter(
+                Condition::any()
+                    .add(game_analysis::Column::Id.is_null())
+                    .add(
+                        Condition::all()
+                            .add(game_analysis::Column::State.eq("retrying"))
+                            .add(game_analysis::Column::RetryAt.lte(now)),
+                    ),
+            )
+            .order_by_asc(game::Column::UpdatedAt)
+            .limit(limit)
+            .all(db)
+            .await
+    }
+}
@@ -0,0 +1,25 @@
+use ::entity::entities::{game_move, game_move::Entity as GameMove};
+use sea_orm::{sea_query::Expr, *};
+
+pub struct Mutation;
+
+impl Mutation {
+    // Fills in what is known from replaying the game, for the move with the game and ply of
+    // `form_data`
+    pub async fn set_move_position(db: &DbConn, form_data: game_move::Model) -> Result<(), DbErr> {
+        GameMove::update_many()
+            .col_expr(game_move::Column::Uci, Expr::value(form_data.uci))
+            .col_expr(game_move::Column::Fen, Expr::value(form_data.fen))
+            .col_expr(game_move::Column::Zobrist, Expr::value(form_data.zobrist))
+            .col_expr(game_move::Column::Material, Expr::value(form_data.material))
+            .col_expr(
+                game_move::Column::Placement,
+                Expr::value(form_data.placement),
+            )
+            .filter(game_move::Column::GameId.eq(form_data.game_id))
+            .filter(game_move::Column::Ply.eq(form_data.ply))
+            .exec(db)
+            .await?;
+        Ok(())
+    }
+}
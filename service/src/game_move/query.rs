@@ -0,0 +1,162 @@
+use ::entity::entities::{game, game_move, game_move::Entity as GameMove};
+use sea_orm::{sea_query::Expr, *};
+use std::collections::HashMap;
+
+pub struct Query;
+
+// What the positions searched for have in common
+#[derive(Clone, Debug, PartialEq)]
+pub enum PositionFilter {
+    Zobrist(i64),
+    // Any of the signatures
+    Material(Vec<String>),
+    // A LIKE pattern over the placement. Not indexed: patterns mostly name single squares, which
+    // gives a trigram index nothing to look up, so every stored position is checked.
+    Placement(String),
+}
+
+// The first move of the game that reached a matching position
+#[derive(Clone, Debug, PartialEq, FromQueryResult)]
+pub struct PositionMatch {
+    pub game_id: i32,
+    pub ply: i32,
+}
+
+impl Query {
+    // Plies `from` to `to`, both included, in order
+    pub async fn find_moves(
+        db: &DbConn,
+        game_id: i32,
+        from: i32,
+        to: i32,
+    ) -> Result<Vec<game_move::Model>, DbErr> {
+        GameMove::find()
+            .filter(game_move::Column::GameId.eq(game_id))
+            .filter(game_move::Column::Ply.between(from, to))
+            .order_by_asc(game_move::Column::Ply)
+            .all(db)
+            .await
+    }
+
+    // The moves at the game and ply of each match
+    pub async fn find_matched_moves(
+        db: &DbConn,
+        matches: &[PositionMatch],
+    ) -> Result<Vec<game_move::Model>, DbErr> {
+        let moves = GameMove::find()
+            .filter(game_move::Column::GameId.is_in(matches.iter().map(|found| found.game_id)))
+            .filter(game_move::Column::Ply.is_in(matches.iter().map(|found| found.ply)))
+            .all(db)
+            .await?;
+        Ok(moves
+            .into_iter()
+            .filter(|mv| {
+                matches
+                    .iter()
+                    .any(|found| found.game_id == mv.game_id && found.ply == mv.ply)
+            })
+            .collect())
+    }
+
+    // The last `count` moves, latest first
+    pub async fn find_last_moves(
+        db: &DbConn,
+        game_id: i32,
+        count: u64,
+    ) -> Result<Vec<game_move::Model>, DbErr> {
+        GameMove::find()
+            .filter(game_move::Column::GameId.eq(game_id))
+            .order_by_desc(game_move::Column::Ply)
+            .limit(count)
+            .all(db)
+            .await
+    }
+
+    // The SAN moves of each game separated by spaces, games without moves are left out
+    pub async fn find_move_lists<C: ConnectionTrait>(
+        db: &C,
+        game_ids: Vec<i32>,
+    ) -> Result<HashMap<i32, String>, DbErr> {
+        let sans: Vec<(i32, String)> = GameMove::find()
+            .select_only()
+            .column(game_move::Column::GameId)
+            .column(game_move::Column::San)
+            .filter(game_move::Column::GameId.is_in(game_ids))
+            .order_by_asc(game_move::Column::GameId)
+            .order_by_asc(game_move::Column::Ply)
+            .into_tuple()
+            .all(db)
+            .await?;
+        let mut lists: HashMap<i32, String> = HashMap::new();
+        for (game_id, san) in sans {
+            let list = lists.entry(game_id).or_default();
+            if !list.is_empty() {
+                list.push(' ');
+            }
+            list.push_str(&san);
+        }
+        Ok(lists)
+    }
+
+    // The number of moves of each game, games without moves are left out
+    pub async fn count_moves(db: &DbConn, game_ids: Vec<i32>) -> Result<HashMap<i32, i64>, DbErr> {
+        let counts: Vec<(i32, i64)> = GameMove::find()
+            .select_only()
+            .column(game_move::Column::GameId)
+            .column_as(game_move::Column::Id.count(), "plies")
+            .filter(game_move::Column::GameId.is_in(game_ids))
+            .group_by(game_move::Column::GameId)
+            .into_tuple()
+            .all(db)
+            .await?;
+        Ok(counts.into_iter().collect())
+    }
+
+    // Games with moves that were back-filled without UCI, FEN, key or material, except those
+    // already found not to replay
+    pub async fn find_games_missing_positions(db: &DbConn) -> Result<Vec<i32>, DbErr> {
+        GameMove::find()
+            .select_only()
+            .column(game_move::Column::GameId)
+            .join(JoinType::InnerJoin, game_move::Relation::Game.def())
+            .filter(game_move::Column::Material.is_null())
+            .filter(game::Column::ReplayFailed.eq(false))
+            .distinct()
+            .into_tuple()
+            .all(db)
+            .await
+    }
+
+    // Finished games that reached a matching position, newest first, `limit` at a time starting
+    // before `before_game_id`
+    pub async fn find_games_by_position(
+        db: &DbConn,
+        filter: &PositionFilter,
+        before_game_id: Option<i32>,
+        limit: u64,
+    ) -> Result<Vec<PositionMatch>, DbErr> {
+        let condition = match filter {
+            PositionFilter::Zobrist(zobrist) => game_move::Column::Zobrist.eq(*zobrist),
+            PositionFilter::Material(signatures) => {
+                game_move::Column::Material.is_in(signatures.clone())
+            }
+            PositionFilter::Placement(pattern) => game_move::Column::Placement.like(pattern),
+        };
+        GameMove::find()
+            .select_only()
+            .column(game_move::Column::GameId)
+            .column_as(Expr::col(game_move::Column::Ply).min(), "ply")
+            .join(JoinType::InnerJoin, game_move::Relation::Game.def())
+            .filter(game::Column::Result.is_not_null())
+            .filter(condition)
+            .apply_if(before_game_id, |query, before| {
+                query.filter(game_move::Column::GameId.lt(before))
+            })
+            .group_by(game_move::Column::GameId)
+            .order_by_desc(game_move::Column::GameId)
+            .limit(limit)
+            .into_model::<PositionMatch>()
+            .all(db)
+            .await
+    }
+}
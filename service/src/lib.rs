@@ -1 +1,13 @@
+pub mod challenges;
+pub mod game;
+pub mod game_analysis;
+pub mod game_move;
+pub mod login_attempts;
+pub mod openings;
+pub mod player_stats;
+pub mod puzzles;
+pub mod ratings;
+pub mod sessions;
+pub mod time_control;
+pub mod tournaments;
 pub mod users;
\ No newline at end of file
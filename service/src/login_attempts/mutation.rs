@@ -0,0 +1,73 @@
+use ::entity::entities::{login_attempts, login_attempts::Entity as LoginAttempts};
+use chrono::NaiveDateTime;
+use sea_orm::{sea_query::Expr, *};
+
+pub struct Mutation;
+
+// Counts one more failure the same way BackoffPolicy::record_failure does, in a single statement
+// so concurrent attempts can't read the same count. Nothing is counted while the key is locked.
+fn reserve_sql() -> String {
+    let failures =
+        "CASE WHEN login_attempts.last_failure_at > $3 THEN login_attempts.failures + 1 ELSE 1 END";
+    let locked_until = |failures: &str| {
+        format!(
+            "CASE WHEN {failures} > $4 THEN $2 + interval '1 millisecond' \
+             * LEAST($5 * power(2, LEAST({failures} - $4 - 1, 30)), $6) END"
+        )
+    };
+    format!(
+        "INSERT INTO login_attempts (key, failures, last_failure_at, locked_until) \
+         VALUES ($1, 1, $2, {}) \
+         ON CONFLICT (key) DO UPDATE SET failures = {failures}, last_failure_at = $2, locked_until = {} \
+         WHERE login_attempts.locked_until IS NULL OR login_attempts.locked_until <= $2 \
+         RETURNING key, failures, last_failure_at, locked_until",
+        locked_until("1"),
+        locked_until(failures),
+    )
+}
+
+impl Mutation {
+    // Returns None when the key is locked out
+    pub async fn reserve_attempt(
+        db: &DbConn,
+        key: &str,
+        now: NaiveDateTime,
+        window_start: NaiveDateTime,
+        free_attempts: i32,
+        base_delay_ms: i64,
+        max_delay_ms: i64,
+    ) -> Result<Option<login_attempts::Model>, DbErr> {
+        LoginAttempts::find()
+            .from_raw_sql(Statement::from_sql_and_values(
+                DbBackend::Postgres,
+                reserve_sql(),
+                [
+                    key.into(),
+                    now.into(),
+                    window_start.into(),
+                    free_attempts.into(),
+                    base_delay_ms.into(),
+                    max_delay_ms.into(),
+                ],
+            ))
+            .one(db)
+            .await
+    }
+
+    // Takes back a reserved attempt that turned out to be a success
+    pub async fn release_attempt(db: &DbConn, key: &str) -> Result<UpdateResult, DbErr> {
+        LoginAttempts::update_many()
+            .col_expr(
+                login_attempts::Column::Failures,
+                Expr::col(login_attempts::Column::Failures).sub(1),
+            )
+            .filter(login_attempts::Column::Key.eq(key))
+            .filter(login_attempts::Column::Failures.gt(0))
+            .exec(db)
+            .await
+    }
+
+    pub async fn delete_attempts(db: &DbConn, key: &str) -> Result<DeleteResult, DbErr> {
+        LoginAttempts::delete_by_id(key.to_owned()).exec(db).await
+    }
+}
@@ -0,0 +1,13 @@
+use ::entity::entities::{login_attempts, login_attempts::Entity as LoginAttempts};
+use sea_orm::*;
+
+pub struct Query;
+
+impl Query {
+    pub async fn find_attempts_by_key(
+        db: &DbConn,
+        key: &str,
+    ) -> Result<Option<login_attempts::Model>, DbErr> {
+        LoginAttempts::find_by_id(key.to_owned()).one(db).await
+    }
+}
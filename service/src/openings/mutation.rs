@@ -0,0 +1,73 @@
+use ::entity::entities::{opening_move, opening_move::Entity as OpeningMove};
+use sea_orm::{
+    sea_query::{Expr, OnConflict, SimpleExpr},
+    *,
+};
+
+pub struct Mutation;
+
+// Existing count plus the one being inserted
+fn add(column: opening_move::Column) -> SimpleExpr {
+    Expr::col((OpeningMove, column)).add(Expr::cust(format!("excluded.{}", column.as_str())))
+}
+
+impl Mutation {
+    // Adds the moves of one game to the counts, all or nothing
+    pub async fn add_game<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        moves: Vec<opening_move::Model>,
+    ) -> Result<(), DbErr> {
+        let txn = db.begin().await?;
+        for form_data in moves {
+            OpeningMove::insert(opening_move::ActiveModel {
+                zobrist: Set(form_data.zobrist),
+                uci: Set(form_data.uci),
+                san: Set(form_data.san),
+                pool: Set(form_data.pool),
+                rating_band: Set(form_data.rating_band),
+                month: Set(form_data.month),
+                games: Set(form_data.games),
+                white_wins: Set(form_data.white_wins),
+                draws: Set(form_data.draws),
+                black_wins: Set(form_data.black_wins),
+                rating_sum: Set(form_data.rating_sum),
+                ..Default::default()
+            })
+            .on_conflict(
+                OnConflict::columns([
+                    opening_move::Column::Zobrist,
+                    opening_move::Column::Pool,
+                    opening_move::Column::RatingBand,
+                    opening_move::Column::Month,
+                    opening_move::Column::Uci,
+                ])
+                .values([
+                    (
+                        opening_move::Column::Games,
+                        add(opening_move::Column::Games),
+                    ),
+                    (
+                        opening_move::Column::WhiteWins,
+                        add(opening_move::Column::WhiteWins),
+                    ),
+                    (
+                        opening_move::Column::Draws,
+                        add(opening_move::Column::Draws),
+                    ),
+                    (
+                        opening_move::Column::BlackWins,
+                        add(opening_move::Column::BlackWins),
+                    ),
+                    (
+                        opening_move::Column::RatingSum,
+                        add(opening_move::Column::RatingSum),
+                    ),
+                ])
+                .to_owned(),
+            )
+            .exec_without_returning(&txn)
+            .await?;
+        }
+        txn.commit().await
+    }
+}
@@ -0,0 +1,86 @@
+use ::entity::entities::{opening_move, opening_move::Entity as OpeningMove};
+use chrono::NaiveDate;
+use sea_orm::{
+    sea_query::{Alias, Expr, Func, SimpleExpr},
+    *,
+};
+
+pub struct Query;
+
+// Unset fields do not filter
+#[derive(Clone, Debug, Default)]
+pub struct OpeningFilter {
+    pub pools: Vec<String>,
+    // Rating bands from `min_band` up to and including `max_band`
+    pub min_band: Option<i32>,
+    pub max_band: Option<i32>,
+    // Months from `since` up to and including `until`
+    pub since: Option<NaiveDate>,
+    pub until: Option<NaiveDate>,
+}
+
+#[derive(Clone, Debug, PartialEq, FromQueryResult)]
+pub struct MoveStats {
+    pub uci: String,
+    pub san: String,
+    pub games: i64,
+    pub white_wins: i64,
+    pub draws: i64,
+    pub black_wins: i64,
+    pub rating_sum: i64,
+}
+
+impl Query {
+    // Moves played from the position with their totals, most played first
+    pub async fn find_moves(
+        db: &DbConn,
+        zobrist: i64,
+        filter: &OpeningFilter,
+    ) -> Result<Vec<MoveStats>, DbErr> {
+        let mut condition = Condition::all().add(opening_move::Column::Zobrist.eq(zobrist));
+        if !filter.pools.is_empty() {
+            condition = condition.add(opening_move::Column::Pool.is_in(filter.pools.clone()));
+        }
+        if let Some(min_band) = filter.min_band {
+            condition = condition.add(opening_move::Column::RatingBand.gte(min_band));
+        }
+        if let Some(max_band) = filter.max_band {
+            condition = condition.add(opening_move::Column::RatingBand.lte(max_band));
+        }
+        if let Some(since) = filter.since {
+            condition = condition.add(opening_move::Column::Month.gte(since));
+        }
+        if let Some(until) = filter.until {
+            condition = condition.add(opening_move::Column::Month.lte(until));
+        }
+
+        let mut select = OpeningMove::find()
+            .select_only()
+            .column(opening_move::Column::Uci)
+            .column(opening_move::Column::San)
+            .filter(condition)
+            .group_by(opening_move::Column::Uci)
+            .group_by(opening_move::Column::San);
+        for column in [
+            opening_move::Column::Games,
+            opening_move::Column::WhiteWins,
+            opening_move::Column::Draws,
+            opening_move::Column::BlackWins,
+            opening_move::Column::RatingSum,
+        ] {
+            // The sum of a bigint is a numeric in Postgres
+            let sum = Func::cast_as(Func::sum(Expr::col(column)), Alias::new("bigint"));
+            select = select.column_as(SimpleExpr::from(sum), column.as_str());
+        }
+        select
+            .order_by_desc(Expr::cust("games"))
+            .into_model::<MoveStats>()
+            .all(db)
+            .await
+    }
+
+    // Whether any game was counted yet
+    pub async fn has_openings(db: &DbConn) -> Result<bool, DbErr> {
+        Ok(OpeningMove::find().one(db).await?.is_some())
+    }
+}
@@ -0,0 +1,126 @@
+use ::entity::entities::{
+    player_opening_stats, player_opening_stats::Entity as PlayerOpeningStats, player_stats,
+    player_stats::Entity as PlayerStats, player_streak, player_streak::Entity as PlayerStreak,
+};
+use sea_orm::{
+    sea_query::{Alias, Expr, OnConflict, SimpleExpr},
+    *,
+};
+
+pub struct Mutation;
+
+// Existing count plus the one being inserted
+fn add<T: ColumnTrait>(table: &'static str, column: T) -> SimpleExpr {
+    Expr::col((Alias::new(table), column)).add(Expr::cust(format!("excluded.{}", column.as_str())))
+}
+
+impl Mutation {
+    // Adds one finished game to the counts of its players, all or nothing. `won` has each
+    // player with whether they won, for the streaks. `db` can be the transaction that finishes
+    // the game.
+    pub async fn add_game<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        stats: Vec<player_stats::Model>,
+        openings: Vec<player_opening_stats::Model>,
+        won: Vec<(i32, bool)>,
+    ) -> Result<(), DbErr> {
+        let txn = db.begin().await?;
+        for form_data in stats {
+            let counts = [
+                player_stats::Column::Games,
+                player_stats::Column::Wins,
+                player_stats::Column::Draws,
+                player_stats::Column::Losses,
+                player_stats::Column::Timeouts,
+                player_stats::Column::Plies,
+            ];
+            PlayerStats::insert(player_stats::ActiveModel {
+                user_id: Set(form_data.user_id),
+                pool: Set(form_data.pool),
+                color: Set(form_data.color),
+                games: Set(form_data.games),
+                wins: Set(form_data.wins),
+                draws: Set(form_data.draws),
+                losses: Set(form_data.losses),
+                timeouts: Set(form_data.timeouts),
+                plies: Set(form_data.plies),
+                ..Default::default()
+            })
+            .on_conflict(
+                OnConflict::columns([
+                    player_stats::Column::UserId,
+                    player_stats::Column::Pool,
+                    player_stats::Column::Color,
+                ])
+                .values(counts.map(|column| (column, add("player_stats", column))))
+                .to_owned(),
+            )
+            .exec_without_returning(&txn)
+            .await?;
+        }
+        for form_data in openings {
+            let counts = [
+                player_opening_stats::Column::Games,
+                player_opening_stats::Column::Wins,
+                player_opening_stats::Column::Draws,
+                player_opening_stats::Column::Losses,
+            ];
+            PlayerOpeningStats::insert(player_opening_stats::ActiveModel {
+                user_id: Set(form_data.user_id),
+                color: Set(form_data.color),
+                eco: Set(form_data.eco),
+                opening: Set(form_data.opening),
+                games: Set(form_data.games),
+                wins: Set(form_data.wins),
+                draws: Set(form_data.draws),
+                losses: Set(form_data.losses),
+                ..Default::default()
+            })
+            .on_conflict(
+                OnConflict::columns([
+                    player_opening_stats::Column::UserId,
+                    player_opening_stats::Column::Color,
+                    player_opening_stats::Column::Eco,
+                    player_opening_stats::Column::Opening,
+                ])
+                .values(counts.map(|column| (column, add("player_opening_stats", column))))
+                .to_owned(),
+            )
+            .exec_without_returning(&txn)
+            .await?;
+        }
+        for (user_id, won) in won {
+            // Locked so games finishing at the same time both count
+            let streak = PlayerStreak::find()
+                .filter(player_streak::Column::UserId.eq(user_id))
+                .lock_exclusive()
+                .one(&txn)
+                .await?;
+            let current = match (&streak, won) {
+                (Some(streak), true) => streak.current + 1,
+                (None, true) => 1,
+                (_, false) => 0,
+            };
+            match streak {
+                Some(streak) => {
+                    let longest = streak.longest.max(current);
+                    let mut streak: player_streak::ActiveModel = streak.into();
+                    streak.current = Set(current);
+                    streak.longest = Set(longest);
+                    streak.update(&txn).await?;
+                }
+                None => {
+                    player_streak::ActiveModel {
+                        user_id: Set(user_id),
+                        current: Set(current),
+                        longest: Set(current),
+                        ..Default::default()
+                    }
+                    .insert(&txn)
+                    .await?;
+                }
+            }
+        }
+        txn.commit().await
+    }
+}
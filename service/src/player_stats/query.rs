@@ -0,0 +1,46 @@
+use ::entity::entities::{
+    player_opening_stats, player_opening_stats::Entity as PlayerOpeningStats, player_stats,
+    player_stats::Entity as PlayerStats, player_streak, player_streak::Entity as PlayerStreak,
+};
+use sea_orm::*;
+
+pub struct Query;
+
+impl Query {
+    // One row per pool and color the player has finished games in
+    pub async fn find_stats(db: &DbConn, user_id: i32) -> Result<Vec<player_stats::Model>, DbErr> {
+        PlayerStats::find()
+            .filter(player_stats::Column::UserId.eq(user_id))
+            .all(db)
+            .await
+    }
+
+    // Most played first
+    pub async fn find_openings(
+        db: &DbConn,
+        user_id: i32,
+        limit: u64,
+    ) -> Result<Vec<player_opening_stats::Model>, DbErr> {
+        PlayerOpeningStats::find()
+            .filter(player_opening_stats::Column::UserId.eq(user_id))
+            .order_by_desc(player_opening_stats::Column::Games)
+            .order_by_asc(player_opening_stats::Column::Eco)
+            .limit(limit)
+            .all(db)
+            .await
+    }
+
+    pub async fn find_streak(
+        db: &DbConn,
+        user_id: i32,
+    ) -> Result<Option<player_streak::Model>, DbErr> {
+        PlayerStreak::find()
+            .filter(player_streak::Column::UserId.eq(user_id))
+            .one(db)
+            .await
+    }
+
+    pub async fn has_stats(db: &DbConn) -> Result<bool, DbErr> {
+        Ok(PlayerStats::find().one(db).await?.is_some())
+    }
+}
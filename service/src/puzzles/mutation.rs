@@ -0,0 +1,146 @@
+use ::entity::entities::{
+    puzzle, puzzle::Entity as Puzzle, puzzle_attempt, puzzle_rating,
+    puzzle_rating::Entity as PuzzleRating,
+};
+use chrono::Utc;
+use sea_orm::{sea_query::OnConflict, *};
+
+use crate::ratings::glicko2::Glicko2;
+
+pub struct Mutation;
+
+impl From<&puzzle::Model> for Glicko2 {
+    fn from(puzzle: &puzzle::Model) -> Self {
+        Glicko2 {
+            rating: puzzle.rating,
+            deviation: puzzle.deviation,
+            volatility: puzzle.volatility,
+        }
+    }
+}
+
+impl From<&puzzle_rating::Model> for Glicko2 {
+    fn from(rating: &puzzle_rating::Model) -> Self {
+        Glicko2 {
+            rating: rating.rating,
+            deviation: rating.deviation,
+            volatility: rating.volatility,
+        }
+    }
+}
+
+impl Mutation {
+    // A puzzle already mined from the same move of the same game is not added again
+    pub async fn create_puzzle(db: &DbConn, form_data: puzzle::Model) -> Result<(), DbErr> {
+        Puzzle::insert(puzzle::ActiveModel {
+            game_id: Set(form_data.game_id),
+            ply: Set(form_data.ply),
+            fen: Set(form_data.fen),
+            solution: Set(form_data.solution),
+            themes: Set(form_data.themes),
+            rating: Set(form_data.rating),
+            deviation: Set(form_data.deviation),
+            volatility: Set(form_data.volatility),
+            ..Default::default()
+        })
+        .on_conflict(
+            OnConflict::columns([puzzle::Column::GameId, puzzle::Column::Ply])
+                .do_nothing()
+                .to_owned(),
+        )
+        .exec_without_returning(db)
+        .await?;
+        Ok(())
+    }
+
+    // Rates the player's first attempt at a puzzle, the puzzle is the opponent and its rating
+    // moves too. Later attempts change nothing and give None.
+    pub async fn record_attempt(
+        db: &DbConn,
+        user_id: i32,
+        puzzle_id: i32,
+        solved: bool,
+    ) -> Result<Option<puzzle_rating::Model>, DbErr> {
+        let txn = db.begin().await?;
+        let attempted = puzzle_attempt::Entity::find()
+            .filter(puzzle_attempt::Column::UserId.eq(user_id))
+            .filter(puzzle_attempt::Column::PuzzleId.eq(puzzle_id))
+            .one(&txn)
+            .await?;
+        if attempted.is_some() {
+            return Ok(None);
+        }
+        let puzzle = Puzzle::find_by_id(puzzle_id)
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+            .ok_or(DbErr::Custom("Cannot find puzzle.".to_owned()))?;
+        let rating = find_or_create_rating(&txn, user_id).await?;
+
+        let score = if solved { 1.0 } else { 0.0 };
+        let player_before = Glicko2::from(&rating);
+        let puzzle_before = Glicko2::from(&puzzle);
+        let player_after = player_before.update(&[(puzzle_before, score)]);
+        let puzzle_after = puzzle_before.update(&[(player_before, 1.0 - score)]);
+
+        puzzle::ActiveModel {
+            id: Set(puzzle.id),
+            rating: Set(puzzle_after.rating),
+            deviation: Set(puzzle_after.deviation),
+            volatility: Set(puzzle_after.volatility),
+            plays: Set(puzzle.plays + 1),
+            ..Default::default()
+        }
+        .update(&txn)
+        .await?;
+        let updated = puzzle_rating::ActiveModel {
+            id: Set(rating.id),
+            rating: Set(player_after.rating),
+            deviation: Set(player_after.deviation),
+            volatility: Set(player_after.volatility),
+            attempts: Set(rating.attempts + 1),
+            solved: Set(rating.solved + solved as i32),
+            updated_at: Set(Some(Utc::now().naive_utc())),
+            ..Default::default()
+        }
+        .update(&txn)
+        .await?;
+        puzzle_attempt::ActiveModel {
+            puzzle_id: Set(puzzle_id),
+            user_id: Set(user_id),
+            solved: Set(solved),
+            rating: Set(player_after.rating),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?;
+        txn.commit().await?;
+        Ok(Some(updated))
+    }
+}
+
+async fn find_or_create_rating(
+    db: &DatabaseTransaction,
+    user_id: i32,
+) -> Result<puzzle_rating::Model, DbErr> {
+    let existing = PuzzleRating::find()
+        .filter(puzzle_rating::Column::UserId.eq(user_id))
+        .lock_exclusive()
+        .one(db)
+        .await?;
+    if let Some(existing) = existing {
+        return Ok(existing);
+    }
+    let initial = Glicko2::default();
+    puzzle_rating::ActiveModel {
+        user_id: Set(user_id),
+        rating: Set(initial.rating),
+        deviation: Set(initial.deviation),
+        volatility: Set(initial.volatility),
+        attempts: Set(0),
+        solved: Set(0),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+}
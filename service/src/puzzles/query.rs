@@ -0,0 +1,83 @@
+use ::entity::entities::{
+    puzzle, puzzle::Entity as Puzzle, puzzle_attempt, puzzle_attempt::Entity as PuzzleAttempt,
+    puzzle_rating, puzzle_rating::Entity as PuzzleRating,
+};
+use chrono::{Datelike, NaiveDate};
+use sea_orm::{
+    sea_query::{Expr, Query as SelectQuery},
+    *,
+};
+
+pub struct Query;
+
+impl Query {
+    pub async fn find_puzzle_by_id(db: &DbConn, id: i32) -> Result<Option<puzzle::Model>, DbErr> {
+        Puzzle::find_by_id(id).one(db).await
+    }
+
+    pub async fn find_puzzle_rating(
+        db: &DbConn,
+        user_id: i32,
+    ) -> Result<Option<puzzle_rating::Model>, DbErr> {
+        PuzzleRating::find()
+            .filter(puzzle_rating::Column::UserId.eq(user_id))
+            .one(db)
+            .await
+    }
+
+    pub async fn find_attempt(
+        db: &DbConn,
+        user_id: i32,
+        puzzle_id: i32,
+    ) -> Result<Option<puzzle_attempt::Model>, DbErr> {
+        PuzzleAttempt::find()
+            .filter(puzzle_attempt::Column::UserId.eq(user_id))
+            .filter(puzzle_attempt::Column::PuzzleId.eq(puzzle_id))
+            .one(db)
+            .await
+    }
+
+    // A random puzzle rated from `min` to `max` that the player has not tried yet, with the
+    // theme when one is given
+    pub async fn find_unplayed_puzzle(
+        db: &DbConn,
+        user_id: i32,
+        min: f64,
+        max: f64,
+        theme: Option<&str>,
+    ) -> Result<Option<puzzle::Model>, DbErr> {
+        let attempted = SelectQuery::select()
+            .column(puzzle_attempt::Column::PuzzleId)
+            .from(PuzzleAttempt)
+            .and_where(puzzle_attempt::Column::UserId.eq(user_id))
+            .to_owned();
+        Puzzle::find()
+            .filter(puzzle::Column::Rating.between(min, max))
+            .filter(puzzle::Column::Id.not_in_subquery(attempted))
+            .apply_if(theme, |query, theme| {
+                query.filter(puzzle::Column::Themes.contains(theme))
+            })
+            .order_by(Expr::cust("RANDOM()"), Order::Asc)
+            .one(db)
+            .await
+    }
+
+    // The same puzzle for everyone on `day`, picked from those that existed before it started
+    pub async fn find_daily_puzzle(
+        db: &DbConn,
+        day: NaiveDate,
+    ) -> Result<Option<puzzle::Model>, DbErr> {
+        let before = Puzzle::find().filter(puzzle::Column::CreatedAt.lt(day.and_hms_opt(0, 0, 0)));
+        let count = before.clone().count(db).await?;
+        if count == 0 {
+            return Ok(None);
+        }
+        // Consecutive days are spread over the puzzles rather than going through them in order
+        let pick = (day.num_days_from_ce() as u64).wrapping_mul(7919) % count;
+        before
+            .order_by_asc(puzzle::Column::Id)
+            .offset(pick)
+            .one(db)
+            .await
+    }
+}
@@ -0,0 +1,203 @@
+// Glicko-2 rating system, http://www.glicko.net/glicko/glicko2.pdf
+// Every game is treated as its own rating period.
+
+pub const DEFAULT_RATING: f64 = 1500.0;
+pub const DEFAULT_DEVIATION: f64 = 350.0;
+pub const DEFAULT_VOLATILITY: f64 = 0.06;
+// Players are provisional until their deviation drops below this
+pub const PROVISIONAL_DEVIATION: f64 = 110.0;
+
+const MIN_DEVIATION: f64 = 45.0;
+const MAX_VOLATILITY: f64 = 0.1;
+// Constrains the change in volatility over time
+const TAU: f64 = 0.75;
+const SCALE: f64 = 173.7178;
+const CONVERGENCE_TOLERANCE: f64 = 0.000001;
+const MAX_ITERATIONS: u32 = 1000;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Glicko2 {
+    pub rating: f64,
+    pub deviation: f64,
+    pub volatility: f64,
+}
+
+impl Default for Glicko2 {
+    fn default() -> Self {
+        Glicko2 {
+            rating: DEFAULT_RATING,
+            deviation: DEFAULT_DEVIATION,
+            volatility: DEFAULT_VOLATILITY,
+        }
+    }
+}
+
+fn g(phi: f64) -> f64 {
+    1.0 / (1.0 + 3.0 * phi * phi / (std::f64::consts::PI * std::f64::consts::PI)).sqrt()
+}
+
+fn expected_score(mu: f64, mu_j: f64, phi_j: f64) -> f64 {
+    1.0 / (1.0 + (-g(phi_j) * (mu - mu_j)).exp())
+}
+
+impl Glicko2 {
+    pub fn is_provisional(&self) -> bool {
+        self.deviation > PROVISIONAL_DEVIATION
+    }
+
+    // Each result is the opponent's rating before the game and the score (1, 0.5 or 0)
+    pub fn update(&self, results: &[(Glicko2, f64)]) -> Glicko2 {
+        self.update_with_tau(results, TAU)
+    }
+
+    fn update_with_tau(&self, results: &[(Glicko2, f64)], tau: f64) -> Glicko2 {
+        let mu = (self.rating - DEFAULT_RATING) / SCALE;
+        let phi = self.deviation / SCALE;
+        let sigma = self.volatility;
+
+        if results.is_empty() {
+            let phi_star = (phi * phi + sigma * sigma).sqrt();
+            return Glicko2 {
+                rating: self.rating,
+                deviation: (phi_star * SCALE).min(DEFAULT_DEVIATION),
+                volatility: sigma,
+            };
+        }
+
+        let mut v_inv = 0.0;
+        let mut delta_sum = 0.0;
+        for (opponent, score) in results {
+            let mu_j = (opponent.rating - DEFAULT_RATING) / SCALE;
+            let phi_j = opponent.deviation / SCALE;
+            let e = expected_score(mu, mu_j, phi_j);
+            v_inv += g(phi_j) * g(phi_j) * e * (1.0 - e);
+            delta_sum += g(phi_j) * (score - e);
+        }
+        let v = 1.0 / v_inv;
+        let delta = v * delta_sum;
+
+        let sigma_prime = new_volatility(phi, sigma, v, delta, tau);
+        let phi_star = (phi * phi + sigma_prime * sigma_prime).sqrt();
+        let phi_prime = 1.0 / (1.0 / (phi_star * phi_star) + 1.0 / v).sqrt();
+        let mu_prime = mu + phi_prime * phi_prime * delta_sum;
+
+        Glicko2 {
+            rating: mu_prime * SCALE + DEFAULT_RATING,
+            deviation: (phi_prime * SCALE).clamp(MIN_DEVIATION, DEFAULT_DEVIATION),
+            volatility: sigma_prime.min(MAX_VOLATILITY),
+        }
+    }
+}
+
+// Step 5 of the paper, Illinois algorithm
+fn new_volatility(phi: f64, sigma: f64, v: f64, delta: f64, tau: f64) -> f64 {
+    let a = (sigma * sigma).ln();
+    let f = |x: f64| {
+        let ex = x.exp();
+        let d = phi * phi + v + ex;
+        ex * (delta * delta - phi * phi - v - ex) / (2.0 * d * d) - (x - a) / (tau * tau)
+    };
+
+    let mut big_a = a;
+    let mut big_b = if delta * delta > phi * phi + v {
+        (delta * delta - phi * phi - v).ln()
+    } else {
+        let mut k = 1.0;
+        while f(a - k * tau) < 0.0 {
+            k += 1.0;
+        }
+        a - k * tau
+    };
+
+    let mut f_a = f(big_a);
+    let mut f_b = f(big_b);
+    let mut iterations = 0;
+    while (big_b - big_a).abs() > CONVERGENCE_TOLERANCE && iterations < MAX_ITERATIONS {
+        let big_c = big_a + (big_a - big_b) * f_a / (f_b - f_a);
+        let f_c = f(big_c);
+        if f_c * f_b <= 0.0 {
+            big_a = big_b;
+            f_a = f_b;
+        } else {
+            f_a /= 2.0;
+        }
+        big_b = big_c;
+        f_b = f_c;
+        iterations += 1;
+    }
+    (big_a / 2.0).exp()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glicko2_paper_example() {
+        let player = Glicko2 {
+            rating: 1500.0,
+            deviation: 200.0,
+            volatility: 0.06,
+        };
+        let results = [
+            (
+                Glicko2 {
+                    rating: 1400.0,
+                    deviation: 30.0,
+                    volatility: 0.06,
+                },
+                1.0,
+            ),
+            (
+                Glicko2 {
+                    rating: 1550.0,
+                    deviation: 100.0,
+                    volatility: 0.06,
+                },
+                0.0,
+            ),
+            (
+                Glicko2 {
+                    rating: 1700.0,
+                    deviation: 300.0,
+                    volatility: 0.06,
+                },
+                0.0,
+            ),
+        ];
+        // The paper uses tau = 0.5
+        let updated = player.update_with_tau(&results, 0.5);
+        assert!((updated.rating - 1464.06).abs() < 0.01);
+        assert!((updated.deviation - 151.52).abs() < 0.01);
+        assert!((updated.volatility - 0.05999).abs() < 0.00001);
+    }
+
+    #[test]
+    fn test_glicko2_win_and_loss_are_symmetric() {
+        let a = Glicko2::default();
+        let b = Glicko2::default();
+        let a_won = a.update(&[(b, 1.0)]);
+        let b_lost = b.update(&[(a, 0.0)]);
+        assert!(a_won.rating > DEFAULT_RATING);
+        assert!((a_won.rating - DEFAULT_RATING - (DEFAULT_RATING - b_lost.rating)).abs() < 1e-9);
+        assert!(a_won.deviation < DEFAULT_DEVIATION);
+
+        let draw = a.update(&[(b, 0.5)]);
+        assert!((draw.rating - DEFAULT_RATING).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_glicko2_provisional() {
+        assert!(Glicko2::default().is_provisional());
+        let established = Glicko2 {
+            rating: 1800.0,
+            deviation: 60.0,
+            volatility: 0.06,
+        };
+        assert!(!established.is_provisional());
+        // Inactivity only increases the deviation
+        let idle = established.update(&[]);
+        assert_eq!(idle.rating, established.rating);
+        assert!(idle.deviation > established.deviation);
+    }
+}
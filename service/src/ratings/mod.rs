@@ -0,0 +1,4 @@
+pub mod glicko2;
+pub mod mutation;
+pub mod pool;
+pub mod query;
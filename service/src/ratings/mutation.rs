@@ -0,0 +1,131 @@
+use ::entity::entities::{
+    game, rating, rating::Entity as Rating, rating_history, time_control::Entity as TimeControl,
+};
+use chrono::Utc;
+use sea_orm::*;
+
+use super::{glicko2::Glicko2, pool::Pool};
+
+pub struct Mutation;
+
+// Score from white's point of view
+pub fn white_score(result: &str) -> Option<f64> {
+    match result {
+        "1-0" => Some(1.0),
+        "0-1" => Some(0.0),
+        "1/2-1/2" => Some(0.5),
+        _ => None,
+    }
+}
+
+impl From<&rating::Model> for Glicko2 {
+    fn from(rating: &rating::Model) -> Self {
+        Glicko2 {
+            rating: rating.rating,
+            deviation: rating.deviation,
+            volatility: rating.volatility,
+        }
+    }
+}
+
+impl Mutation {
+    // Updates both players' ratings for a finished rated game and records the new values in the
+    // history. `db` can be the transaction that stores the result.
+    pub async fn apply_game_result<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        game: &game::Model,
+    ) -> Result<(), DbErr> {
+        let score = match game.result.as_deref().and_then(white_score) {
+            Some(score) => score,
+            None => return Err(DbErr::Custom("Game has no result.".to_owned())),
+        };
+        if !game.rated || game.player_white == game.player_black {
+            return Ok(());
+        }
+
+        let time_control = TimeControl::find_by_id(game.time_control)
+            .one(db)
+            .await?
+            .ok_or(DbErr::Custom("Cannot find time control.".to_owned()))?;
+        let pool = Pool::from_time(time_control.time);
+
+        let txn = db.begin().await?;
+        // Rows are always locked lowest user id first, so two games between the same players
+        // finishing at once cannot deadlock
+        let first =
+            find_or_create_rating(&txn, game.player_white.min(game.player_black), pool).await?;
+        let second =
+            find_or_create_rating(&txn, game.player_white.max(game.player_black), pool).await?;
+        let (white, black) = match game.player_white < game.player_black {
+            true => (first, second),
+            false => (second, first),
+        };
+        let white_before = Glicko2::from(&white);
+        let black_before = Glicko2::from(&black);
+        let white_after = white_before.update(&[(black_before, score)]);
+        let black_after = black_before.update(&[(white_before, 1.0 - score)]);
+
+        save_rating(&txn, white, white_after, game.id).await?;
+        save_rating(&txn, black, black_after, game.id).await?;
+        txn.commit().await
+    }
+}
+
+async fn find_or_create_rating(
+    db: &DatabaseTransaction,
+    user_id: i32,
+    pool: Pool,
+) -> Result<rating::Model, DbErr> {
+    let existing = Rating::find()
+        .filter(rating::Column::UserId.eq(user_id))
+        .filter(rating::Column::Pool.eq(pool.as_str()))
+        .lock_exclusive()
+        .one(db)
+        .await?;
+    if let Some(existing) = existing {
+        return Ok(existing);
+    }
+    let initial = Glicko2::default();
+    rating::ActiveModel {
+        user_id: Set(user_id),
+        pool: Set(pool.as_str().to_owned()),
+        rating: Set(initial.rating),
+        deviation: Set(initial.deviation),
+        volatility: Set(initial.volatility),
+        games: Set(0),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+}
+
+async fn save_rating(
+    db: &DatabaseTransaction,
+    current: rating::Model,
+    updated: Glicko2,
+    game_id: i32,
+) -> Result<(), DbErr> {
+    rating::ActiveModel {
+        id: Set(current.id),
+        rating: Set(updated.rating),
+        deviation: Set(updated.deviation),
+        volatility: Set(updated.volatility),
+        games: Set(current.games + 1),
+        updated_at: Set(Some(Utc::now().naive_utc())),
+        ..Default::default()
+    }
+    .update(db)
+    .await?;
+    rating_history::ActiveModel {
+        user_id: Set(current.user_id),
+        pool: Set(current.pool),
+        game_id: Set(Some(game_id)),
+        rating: Set(updated.rating),
+        deviation: Set(updated.deviation),
+        volatility: Set(updated.volatility),
+        ..Default::default()
+    }
+    .insert(db)
+    .await?;
+    Ok(())
+}
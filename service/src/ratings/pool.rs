@@ -0,0 +1,66 @@
+// Separate rating pools per time control category, by initial clock time in seconds
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum Pool {
+    Bullet,
+    Blitz,
+    Rapid,
+    Classical,
+    Correspondence,
+}
+
+impl Pool {
+    pub const ALL: [Pool; 5] = [
+        Pool::Bullet,
+        Pool::Blitz,
+        Pool::Rapid,
+        Pool::Classical,
+        Pool::Correspondence,
+    ];
+
+    pub fn from_time(seconds: i32) -> Pool {
+        match seconds {
+            s if s < 180 => Pool::Bullet,
+            s if s < 600 => Pool::Blitz,
+            s if s < 1800 => Pool::Rapid,
+            s if s < 24 * 60 * 60 => Pool::Classical,
+            _ => Pool::Correspondence,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Pool::Bullet => "bullet",
+            Pool::Blitz => "blitz",
+            Pool::Rapid => "rapid",
+            Pool::Classical => "classical",
+            Pool::Correspondence => "correspondence",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Pool> {
+        Pool::ALL.into_iter().find(|pool| pool.as_str() == s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pool_from_time() {
+        assert_eq!(Pool::from_time(60), Pool::Bullet);
+        assert_eq!(Pool::from_time(180), Pool::Blitz);
+        assert_eq!(Pool::from_time(300), Pool::Blitz);
+        assert_eq!(Pool::from_time(900), Pool::Rapid);
+        assert_eq!(Pool::from_time(5400), Pool::Classical);
+        assert_eq!(Pool::from_time(3 * 24 * 60 * 60), Pool::Correspondence);
+    }
+
+    #[test]
+    fn test_pool_str() {
+        for pool in Pool::ALL {
+            assert_eq!(Pool::parse(pool.as_str()), Some(pool));
+        }
+        assert_eq!(Pool::parse("armageddon"), None);
+    }
+}
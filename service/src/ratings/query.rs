@@ -0,0 +1,72 @@
+use ::entity::entities::{
+    rating, rating::Entity as Rating, rating_history, rating_history::Entity as RatingHistory,
+};
+use sea_orm::{sea_query::Expr, *};
+
+use super::pool::Pool;
+
+pub struct Query;
+
+impl Query {
+    pub async fn find_ratings_by_user(
+        db: &DbConn,
+        user_id: i32,
+    ) -> Result<Vec<rating::Model>, DbErr> {
+        Rating::find()
+            .filter(rating::Column::UserId.eq(user_id))
+            .all(db)
+            .await
+    }
+
+    pub async fn find_rating<C: ConnectionTrait>(
+        db: &C,
+        user_id: i32,
+        pool: Pool,
+    ) -> Result<Option<rating::Model>, DbErr> {
+        Rating::find()
+            .filter(rating::Column::UserId.eq(user_id))
+            .filter(rating::Column::Pool.eq(pool.as_str()))
+            .one(db)
+            .await
+    }
+
+    pub async fn find_rating_history(
+        db: &DbConn,
+        user_id: i32,
+        pool: Pool,
+    ) -> Result<Vec<rating_history::Model>, DbErr> {
+        RatingHistory::find()
+            .filter(rating_history::Column::UserId.eq(user_id))
+            .filter(rating_history::Column::Pool.eq(pool.as_str()))
+            .order_by_asc(rating_history::Column::CreatedAt)
+            .all(db)
+            .await
+    }
+
+    // The rating a rated game left the player with
+    pub async fn find_rating_after_game<C: ConnectionTrait>(
+        db: &C,
+        user_id: i32,
+        game_id: i32,
+    ) -> Result<Option<rating_history::Model>, DbErr> {
+        RatingHistory::find()
+            .filter(rating_history::Column::UserId.eq(user_id))
+            .filter(rating_history::Column::GameId.eq(game_id))
+            .one(db)
+            .await
+    }
+
+    // The highest rating each pool's games took the player to, by pool name
+    pub async fn find_peak_ratings(db: &DbConn, user_id: i32) -> Result<Vec<(String, f64)>, DbErr> {
+        RatingHistory::find()
+            .select_only()
+            .column(rating_history::Column::Pool)
+            .column_as(Expr::col(rating_history::Column::Rating).max(), "peak")
+            .filter(rating_history::Column::UserId.eq(user_id))
+            .filter(rating_history::Column::GameId.is_not_null())
+            .group_by(rating_history::Column::Pool)
+            .into_tuple()
+            .all(db)
+            .await
+    }
+}
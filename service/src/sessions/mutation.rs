@@ -0,0 +1,83 @@
+use ::entity::entities::{sessions, sessions::Entity as Sessions};
+use chrono::{NaiveDateTime, Utc};
+use sea_orm::{sea_query::Expr, *};
+
+pub struct Mutation;
+
+impl Mutation {
+    pub async fn create_session(
+        db: &DbConn,
+        form_data: sessions::Model,
+    ) -> Result<sessions::Model, DbErr> {
+        sessions::ActiveModel {
+            user_id: Set(form_data.user_id),
+            device: Set(form_data.device.to_owned()),
+            ip_address: Set(form_data.ip_address.to_owned()),
+            expires_at: Set(form_data.expires_at),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+    }
+
+    // Moves the session on to the next refresh generation when `generation` is still the current
+    // one, returns whether it did. A refresh token of an older generation is being reused.
+    pub async fn rotate_refresh(
+        db: &DbConn,
+        id: i32,
+        generation: i32,
+        expires_at: NaiveDateTime,
+    ) -> Result<bool, DbErr> {
+        let result = Sessions::update_many()
+            .col_expr(
+                sessions::Column::RefreshGeneration,
+                Expr::value(generation + 1),
+            )
+            .col_expr(
+                sessions::Column::LastSeenAt,
+                Expr::value(Utc::now().naive_utc()),
+            )
+            .col_expr(sessions::Column::ExpiresAt, Expr::value(expires_at))
+            .filter(sessions::Column::Id.eq(id))
+            .filter(sessions::Column::RefreshGeneration.eq(generation))
+            .filter(sessions::Column::RevokedAt.is_null())
+            .exec(db)
+            .await?;
+        Ok(result.rows_affected == 1)
+    }
+
+    // Returns the ids of the sessions that were revoked
+    pub async fn revoke_session(db: &DbConn, id: i32, user_id: i32) -> Result<Vec<i32>, DbErr> {
+        revoke(
+            db,
+            Condition::all()
+                .add(sessions::Column::Id.eq(id))
+                .add(sessions::Column::UserId.eq(user_id)),
+        )
+        .await
+    }
+
+    pub async fn revoke_all_sessions(
+        db: &DbConn,
+        user_id: i32,
+        except: Option<i32>,
+    ) -> Result<Vec<i32>, DbErr> {
+        let mut condition = Condition::all().add(sessions::Column::UserId.eq(user_id));
+        if let Some(except) = except {
+            condition = condition.add(sessions::Column::Id.ne(except));
+        }
+        revoke(db, condition).await
+    }
+}
+
+async fn revoke(db: &DbConn, condition: Condition) -> Result<Vec<i32>, DbErr> {
+    let revoked = Sessions::update_many()
+        .col_expr(
+            sessions::Column::RevokedAt,
+            Expr::value(Utc::now().naive_utc()),
+        )
+        .filter(condition.add(sessions::Column::RevokedAt.is_null()))
+        .exec_with_returning(db)
+        .await?;
+    Ok(revoked.into_iter().map(|session| session.id).collect())
+}
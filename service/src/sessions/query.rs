@@ -0,0 +1,39 @@
+use ::entity::entities::{sessions, sessions::Entity as Sessions};
+use chrono::Utc;
+use sea_orm::*;
+
+pub struct Query;
+
+impl Query {
+    pub async fn find_session_by_id(
+        db: &DbConn,
+        id: i32,
+    ) -> Result<Option<sessions::Model>, DbErr> {
+        Sessions::find_by_id(id).one(db).await
+    }
+
+    pub async fn find_active_sessions_by_user(
+        db: &DbConn,
+        user_id: i32,
+    ) -> Result<Vec<sessions::Model>, DbErr> {
+        Sessions::find()
+            .filter(sessions::Column::UserId.eq(user_id))
+            .filter(sessions::Column::RevokedAt.is_null())
+            .filter(sessions::Column::ExpiresAt.gt(Utc::now().naive_utc()))
+            .order_by_desc(sessions::Column::LastSeenAt)
+            .all(db)
+            .await
+    }
+
+    // Sessions that were revoked but whose tokens could still be presented
+    pub async fn find_revoked_unexpired_session_ids(db: &DbConn) -> Result<Vec<i32>, DbErr> {
+        Sessions::find()
+            .select_only()
+            .column(sessions::Column::Id)
+            .filter(sessions::Column::RevokedAt.is_not_null())
+            .filter(sessions::Column::ExpiresAt.gt(Utc::now().naive_utc()))
+            .into_tuple()
+            .all(db)
+            .await
+    }
+}
@@ -0,0 +1 @@
+pub mod query;
\ No newline at end of file
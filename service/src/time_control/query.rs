@@ -0,0 +1,20 @@
+use ::entity::entities::{time_control, time_control::Entity as TimeControl};
+use sea_orm::*;
+
+pub struct Query;
+
+impl Query {
+    pub async fn find_time_control_by_id<C: ConnectionTrait>(
+        db: &C,
+        id: i32,
+    ) -> Result<Option<time_control::Model>, DbErr> {
+        TimeControl::find_by_id(id).one(db).await
+    }
+
+    pub async fn find_time_controls(db: &DbConn) -> Result<Vec<time_control::Model>, DbErr> {
+        TimeControl::find()
+            .order_by_asc(time_control::Column::Id)
+            .all(db)
+            .await
+    }
+}
@@ -0,0 +1,263 @@
+use ::entity::entities::{
+    tournament, tournament::Entity as Tournament, tournament_match,
+    tournament_match::Entity as TournamentMatch, tournament_pairing,
+    tournament_pairing::Entity as TournamentPairing, tournament_player,
+    tournament_player::Entity as TournamentPlayer,
+};
+use chrono::{NaiveDateTime, Utc};
+use sea_orm::{sea_query::Expr, *};
+
+pub struct Mutation;
+
+impl Mutation {
+    pub async fn create_tournament(
+        db: &DbConn,
+        form_data: tournament::Model,
+    ) -> Result<tournament::Model, DbErr> {
+        tournament::ActiveModel {
+            name: Set(form_data.name.to_owned()),
+            format: Set(form_data.format.to_owned()),
+            time_control: Set(form_data.time_control),
+            rated: Set(form_data.rated),
+            rounds: Set(form_data.rounds),
+            duration: Set(form_data.duration),
+            series_games: Set(form_data.series_games),
+            created_by: Set(form_data.created_by),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+    }
+
+    pub async fn add_player(
+        db: &DbConn,
+        form_data: tournament_player::Model,
+    ) -> Result<tournament_player::Model, DbErr> {
+        tournament_player::ActiveModel {
+            tournament_id: Set(form_data.tournament_id),
+            user_id: Set(form_data.user_id),
+            rating: Set(form_data.rating),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+    }
+
+    pub async fn remove_player(db: &DbConn, id: i32) -> Result<DeleteResult, DbErr> {
+        TournamentPlayer::delete_by_id(id).exec(db).await
+    }
+
+    // Withdrawn players stay in the standings but are not paired any more
+    pub async fn withdraw_player(db: &DbConn, id: i32) -> Result<tournament_player::Model, DbErr> {
+        let player: tournament_player::ActiveModel = TournamentPlayer::find_by_id(id)
+            .one(db)
+            .await?
+            .ok_or(DbErr::Custom("Cannot find tournament player.".to_owned()))
+            .map(Into::into)?;
+
+        tournament_player::ActiveModel {
+            id: player.id,
+            withdrawn: Set(true),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+    }
+
+    // Moves the tournament from round `from` to the next one. Returns false if another
+    // request already did, so a round is only ever paired once.
+    pub async fn advance_round(db: &DbConn, id: i32, from: i32) -> Result<bool, DbErr> {
+        let now = Utc::now().naive_utc();
+        let update = Tournament::update_many()
+            .col_expr(tournament::Column::CurrentRound, Expr::value(from + 1))
+            .col_expr(tournament::Column::State, Expr::value("running"))
+            .col_expr(
+                tournament::Column::StartedAt,
+                Expr::col(tournament::Column::StartedAt).if_null(now),
+            )
+            .col_expr(tournament::Column::UpdatedAt, Expr::value(now))
+            .filter(tournament::Column::Id.eq(id))
+            .filter(tournament::Column::CurrentRound.eq(from))
+            .filter(tournament::Column::State.ne("finished"))
+            .exec(db)
+            .await?;
+        Ok(update.rows_affected > 0)
+    }
+
+    // Arenas have no rounds to advance, they run from now until `ends_at`
+    pub async fn start_arena(db: &DbConn, id: i32, ends_at: NaiveDateTime) -> Result<bool, DbErr> {
+        let now = Utc::now().naive_utc();
+        let update = Tournament::update_many()
+            .col_expr(tournament::Column::State, Expr::value("running"))
+            .col_expr(tournament::Column::StartedAt, Expr::value(now))
+            .col_expr(tournament::Column::EndsAt, Expr::value(ends_at))
+            .col_expr(tournament::Column::UpdatedAt, Expr::value(now))
+            .filter(tournament::Column::Id.eq(id))
+            .filter(tournament::Column::State.eq("registration"))
+            .exec(db)
+            .await?;
+        Ok(update.rows_affected > 0)
+    }
+
+    pub async fn set_rounds(db: &DbConn, id: i32, rounds: i32) -> Result<tournament::Model, DbErr> {
+        let tournament: tournament::ActiveModel = Tournament::find_by_id(id)
+            .one(db)
+            .await?
+            .ok_or(DbErr::Custom("Cannot find tournament.".to_owned()))
+            .map(Into::into)?;
+
+        tournament::ActiveModel {
+            id: tournament.id,
+            rounds: Set(Some(rounds)),
+            updated_at: Set(Some(Utc::now().naive_utc())),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+    }
+
+    pub async fn finish_tournament(db: &DbConn, id: i32) -> Result<tournament::Model, DbErr> {
+        let tournament: tournament::ActiveModel = Tournament::find_by_id(id)
+            .one(db)
+            .await?
+            .ok_or(DbErr::Custom("Cannot find tournament.".to_owned()))
+            .map(Into::into)?;
+
+        let now = Utc::now().naive_utc();
+        tournament::ActiveModel {
+            id: tournament.id,
+            state: Set("finished".to_owned()),
+            finished_at: Set(Some(now)),
+            updated_at: Set(Some(now)),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+    }
+
+    pub async fn create_pairing(
+        db: &DbConn,
+        form_data: tournament_pairing::Model,
+    ) -> Result<tournament_pairing::Model, DbErr> {
+        tournament_pairing::ActiveModel {
+            tournament_id: Set(form_data.tournament_id),
+            round: Set(form_data.round),
+            player_white: Set(form_data.player_white),
+            player_black: Set(form_data.player_black),
+            game_id: Set(form_data.game_id),
+            result: Set(form_data.result.to_owned()),
+            match_id: Set(form_data.match_id),
+            stage: Set(form_data.stage.to_owned()),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+    }
+
+    pub async fn set_pairing_result(
+        db: &DbConn,
+        id: i32,
+        result: String,
+    ) -> Result<tournament_pairing::Model, DbErr> {
+        let pairing: tournament_pairing::ActiveModel = TournamentPairing::find_by_id(id)
+            .one(db)
+            .await?
+            .ok_or(DbErr::Custom("Cannot find tournament pairing.".to_owned()))
+            .map(Into::into)?;
+
+        tournament_pairing::ActiveModel {
+            id: pairing.id,
+            result: Set(Some(result)),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+    }
+
+    // Returns false if that side already went berserk
+    pub async fn set_berserk(db: &DbConn, id: i32, white: bool) -> Result<bool, DbErr> {
+        let column = if white {
+            tournament_pairing::Column::WhiteBerserk
+        } else {
+            tournament_pairing::Column::BlackBerserk
+        };
+        let update = TournamentPairing::update_many()
+            .col_expr(column, Expr::value(true))
+            .filter(tournament_pairing::Column::Id.eq(id))
+            .filter(column.eq(false))
+            .exec(db)
+            .await?;
+        Ok(update.rows_affected > 0)
+    }
+
+    pub async fn create_match(
+        db: &DbConn,
+        form_data: tournament_match::Model,
+    ) -> Result<tournament_match::Model, DbErr> {
+        tournament_match::ActiveModel {
+            tournament_id: Set(form_data.tournament_id),
+            bracket: Set(form_data.bracket.to_owned()),
+            round: Set(form_data.round),
+            position: Set(form_data.position),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+    }
+
+    // Returns false if the match was already started
+    pub async fn start_match(
+        db: &DbConn,
+        id: i32,
+        player_one: i32,
+        player_two: i32,
+    ) -> Result<bool, DbErr> {
+        let update = TournamentMatch::update_many()
+            .col_expr(tournament_match::Column::PlayerOne, Expr::value(player_one))
+            .col_expr(tournament_match::Column::PlayerTwo, Expr::value(player_two))
+            .col_expr(tournament_match::Column::State, Expr::value("playing"))
+            .col_expr(
+                tournament_match::Column::UpdatedAt,
+                Expr::value(Utc::now().naive_utc()),
+            )
+            .filter(tournament_match::Column::Id.eq(id))
+            .filter(tournament_match::Column::State.eq("pending"))
+            .exec(db)
+            .await?;
+        Ok(update.rows_affected > 0)
+    }
+
+    // Returns false if the match was already finished
+    pub async fn finish_match(
+        db: &DbConn,
+        form_data: tournament_match::Model,
+    ) -> Result<bool, DbErr> {
+        let update = TournamentMatch::update_many()
+            .col_expr(
+                tournament_match::Column::PlayerOne,
+                Expr::value(form_data.player_one),
+            )
+            .col_expr(
+                tournament_match::Column::PlayerTwo,
+                Expr::value(form_data.player_two),
+            )
+            .col_expr(
+                tournament_match::Column::Winner,
+                Expr::value(form_data.winner),
+            )
+            .col_expr(
+                tournament_match::Column::Loser,
+                Expr::value(form_data.loser),
+            )
+            .col_expr(tournament_match::Column::State, Expr::value("finished"))
+            .col_expr(
+                tournament_match::Column::UpdatedAt,
+                Expr::value(Utc::now().naive_utc()),
+            )
+            .filter(tournament_match::Column::Id.eq(form_data.id))
+            .filter(tournament_match::Column::State.ne("finished"))
+            .exec(db)
+            .await?;
+        Ok(update.rows_affected > 0)
+    }
+}
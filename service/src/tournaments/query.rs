@@ -0,0 +1,103 @@
+use ::entity::entities::{
+    tournament, tournament::Entity as Tournament, tournament_match,
+    tournament_match::Entity as TournamentMatch, tournament_pairing,
+    tournament_pairing::Entity as TournamentPairing, tournament_player,
+    tournament_player::Entity as TournamentPlayer,
+};
+use sea_orm::*;
+
+pub struct Query;
+
+impl Query {
+    pub async fn find_tournament_by_id(
+        db: &DbConn,
+        id: i32,
+    ) -> Result<Option<tournament::Model>, DbErr> {
+        Tournament::find_by_id(id).one(db).await
+    }
+
+    pub async fn find_players(
+        db: &DbConn,
+        tournament_id: i32,
+    ) -> Result<Vec<tournament_player::Model>, DbErr> {
+        TournamentPlayer::find()
+            .filter(tournament_player::Column::TournamentId.eq(tournament_id))
+            .order_by_asc(tournament_player::Column::Id)
+            .all(db)
+            .await
+    }
+
+    pub async fn find_player(
+        db: &DbConn,
+        tournament_id: i32,
+        user_id: i32,
+    ) -> Result<Option<tournament_player::Model>, DbErr> {
+        TournamentPlayer::find()
+            .filter(tournament_player::Column::TournamentId.eq(tournament_id))
+            .filter(tournament_player::Column::UserId.eq(user_id))
+            .one(db)
+            .await
+    }
+
+    pub async fn find_pairings(
+        db: &DbConn,
+        tournament_id: i32,
+    ) -> Result<Vec<tournament_pairing::Model>, DbErr> {
+        TournamentPairing::find()
+            .filter(tournament_pairing::Column::TournamentId.eq(tournament_id))
+            .order_by_asc(tournament_pairing::Column::Round)
+            .order_by_asc(tournament_pairing::Column::Id)
+            .all(db)
+            .await
+    }
+
+    pub async fn find_pairing_by_game(
+        db: &DbConn,
+        game_id: i32,
+    ) -> Result<Option<tournament_pairing::Model>, DbErr> {
+        TournamentPairing::find()
+            .filter(tournament_pairing::Column::GameId.eq(game_id))
+            .one(db)
+            .await
+    }
+
+    pub async fn find_running_tournaments(
+        db: &DbConn,
+        format: &str,
+    ) -> Result<Vec<tournament::Model>, DbErr> {
+        Tournament::find()
+            .filter(tournament::Column::Format.eq(format))
+            .filter(tournament::Column::State.eq("running"))
+            .all(db)
+            .await
+    }
+
+    pub async fn find_matches(
+        db: &DbConn,
+        tournament_id: i32,
+    ) -> Result<Vec<tournament_match::Model>, DbErr> {
+        TournamentMatch::find()
+            .filter(tournament_match::Column::TournamentId.eq(tournament_id))
+            .order_by_asc(tournament_match::Column::Id)
+            .all(db)
+            .await
+    }
+
+    pub async fn find_match_by_id(
+        db: &DbConn,
+        id: i32,
+    ) -> Result<Option<tournament_match::Model>, DbErr> {
+        TournamentMatch::find_by_id(id).one(db).await
+    }
+
+    pub async fn find_pairings_by_match(
+        db: &DbConn,
+        match_id: i32,
+    ) -> Result<Vec<tournament_pairing::Model>, DbErr> {
+        TournamentPairing::find()
+            .filter(tournament_pairing::Column::MatchId.eq(match_id))
+            .order_by_asc(tournament_pairing::Column::Id)
+            .all(db)
+            .await
+    }
+}
@@ -1,53 +1,132 @@
-use ::entity::entities::users;
-use sea_orm::*;
+use ::entity::entities::{
+    challenge, challenge::Entity as Challenge, tournament, tournament_player,
+    tournament_player::Entity as TournamentPlayer, users, users::Entity as Users,
+};
+use chrono::Utc;
+use sea_orm::{sea_query::Expr, *};
+
+// Deleted accounts are renamed to this and their id, registration rejects names starting with it
+pub const DELETED_PREFIX: &str = "deleted_";
 
 pub struct Mutation;
 
 impl Mutation {
-    pub async fn create_user(
-        db: &DbConn,
-        form_data: users::Model,
-    ) -> Result<users::Model, DbErr> {
+    pub async fn create_user(db: &DbConn, form_data: users::Model) -> Result<users::Model, DbErr> {
         users::ActiveModel {
             username: Set(form_data.username.to_owned()),
             password: Set(form_data.password.to_owned()),
+            bot_level: Set(form_data.bot_level),
             ..Default::default()
         }
         .insert(db)
         .await
     }
 
-    // pub async fn update_post_by_id(
-    //     db: &DbConn,
-    //     id: i32,
-    //     form_data: post::Model,
-    // ) -> Result<post::Model, DbErr> {
-    //     let post: post::ActiveModel = Post::find_by_id(id)
-    //         .one(db)
-    //         .await?
-    //         .ok_or(DbErr::Custom("Cannot find post.".to_owned()))
-    //         .map(Into::into)?;
-
-    //     post::ActiveModel {
-    //         id: post.id,
-    //         title: Set(form_data.title.to_owned()),
-    //         text: Set(form_data.text.to_owned()),
-    //     }
-    //     .update(db)
-    //     .await
-    // }
-
-    // pub async fn delete_post(db: &DbConn, id: i32) -> Result<DeleteResult, DbErr> {
-    //     let post: post::ActiveModel = Post::find_by_id(id)
-    //         .one(db)
-    //         .await?
-    //         .ok_or(DbErr::Custom("Cannot find post.".to_owned()))
-    //         .map(Into::into)?;
-
-    //     post.delete(db).await
-    // }
-
-    // pub async fn delete_all_posts(db: &DbConn) -> Result<DeleteResult, DbErr> {
-    //     Post::delete_many().exec(db).await
-    // }
+    pub async fn update_password_by_id(
+        db: &DbConn,
+        id: i32,
+        password: &str,
+    ) -> Result<users::Model, DbErr> {
+        let user = find_active_user(db, id).await?;
+
+        users::ActiveModel {
+            id: user.id,
+            password: Set(password.to_owned()),
+            updated_at: Set(Some(Utc::now().naive_utc())),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+    }
+
+    pub async fn update_profile_by_id(
+        db: &DbConn,
+        id: i32,
+        form_data: users::Model,
+    ) -> Result<users::Model, DbErr> {
+        let user = find_active_user(db, id).await?;
+
+        users::ActiveModel {
+            id: user.id,
+            display_name: Set(form_data.display_name.to_owned()),
+            country: Set(form_data.country.to_owned()),
+            bio: Set(form_data.bio.to_owned()),
+            avatar_url: Set(form_data.avatar_url.to_owned()),
+            updated_at: Set(Some(Utc::now().naive_utc())),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+    }
+
+    // Accounts are anonymized instead of deleted so that games keep pointing at a valid user.
+    // Pending challenges are cancelled and tournaments left, a running one by withdrawing. Active
+    // games are up to the caller to resign, so the results count like any other.
+    pub async fn delete_user(db: &DbConn, id: i32) -> Result<users::Model, DbErr> {
+        let txn = db.begin().await?;
+        let user = find_active_user(&txn, id).await?;
+        let now = Utc::now().naive_utc();
+
+        Challenge::update_many()
+            .col_expr(challenge::Column::State, Expr::value("cancelled"))
+            .col_expr(challenge::Column::UpdatedAt, Expr::value(now))
+            .filter(challenge::Column::State.eq("pending"))
+            .filter(
+                Condition::any()
+                    .add(challenge::Column::Challenger.eq(id))
+                    .add(challenge::Column::Challenged.eq(id)),
+            )
+            .exec(&txn)
+            .await?;
+        TournamentPlayer::delete_many()
+            .filter(tournament_player::Column::UserId.eq(id))
+            .filter(
+                tournament_player::Column::TournamentId.in_subquery(tournaments_in("registration")),
+            )
+            .exec(&txn)
+            .await?;
+        TournamentPlayer::update_many()
+            .col_expr(tournament_player::Column::Withdrawn, Expr::value(true))
+            .filter(tournament_player::Column::UserId.eq(id))
+            .filter(tournament_player::Column::TournamentId.in_subquery(tournaments_in("running")))
+            .exec(&txn)
+            .await?;
+
+        let user = users::ActiveModel {
+            id: user.id,
+            username: Set(format!("{DELETED_PREFIX}{id}")),
+            password: Set(String::new()),
+            display_name: Set(None),
+            country: Set(None),
+            bio: Set(None),
+            avatar_url: Set(None),
+            updated_at: Set(Some(now)),
+            deleted_at: Set(Some(now)),
+            ..Default::default()
+        }
+        .update(&txn)
+        .await?;
+        txn.commit().await?;
+        Ok(user)
+    }
+}
+
+fn tournaments_in(state: &str) -> sea_query::SelectStatement {
+    sea_query::Query::select()
+        .column(tournament::Column::Id)
+        .from(tournament::Entity)
+        .and_where(tournament::Column::State.eq(state))
+        .to_owned()
+}
+
+async fn find_active_user<C: ConnectionTrait>(
+    db: &C,
+    id: i32,
+) -> Result<users::ActiveModel, DbErr> {
+    Users::find_by_id(id)
+        .filter(users::Column::DeletedAt.is_null())
+        .one(db)
+        .await?
+        .ok_or(DbErr::Custom("Cannot find user.".to_owned()))
+        .map(Into::into)
 }
@@ -1,35 +1,61 @@
 use ::entity::entities::{users, users::Entity as Users};
-use sea_orm::*;
+use sea_orm::{
+    sea_query::{Expr, Func, SimpleExpr},
+    *,
+};
 
 pub struct Query;
 
+// Usernames are unique regardless of case, see the `idx_users_username_lower` index
+fn username_eq(username: &str) -> SimpleExpr {
+    Expr::expr(Func::lower(Expr::col(users::Column::Username))).eq(username.to_lowercase())
+}
+
 impl Query {
-    pub async fn find_user_by_id(db: &DbConn, id: i32) -> Result<Option<users::Model>, DbErr> {
+    pub async fn find_user_by_id<C: ConnectionTrait>(
+        db: &C,
+        id: i32,
+    ) -> Result<Option<users::Model>, DbErr> {
         Users::find_by_id(id).one(db).await
     }
 
+    pub async fn find_users_by_ids(db: &DbConn, ids: Vec<i32>) -> Result<Vec<users::Model>, DbErr> {
+        Users::find()
+            .filter(users::Column::Id.is_in(ids))
+            .all(db)
+            .await
+    }
+
     pub async fn find_user_by_username(
         db: &DbConn,
         username: &str,
     ) -> Result<Option<users::Model>, DbErr> {
-        Users::find()
-            .filter(users::Column::Username.contains(username))
-            .one(db)
-            .await
+        Users::find().filter(username_eq(username)).one(db).await
     }
 
-    pub async fn find_user_by_username_password(
+    // The account a login is for, its password is checked by the caller against the stored hash
+    pub async fn find_user_for_login(
         db: &DbConn,
         username: &str,
-        password: &str,
     ) -> Result<Option<users::Model>, DbErr> {
         Users::find()
-            .filter(users::Column::Username.contains(username))
-            .filter(users::Column::Password.contains(password))
+            .filter(username_eq(username))
+            .filter(users::Column::DeletedAt.is_null())
+            // Bots have no password and play from the server only
+            .filter(users::Column::BotLevel.is_null())
             .one(db)
             .await
     }
 
+    pub async fn find_bots(db: &DbConn) -> Result<Vec<users::Model>, DbErr> {
+        Users::find()
+            .filter(users::Column::BotLevel.is_not_null())
+            .filter(users::Column::DeletedAt.is_null())
+            .order_by_asc(users::Column::BotLevel)
+            .all(db)
+            .await
+    }
+
     // If ok, returns (post models, num pages).
     // pub async fn find_posts_in_page(
     //     db: &DbConn,
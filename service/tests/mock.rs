@@ -0,0 +1,91 @@
+use entity::entities::users;
+use sea_orm::{DatabaseBackend, MockDatabase, MockExecResult, Transaction};
+use service::users::mutation::{Mutation, DELETED_PREFIX};
+
+fn user(id: i32, username: &str, password: &str) -> users::Model {
+    users::Model {
+        id,
+        username: username.to_string(),
+        password: password.to_string(),
+        created_at: Default::default(),
+        updated_at: None,
+        display_name: Some("Alice".to_string()),
+        country: Some("NL".to_string()),
+        bio: None,
+        avatar_url: None,
+        deleted_at: None,
+        bot_level: None,
+    }
+}
+
+fn statements(log: Vec<Transaction>) -> Vec<String> {
+    log.iter()
+        .map(|transaction| format!("{:?}", transaction))
+        .collect()
+}
+
+#[tokio::test]
+async fn test_delete_user_anonymizes() {
+    let db = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([
+            [user(7, "alice", "$argon2id$hash")],
+            [user(7, &format!("{DELETED_PREFIX}7"), "")],
+        ])
+        .append_exec_results([
+            MockExecResult {
+                last_insert_id: 0,
+                rows_affected: 1,
+            },
+            MockExecResult {
+                last_insert_id: 0,
+                rows_affected: 0,
+            },
+            MockExecResult {
+                last_insert_id: 0,
+                rows_affected: 1,
+            },
+        ])
+        .into_connection();
+    let deleted = Mutation::delete_user(&db, 7).await.unwrap();
+    assert_eq!(deleted.username, "deleted_7");
+
+    // Everything happens in one transaction
+    let log = statements(db.into_transaction_log());
+    assert_eq!(log.len(), 1);
+    let log = &log[0];
+    // Only active accounts can be deleted
+    assert!(log.contains("deleted_at") && log.contains("IS NULL"));
+    // Pending challenges are cancelled and tournament entries withdrawn
+    assert!(log.contains("cancelled"));
+    assert!(log.contains("withdrawn"));
+    assert!(log.contains("deleted_7"));
+    assert!(!log.contains("$argon2id$hash"));
+    assert!(!log.contains("Alice"));
+}
+
+#[tokio::test]
+async fn test_delete_user_needs_an_active_account() {
+    let db = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([Vec::<users::Model>::new()])
+        .into_connection();
+    assert!(Mutation::delete_user(&db, 7).await.is_err());
+    assert_eq!(db.into_transaction_log().len(), 1);
+}
+
+#[tokio::test]
+async fn test_update_password_by_id() {
+    let db = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([
+            [user(7, "alice", "old")],
+            [user(7, "alice", "$argon2id$new")],
+        ])
+        .into_connection();
+    let updated = Mutation::update_password_by_id(&db, 7, "$argon2id$new")
+        .await
+        .unwrap();
+    assert_eq!(updated.password, "$argon2id$new");
+
+    let log = statements(db.into_transaction_log());
+    assert!(log[1].contains("UPDATE"));
+    assert!(log[1].contains("$argon2id$new"));
+}